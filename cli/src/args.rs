@@ -1,5 +1,5 @@
 use crate::Error;
-use std::{env, fs};
+use std::{env, fs, num::NonZeroUsize};
 
 /// Which action should be executed?
 ///
@@ -11,19 +11,124 @@ pub enum Action {
 	Help,
 	/// Print the current version.
 	Version,
-	/// Enter the REPL.
-	Repl,
-	/// Evaluate the arguments.
-	Eval { exprs: Vec<String> },
+	/// Enter the REPL, or evaluate standard input if it isn't a terminal.
+	/// `json` only applies to standard input, and `persist` (whether to save
+	/// and restore variables) only applies to the REPL.
+	Repl {
+		json: bool,
+		precision: Option<NonZeroUsize>,
+		persist: bool,
+	},
+	/// Evaluate the arguments. If `json` is true, results are printed as
+	/// JSON objects. `precision` is the default number of significant figures.
+	Eval {
+		exprs: Vec<Input>,
+		json: bool,
+		precision: Option<NonZeroUsize>,
+	},
 	/// Show the default config file
 	DefaultConfig,
 }
 
+/// Something to evaluate, given on the command line
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Input {
+	/// A single expression. Only the result of the last expression is printed.
+	Expr(String),
+	/// The statements of a script file, which are evaluated one by one.
+	/// Every non-empty result is printed.
+	Script(Vec<String>),
+}
+
+/// Splits a script into the statements that should be evaluated. A statement
+/// usually ends at the end of a line, but continues onto the next line while a
+/// parenthesis or a `/* */` block comment is still open, or if the last line
+/// with any code ends with an operator like `+`. Blank lines and lines starting
+/// with `#` (including a `#!/usr/bin/env fend` shebang line) are skipped.
+fn script_statements(contents: &str) -> Vec<String> {
+	let mut statements = vec![];
+	let mut statement = String::new();
+	let mut parens = 0_usize;
+	let mut block_comments = 0_usize;
+	let mut ends_with_operator = false;
+	for line in contents.lines() {
+		if statement.is_empty() && line.trim_start().starts_with('#') {
+			continue;
+		}
+		let mut last_char = None;
+		// the quote that ends the current string literal, if any
+		let mut string_terminator = None;
+		let mut chars = line.chars().peekable();
+		while let Some(ch) = chars.next() {
+			if block_comments > 0 {
+				if ch == '*' && chars.peek() == Some(&'/') {
+					chars.next();
+					block_comments -= 1;
+				} else if ch == '/' && chars.peek() == Some(&'*') {
+					chars.next();
+					block_comments += 1;
+				}
+				continue;
+			}
+			if let Some(terminator) = string_terminator {
+				if ch == '\\' {
+					chars.next();
+				} else if ch == terminator {
+					string_terminator = None;
+				}
+				last_char = Some(ch);
+				continue;
+			}
+			match ch {
+				'/' if chars.peek() == Some(&'*') => {
+					chars.next();
+					block_comments += 1;
+					continue;
+				}
+				'#' if chars.peek().is_none_or(|ch| ch.is_whitespace()) => break,
+				// quotes after a number are units like `5'10"` rather than strings
+				'"' | '\'' if !last_char.is_some_and(|ch: char| ch.is_ascii_digit()) => {
+					string_terminator = Some(ch);
+				}
+				'(' => parens += 1,
+				')' => parens = parens.saturating_sub(1),
+				_ => (),
+			}
+			if !ch.is_whitespace() {
+				last_char = Some(ch);
+			}
+		}
+		if !statement.is_empty() {
+			statement.push('\n');
+		}
+		statement.push_str(line);
+		// lines without any code (e.g. blank lines) don't end the statement
+		if let Some(ch) = last_char {
+			ends_with_operator = "+-*/^=(,\\×÷·−".contains(ch);
+		}
+		if block_comments == 0 && parens == 0 && !ends_with_operator {
+			let trimmed = statement.trim();
+			if !trimmed.is_empty() {
+				statements.push(trimmed.to_string());
+			}
+			statement.clear();
+		}
+	}
+	let trimmed = statement.trim();
+	if !trimmed.is_empty() {
+		statements.push(trimmed.to_string());
+	}
+	statements
+}
+
 impl Action {
 	pub fn from_args(args: &[String]) -> Result<Self, Error> {
 		let mut print_help = false;
 		let mut print_version = false;
 		let mut print_default_config = false;
+		let mut json = false;
+		let mut precision = None;
+		let mut persist = true;
 		let mut before_double_dash = true;
 		let mut exprs = vec![];
 		let mut expr = String::new();
@@ -38,24 +143,39 @@ impl Action {
 				(true, "--default-config" | "--print-default-config") => {
 					print_default_config = true;
 				}
+				(true, "--json") => json = true,
+				(true, "--no-persist") => persist = false,
+				(true, "--precision") => {
+					idx += 1;
+					let sf = args
+						.get(idx)
+						.ok_or("expected a number of significant figures")?;
+					let sf = sf
+						.parse::<usize>()
+						.map_err(|_| format!("invalid number of significant figures: '{sf}'"))?;
+					precision = Some(
+						NonZeroUsize::new(sf)
+							.ok_or("cannot format a number with zero significant figures")?,
+					);
+				}
 				(true, "-f" | "--file") => {
 					idx += 1;
 					let filename = args.get(idx).ok_or("expected a filename")?;
 					let contents = fs::read_to_string(filename)?;
 					if !expr.is_empty() {
-						exprs.push(expr);
+						exprs.push(Input::Expr(expr));
 						expr = String::new();
 					}
-					exprs.push(contents);
+					exprs.push(Input::Script(script_statements(&contents)));
 				}
 				(true, "-e" | "--eval") => {
 					idx += 1;
 					let e = args.get(idx).ok_or("expected an expression")?;
 					if !expr.is_empty() {
-						exprs.push(expr);
+						exprs.push(Input::Expr(expr));
 						expr = String::new();
 					}
-					exprs.push(e.to_string());
+					exprs.push(Input::Expr(e.to_string()));
 				}
 				(true, "--") => before_double_dash = false,
 				(_, arg) => {
@@ -63,10 +183,10 @@ impl Action {
 					if before_double_dash {
 						if let Ok(contents) = fs::read_to_string(arg) {
 							if !expr.is_empty() {
-								exprs.push(expr);
+								exprs.push(Input::Expr(expr));
 								expr = String::new();
 							}
-							exprs.push(contents);
+							exprs.push(Input::Expr(contents));
 							read_file = true;
 						}
 					}
@@ -90,13 +210,21 @@ impl Action {
 		} else if print_default_config {
 			Self::DefaultConfig
 		} else if exprs.is_empty() && expr.is_empty() {
-			Self::Repl
+			Self::Repl {
+				json,
+				precision,
+				persist,
+			}
 		} else {
 			// If neither help nor version is requested, evaluate the arguments
 			if !expr.is_empty() {
-				exprs.push(expr);
+				exprs.push(Input::Expr(expr));
+			}
+			Self::Eval {
+				exprs,
+				json,
+				precision,
 			}
-			Self::Eval { exprs }
 		})
 	}
 
@@ -108,7 +236,8 @@ impl Action {
 
 #[cfg(test)]
 mod tests {
-	use super::Action;
+	use super::{script_statements, Action, Input};
+	use std::num::NonZeroUsize;
 
 	macro_rules! action {
 		($( $arg:literal ),*) => {
@@ -118,7 +247,17 @@ mod tests {
 
 	fn eval(expr: &str) -> Action {
 		Action::Eval {
-			exprs: vec![expr.to_string()],
+			exprs: vec![Input::Expr(expr.to_string())],
+			json: false,
+			precision: None,
+		}
+	}
+
+	fn repl() -> Action {
+		Action::Repl {
+			json: false,
+			precision: None,
+			persist: true,
 		}
 	}
 
@@ -155,10 +294,92 @@ mod tests {
 
 	#[test]
 	fn empty_arguments() {
-		assert_eq!(Action::Repl, action![]);
-		assert_eq!(Action::Repl, action![""]);
-		assert_eq!(Action::Repl, action!["", ""]);
-		assert_eq!(Action::Repl, action!["\t", " "]);
+		assert_eq!(repl(), action![]);
+		assert_eq!(repl(), action![""]);
+		assert_eq!(repl(), action!["", ""]);
+		assert_eq!(repl(), action!["\t", " "]);
 		assert_eq!(eval("1"), action!["\t", " ", "1"]);
 	}
+
+	#[test]
+	fn script_statements_skip_comments_and_blank_lines() {
+		let script = "#!/usr/bin/env fend\n# set up\na = 5\n\n  2a  \n# done\n";
+		assert_eq!(script_statements(script), vec!["a = 5", "2a"]);
+		assert!(script_statements("").is_empty());
+	}
+
+	#[test]
+	fn script_statements_span_multiple_lines() {
+		assert_eq!(script_statements("1 +\n2\n3"), vec!["1 +\n2", "3"]);
+		assert_eq!(
+			script_statements("gcd(12,\n  18)\nsqrt(\n4\n)"),
+			vec!["gcd(12,\n  18)", "sqrt(\n4\n)"]
+		);
+		assert_eq!(
+			script_statements("/* a\n(multi-line\n comment */ 5\n6 # ok\n"),
+			vec!["/* a\n(multi-line\n comment */ 5", "6 # ok"]
+		);
+		assert_eq!(script_statements("3 /\n\n4\n5"), vec!["3 /\n\n4", "5"]);
+		assert_eq!(
+			script_statements("'(' + \"a\"\n\"\\\"(\"\n5'10\" to m"),
+			vec!["'(' + \"a\"", "\"\\\"(\"", "5'10\" to m"]
+		);
+	}
+
+	#[test]
+	fn json_argument() {
+		assert_eq!(
+			Action::Eval {
+				exprs: vec![Input::Expr("1 km to m".to_string())],
+				json: true,
+				precision: None,
+			},
+			action!["--json", "1 km to m"]
+		);
+		assert_eq!(
+			Action::Repl {
+				json: true,
+				precision: None,
+				persist: true,
+			},
+			action!["--json"]
+		);
+		assert_eq!(eval("--json"), action!["--", "--json"]);
+	}
+
+	#[test]
+	fn precision_argument() {
+		assert_eq!(
+			Action::Eval {
+				exprs: vec![Input::Expr("pi".to_string())],
+				json: false,
+				precision: NonZeroUsize::new(5),
+			},
+			action!["--precision", "5", "pi"]
+		);
+		assert_eq!(
+			Action::Repl {
+				json: false,
+				precision: NonZeroUsize::new(3),
+				persist: true,
+			},
+			action!["--precision", "3"]
+		);
+		assert!(Action::from_args(&["--precision".to_string()]).is_err());
+		assert!(Action::from_args(&["--precision".to_string(), "x".to_string()]).is_err());
+		assert!(Action::from_args(&["--precision".to_string(), "0".to_string()]).is_err());
+	}
+
+	#[test]
+	fn no_persist_argument() {
+		assert_eq!(
+			Action::Repl {
+				json: false,
+				precision: None,
+				persist: false,
+			},
+			action!["--no-persist"]
+		);
+		assert_eq!(eval("1 + 1"), action!["--no-persist", "1 + 1"]);
+	}
 }
@@ -1,6 +1,6 @@
-use std::{cell::RefCell, time};
+use std::{cell::RefCell, fs, time};
 
-use crate::{config, exchange_rates};
+use crate::{config, exchange_rates, file_paths, Error};
 
 pub struct HintInterrupt {
 	start: time::Instant,
@@ -39,12 +39,14 @@ impl InnerCtx {
 			res.core_ctx.use_coulomb_and_farad();
 		}
 		for custom_unit in &config.custom_units {
-			res.core_ctx.define_custom_unit_v1(
+			if let Err(e) = res.core_ctx.define_custom_unit_v2(
 				&custom_unit.singular,
 				&custom_unit.plural,
 				&custom_unit.definition,
 				&custom_unit.attribute.to_fend_core(),
-			);
+			) {
+				eprintln!("Warning: ignoring custom unit: {e}");
+			}
 		}
 		res.core_ctx
 			.set_decimal_separator_style(config.decimal_separator);
@@ -82,6 +84,14 @@ impl<'a> Context<'a> {
 		fend_core::evaluate_with_interrupt(line, &mut ctx_borrow.core_ctx, int)
 	}
 
+	pub fn set_output_width(&self, width: usize) {
+		self.ctx.borrow_mut().core_ctx.set_output_width(width);
+	}
+
+	pub fn set_default_sf_limit(&self, sf: Option<std::num::NonZeroUsize>) {
+		self.ctx.borrow_mut().core_ctx.set_default_sf_limit(sf);
+	}
+
 	pub fn eval_hint(&self, line: &str) -> fend_core::FendResult {
 		let mut ctx_borrow = self.ctx.borrow_mut();
 		ctx_borrow.core_ctx.set_output_mode_terminal();
@@ -90,6 +100,10 @@ impl<'a> Context<'a> {
 		fend_core::evaluate_preview_with_interrupt(line, &mut ctx_borrow.core_ctx, &int)
 	}
 
+	pub fn get_completions(&self, prefix: &str) -> (usize, Vec<fend_core::Completion>) {
+		fend_core::get_completions_for_prefix_with_context(prefix, &self.ctx.borrow().core_ctx)
+	}
+
 	pub fn serialize(&self) -> Result<Vec<u8>, String> {
 		let mut result = vec![];
 		self.ctx
@@ -99,6 +113,46 @@ impl<'a> Context<'a> {
 		Ok(result)
 	}
 
+	/// Restores the variables saved by [`Self::save_variables`]. A missing or
+	/// corrupt file is ignored.
+	pub fn load_variables(&self) {
+		let Ok(path) = file_paths::get_variables_file_location(file_paths::DirMode::DontCreate)
+		else {
+			return;
+		};
+		let Ok(bytes) = fs::read(path) else {
+			return;
+		};
+		// on error, the existing variables are left unchanged
+		let _ = self
+			.ctx
+			.borrow_mut()
+			.core_ctx
+			.deserialize_variables(&mut bytes.as_slice());
+	}
+
+	pub fn save_variables(&self) -> Result<(), Error> {
+		let bytes = self.serialize()?;
+		let path = file_paths::get_variables_file_location(file_paths::DirMode::Create)?;
+		fs::write(path, bytes)?;
+		Ok(())
+	}
+
+	/// Returns the names and values of all variables, except for `_` and
+	/// `ans` which are set automatically
+	pub fn variables(&self) -> Vec<(String, String)> {
+		let core_ctx = &mut self.ctx.borrow_mut().core_ctx;
+		core_ctx
+			.variable_names()
+			.into_iter()
+			.filter(|name| !matches!(name.as_str(), "_" | "ans"))
+			.filter_map(|name| {
+				let value = core_ctx.variable_value(&name)?;
+				Some((name, value))
+			})
+			.collect()
+	}
+
 	pub fn get_input_typed(&self) -> bool {
 		self.ctx.borrow().input_typed
 	}
@@ -101,6 +101,12 @@ pub fn get_history_file_location(mode: DirMode) -> Result<path::PathBuf, io::Err
 	Ok(history_path)
 }
 
+pub fn get_variables_file_location(mode: DirMode) -> Result<path::PathBuf, io::Error> {
+	let mut variables_path = get_cache_dir(mode)?;
+	variables_path.push("variables");
+	Ok(variables_path)
+}
+
 pub fn get_cache_dir(mode: DirMode) -> Result<path::PathBuf, io::Error> {
 	// first try $FEND_CACHE_DIR
 	if let Some(env_var_cache_dir) = env::var_os("FEND_CACHE_DIR") {
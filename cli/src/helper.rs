@@ -68,7 +68,7 @@ impl rustyline::completion::Completer for Helper<'_> {
 		pos: usize,
 		_ctx: &rustyline::Context<'_>,
 	) -> rustyline::Result<(usize, Vec<Self::Candidate>)> {
-		let (pos, completions) = fend_core::get_completions_for_prefix(&line[..pos]);
+		let (pos, completions) = self.ctx.get_completions(&line[..pos]);
 		let v: Vec<_> = completions
 			.into_iter()
 			.map(|c| FendCandidate { completion: c })
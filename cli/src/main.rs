@@ -4,7 +4,7 @@
 #![deny(elided_lifetimes_in_paths)]
 
 use std::fmt::Write;
-use std::{error, io, process};
+use std::{error, io, num::NonZeroUsize, process};
 
 mod args;
 mod color;
@@ -38,6 +38,44 @@ fn print_spans(spans: Vec<fend_core::SpanRef<'_>>, config: &config::Config) -> S
 	result
 }
 
+/// Formats a result like `{"ok":true,"result":"1000 m"}`, or an error like
+/// `{"ok":false,"message":"..."}`
+fn format_json_result(result: &Result<fend_core::FendResult, String>) -> String {
+	let mut escaped = String::new();
+	match result {
+		Ok(res) => {
+			fend_core::json::escape_string(res.get_main_result(), &mut escaped);
+			format!(r#"{{"ok":true,"result":"{escaped}"}}"#)
+		}
+		Err(msg) => {
+			fend_core::json::escape_string(msg, &mut escaped);
+			format!(r#"{{"ok":false,"message":"{escaped}"}}"#)
+		}
+	}
+}
+
+fn eval_and_print_json(
+	line: &str,
+	context: &mut Context<'_>,
+	print_res: bool,
+	int: &impl fend_core::Interrupt,
+	config: &config::Config,
+) -> EvalResult {
+	let result = context.eval(line, int, config);
+	let eval_result = match &result {
+		Ok(res) if res.is_unit_type() || res.get_main_result().is_empty() => {
+			return EvalResult::NoInput;
+		}
+		Ok(_) => EvalResult::Ok,
+		Err(_) => EvalResult::Err,
+	};
+	// errors are always printed, since evaluation stops afterwards
+	if print_res || matches!(eval_result, EvalResult::Err) {
+		println!("{}", format_json_result(&result));
+	}
+	eval_result
+}
+
 fn eval_and_print_res(
 	line: &str,
 	context: &mut Context<'_>,
@@ -52,6 +90,9 @@ fn eval_and_print_res(
 				return EvalResult::NoInput;
 			}
 			if print_res {
+				for info in res.get_other_info() {
+					println!("{info}");
+				}
 				let string_result = if config.enable_colors {
 					print_spans(result, config)
 				} else {
@@ -98,9 +139,13 @@ fn print_help(explain_quitting: bool) {
 	}
 }
 
-fn repl_loop(config: &config::Config) -> ExitCode {
+fn repl_loop(config: &config::Config, precision: Option<NonZeroUsize>, persist: bool) -> ExitCode {
 	let core_context = std::cell::RefCell::new(context::InnerCtx::new(config));
 	let mut context = Context::new(&core_context);
+	context.set_default_sf_limit(precision);
+	if persist {
+		context.load_variables();
+	}
 	let mut prompt_state = match terminal::init_prompt(config, &context) {
 		Ok(prompt_state) => prompt_state,
 		Err(err) => {
@@ -123,8 +168,20 @@ fn repl_loop(config: &config::Config) -> ExitCode {
 					Ok(res) => println!("{res:?}"),
 					Err(e) => eprintln!("{e}"),
 				},
+				":vars" => {
+					let variables = context.variables();
+					if variables.is_empty() {
+						println!("no variables");
+					}
+					for (name, value) in variables {
+						println!("{name} = {value}");
+					}
+				}
 				line => {
 					interrupt.reset();
+					if let Some(width) = prompt_state.width() {
+						context.set_output_width(width);
+					}
 					match eval_and_print_res(line, &mut context, true, &interrupt, config) {
 						EvalResult::Ok => {
 							last_command_success = true;
@@ -161,6 +218,11 @@ fn repl_loop(config: &config::Config) -> ExitCode {
 			}
 		}
 	}
+	if persist {
+		if let Err(e) = context.save_variables() {
+			eprintln!("Error: failed to save variables: {e}");
+		}
+	}
 	if last_command_success {
 		ExitCode::SUCCESS
 	} else {
@@ -168,20 +230,27 @@ fn repl_loop(config: &config::Config) -> ExitCode {
 	}
 }
 
-fn eval_exprs(exprs: &[String]) -> ExitCode {
+fn eval_exprs(exprs: &[args::Input], json: bool, precision: Option<NonZeroUsize>) -> ExitCode {
 	let config = config::read();
 	let core_context = std::cell::RefCell::new(context::InnerCtx::new(&config));
+	Context::new(&core_context).set_default_sf_limit(precision);
 	for (i, expr) in exprs.iter().enumerate() {
-		let print_res = i == exprs.len() - 1;
-		match eval_and_print_res(
-			expr.as_str(),
-			&mut Context::new(&core_context),
-			print_res,
-			&interrupt::Never::default(),
-			&config,
-		) {
-			EvalResult::Ok | EvalResult::NoInput => (),
-			EvalResult::Err => return ExitCode::FAILURE,
+		let (lines, print_res) = match expr {
+			args::Input::Expr(expr) => (std::slice::from_ref(expr), i == exprs.len() - 1),
+			args::Input::Script(lines) => (lines.as_slice(), true),
+		};
+		for line in lines {
+			let mut context = Context::new(&core_context);
+			let int = interrupt::Never::default();
+			let res = if json {
+				eval_and_print_json(line.as_str(), &mut context, print_res, &int, &config)
+			} else {
+				eval_and_print_res(line.as_str(), &mut context, print_res, &int, &config)
+			};
+			match res {
+				EvalResult::Ok | EvalResult::NoInput => (),
+				EvalResult::Err => return ExitCode::FAILURE,
+			}
 		}
 	}
 	ExitCode::SUCCESS
@@ -206,13 +275,21 @@ fn real_main() -> ExitCode {
 		ArgsAction::DefaultConfig => {
 			println!("{}", config::DEFAULT_CONFIG_FILE);
 		}
-		ArgsAction::Eval { exprs } => {
-			return eval_exprs(&exprs);
+		ArgsAction::Eval {
+			exprs,
+			json,
+			precision,
+		} => {
+			return eval_exprs(&exprs, json, precision);
 		}
-		ArgsAction::Repl => {
+		ArgsAction::Repl {
+			json,
+			precision,
+			persist,
+		} => {
 			if terminal::is_terminal_stdin() {
 				let config = config::read();
-				return repl_loop(&config);
+				return repl_loop(&config, precision, persist);
 			}
 			let mut input = String::new();
 			match io::Read::read_to_string(&mut io::stdin(), &mut input) {
@@ -222,7 +299,7 @@ fn real_main() -> ExitCode {
 					return ExitCode::FAILURE;
 				}
 			}
-			return eval_exprs(&[input]);
+			return eval_exprs(&[args::Input::Expr(input)], json, precision);
 		}
 	}
 	ExitCode::SUCCESS
@@ -231,3 +308,27 @@ fn real_main() -> ExitCode {
 fn main() -> process::ExitCode {
 	real_main()
 }
+
+#[cfg(test)]
+mod tests {
+	use super::format_json_result;
+
+	#[test]
+	fn json_success() {
+		let mut ctx = fend_core::Context::new();
+		let res = fend_core::evaluate("1 km to m", &mut ctx);
+		assert_eq!(format_json_result(&res), r#"{"ok":true,"result":"1000 m"}"#);
+		let res = fend_core::evaluate("\"a\\\"b\"", &mut ctx);
+		assert_eq!(format_json_result(&res), r#"{"ok":true,"result":"a\"b"}"#);
+	}
+
+	#[test]
+	fn json_error() {
+		let mut ctx = fend_core::Context::new();
+		let res = fend_core::evaluate("1 km to kg", &mut ctx);
+		assert_eq!(
+			format_json_result(&res),
+			r#"{"ok":false,"message":"cannot convert from km to kg: units 'meter' and 'kilogram' are incompatible"}"#
+		);
+	}
+}
@@ -93,4 +93,8 @@ impl PromptState<'_> {
 		mem::drop(save_history(&mut self.rl, self.history_path.as_ref()));
 		Ok(res?)
 	}
+
+	pub fn width(&mut self) -> Option<usize> {
+		self.rl.dimensions().map(|(cols, _rows)| cols)
+	}
 }
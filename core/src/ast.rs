@@ -1,3 +1,4 @@
+use crate::date::{Date, DateTime, DayOfWeek, TimeZoneTarget};
 use crate::error::{FendError, Interrupt};
 use crate::eval::evaluate_to_value;
 use crate::ident::Ident;
@@ -5,7 +6,7 @@ use crate::interrupt::test_int;
 use crate::num::{Base, FormattingStyle, Number, Range, RangeBound};
 use crate::result::FResult;
 use crate::scope::Scope;
-use crate::serialize::{Deserialize, Serialize};
+use crate::serialize::{check_nesting_depth, Deserialize, Serialize};
 use crate::value::{built_in_function::BuiltInFunction, ApplyMulHandling, Value};
 use crate::{Attrs, Context, DecimalSeparatorStyle};
 use std::borrow::Cow;
@@ -100,6 +101,58 @@ impl fmt::Display for Bop {
 	}
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ComparisonOp {
+	Less,
+	LessOrEqual,
+	Greater,
+	GreaterOrEqual,
+}
+
+impl ComparisonOp {
+	fn matches(self, ordering: cmp::Ordering) -> bool {
+		match self {
+			Self::Less => ordering.is_lt(),
+			Self::LessOrEqual => ordering.is_le(),
+			Self::Greater => ordering.is_gt(),
+			Self::GreaterOrEqual => ordering.is_ge(),
+		}
+	}
+
+	fn serialize(self, write: &mut impl io::Write) -> FResult<()> {
+		let n: u8 = match self {
+			Self::Less => 0,
+			Self::LessOrEqual => 1,
+			Self::Greater => 2,
+			Self::GreaterOrEqual => 3,
+		};
+		n.serialize(write)?;
+		Ok(())
+	}
+
+	fn deserialize(read: &mut impl io::Read) -> FResult<Self> {
+		Ok(match u8::deserialize(read)? {
+			0 => Self::Less,
+			1 => Self::LessOrEqual,
+			2 => Self::Greater,
+			3 => Self::GreaterOrEqual,
+			_ => return Err(FendError::DeserializationError),
+		})
+	}
+}
+
+impl fmt::Display for ComparisonOp {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let s = match self {
+			Self::Less => "<",
+			Self::LessOrEqual => "<=",
+			Self::Greater => ">",
+			Self::GreaterOrEqual => ">=",
+		};
+		write!(f, "{s}")
+	}
+}
+
 #[derive(Clone, Debug)]
 pub(crate) enum Expr {
 	Literal(Value),
@@ -125,9 +178,36 @@ pub(crate) enum Expr {
 	Assign(Ident, Box<Expr>),
 	Equality(bool, Box<Expr>, Box<Expr>),
 	Statements(Box<Expr>, Box<Expr>),
+	// Comma-separated list, e.g. function arguments
+	List(Vec<Self>),
+	Comparison(ComparisonOp, Box<Self>, Box<Self>),
+	// `if <condition> then <expr> else <expr>`
+	If(Box<Self>, Box<Self>, Box<Self>),
+	// `unset <ident>`
+	Unset(Ident),
+	// `next <weekday>`, e.g. `next friday`
+	NextWeekday(DayOfWeek),
+	// `days until <date>`
+	DaysUntil(Box<Self>),
+	// `<expr> where <unit> = <definition>`
+	DefineUnit(Ident, Box<Self>, Box<Self>),
+	// `float32 bits of <expr>` (if true) or `float64 bits of <expr>`
+	FloatBits(bool, Box<Self>),
+	// `% on <expr>` (if true) or `% off <expr>`, which is applied to a
+	// percentage like in `15% off 200`
+	PercentChange(bool, Box<Self>),
 }
 
 impl Expr {
+	/// Whether the last statement in this expression is an assignment
+	pub(crate) fn ends_with_assignment(&self) -> bool {
+		match self {
+			Self::Assign(_, _) => true,
+			Self::Statements(_, b) | Self::DefineUnit(_, _, b) => b.ends_with_assignment(),
+			_ => false,
+		}
+	}
+
 	pub(crate) fn compare<I: Interrupt>(
 		&self,
 		other: &Self,
@@ -138,7 +218,7 @@ impl Expr {
 			(Self::Literal(a), Self::Literal(b)) => {
 				a.compare(b, ctx, int)? == Some(cmp::Ordering::Equal)
 			}
-			(Self::Ident(a), Self::Ident(b)) => a == b,
+			(Self::Ident(a), Self::Ident(b)) | (Self::Unset(a), Self::Unset(b)) => a == b,
 			(Self::Parens(a), Self::Parens(b)) => a.compare(b, ctx, int)?,
 			(Self::UnaryMinus(a), Self::UnaryMinus(b)) => a.compare(b, ctx, int)?,
 			(Self::UnaryPlus(a), Self::UnaryPlus(b)) => a.compare(b, ctx, int)?,
@@ -168,10 +248,39 @@ impl Expr {
 			(Self::Statements(a1, a2), Self::Statements(b1, b2)) => {
 				a1.compare(b1, ctx, int)? && a2.compare(b2, ctx, int)?
 			}
+			(Self::List(a), Self::List(b)) => {
+				if a.len() != b.len() {
+					return Ok(false);
+				}
+				for (a, b) in a.iter().zip(b.iter()) {
+					if !a.compare(b, ctx, int)? {
+						return Ok(false);
+					}
+				}
+				true
+			}
+			(Self::Comparison(a1, a2, a3), Self::Comparison(b1, b2, b3)) => {
+				a1 == b1 && a2.compare(b2, ctx, int)? && a3.compare(b3, ctx, int)?
+			}
+			(Self::If(a1, a2, a3), Self::If(b1, b2, b3)) => {
+				a1.compare(b1, ctx, int)?
+					&& a2.compare(b2, ctx, int)?
+					&& a3.compare(b3, ctx, int)?
+			}
+			(Self::NextWeekday(a), Self::NextWeekday(b)) => a == b,
+			(Self::DaysUntil(a), Self::DaysUntil(b)) => a.compare(b, ctx, int)?,
+			(Self::DefineUnit(a1, a2, a3), Self::DefineUnit(b1, b2, b3)) => {
+				a1 == b1 && a2.compare(b2, ctx, int)? && a3.compare(b3, ctx, int)?
+			}
+			(Self::FloatBits(a1, a2), Self::FloatBits(b1, b2))
+			| (Self::PercentChange(a1, a2), Self::PercentChange(b1, b2)) => {
+				a1 == b1 && a2.compare(b2, ctx, int)?
+			}
 			_ => false,
 		})
 	}
 
+	#[allow(clippy::too_many_lines)]
 	pub(crate) fn serialize(&self, write: &mut impl io::Write) -> FResult<()> {
 		match self {
 			Self::Literal(x) => {
@@ -254,60 +363,142 @@ impl Expr {
 				a.serialize(write)?;
 				b.serialize(write)?;
 			}
+			Self::List(items) => {
+				17u8.serialize(write)?;
+				items.len().serialize(write)?;
+				for item in items {
+					item.serialize(write)?;
+				}
+			}
+			Self::Comparison(op, a, b) => {
+				18u8.serialize(write)?;
+				op.serialize(write)?;
+				a.serialize(write)?;
+				b.serialize(write)?;
+			}
+			Self::If(cond, a, b) => {
+				19u8.serialize(write)?;
+				cond.serialize(write)?;
+				a.serialize(write)?;
+				b.serialize(write)?;
+			}
+			Self::Unset(a) => {
+				20u8.serialize(write)?;
+				a.serialize(write)?;
+			}
+			Self::NextWeekday(a) => {
+				21u8.serialize(write)?;
+				a.serialize(write)?;
+			}
+			Self::DaysUntil(a) => {
+				22u8.serialize(write)?;
+				a.serialize(write)?;
+			}
+			Self::DefineUnit(name, definition, body) => {
+				23u8.serialize(write)?;
+				name.serialize(write)?;
+				definition.serialize(write)?;
+				body.serialize(write)?;
+			}
+			Self::FloatBits(single_precision, a) => {
+				24u8.serialize(write)?;
+				single_precision.serialize(write)?;
+				a.serialize(write)?;
+			}
+			Self::PercentChange(increase, a) => {
+				25u8.serialize(write)?;
+				increase.serialize(write)?;
+				a.serialize(write)?;
+			}
 		}
 		Ok(())
 	}
 
-	pub(crate) fn deserialize(read: &mut impl io::Read) -> FResult<Self> {
+	pub(crate) fn deserialize(read: &mut impl io::Read, depth: usize) -> FResult<Self> {
+		let depth = check_nesting_depth(depth)?;
 		Ok(match u8::deserialize(read)? {
-			0 => Self::Literal(Value::deserialize(read)?),
+			0 => Self::Literal(Value::deserialize(read, depth)?),
 			1 => Self::Ident(Ident::deserialize(read)?),
-			2 => Self::Parens(Box::new(Self::deserialize(read)?)),
-			3 => Self::UnaryMinus(Box::new(Self::deserialize(read)?)),
-			4 => Self::UnaryPlus(Box::new(Self::deserialize(read)?)),
-			5 => Self::UnaryDiv(Box::new(Self::deserialize(read)?)),
-			6 => Self::Factorial(Box::new(Self::deserialize(read)?)),
+			2 => Self::Parens(Box::new(Self::deserialize(read, depth)?)),
+			3 => Self::UnaryMinus(Box::new(Self::deserialize(read, depth)?)),
+			4 => Self::UnaryPlus(Box::new(Self::deserialize(read, depth)?)),
+			5 => Self::UnaryDiv(Box::new(Self::deserialize(read, depth)?)),
+			6 => Self::Factorial(Box::new(Self::deserialize(read, depth)?)),
 			7 => Self::Bop(
 				Bop::deserialize(read)?,
-				Box::new(Self::deserialize(read)?),
-				Box::new(Self::deserialize(read)?),
+				Box::new(Self::deserialize(read, depth)?),
+				Box::new(Self::deserialize(read, depth)?),
 			),
 			8 => Self::Apply(
-				Box::new(Self::deserialize(read)?),
-				Box::new(Self::deserialize(read)?),
+				Box::new(Self::deserialize(read, depth)?),
+				Box::new(Self::deserialize(read, depth)?),
 			),
 			9 => Self::ApplyFunctionCall(
-				Box::new(Self::deserialize(read)?),
-				Box::new(Self::deserialize(read)?),
+				Box::new(Self::deserialize(read, depth)?),
+				Box::new(Self::deserialize(read, depth)?),
 			),
 			10 => Self::ApplyMul(
-				Box::new(Self::deserialize(read)?),
-				Box::new(Self::deserialize(read)?),
+				Box::new(Self::deserialize(read, depth)?),
+				Box::new(Self::deserialize(read, depth)?),
 			),
 			11 => Self::As(
-				Box::new(Self::deserialize(read)?),
-				Box::new(Self::deserialize(read)?),
+				Box::new(Self::deserialize(read, depth)?),
+				Box::new(Self::deserialize(read, depth)?),
 			),
 			12 => Self::Fn(
 				Ident::deserialize(read)?,
-				Box::new(Self::deserialize(read)?),
+				Box::new(Self::deserialize(read, depth)?),
 			),
 			13 => Self::Of(
 				Ident::deserialize(read)?,
-				Box::new(Self::deserialize(read)?),
+				Box::new(Self::deserialize(read, depth)?),
 			),
 			14 => Self::Assign(
 				Ident::deserialize(read)?,
-				Box::new(Self::deserialize(read)?),
+				Box::new(Self::deserialize(read, depth)?),
 			),
 			15 => Self::Statements(
-				Box::new(Self::deserialize(read)?),
-				Box::new(Self::deserialize(read)?),
+				Box::new(Self::deserialize(read, depth)?),
+				Box::new(Self::deserialize(read, depth)?),
 			),
 			16 => Self::Equality(
 				bool::deserialize(read)?,
-				Box::new(Self::deserialize(read)?),
-				Box::new(Self::deserialize(read)?),
+				Box::new(Self::deserialize(read, depth)?),
+				Box::new(Self::deserialize(read, depth)?),
+			),
+			17 => Self::List({
+				let len = usize::deserialize(read)?;
+				let mut v = vec![];
+				for _ in 0..len {
+					v.push(Self::deserialize(read, depth)?);
+				}
+				v
+			}),
+			18 => Self::Comparison(
+				ComparisonOp::deserialize(read)?,
+				Box::new(Self::deserialize(read, depth)?),
+				Box::new(Self::deserialize(read, depth)?),
+			),
+			19 => Self::If(
+				Box::new(Self::deserialize(read, depth)?),
+				Box::new(Self::deserialize(read, depth)?),
+				Box::new(Self::deserialize(read, depth)?),
+			),
+			20 => Self::Unset(Ident::deserialize(read)?),
+			21 => Self::NextWeekday(DayOfWeek::deserialize(read)?),
+			22 => Self::DaysUntil(Box::new(Self::deserialize(read, depth)?)),
+			23 => Self::DefineUnit(
+				Ident::deserialize(read)?,
+				Box::new(Self::deserialize(read, depth)?),
+				Box::new(Self::deserialize(read, depth)?),
+			),
+			24 => Self::FloatBits(
+				bool::deserialize(read)?,
+				Box::new(Self::deserialize(read, depth)?),
+			),
+			25 => Self::PercentChange(
+				bool::deserialize(read)?,
+				Box::new(Self::deserialize(read, depth)?),
 			),
 			_ => return Err(FendError::DeserializationError),
 		})
@@ -372,6 +563,46 @@ impl Expr {
 				if *is_equals { "==" } else { "!=" },
 				b.format(attrs, ctx, int)?
 			),
+			Self::List(items) => {
+				let mut res = "(".to_string();
+				for (i, item) in items.iter().enumerate() {
+					if i != 0 {
+						res.push_str(", ");
+					}
+					res.push_str(&item.format(attrs, ctx, int)?);
+				}
+				res.push(')');
+				res
+			}
+			Self::Comparison(op, a, b) => format!(
+				"{} {op} {}",
+				a.format(attrs, ctx, int)?,
+				b.format(attrs, ctx, int)?
+			),
+			Self::If(cond, a, b) => format!(
+				"if {} then {} else {}",
+				cond.format(attrs, ctx, int)?,
+				a.format(attrs, ctx, int)?,
+				b.format(attrs, ctx, int)?
+			),
+			Self::Unset(a) => format!("unset {a}"),
+			Self::NextWeekday(a) => format!("next {a}"),
+			Self::DaysUntil(a) => format!("days until {}", a.format(attrs, ctx, int)?),
+			Self::DefineUnit(name, definition, body) => format!(
+				"{} where {name} = {}",
+				body.format(attrs, ctx, int)?,
+				definition.format(attrs, ctx, int)?
+			),
+			Self::FloatBits(single_precision, a) => format!(
+				"float{} bits of {}",
+				if *single_precision { 32 } else { 64 },
+				a.format(attrs, ctx, int)?
+			),
+			Self::PercentChange(increase, a) => format!(
+				"% {} {}",
+				if *increase { "on" } else { "off" },
+				a.format(attrs, ctx, int)?
+			),
 		})
 	}
 }
@@ -424,13 +655,10 @@ pub(crate) fn evaluate<I: Interrupt>(
 			Expr::Factorial,
 			scope,
 		)?,
-		Expr::Bop(Bop::Plus, a, b) => evaluate_add(
-			eval!(*a)?,
-			eval!(*b)?,
-			scope,
-			context.decimal_separator,
-			int,
-		)?,
+		Expr::Bop(Bop::Plus, a, b) => match eval!(*a)? {
+			Value::DateTime(a) => add_to_date_time(a, eval!(*b)?, false, attrs, context, int)?,
+			a => evaluate_add(a, eval!(*b)?, scope, context.decimal_separator, int)?,
+		},
 		Expr::Bop(Bop::Minus, a, b) => {
 			let a = eval!(*a)?;
 			match a {
@@ -440,6 +668,7 @@ pub(crate) fn evaluate<I: Interrupt>(
 					int,
 				)?)),
 				Value::Date(a) => a.sub(eval!(*b)?, int)?,
+				Value::DateTime(a) => add_to_date_time(a, eval!(*b)?, true, attrs, context, int)?,
 				f @ (Value::BuiltInFunction(_) | Value::Fn(_, _, _)) => f.apply(
 					Expr::UnaryMinus(b),
 					ApplyMulHandling::OnlyApply,
@@ -509,6 +738,29 @@ pub(crate) fn evaluate<I: Interrupt>(
 						scope.clone(),
 					)?
 					.apply(*expr, ApplyMulHandling::Both, scope, attrs, context, int)?,
+				// `15% off 200` is `200 * (1 - 15/100)`, and `15% on 200` is
+				// `200 * (1 + 15/100)`
+				(a, Expr::PercentChange(increase, expr)) => {
+					let bop = if increase { Bop::Plus } else { Bop::Minus };
+					let literal =
+						|n: u64| Box::new(Expr::Literal(Value::Num(Box::new(Number::from(n)))));
+					eval!(a)?
+						.handle_num(
+							|x| {
+								let fraction = x.div(Number::from(100), int)?;
+								Number::from(1).bop(bop, fraction, attrs, context, int)
+							},
+							|f| {
+								Expr::Bop(
+									bop,
+									literal(1),
+									Box::new(Expr::Bop(Bop::Div, f, literal(100))),
+								)
+							},
+							scope.clone(),
+						)?
+						.apply(*expr, ApplyMulHandling::Both, scope, attrs, context, int)?
+				}
 				(a, b) => eval!(a)?.apply(b, ApplyMulHandling::Both, scope, attrs, context, int)?,
 			}
 		}
@@ -520,7 +772,19 @@ pub(crate) fn evaluate<I: Interrupt>(
 		Expr::Of(a, b) => eval!(*b)?.get_object_member(&a)?,
 		Expr::Assign(a, b) => {
 			let rhs = evaluate(*b, scope, attrs, context, int)?;
-			context.variables.insert(a.to_string(), rhs.clone());
+			let name = a.to_string();
+			if let Some(max) = context.max_variables {
+				// `_` and `ans` are set automatically, so they don't count
+				let count = context
+					.variables
+					.keys()
+					.filter(|k| !matches!(k.as_str(), "_" | "ans"))
+					.count();
+				if count >= max && !context.variables.contains_key(&name) {
+					return Err(FendError::TooManyVariables(max));
+				}
+			}
+			context.variables.insert(name, rhs.clone());
 			rhs
 		}
 		Expr::Statements(a, b) => {
@@ -535,9 +799,156 @@ pub(crate) fn evaluate<I: Interrupt>(
 				Some(cmp::Ordering::Greater | cmp::Ordering::Less) | None => !is_equals,
 			})
 		}
+		Expr::List(items) => {
+			let mut values = Vec::with_capacity(items.len());
+			for item in items {
+				values.push(eval!(item)?);
+			}
+			Value::List(values)
+		}
+		Expr::Comparison(op, a, b) => {
+			let lhs = evaluate(*a, scope.clone(), attrs, context, int)?;
+			let rhs = evaluate(*b, scope, attrs, context, int)?;
+			match lhs.compare(&rhs, context, int)? {
+				Some(ordering) => Value::Bool(op.matches(ordering)),
+				None => return Err(FendError::UnableToCompare),
+			}
+		}
+		Expr::If(cond, a, b) => {
+			if eval!(*cond)?.as_bool()? {
+				evaluate(*a, scope, attrs, context, int)?
+			} else {
+				evaluate(*b, scope, attrs, context, int)?
+			}
+		}
+		Expr::Unset(a) => {
+			// unsetting an undefined variable is not an error
+			context.variables.remove(a.as_str());
+			Value::Unit
+		}
+		Expr::NextWeekday(a) => Value::Date(Date::today(context)?.next_weekday(a)),
+		Expr::DaysUntil(a) => {
+			let Value::Date(date) = eval!(*a)? else {
+				return Err(FendError::ExpectedADate);
+			};
+			let days = Date::today(context)?.days_until(date);
+			let days_unit = crate::units::query_unit_static("days", attrs, context, int)?;
+			let num_days = Number::from(days.unsigned_abs()).mul(days_unit.expect_num()?, int)?;
+			Value::Num(Box::new(if days < 0 { -num_days } else { num_days }))
+		}
+		Expr::DefineUnit(name, definition, body) => {
+			define_inline_unit(&name, &definition, scope.clone(), attrs, context, int)?;
+			evaluate(*body, scope, attrs, context, int)?
+		}
+		// only valid after a percentage, e.g. `15% off 200`
+		Expr::PercentChange(_, _) => return Err(FendError::ExpectedANumber),
+		Expr::FloatBits(single_precision, a) => {
+			Value::Num(Box::new(eval!(*a)?.expect_num()?.into_float_bits(
+				single_precision,
+				context.decimal_separator,
+				int,
+			)?))
+		}
 	})
 }
 
+/// Defines a custom unit from `<expr> where <unit> = <definition>`. Like
+/// variables, the unit stays defined for later calculations.
+fn define_inline_unit<I: Interrupt>(
+	name: &Ident,
+	definition: &Expr,
+	scope: Option<Arc<Scope>>,
+	attrs: Attrs,
+	context: &mut crate::Context,
+	int: &I,
+) -> FResult<()> {
+	let is_variable = context.variables.contains_key(name.as_str())
+		|| match &scope {
+			Some(scope) => scope.get(name, attrs, context, int)?.is_some(),
+			None => false,
+		};
+	let name = name.as_str();
+	if !crate::lexer::is_valid_identifier(name) {
+		return Err(FendError::InvalidUnitName(name.to_string()));
+	}
+	// units defined with `where` may be redefined, but built-in units,
+	// functions and variables may not
+	let mut builtin_context = crate::Context::new();
+	if is_variable
+		|| crate::units::query_unit_static(name, attrs, &mut builtin_context, int).is_ok()
+		|| !matches!(
+			resolve_builtin_identifier(
+				&Ident::new_string(name.to_string()),
+				None,
+				attrs,
+				&mut builtin_context,
+				int,
+			),
+			Err(FendError::IdentifierNotFound(_))
+		) {
+		return Err(FendError::CannotRedefineUnit(name.to_string()));
+	}
+	// report errors in the definition now, rather than when the unit is used
+	let value = evaluate(definition.clone(), scope, attrs, context, int)?.expect_num()?;
+	if value.is_unitless(int)? {
+		return Err(FendError::InlineUnitWithoutUnit(name.to_string()));
+	}
+	let definition = definition.format(attrs, context, int)?;
+	// `widget` can also be written as `widgets`, but abbreviations like `ft`
+	// have no plural
+	let plural = if name.chars().count() <= 2
+		|| !name.chars().all(char::is_alphabetic)
+		|| name.ends_with('s')
+	{
+		String::new()
+	} else {
+		format!("{name}s")
+	};
+	context
+		.custom_units
+		.retain(|(s, p, _)| ![name, plural.as_str()].iter().any(|n| n == s || n == p));
+	context
+		.custom_units
+		.push((name.to_string(), plural, definition));
+	Ok(())
+}
+
+/// Adds (or subtracts) a time like `90 minutes` to a date and time. Months
+/// and years are added to the date, so the time of day stays the same.
+fn add_to_date_time<I: Interrupt>(
+	date_time: DateTime,
+	rhs: Value,
+	subtract: bool,
+	attrs: Attrs,
+	context: &mut crate::Context,
+	int: &I,
+) -> FResult<Value> {
+	let rhs = rhs.expect_num()?;
+	if rhs.unit_equal_to("month", int)? || rhs.unit_equal_to("year", int)? {
+		let rhs = Value::Num(Box::new(rhs));
+		let date = if subtract {
+			date_time.date().sub(rhs, int)?
+		} else {
+			date_time.date().add(rhs, int)?
+		};
+		let Value::Date(date) = date else {
+			return Err(FendError::ExpectedADate);
+		};
+		return Ok(Value::DateTime(date_time.with_date(date)));
+	}
+	let second = crate::units::query_unit_static("second", attrs, context, int)?.expect_num()?;
+	let ds = context.decimal_separator;
+	let mut seconds = rhs
+		.convert_to(second.clone(), ds, int)?
+		.div(second, int)?
+		.into_unitless_complex(ds, int)?
+		.try_as_i64(int)?;
+	if subtract {
+		seconds = seconds.checked_neg().ok_or(FendError::ValueTooLarge)?;
+	}
+	Ok(Value::DateTime(date_time.add_seconds(seconds)?))
+}
+
 fn evaluate_add<I: Interrupt>(
 	a: Value,
 	b: Value,
@@ -585,13 +996,159 @@ fn evaluate_add<I: Interrupt>(
 
 #[allow(clippy::too_many_lines)]
 fn evaluate_as<I: Interrupt>(
-	a: Expr,
+	mut a: Expr,
 	b: Expr,
 	scope: Option<Arc<Scope>>,
 	attrs: Attrs,
 	context: &mut crate::Context,
 	int: &I,
 ) -> FResult<Value> {
+	if let Expr::Apply(target, modifier) = &b {
+		// `to <unit> showing work` lists the conversion factors
+		if let (Expr::Apply(target, showing), Expr::Ident(work)) = (&**target, &**modifier) {
+			if matches!(&**showing, Expr::Ident(i) if i.as_str() == "showing")
+				&& work.as_str() == "work"
+			{
+				let value = evaluate(a, scope.clone(), attrs, context, int)?;
+				let result = evaluate_as(
+					Expr::Literal(value.clone()),
+					(**target).clone(),
+					scope,
+					attrs,
+					context,
+					int,
+				)?;
+				if let (Value::Num(from), Value::Num(to)) = (&value, &result) {
+					if let Some(work) = from.conversion_work(to, context, int)? {
+						context.other_info.push(work);
+					}
+				}
+				return Ok(result);
+			}
+		}
+		// `to ordinal number` writes e.g. "21st"
+		if matches!(&**target, Expr::Ident(i) if i.as_str() == "ordinal")
+			&& matches!(&**modifier, Expr::Ident(i) if i.as_str() == "number")
+		{
+			let uint = evaluate(a, scope, attrs, context, int)?
+				.expect_num()?
+				.into_unitless_complex(context.decimal_separator, int)?
+				.try_as_real()?
+				.try_as_biguint(int)?;
+			return Ok(Value::String(borrow::Cow::Owned(
+				uint.to_ordinal_number(int)?,
+			)));
+		}
+		// `to <unit> exact` only succeeds if the result is exact
+		if matches!(&**modifier, Expr::Ident(i) if i.as_str() == "exact") {
+			let value = evaluate_as(a, (**target).clone(), scope, attrs, context, int)?;
+			if let Value::Num(n) = &value {
+				if !n.is_displayed_exactly(context, int)? {
+					return Err(FendError::ConversionNotExact);
+				}
+			}
+			return Ok(value);
+		}
+	}
+	// `to /16` rounds to the nearest sixteenth
+	if let Expr::UnaryDiv(den) = &b {
+		let den = evaluate((**den).clone(), scope.clone(), attrs, context, int)?
+			.expect_num()?
+			.into_unitless_complex(context.decimal_separator, int)?
+			.try_as_real()?
+			.try_as_biguint(int)?;
+		let value = evaluate(a, scope, attrs, context, int)?.expect_num()?;
+		return Ok(Value::Num(Box::new(value.round_to_denominator(&den, int)?)));
+	}
+	if let Some((base, digits)) = as_base_with_digits(&b) {
+		let Value::Base(base) = evaluate(base.clone(), scope.clone(), attrs, context, int)? else {
+			return Err(FendError::DigitsRequireABase);
+		};
+		let digits = evaluate(digits.clone(), scope.clone(), attrs, context, int)?
+			.expect_num()?
+			.try_as_usize(context.decimal_separator, int)?;
+		if !(1..=Base::MAX_MIN_DIGITS).contains(&digits) {
+			return Err(FendError::OutOfRange {
+				value: Box::new(digits),
+				range: Range {
+					start: RangeBound::Closed(Box::new(1)),
+					end: RangeBound::Closed(Box::new(Base::MAX_MIN_DIGITS)),
+				},
+			});
+		}
+		return Ok(Value::Num(Box::new(
+			evaluate(a, scope, attrs, context, int)?
+				.expect_num()?
+				.with_base(base.with_min_digits(digits)),
+		)));
+	}
+	if let Some((width, base)) = as_bit_width_and_base(&b) {
+		let width = evaluate(width.clone(), scope.clone(), attrs, context, int)?
+			.expect_num()?
+			.try_as_usize(context.decimal_separator, int)?;
+		// the result is padded to the full width, so this uses the same
+		// limit as `binary with N digits`
+		if !(1..=Base::MAX_MIN_DIGITS).contains(&width) {
+			return Err(FendError::OutOfRange {
+				value: Box::new(width),
+				range: Range {
+					start: RangeBound::Closed(Box::new(1)),
+					end: RangeBound::Closed(Box::new(Base::MAX_MIN_DIGITS)),
+				},
+			});
+		}
+		let Value::Base(base) = evaluate(base.clone(), scope.clone(), attrs, context, int)? else {
+			return Err(FendError::BitWidthRequiresABase);
+		};
+		return Ok(Value::Num(Box::new(
+			evaluate(a, scope, attrs, context, int)?
+				.expect_num()?
+				.into_twos_complement(width as u64, base, context.decimal_separator, int)?,
+		)));
+	}
+	if let Some(zone) = as_time_zone(&b) {
+		let value = evaluate(a, scope.clone(), attrs, context, int)?;
+		if let Value::DateTime(date_time) = &value {
+			let target = match zone {
+				TimeZoneExpr::Named(name) => TimeZoneTarget::Named(name),
+				TimeZoneExpr::Utc(None) => TimeZoneTarget::FixedOffset(0),
+				TimeZoneExpr::Utc(Some((offset, negative))) => {
+					// the offset is given in hours
+					let offset_secs = evaluate(offset.clone(), scope.clone(), attrs, context, int)?
+						.expect_num()?
+						.mul(Number::from(3600), int)?
+						.into_unitless_complex(context.decimal_separator, int)?
+						.try_as_i64(int)?;
+					TimeZoneTarget::FixedOffset(if negative { -offset_secs } else { offset_secs })
+				}
+			};
+			let name = match &target {
+				TimeZoneTarget::Named(name) => Some(name.clone()),
+				TimeZoneTarget::FixedOffset(_) => None,
+			};
+			return match date_time.to_time_zone(target, context)? {
+				Some(converted) => Ok(Value::DateTime(converted)),
+				None => Err(FendError::UnknownTimeZone(name.unwrap_or_default())),
+			};
+		}
+		if let Value::Date(_) = &value {
+			return Err(FendError::TimeZoneRequiresATime);
+		}
+		a = Expr::Literal(value);
+	}
+	// `to permille` uses the per mille sign, just like `to %`
+	let b = match b {
+		Expr::Ident(ident) if matches!(ident.as_str(), "permille" | "per_mille") => {
+			Expr::Ident(Ident::new_str("\u{2030}"))
+		}
+		b => b,
+	};
+	if is_hex_float(&b) {
+		let hex = evaluate(a, scope, attrs, context, int)?
+			.expect_num()?
+			.into_hex_float(context.decimal_separator, int)?;
+		return Ok(Value::String(borrow::Cow::Owned(hex)));
+	}
 	if let Expr::Ident(ident) = &b {
 		match ident.as_str() {
 			"bool" | "boolean" => {
@@ -652,7 +1209,8 @@ fn evaluate_as<I: Interrupt>(
 				if a == 0 {
 					return Err(FendError::RomanNumeralZero);
 				}
-				let upper_limit = 1_000_000_000;
+				// largest number that can be written using double overlines
+				let upper_limit = 3_999_999_999;
 				if a > upper_limit {
 					return Err(FendError::OutOfRange {
 						value: Box::new(a),
@@ -664,13 +1222,37 @@ fn evaluate_as<I: Interrupt>(
 				}
 				return Ok(Value::String(borrow::Cow::Owned(to_roman(a, true))));
 			}
+			"duration" => {
+				let second = evaluate_to_value("second", scope.clone(), attrs, context, int)?
+					.expect_num()?;
+				let duration = evaluate(a, scope, attrs, context, int)?
+					.expect_num()?
+					.format_as_duration(second, context, int)?;
+				return Ok(Value::String(borrow::Cow::Owned(duration)));
+			}
 			"words" => {
+				// the base is deliberately ignored here, since English number
+				// words always describe the (base 10) value
+				let words = evaluate(a, scope, attrs, context, int)?
+					.expect_num()?
+					.into_words(context.decimal_separator, int)?;
+				return Ok(Value::String(borrow::Cow::Owned(words)));
+			}
+			"continued_fraction" => {
+				let fraction = evaluate(a, scope, attrs, context, int)?
+					.expect_num()?
+					.into_continued_fraction(context.decimal_separator, int)?;
+				return Ok(Value::String(borrow::Cow::Owned(fraction)));
+			}
+			"ordinal" => {
 				let uint = evaluate(a, scope, attrs, context, int)?
 					.expect_num()?
 					.into_unitless_complex(context.decimal_separator, int)?
 					.try_as_real()?
 					.try_as_biguint(int)?;
-				return Ok(Value::String(borrow::Cow::Owned(uint.to_words(int)?)));
+				return Ok(Value::String(borrow::Cow::Owned(
+					uint.to_ordinal_words(int)?,
+				)));
 			}
 			_ => (),
 		}
@@ -692,10 +1274,24 @@ fn evaluate_as<I: Interrupt>(
 		Value::Sf => {
 			return Err(FendError::SpecifyNumSf);
 		}
-		Value::Base(base) => Value::Num(Box::new(
+		Value::Base(base) => match evaluate(a, scope, attrs, context, int)? {
+			// e.g. `factorize 360 to hex`
+			Value::Map(kv) => Value::Map(
+				kv.into_iter()
+					.map(|(k, v)| {
+						Ok((
+							Value::Num(Box::new(k.expect_num()?.with_base(base))),
+							Value::Num(Box::new(v.expect_num()?.with_base(base))),
+						))
+					})
+					.collect::<FResult<_>>()?,
+			),
+			a => Value::Num(Box::new(a.expect_num()?.with_base(base))),
+		},
+		Value::Grouped => Value::Num(Box::new(
 			evaluate(a, scope, attrs, context, int)?
 				.expect_num()?
-				.with_base(base),
+				.with_digit_grouping(),
 		)),
 		other => {
 			return Err(FendError::CannotConvertValueTo(other.type_name()));
@@ -703,6 +1299,92 @@ fn evaluate_as<I: Interrupt>(
 	})
 }
 
+/// Matches the conversion targets `hex float`, `hex_float` and `hexfloat`
+fn is_hex_float(expr: &Expr) -> bool {
+	match expr {
+		Expr::Ident(ident) => matches!(ident.as_str(), "hex_float" | "hexfloat"),
+		Expr::Apply(lhs, rhs) | Expr::ApplyMul(lhs, rhs) => matches!(
+			(&**lhs, &**rhs),
+			(Expr::Ident(hex), Expr::Ident(float))
+				if matches!(hex.as_str(), "hex" | "hexadecimal") && float.as_str() == "float"
+		),
+		_ => false,
+	}
+}
+
+/// Matches two's complement conversion targets like `8 bit hex` or
+/// `16 bits binary`, returning the width and the base
+fn as_bit_width_and_base(expr: &Expr) -> Option<(&Expr, &Expr)> {
+	let Expr::ApplyMul(lhs, base) = expr else {
+		return None;
+	};
+	let Expr::ApplyMul(width, bit) = &**lhs else {
+		return None;
+	};
+	if !matches!(&**bit, Expr::Ident(i) if i.as_str() == "bit" || i.as_str() == "bits") {
+		return None;
+	}
+	Some((width, base))
+}
+
+/// Matches conversion targets like `binary with 8 digits`, returning the
+/// base and the number of digits
+fn as_base_with_digits(expr: &Expr) -> Option<(&Expr, &Expr)> {
+	let Expr::Apply(lhs, suffix) = expr else {
+		return None;
+	};
+	if !matches!(&**suffix, Expr::Ident(i) if i.as_str() == "digits" || i.as_str() == "digit") {
+		return None;
+	}
+	let (Expr::Apply(lhs, digits) | Expr::ApplyFunctionCall(lhs, digits)) = &**lhs else {
+		return None;
+	};
+	let Expr::Apply(base, with) = &**lhs else {
+		return None;
+	};
+	if !matches!(&**with, Expr::Ident(i) if i.as_str() == "with") {
+		return None;
+	}
+	Some((base, digits))
+}
+
+enum TimeZoneExpr<'a> {
+	/// e.g. `America/New_York`
+	Named(String),
+	/// `UTC`, optionally with an offset in hours and whether it's negative,
+	/// e.g. `UTC+2` or `UTC-5`
+	Utc(Option<(&'a Expr, bool)>),
+}
+
+/// Matches conversion targets that look like time zones, e.g. `UTC`, `UTC+2`
+/// or `Europe/London`
+fn as_time_zone(expr: &Expr) -> Option<TimeZoneExpr<'_>> {
+	fn zone_name(expr: &Expr) -> Option<String> {
+		match expr {
+			Expr::Ident(ident) => Some(ident.as_str().to_string()),
+			Expr::Bop(Bop::Div, lhs, rhs) => {
+				Some(format!("{}/{}", zone_name(lhs)?, zone_name(rhs)?))
+			}
+			_ => None,
+		}
+	}
+	let is_utc =
+		|expr: &Expr| matches!(expr, Expr::Ident(i) if i.as_str() == "UTC" || i.as_str() == "GMT");
+	match expr {
+		Expr::Bop(Bop::Plus, lhs, rhs) if is_utc(lhs) => {
+			Some(TimeZoneExpr::Utc(Some((rhs, false))))
+		}
+		Expr::Bop(Bop::Minus, lhs, rhs) if is_utc(lhs) => {
+			Some(TimeZoneExpr::Utc(Some((rhs, true))))
+		}
+		_ if is_utc(expr) => Some(TimeZoneExpr::Utc(None)),
+		// named time zones always contain a slash, so that e.g. `to string`
+		// isn't mistaken for a time zone
+		Expr::Bop(Bop::Div, _, _) => zone_name(expr).map(TimeZoneExpr::Named),
+		_ => None,
+	}
+}
+
 pub(crate) fn resolve_identifier<I: Interrupt>(
 	ident: &Ident,
 	scope: Option<Arc<Scope>>,
@@ -719,6 +1401,25 @@ pub(crate) fn resolve_identifier<I: Interrupt>(
 	if let Some(val) = context.variables.get(ident.as_str()) {
 		return Ok(val.clone());
 	}
+	// `carbon-14` is a subtraction rather than an isotope if `carbon` is a variable
+	if let Some((element, mass_number)) = split_isotope(ident.as_str()) {
+		let is_variable = context.variables.contains_key(element)
+			|| match &scope {
+				Some(scope) => scope
+					.get(&Ident::new_string(element.to_string()), attrs, context, int)?
+					.is_some(),
+				None => false,
+			};
+		if is_variable {
+			return evaluate_to_value(
+				&format!("({element}) - {mass_number}"),
+				scope,
+				attrs,
+				context,
+				int,
+			);
+		}
+	}
 
 	let builtin_result = resolve_builtin_identifier(ident, cloned_scope, attrs, context, int);
 	if !matches!(builtin_result, Err(FendError::IdentifierNotFound(_))) {
@@ -743,10 +1444,146 @@ pub(crate) fn resolve_identifier<I: Interrupt>(
 		context,
 		int,
 	);
+	if lowercase_builtin_result.is_ok() {
+		return lowercase_builtin_result;
+	}
+	// ISO 8601 durations like `PT1H30M`
+	if let Some(duration) = ident.as_str().strip_prefix('P') {
+		if duration.starts_with(|c: char| c.is_ascii_digit() || c == 'T') {
+			let parts = parse_iso_8601_duration(duration)
+				.ok_or_else(|| FendError::InvalidIsoDuration(ident.to_string()))?;
+			return evaluate_to_value(&parts, None, attrs, context, int);
+		}
+	}
+	// unknown identifiers like `MCMLXV` are interpreted as roman numerals
+	if ident.as_str().bytes().all(|b| b"IVXLCDM".contains(&b)) {
+		return from_roman(ident.as_str())
+			.map(|n| Value::Num(Box::new(Number::from(n))))
+			.ok_or_else(|| FendError::InvalidRomanNumeral(ident.to_string()));
+	}
 	// "Unknown identifier" errors should use the uppercase ident.
-	lowercase_builtin_result.or(unit_result)
+	unit_result
+}
+
+/// Names of all built-in functions handled by [`resolve_builtin_identifier`],
+/// sorted alphabetically
+pub(crate) const BUILTIN_FUNCTIONS: &[&str] = &[
+	"Im",
+	"Re",
+	"abs",
+	"acos",
+	"acosh",
+	"approx.",
+	"approximately",
+	"arg",
+	"asin",
+	"asinh",
+	"atan",
+	"atan2",
+	"atanh",
+	"average",
+	"base",
+	"beta",
+	"bitlength",
+	"byteswap",
+	"cbrt",
+	"ceil",
+	"cis",
+	"clamp",
+	"conjugate",
+	"cos",
+	"cosh",
+	"cubic",
+	"exp",
+	"factorise",
+	"factorize",
+	"fib",
+	"fibonacci",
+	"floor",
+	"gcd",
+	"hypot",
+	"im",
+	"imag",
+	"inv",
+	"isqrt",
+	"lcm",
+	"lgamma",
+	"ln",
+	"log",
+	"log10",
+	"log2",
+	"log_gamma",
+	"mean",
+	"median",
+	"mode",
+	"nextprime",
+	"not",
+	"popcount",
+	"prevprime",
+	"quadratic",
+	"re",
+	"real",
+	"reciprocal",
+	"roll",
+	"round",
+	"sample",
+	"sign",
+	"signum",
+	"sin",
+	"sinh",
+	"sqrt",
+	"square",
+	"std",
+	"stdev",
+	"tan",
+	"tanh",
+	"variance",
+];
+
+/// Names of the built-in constants handled by [`resolve_builtin_identifier`],
+/// sorted alphabetically. Other constants (e.g. `planck`) are defined as units.
+pub(crate) const BUILTIN_CONSTANTS: &[&str] = &["e", "i", "phi", "pi", "tau", "\u{3c0}", "\u{3c4}"];
+
+/// Half-lives of some well-known radioactive isotopes, which can be looked up
+/// like `half-life of carbon-14`
+const ISOTOPES: &[(&str, usize, &str)] = &[
+	("hydrogen", 3, "12.32 years"),
+	("carbon", 14, "5700 years"),
+	("potassium", 40, "1.248e9 years"),
+	("cobalt", 60, "5.2714 years"),
+	("strontium", 90, "28.91 years"),
+	("iodine", 131, "8.0252 days"),
+	("caesium", 137, "30.08 years"),
+	("cesium", 137, "30.08 years"),
+	("radon", 222, "3.8235 days"),
+	("radium", 226, "1600 years"),
+	("thorium", 232, "1.40e10 years"),
+	("uranium", 235, "7.04e8 years"),
+	("uranium", 238, "4.468e9 years"),
+	("plutonium", 239, "24110 years"),
+];
+
+/// Whether isotopes of this element are known, so that e.g. `carbon-14`
+/// should be parsed as an isotope rather than a subtraction
+pub(crate) fn is_isotope_element(name: &str) -> bool {
+	ISOTOPES.iter().any(|(element, _, _)| *element == name)
 }
 
+/// Splits isotopes like `carbon-14` into the element and the mass number
+fn split_isotope(name: &str) -> Option<(&str, &str)> {
+	name.split_once('-')
+		.filter(|(element, _)| is_isotope_element(element))
+}
+
+fn isotope_half_life(name: &str) -> Option<&'static str> {
+	let (element, mass_number) = split_isotope(name)?;
+	ISOTOPES
+		.iter()
+		.find(|(e, m, _)| *e == element && m.to_string() == mass_number)
+		.map(|(_, _, half_life)| *half_life)
+}
+
+#[allow(clippy::too_many_lines)]
 fn resolve_builtin_identifier<I: Interrupt>(
 	ident: &Ident,
 	scope: Option<Arc<Scope>>,
@@ -775,6 +1612,10 @@ fn resolve_builtin_identifier<I: Interrupt>(
 		"false" => Value::Bool(false),
 		"sample" | "roll" => Value::BuiltInFunction(BuiltInFunction::Sample),
 		"mean" | "average" => Value::BuiltInFunction(BuiltInFunction::Mean),
+		"median" => Value::BuiltInFunction(BuiltInFunction::Median),
+		"mode" => Value::BuiltInFunction(BuiltInFunction::Mode),
+		"variance" => Value::BuiltInFunction(BuiltInFunction::Variance),
+		"stdev" | "std" => evaluate_to_value("x: (variance x)^(1/2)", scope, attrs, context, int)?,
 		"sqrt" => evaluate_to_value("x: x^(1/2)", scope, attrs, context, int)?,
 		"cbrt" => evaluate_to_value("x: x^(1/3)", scope, attrs, context, int)?,
 		"real" | "re" | "Re" => Value::BuiltInFunction(BuiltInFunction::Real),
@@ -806,10 +1647,27 @@ fn resolve_builtin_identifier<I: Interrupt>(
 			int,
 		)?,
 		"ln" => Value::BuiltInFunction(BuiltInFunction::Ln),
+		"lgamma" | "log_gamma" => Value::BuiltInFunction(BuiltInFunction::Lgamma),
 		"log2" => Value::BuiltInFunction(BuiltInFunction::Log2),
 		"log" | "log10" => Value::BuiltInFunction(BuiltInFunction::Log10),
 		"not" => Value::BuiltInFunction(BuiltInFunction::Not),
 		"fib" | "fibonacci" => Value::BuiltInFunction(BuiltInFunction::Fibonacci),
+		"quadratic" => Value::BuiltInFunction(BuiltInFunction::Quadratic),
+		"gcd" => Value::BuiltInFunction(BuiltInFunction::Gcd),
+		"lcm" => Value::BuiltInFunction(BuiltInFunction::Lcm),
+		"isqrt" => Value::BuiltInFunction(BuiltInFunction::Isqrt),
+		"popcount" => Value::BuiltInFunction(BuiltInFunction::Popcount),
+		"bitlength" => Value::BuiltInFunction(BuiltInFunction::Bitlength),
+		"byteswap" => Value::BuiltInFunction(BuiltInFunction::Byteswap),
+		"nextprime" => Value::BuiltInFunction(BuiltInFunction::NextPrime),
+		"factorize" | "factorise" => Value::BuiltInFunction(BuiltInFunction::Factorize),
+		"clamp" => Value::BuiltInFunction(BuiltInFunction::Clamp),
+		"sign" | "signum" => Value::BuiltInFunction(BuiltInFunction::Sign),
+		"reciprocal" | "inv" => Value::BuiltInFunction(BuiltInFunction::Reciprocal),
+		"hypot" => Value::BuiltInFunction(BuiltInFunction::Hypot),
+		"atan2" => Value::BuiltInFunction(BuiltInFunction::Atan2),
+		"beta" => Value::BuiltInFunction(BuiltInFunction::Beta),
+		"prevprime" => Value::BuiltInFunction(BuiltInFunction::PrevPrime),
 		"exp" => evaluate_to_value("x: e^x", scope, attrs, context, int)?,
 		"approx." | "approximately" => Value::BuiltInFunction(BuiltInFunction::Approximately),
 		"auto" => Value::Format(FormattingStyle::Auto),
@@ -817,7 +1675,9 @@ fn resolve_builtin_identifier<I: Interrupt>(
 		"frac" | "fraction" => Value::Format(FormattingStyle::ImproperFraction),
 		"mixed_frac" | "mixed_fraction" => Value::Format(FormattingStyle::MixedFraction),
 		"float" => Value::Format(FormattingStyle::ExactFloat),
+		"scientific" | "sci" => Value::Format(FormattingStyle::Scientific(None)),
 		"dp" => Value::Dp,
+		"grouped" => Value::Grouped,
 		"sf" => Value::Sf,
 		"base" => Value::BuiltInFunction(BuiltInFunction::Base),
 		"dec" | "decimal" => Value::Base(Base::from_plain_base(10)?),
@@ -837,41 +1697,51 @@ fn resolve_builtin_identifier<I: Interrupt>(
 			("mass".into(), eval_box!("5.97237e24 kg")),
 			("volume".into(), eval_box!("1.08321e12 km^3")),
 		]),
+		"now" => Value::DateTime(DateTime::now(context)?),
 		"today" => Value::Date(crate::date::Date::today(context)?),
 		"tomorrow" => Value::Date(crate::date::Date::today(context)?.next()),
 		"yesterday" => Value::Date(crate::date::Date::today(context)?.prev()),
 		"trans" => Value::String(Cow::Borrowed("🏳️‍⚧️")),
-		_ => return Err(FendError::IdentifierNotFound(ident.clone())),
+		name => match isotope_half_life(name) {
+			Some(half_life) => Value::Object(vec![("half_life".into(), eval_box!(half_life))]),
+			None => return Err(FendError::IdentifierNotFound(ident.clone())),
+		},
 	})
 }
 
+const ROMAN_NUMERALS: [(&str, usize); 13] = [
+	("M", 1000),
+	("CM", 900),
+	("D", 500),
+	("CD", 400),
+	("C", 100),
+	("XC", 90),
+	("L", 50),
+	("XL", 40),
+	("X", 10),
+	("IX", 9),
+	("V", 5),
+	("IV", 4),
+	("I", 1),
+];
+
 fn to_roman(mut num: usize, large: bool) -> String {
 	// based on https://stackoverflow.com/a/41358305
 	let mut result = String::new();
-	let values = [
-		("M", 1000),
-		("CM", 900),
-		("D", 500),
-		("CD", 400),
-		("C", 100),
-		("XC", 90),
-		("L", 50),
-		("XL", 40),
-		("X", 10),
-		("IX", 9),
-		("V", 5),
-		("IV", 4),
-		("I", 1),
-	];
+	let values = ROMAN_NUMERALS;
 	if large {
-		for &(r, mut n) in &values[0..values.len() - 1] {
-			n *= 1000;
-			let q = num / n;
-			num -= q * n;
-			for _ in 0..q {
-				for ch in r.chars() {
-					result.push(ch);
-					result.push('\u{305}'); // combining overline
+		// a double overline multiplies the value by 1,000,000, and a single
+		// overline multiplies it by 1000
+		for (scale, overline) in [(1_000_000, '\u{33f}'), (1000, '\u{305}')] {
+			for &(r, n) in &values[0..values.len() - 1] {
+				let n = n * scale;
+				let q = num / n;
+				num -= q * n;
+				for _ in 0..q {
+					for ch in r.chars() {
+						result.push(ch);
+						result.push(overline);
+					}
 				}
 			}
 		}
@@ -885,3 +1755,69 @@ fn to_roman(mut num: usize, large: bool) -> String {
 	}
 	result
 }
+
+/// Converts an ISO 8601 duration (without the leading `P`) like `1DT2H30M`
+/// into an expression like `1 days + 2 hours + 30 minutes`. Only weeks, days,
+/// hours, minutes and seconds are supported, since the length of years and
+/// months is ambiguous.
+fn parse_iso_8601_duration(duration: &str) -> Option<String> {
+	fn parse_components(
+		mut s: &str,
+		designators: &[(char, &str)],
+		parts: &mut Vec<String>,
+	) -> Option<()> {
+		// designators must appear in order, and at most once each
+		let mut next_designator = 0;
+		while !s.is_empty() {
+			let digits = s.find(|c: char| !c.is_ascii_digit())?;
+			if digits == 0 {
+				return None;
+			}
+			let (number, rest) = s.split_at(digits);
+			let designator = rest.chars().next()?;
+			let idx = designators[next_designator..]
+				.iter()
+				.position(|&(d, _)| d == designator)?;
+			next_designator += idx + 1;
+			parts.push(format!("{number} {}", designators[next_designator - 1].1));
+			s = &rest[designator.len_utf8()..];
+		}
+		Some(())
+	}
+
+	let (date, time) = match duration.split_once('T') {
+		Some((_, "")) => return None,
+		Some((date, time)) => (date, Some(time)),
+		None => (duration, None),
+	};
+	let mut parts = vec![];
+	parse_components(date, &[('W', "weeks"), ('D', "days")], &mut parts)?;
+	if let Some(time) = time {
+		parse_components(
+			time,
+			&[('H', "hours"), ('M', "minutes"), ('S', "seconds")],
+			&mut parts,
+		)?;
+	}
+	if parts.is_empty() {
+		return None;
+	}
+	Some(parts.join(" + "))
+}
+
+/// Parses a roman numeral between 1 and 3999. Only numerals in their standard
+/// form are accepted, so e.g. `IIII` or `VV` return `None`.
+fn from_roman(numeral: &str) -> Option<u64> {
+	let mut rest = numeral;
+	let mut result = 0;
+	for (r, n) in ROMAN_NUMERALS {
+		while let Some(remaining) = rest.strip_prefix(r) {
+			rest = remaining;
+			result += n;
+		}
+	}
+	if !rest.is_empty() || result == 0 || result >= 4000 || to_roman(result, false) != numeral {
+		return None;
+	}
+	u64::try_from(result).ok()
+}
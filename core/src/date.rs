@@ -1,17 +1,43 @@
 use std::{fmt, io};
 
+mod date_time;
 mod day;
 mod day_of_week;
 mod month;
 mod parser;
 mod year;
 
+pub(crate) use date_time::{DateTime, TimeZoneTarget};
 use day::Day;
 pub(crate) use day_of_week::DayOfWeek;
 pub(crate) use month::Month;
 use year::Year;
 
-use crate::{error::FendError, ident::Ident, result::FResult, value::Value, Interrupt};
+use crate::{
+	error::FendError, ident::Ident, num::Number, result::FResult, value::Value, Interrupt,
+};
+
+/// Returns the current local time in milliseconds since 1 January 1970,
+/// taking the time zone offset into account
+fn local_ms_since_epoch(context: &crate::Context) -> FResult<i64> {
+	let Some(current_time_info) = &context.current_time else {
+		return Err(FendError::UnableToGetCurrentDate);
+	};
+	i64::try_from(current_time_info.elapsed_unix_time_ms)
+		.map_err(|_| FendError::ValueTooLarge)?
+		.checked_add(current_time_info.timezone_offset_secs.saturating_mul(1000))
+		.ok_or(FendError::ValueTooLarge)
+}
+
+/// The order in which the parts of a date are displayed
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub(crate) enum DateStyle {
+	/// e.g. `Thursday, 4 July 2024`
+	#[default]
+	DayMonthYear,
+	/// e.g. `Thursday, July 4, 2024`
+	MonthDayYear,
+}
 
 #[derive(Copy, Clone, Eq, PartialEq)]
 pub(crate) struct Date {
@@ -22,26 +48,35 @@ pub(crate) struct Date {
 
 impl Date {
 	pub(crate) fn today(context: &crate::Context) -> FResult<Self> {
-		let Some(current_time_info) = &context.current_time else {
-			return Err(FendError::UnableToGetCurrentDate);
-		};
-		let mut ms_since_epoch: i64 = current_time_info.elapsed_unix_time_ms.try_into().unwrap();
-		ms_since_epoch -= current_time_info.timezone_offset_secs * 1000;
-		let mut days = ms_since_epoch / 86_400_000; // no leap seconds
-		let mut year = Year::new(1970);
-		while days >= year.number_of_days().into() {
-			year = year.next();
-			days -= i64::from(year.number_of_days());
+		let days = local_ms_since_epoch(context)?.div_euclid(86_400_000); // no leap seconds
+		Self::from_days_since_epoch(days)
+	}
+
+	/// The inverse of `days_since_epoch`
+	fn from_days_since_epoch(days: i64) -> FResult<Self> {
+		let days = days.checked_add(719_468).ok_or(FendError::ValueTooLarge)?;
+		let era = days.div_euclid(146_097);
+		let day_of_era = days - era * 146_097;
+		let year_of_era =
+			(day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+		let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+		// months are counted from March here
+		let month = (5 * day_of_year + 2) / 153;
+		let day = day_of_year - (153 * month + 2) / 5 + 1;
+		let month = if month < 10 { month + 3 } else { month - 9 };
+		let mut year = year_of_era + era * 400 + i64::from(month <= 2);
+		if year <= 0 {
+			// there is no year 0
+			year -= 1;
 		}
-		let mut month = Month::January;
-		while days >= month.number_of_days(year).into() {
-			month = month.next();
-			days -= i64::from(month.number_of_days(year));
+		let mut result_month = Month::January;
+		for _ in 1..month {
+			result_month = result_month.next();
 		}
 		Ok(Self {
-			year,
-			month,
-			day: Day::new(days.try_into().unwrap()),
+			year: Year::new(i32::try_from(year).map_err(|_| FendError::ValueTooLarge)?),
+			month: result_month,
+			day: Day::new(u8::try_from(day).unwrap()),
 		})
 	}
 
@@ -120,6 +155,85 @@ impl Date {
 		}
 	}
 
+	/// Returns the first date after this one that falls on the given weekday,
+	/// so e.g. the next Friday after a Friday is one week later.
+	pub(crate) fn next_weekday(self, day_of_week: DayOfWeek) -> Self {
+		let mut result = self.next();
+		while result.day_of_week() != day_of_week {
+			result = result.next();
+		}
+		result
+	}
+
+	/// Number of days since 1 January 1970, which is negative for earlier
+	/// dates. This uses the proleptic Gregorian calendar, where the year
+	/// before 1 AD is 1 BC (represented as -1).
+	fn days_since_epoch(self) -> i64 {
+		let month = i64::from(self.month.as_u8());
+		let mut year = i64::from(self.year.value());
+		if year < 0 {
+			year += 1;
+		}
+		// count years from March so that leap days come at the end of a year
+		if month <= 2 {
+			year -= 1;
+		}
+		let era = year.div_euclid(400);
+		let year_of_era = year - era * 400;
+		let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + i64::from(self.day.value()) - 1;
+		let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+		era * 146_097 + day_of_era - 719_468
+	}
+
+	/// Returns the day of the year, from 1 (1 January) to 366
+	fn day_of_year(self) -> u64 {
+		let first_day = Self {
+			year: self.year,
+			month: Month::January,
+			day: Day::new(1),
+		};
+		(first_day.days_until(self) + 1).try_into().unwrap()
+	}
+
+	/// Returns the ISO 8601 week number, from 1 to 53. Weeks start on
+	/// Monday, and week 1 is the week containing the first Thursday of the
+	/// year, so e.g. 1 January 2021 is in week 53 of 2020.
+	fn iso_week_number(self) -> u64 {
+		// Monday is 1, Sunday is 7
+		let iso_weekday = |date: Self| match date.day_of_week() {
+			DayOfWeek::Sunday => 7,
+			d => i64::from(d.as_u8()),
+		};
+		let weeks_in_year = |year: Year| -> i64 {
+			let first_day = Self {
+				year,
+				month: Month::January,
+				day: Day::new(1),
+			};
+			match first_day.day_of_week() {
+				DayOfWeek::Thursday => 53,
+				DayOfWeek::Wednesday if year.is_leap_year() => 53,
+				_ => 52,
+			}
+		};
+		let day_of_year = i64::try_from(self.day_of_year()).unwrap();
+		let week = (day_of_year - iso_weekday(self) + 10) / 7;
+		let week = if week < 1 {
+			weeks_in_year(self.year.prev())
+		} else if week > weeks_in_year(self.year) {
+			1
+		} else {
+			week
+		};
+		week.try_into().unwrap()
+	}
+
+	/// Number of days from this date until `other`, which is negative if
+	/// `other` is in the past
+	pub(crate) fn days_until(self, other: Self) -> i64 {
+		other.days_since_epoch() - self.days_since_epoch()
+	}
+
 	pub(crate) fn diff_months(self, mut months: i64) -> FResult<Self> {
 		let mut result = self;
 		while months >= 12 {
@@ -174,6 +288,19 @@ impl Date {
 		parser::parse_date(s)
 	}
 
+	pub(crate) fn format(self, style: DateStyle) -> String {
+		match style {
+			DateStyle::DayMonthYear => self.to_string(),
+			DateStyle::MonthDayYear => format!(
+				"{}, {} {}, {}",
+				self.day_of_week(),
+				self.month,
+				self.day,
+				self.year
+			),
+		}
+	}
+
 	pub(crate) fn serialize(self, write: &mut impl io::Write) -> FResult<()> {
 		self.year.serialize(write)?;
 		self.month.serialize(write)?;
@@ -182,17 +309,21 @@ impl Date {
 	}
 
 	pub(crate) fn deserialize(read: &mut impl io::Read) -> FResult<Self> {
-		Ok(Self {
-			year: Year::deserialize(read)?,
-			month: Month::deserialize(read)?,
-			day: Day::deserialize(read)?,
-		})
+		let year = Year::deserialize(read)?;
+		let month = Month::deserialize(read)?;
+		let day = Day::deserialize(read)?;
+		if day.value() > month.number_of_days(year) {
+			return Err(FendError::DeserializationError);
+		}
+		Ok(Self { year, month, day })
 	}
 
 	pub(crate) fn get_object_member(self, key: &Ident) -> FResult<crate::value::Value> {
 		Ok(match key.as_str() {
 			"month" => Value::Month(self.month),
-			"day_of_week" => Value::DayOfWeek(self.day_of_week()),
+			"day_of_week" | "weekday" => Value::DayOfWeek(self.day_of_week()),
+			"day_of_year" => Value::Num(Box::new(Number::from(self.day_of_year()))),
+			"week_number" => Value::Num(Box::new(Number::from(self.iso_week_number()))),
 			_ => return Err(FendError::CouldNotFindKey(key.to_string())),
 		})
 	}
@@ -206,6 +337,26 @@ impl Date {
 				result = result.next();
 			}
 			Ok(Value::Date(result))
+		} else if rhs.unit_equal_to("week", int)? {
+			let num_weeks = rhs.try_as_usize_unit(int)?;
+			let mut result = self;
+			for _ in 0..num_weeks {
+				for _ in 0..7 {
+					result = result.next();
+				}
+			}
+			Ok(Value::Date(result))
+		} else if rhs.unit_equal_to("month", int)? {
+			let num_months = rhs.try_as_usize_unit(int)?;
+			let result =
+				self.diff_months(i64::try_from(num_months).map_err(|_| FendError::ValueTooLarge)?)?;
+			Ok(Value::Date(result))
+		} else if rhs.unit_equal_to("year", int)? {
+			let num_years = rhs.try_as_usize_unit(int)?;
+			let num_months = num_years * 12;
+			let result =
+				self.diff_months(i64::try_from(num_months).map_err(|_| FendError::ValueTooLarge)?)?;
+			Ok(Value::Date(result))
 		} else {
 			Err(FendError::ExpectedANumber)
 		}
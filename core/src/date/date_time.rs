@@ -0,0 +1,234 @@
+use std::{fmt, io};
+
+use super::Date;
+use crate::{
+	error::FendError,
+	ident::Ident,
+	result::FResult,
+	serialize::{Deserialize, Serialize},
+	value::Value,
+};
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// UTC offsets range from UTC-12 to UTC+14, so anything beyond 18 hours is
+/// almost certainly a mistake
+const MAX_OFFSET_SECS: i64 = 18 * 60 * 60;
+
+/// A date together with a time of day, in whole seconds
+#[derive(Clone, Eq, PartialEq)]
+pub(crate) struct DateTime {
+	date: Date,
+	seconds: u32,
+	/// The time zone this date and time is in, or `None` for the local time
+	/// zone of the context
+	zone: Option<TimeZone>,
+}
+
+#[derive(Clone, Eq, PartialEq)]
+struct TimeZone {
+	name: String,
+	offset_secs: i64,
+}
+
+/// A time zone to convert a date and time to
+pub(crate) enum TimeZoneTarget {
+	/// A named time zone like `America/New_York`, which is looked up using
+	/// the context's time zone resolver
+	Named(String),
+	/// A fixed offset from UTC in seconds, e.g. `UTC+2`
+	FixedOffset(i64),
+}
+
+impl DateTime {
+	pub(crate) fn now(context: &crate::Context) -> FResult<Self> {
+		let ms_since_epoch = super::local_ms_since_epoch(context)?;
+		Self::from_secs_since_epoch(ms_since_epoch.div_euclid(1000), None)
+	}
+
+	fn from_secs_since_epoch(secs: i64, zone: Option<TimeZone>) -> FResult<Self> {
+		let date = Date::from_days_since_epoch(secs.div_euclid(SECONDS_PER_DAY.into()))?;
+		let seconds = secs.rem_euclid(SECONDS_PER_DAY.into());
+		Ok(Self {
+			date,
+			seconds: seconds.try_into().unwrap(),
+			zone,
+		})
+	}
+
+	pub(crate) fn date(&self) -> Date {
+		self.date
+	}
+
+	/// Converts this date and time to the given time zone. Returns `None` if
+	/// the time zone is unknown.
+	pub(crate) fn to_time_zone(
+		&self,
+		target: TimeZoneTarget,
+		context: &crate::Context,
+	) -> FResult<Option<Self>> {
+		let source_offset = match &self.zone {
+			Some(zone) => zone.offset_secs,
+			None => context
+				.current_time
+				.as_ref()
+				.map_or(0, |info| info.timezone_offset_secs),
+		};
+		let utc_secs = self
+			.date
+			.days_since_epoch()
+			.checked_mul(SECONDS_PER_DAY.into())
+			.and_then(|secs| secs.checked_add(self.seconds.into()))
+			.and_then(|secs| secs.checked_sub(source_offset))
+			.ok_or(FendError::ValueTooLarge)?;
+		let zone = match target {
+			TimeZoneTarget::FixedOffset(offset_secs) => TimeZone {
+				name: format_utc_offset(offset_secs),
+				offset_secs,
+			},
+			TimeZoneTarget::Named(name) => {
+				let Some(resolver) = &context.timezone_resolver else {
+					return Ok(None);
+				};
+				let Some(offset_secs) = resolver.utc_offset_secs(&name, utc_secs) else {
+					return Ok(None);
+				};
+				TimeZone { name, offset_secs }
+			}
+		};
+		if zone.offset_secs.abs() > MAX_OFFSET_SECS {
+			return Err(FendError::InvalidUtcOffset);
+		}
+		let local_secs = utc_secs
+			.checked_add(zone.offset_secs)
+			.ok_or(FendError::ValueTooLarge)?;
+		Ok(Some(Self::from_secs_since_epoch(local_secs, Some(zone))?))
+	}
+
+	pub(crate) fn with_date(self, date: Date) -> Self {
+		Self { date, ..self }
+	}
+
+	pub(crate) fn add_seconds(self, seconds: i64) -> FResult<Self> {
+		let total = seconds
+			.checked_add(self.seconds.into())
+			.ok_or(FendError::ValueTooLarge)?;
+		let days = total.div_euclid(SECONDS_PER_DAY.into());
+		let date = Date::from_days_since_epoch(
+			self.date
+				.days_since_epoch()
+				.checked_add(days)
+				.ok_or(FendError::ValueTooLarge)?,
+		)?;
+		Ok(Self {
+			date,
+			seconds: total.rem_euclid(SECONDS_PER_DAY.into()).try_into().unwrap(),
+			zone: self.zone,
+		})
+	}
+
+	pub(crate) fn format(&self, style: super::DateStyle) -> String {
+		format!(
+			"{} {}{}",
+			self.date.format(style),
+			self.time_of_day(),
+			self.zone_suffix()
+		)
+	}
+
+	fn time_of_day(&self) -> String {
+		format!(
+			"{:02}:{:02}:{:02}",
+			self.seconds / 3600,
+			self.seconds / 60 % 60,
+			self.seconds % 60
+		)
+	}
+
+	fn zone_suffix(&self) -> String {
+		match &self.zone {
+			Some(zone) => format!(" {}", zone.name),
+			None => String::new(),
+		}
+	}
+
+	pub(crate) fn serialize(&self, write: &mut impl io::Write) -> FResult<()> {
+		self.date.serialize(write)?;
+		self.seconds.serialize(write)?;
+		match &self.zone {
+			Some(zone) => {
+				true.serialize(write)?;
+				zone.name.as_str().serialize(write)?;
+				zone.offset_secs.serialize(write)?;
+			}
+			None => false.serialize(write)?,
+		}
+		Ok(())
+	}
+
+	pub(crate) fn deserialize(read: &mut impl io::Read) -> FResult<Self> {
+		let date = Date::deserialize(read)?;
+		let seconds = u32::deserialize(read)?;
+		if seconds >= SECONDS_PER_DAY {
+			return Err(FendError::DeserializationError);
+		}
+		let zone = if bool::deserialize(read)? {
+			let name = String::deserialize(read)?;
+			let offset_secs = i64::deserialize(read)?;
+			if offset_secs.abs() > MAX_OFFSET_SECS {
+				return Err(FendError::DeserializationError);
+			}
+			Some(TimeZone { name, offset_secs })
+		} else {
+			None
+		};
+		Ok(Self {
+			date,
+			seconds,
+			zone,
+		})
+	}
+
+	pub(crate) fn get_object_member(&self, key: &Ident) -> FResult<Value> {
+		match key.as_str() {
+			"date" => Ok(Value::Date(self.date)),
+			_ => self.date.get_object_member(key),
+		}
+	}
+}
+
+impl fmt::Debug for DateTime {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{:?} {}{}",
+			self.date,
+			self.time_of_day(),
+			self.zone_suffix()
+		)
+	}
+}
+
+impl fmt::Display for DateTime {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{self:?}")
+	}
+}
+
+/// Formats an offset like `UTC`, `UTC+2` or `UTC-3:30`
+fn format_utc_offset(offset_secs: i64) -> String {
+	if offset_secs == 0 {
+		return "UTC".to_string();
+	}
+	let sign = if offset_secs < 0 { '-' } else { '+' };
+	let hours = offset_secs.abs() / 3600;
+	let minutes = offset_secs.abs() / 60 % 60;
+	let seconds = offset_secs.abs() % 60;
+	if seconds != 0 {
+		format!("UTC{sign}{hours}:{minutes:02}:{seconds:02}")
+	} else if minutes != 0 {
+		format!("UTC{sign}{hours}:{minutes:02}")
+	} else {
+		format!("UTC{sign}{hours}")
+	}
+}
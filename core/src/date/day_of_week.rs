@@ -44,6 +44,20 @@ impl DayOfWeek {
 		}
 	}
 
+	/// Parses a full weekday name like `friday`, ignoring case
+	pub(crate) fn parse(s: &str) -> Option<Self> {
+		Some(match s.to_ascii_lowercase().as_str() {
+			"sunday" => Self::Sunday,
+			"monday" => Self::Monday,
+			"tuesday" => Self::Tuesday,
+			"wednesday" => Self::Wednesday,
+			"thursday" => Self::Thursday,
+			"friday" => Self::Friday,
+			"saturday" => Self::Saturday,
+			_ => return None,
+		})
+	}
+
 	pub(crate) fn serialize(self, write: &mut impl io::Write) -> FResult<()> {
 		self.as_u8().serialize(write)?;
 		Ok(())
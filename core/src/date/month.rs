@@ -97,7 +97,7 @@ impl Month {
 		Self::try_from(u8::deserialize(read)?).map_err(|_| FendError::DeserializationError)
 	}
 
-	fn as_u8(self) -> u8 {
+	pub(crate) fn as_u8(self) -> u8 {
 		match self {
 			Self::January => 1,
 			Self::February => 2,
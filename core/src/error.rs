@@ -36,6 +36,11 @@ pub(crate) enum FendError {
 	ExpectedANumber,
 	ExpectedABool(&'static str),
 	InvalidDiceSyntax,
+	InvalidDiceSelection {
+		action: &'static str,
+		selected: u32,
+		count: u32,
+	},
 	SpecifyNumDp,
 	SpecifyNumSf,
 	UnableToInvertFunction(&'static str),
@@ -92,6 +97,34 @@ pub(crate) enum FendError {
 		after: date::Date,
 	},
 	RomanNumeralZero,
+	InvalidRomanNumeral(String),
+	ScientificNotationBaseTooLarge,
+	WrongNumberOfArguments {
+		function: &'static str,
+		found: usize,
+	},
+	NotAQuadratic,
+	UnableToCompare,
+	InvalidClampBounds,
+	InvalidLogarithmBase,
+	OutputTooLong(usize),
+	TooManyVariables(usize),
+	DigitsRequireABase,
+	BitWidthRequiresABase,
+	ConversionNotExact,
+	InvalidUnitName(String),
+	CannotRedefineUnit(String),
+	InlineUnitWithoutUnit(String),
+	InvalidIsoDuration(String),
+	ExpectedADate,
+	InvalidSeparator(char),
+	IdenticalSeparators(char),
+	ModuloForIntegers,
+	UnknownTimeZone(String),
+	InvalidUtcOffset,
+	TimeZoneRequiresATime,
+	UnterminatedBlockComment,
+	InexactNumberToWords,
 }
 
 impl fmt::Display for FendError {
@@ -185,6 +218,11 @@ impl fmt::Display for FendError {
 			Self::InexactNumberToInt => write!(f, "cannot convert inexact number to integer"),
 			Self::ExpectedANumber => write!(f, "expected a number"),
 			Self::InvalidDiceSyntax => write!(f, "invalid dice syntax, try e.g. `4d6`"),
+			Self::InvalidDiceSelection {
+				action,
+				selected,
+				count,
+			} => write!(f, "cannot {action} {selected} out of {count} dice"),
 			Self::InvalidOperandsForSubtraction => write!(f, "invalid operands for subtraction"),
 			Self::CannotFormatWithZeroSf => {
 				write!(f, "cannot format a number with zero significant figures")
@@ -242,6 +280,122 @@ impl fmt::Display for FendError {
 				)
 			}
 			Self::RomanNumeralZero => write!(f, "zero cannot be represented as a roman numeral"),
+			Self::InvalidRomanNumeral(s) => write!(f, "'{s}' is not a valid roman numeral"),
+			Self::ScientificNotationBaseTooLarge => write!(
+				f,
+				"scientific notation is only supported in base 10 and below"
+			),
+			Self::WrongNumberOfArguments { function, found } => {
+				write!(f, "'{function}' does not accept {found} argument")?;
+				if *found != 1 {
+					write!(f, "s")?;
+				}
+				Ok(())
+			}
+			Self::NotAQuadratic => {
+				write!(f, "the leading coefficient of a quadratic cannot be zero")
+			}
+			Self::UnableToCompare => write!(f, "unable to compare these values"),
+			Self::InvalidClampBounds => {
+				write!(f, "the lower bound cannot be greater than the upper bound")
+			}
+			Self::InvalidLogarithmBase => {
+				write!(
+					f,
+					"the base of a logarithm must be positive and not equal to 1"
+				)
+			}
+			Self::OutputTooLong(max) => {
+				write!(f, "output too long: the result has more than {max} digits")
+			}
+			Self::TooManyVariables(max) => {
+				write!(f, "cannot define more than {max} variables")
+			}
+			Self::ConversionNotExact => write!(f, "conversion is not exact"),
+			Self::InvalidUnitName(name) => write!(f, "`{name}` is not a valid unit name"),
+			Self::CannotRedefineUnit(name) => {
+				write!(f, "cannot redefine '{name}', since it is already defined")
+			}
+			Self::InlineUnitWithoutUnit(name) => write!(
+				f,
+				"unit '{name}' must be defined in terms of other units, e.g. `{name} = 3 kg`"
+			),
+			Self::InvalidIsoDuration(s) => write!(
+				f,
+				"'{s}' is not a valid ISO 8601 duration (only weeks, days, hours, minutes and seconds are supported)"
+			),
+			Self::ExpectedADate => write!(f, "expected a date"),
+			Self::ModuloForIntegers => write!(f, "modulo is only supported for integers"),
+			Self::UnknownTimeZone(name) => write!(f, "unknown time zone '{name}'"),
+			Self::TimeZoneRequiresATime => write!(
+				f,
+				"dates without a time can't be converted to a time zone"
+			),
+			Self::UnterminatedBlockComment => write!(f, "unterminated block comment"),
+			Self::InexactNumberToWords => {
+				write!(f, "only exact numbers can be written in words")
+			}
+			Self::InvalidUtcOffset => {
+				write!(f, "UTC offsets must be between -18 and +18 hours")
+			}
+			Self::InvalidSeparator(ch) => write!(f, "'{ch}' cannot be used as a separator"),
+			Self::IdenticalSeparators(ch) => write!(
+				f,
+				"the decimal and group separators must be different (both are '{ch}')"
+			),
+			Self::DigitsRequireABase => write!(
+				f,
+				"a number of digits can only be specified for bases, e.g. `to binary with 8 digits`"
+			),
+			Self::BitWidthRequiresABase => write!(
+				f,
+				"a bit width can only be combined with a base, e.g. `to 8 bit hex`"
+			),
+		}
+	}
+}
+
+impl FendError {
+	/// Returns a terse version of this error message, without any details
+	/// about the values involved or any underlying errors.
+	pub(crate) fn short_description(&self) -> String {
+		match self {
+			Self::IncompatibleConversion { .. } => "incompatible units".to_string(),
+			Self::OutOfRange { .. } => "value out of range".to_string(),
+			Self::MustBeAnInteger(_) => "expected an integer".to_string(),
+			Self::WrongNumberOfArguments { .. } => "wrong number of arguments".to_string(),
+			Self::NonExistentDate { .. } => "date does not exist".to_string(),
+			_ => self.to_string(),
+		}
+	}
+
+	pub(crate) fn kind(&self) -> crate::ErrorKind {
+		use crate::ErrorKind;
+		match self {
+			Self::DivideByZero | Self::ModuloByZero => ErrorKind::DivideByZero,
+			Self::IdentifierNotFound(_) => ErrorKind::UnknownIdentifier,
+			Self::IncompatibleConversion { .. } => ErrorKind::IncompatibleUnits,
+			Self::Interrupted => ErrorKind::Interrupted,
+			Self::ParseError(_)
+			| Self::ExpectedADigit(_)
+			| Self::ExpectedChar(_, _)
+			| Self::ExpectedDigitSeparator(_)
+			| Self::DigitSeparatorsNotAllowed
+			| Self::DigitSeparatorsOnlyBetweenDigits
+			| Self::InvalidCharAtBeginningOfIdent(_)
+			| Self::UnexpectedChar(_)
+			| Self::UnterminatedStringLiteral
+			| Self::UnknownBackslashEscapeSequence(_)
+			| Self::BackslashXOutOfRange
+			| Self::ExpectedALetterOrCode
+			| Self::InvalidUnicodeEscapeSequence
+			| Self::InvalidBasePrefix
+			| Self::ExpectedACharacter
+			| Self::InvalidDiceSyntax
+			| Self::InvalidUnitName(_)
+			| Self::InvalidIsoDuration(_)
+			| Self::UnterminatedBlockComment => ErrorKind::Parse,
+			_ => ErrorKind::Other,
 		}
 	}
 }
@@ -1,7 +1,13 @@
 use std::sync::Arc;
 
 use crate::{
-	ast, error::Interrupt, lexer, parser, result::FResult, scope::Scope, value::Value, Span,
+	ast,
+	error::{FendError, Interrupt},
+	lexer, parser,
+	result::FResult,
+	scope::Scope,
+	value::Value,
+	ErrorKind, Span,
 };
 
 pub(crate) fn evaluate_to_value<I: Interrupt>(
@@ -13,26 +19,30 @@ pub(crate) fn evaluate_to_value<I: Interrupt>(
 ) -> FResult<Value> {
 	let lex = lexer::lex(input, context, int);
 	let mut tokens = vec![];
-	let mut missing_open_parens: i32 = 0;
 	for token in lex {
-		let token = token?;
-		if matches!(token, lexer::Token::Symbol(lexer::Symbol::CloseParens)) {
-			missing_open_parens += 1;
-		}
-		tokens.push(token);
-	}
-	for _ in 0..missing_open_parens {
-		tokens.insert(0, lexer::Token::Symbol(lexer::Symbol::OpenParens));
+		tokens.push(token?);
 	}
+	insert_missing_open_parens(&mut tokens);
 	let parsed = parser::parse_tokens(&tokens)?;
 	let result = ast::evaluate(parsed, scope, attrs, context, int)?;
 	Ok(result)
 }
 
+fn insert_missing_open_parens(tokens: &mut Vec<lexer::Token>) {
+	let missing_open_parens = tokens
+		.iter()
+		.filter(|t| matches!(t, lexer::Token::Symbol(lexer::Symbol::CloseParens)))
+		.count();
+	for _ in 0..missing_open_parens {
+		tokens.insert(0, lexer::Token::Symbol(lexer::Symbol::OpenParens));
+	}
+}
+
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 #[allow(clippy::struct_excessive_bools)]
 pub(crate) struct Attrs {
 	pub(crate) debug: bool,
+	pub(crate) exact: bool,
 	pub(crate) show_approx: bool,
 	pub(crate) plain_number: bool,
 	pub(crate) trailing_newline: bool,
@@ -42,6 +52,7 @@ impl Default for Attrs {
 	fn default() -> Self {
 		Self {
 			debug: false,
+			exact: false,
 			show_approx: true,
 			plain_number: false,
 			trailing_newline: true,
@@ -55,7 +66,14 @@ fn parse_attrs(mut input: &str) -> (Attrs, &str) {
 		if let Some(remaining) = input.strip_prefix("@debug ") {
 			attrs.debug = true;
 			input = remaining;
-		} else if let Some(remaining) = input.strip_prefix("@noapprox ") {
+		} else if let Some(remaining) = input.strip_prefix("@exact ") {
+			attrs.exact = true;
+			input = remaining;
+		} else if let Some(remaining) = input
+			.strip_prefix("@noapprox ")
+			.or_else(|| input.strip_prefix("@no_approx "))
+			.or_else(|| input.strip_prefix("@plain "))
+		{
 			attrs.show_approx = false;
 			input = remaining;
 		} else if let Some(remaining) = input.strip_prefix("@plain_number ") {
@@ -71,26 +89,122 @@ fn parse_attrs(mut input: &str) -> (Attrs, &str) {
 	(attrs, input)
 }
 
-/// This also saves the calculation result in a variable `_` and `ans`
+fn is_approximate(spans: &[Span]) -> bool {
+	spans
+		.iter()
+		.any(|span| span.kind == crate::SpanKind::Ident && span.string == "approx. ")
+}
+
+/// Returns the formatted spans, whether the result is the `()` type, the
+/// attributes and whether the result is approximate. This also saves the
+/// calculation result in a variable `_` and `ans`.
 pub(crate) fn evaluate_to_spans<I: Interrupt>(
 	input: &str,
 	scope: Option<Arc<Scope>>,
 	context: &mut crate::Context,
 	int: &I,
-) -> FResult<(Vec<Span>, bool, Attrs)> {
+) -> FResult<(Vec<Span>, bool, Attrs, bool)> {
 	let (attrs, input) = parse_attrs(input);
 	let value = evaluate_to_value(input, scope, attrs, context, int)?;
 	context.variables.insert("_".to_string(), value.clone());
 	context.variables.insert("ans".to_string(), value.clone());
-	Ok((
-		if attrs.debug {
-			vec![Span::from_string(format!("{value:?}"))]
-		} else {
-			let mut spans = vec![];
-			value.format(0, &mut spans, attrs, context, int)?;
-			spans
-		},
-		value.is_unit(),
-		attrs,
-	))
+	let spans = if attrs.debug {
+		vec![Span::from_string(format!("{value:?}"))]
+	} else {
+		let mut spans = vec![];
+		value.format(0, &mut spans, attrs, context, int)?;
+		spans
+	};
+	// `@noapprox` only hides the `approx.` prefix, so the result is still
+	// reported as approximate
+	let approximate = if attrs.show_approx || attrs.debug {
+		is_approximate(&spans)
+	} else {
+		let mut approx_spans = vec![];
+		let attrs = Attrs {
+			show_approx: true,
+			..attrs
+		};
+		value.format(0, &mut approx_spans, attrs, context, int)?;
+		is_approximate(&approx_spans)
+	};
+	Ok((spans, value.is_unit(), attrs, approximate))
+}
+
+/// Checks whether the last statement in the input is an assignment like
+/// `a = 5`. Inputs that fail to parse return `false`.
+pub(crate) fn ends_with_assignment<I: Interrupt>(
+	input: &str,
+	context: &crate::Context,
+	int: &I,
+) -> bool {
+	let (_, input) = parse_attrs(input);
+	let mut tokens = vec![];
+	for token in lexer::lex(input, context, int) {
+		let Ok(token) = token else {
+			return false;
+		};
+		tokens.push(token);
+	}
+	insert_missing_open_parens(&mut tokens);
+	parser::parse_tokens(&tokens).is_ok_and(|expr| expr.ends_with_assignment())
+}
+
+/// Returns all identifiers in the input, in order. Inputs that fail to lex
+/// only return the identifiers before the error.
+pub(crate) fn identifiers_in<I: Interrupt>(
+	input: &str,
+	context: &crate::Context,
+	int: &I,
+) -> Vec<String> {
+	let (_, input) = parse_attrs(input);
+	lexer::lex(input, context, int)
+		.map_while(Result::ok)
+		.filter_map(|token| match token {
+			lexer::Token::Ident(ident) => Some(ident.as_str().to_string()),
+			_ => None,
+		})
+		.collect()
+}
+
+/// Finds the start and end byte offsets of the part of the input that caused
+/// the given error, if possible. This only handles errors that can be traced
+/// back to a specific token: lexer and parser errors, and unknown identifiers.
+pub(crate) fn error_span<I: Interrupt>(
+	input: &str,
+	error: &FendError,
+	context: &crate::Context,
+	int: &I,
+) -> Option<(usize, usize)> {
+	if !matches!(
+		error.kind(),
+		ErrorKind::Parse | ErrorKind::UnknownIdentifier
+	) {
+		return None;
+	}
+	let (_, expr) = parse_attrs(input);
+	let offset = input.len() - expr.len();
+	let tokens = match lexer::lex_with_spans(expr, context, int) {
+		Ok(tokens) => tokens,
+		Err((start, end)) => return Some((offset + start, offset + end)),
+	};
+	let span = match error {
+		FendError::IdentifierNotFound(ident) => {
+			tokens.iter().find_map(|(token, span)| match token {
+				lexer::Token::Ident(i) if i.as_str() == ident.as_str() => Some(*span),
+				_ => None,
+			})?
+		}
+		FendError::ParseError(parser::ParseError::ExpectedAToken) => (expr.len(), expr.len()),
+		FendError::ParseError(parser::ParseError::UnexpectedInput) => {
+			let mut parser_tokens = tokens.iter().map(|(t, _)| t.clone()).collect();
+			insert_missing_open_parens(&mut parser_tokens);
+			let (_, remaining) = parser::parse_expression(&parser_tokens).ok()?;
+			let first_unparsed = tokens.len().checked_sub(remaining.len())?;
+			let start = tokens.get(first_unparsed)?.1 .0;
+			(start, tokens.last()?.1 .1)
+		}
+		_ => return None,
+	};
+	Some((offset + span.0, offset + span.1))
 }
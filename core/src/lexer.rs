@@ -23,6 +23,7 @@ pub(crate) enum Symbol {
 	Sub,
 	Mul,
 	Div,
+	Per, // like `Div`, but `per 2 servings` divides by the whole quantity
 	Mod,
 	Pow,
 	BitwiseAnd,
@@ -40,8 +41,17 @@ pub(crate) enum Symbol {
 	Equals,       // used for assignment
 	DoubleEquals, // used for equality
 	NotEquals,
+	LessThan,
+	LessThanOrEqual,
+	GreaterThan,
+	GreaterThanOrEqual,
+	If,
+	Then,
+	Else,
+	Where, // used for inline unit definitions
 	Combination,
 	Permutation,
+	Comma, // used to separate function arguments
 }
 
 impl fmt::Display for Symbol {
@@ -53,6 +63,7 @@ impl fmt::Display for Symbol {
 			Self::Sub => "-",
 			Self::Mul => "*",
 			Self::Div => "/",
+			Self::Per => "per",
 			Self::Mod => "mod",
 			Self::Pow => "^",
 			Self::BitwiseAnd => "&",
@@ -70,8 +81,17 @@ impl fmt::Display for Symbol {
 			Self::Equals => "=",
 			Self::DoubleEquals => "==",
 			Self::NotEquals => "!=",
+			Self::LessThan => "<",
+			Self::LessThanOrEqual => "<=",
+			Self::GreaterThan => ">",
+			Self::GreaterThanOrEqual => ">=",
+			Self::If => "if",
+			Self::Then => "then",
+			Self::Else => "else",
+			Self::Where => "where",
 			Self::Combination => "nCr",
 			Self::Permutation => "nPr",
+			Self::Comma => ",",
 		};
 		write!(f, "{s}")?;
 		Ok(())
@@ -112,13 +132,61 @@ fn parse_digit_separator(
 	decimal_separator: DecimalSeparatorStyle,
 ) -> FResult<((), &str)> {
 	let (parsed_ch, input) = parse_char(input)?;
-	if parsed_ch == '_' || parsed_ch == decimal_separator.thousands_separator() {
+	// whitespace separators are handled by `parse_group_separator`
+	if parsed_ch == '_'
+		|| (parsed_ch == decimal_separator.thousands_separator() && !parsed_ch.is_whitespace())
+	{
 		Ok(((), input))
 	} else {
 		Err(FendError::ExpectedDigitSeparator(parsed_ch))
 	}
 }
 
+// Unicode spaces and apostrophes are commonly used to group digits (e.g.
+// `1 234 567` or the Swiss `1'234'567`). Since these characters have other
+// meanings (e.g. `5'10"` for feet and inches), they are only treated as
+// digit separators when directly followed by a group of exactly three digits.
+fn parse_group_separator(
+	input: &str,
+	base: Base,
+	decimal_separator: DecimalSeparatorStyle,
+) -> Option<&str> {
+	let (ch, remaining) = parse_char(input).ok()?;
+	let is_custom_separator = ch.is_whitespace() && ch == decimal_separator.thousands_separator();
+	if !matches!(ch, '\'' | '\u{a0}' | '\u{2009}' | '\u{202f}') && !is_custom_separator {
+		return None;
+	}
+	let mut after_group = remaining;
+	for _ in 0..3 {
+		let (_, next) = parse_ascii_digit(after_group, base).ok()?;
+		after_group = next;
+	}
+	if parse_ascii_digit(after_group, base).is_ok() {
+		return None;
+	}
+	if ch == '\'' && (after_group.starts_with('"') || after_group.starts_with('″')) {
+		// e.g. `1'234"` is an (unusual) length in feet and inches
+		return None;
+	}
+	Some(remaining)
+}
+
+// Returns true if the input starts with a comma that isn't directly followed
+// by a digit. Such commas separate function arguments rather than digits.
+fn is_argument_separator(input: &str) -> bool {
+	let mut chars = input.chars();
+	chars.next() == Some(',') && !chars.next().is_some_and(|ch| ch.is_ascii_digit())
+}
+
+// Returns true if the input starts with exactly three digits, like the digits
+// after the comma in `1,000`
+fn is_digit_group(input: &str) -> bool {
+	let bytes = input.as_bytes();
+	bytes.len() >= 3
+		&& bytes[..3].iter().all(u8::is_ascii_digit)
+		&& !bytes.get(3).is_some_and(u8::is_ascii_digit)
+}
+
 // Parses a plain integer with no whitespace and no base prefix.
 // Leading minus sign is not allowed.
 fn parse_integer<'a, E: From<FendError>>(
@@ -132,6 +200,16 @@ fn parse_integer<'a, E: From<FendError>>(
 	process_digit(digit)?;
 	let mut parsed_digit_separator;
 	loop {
+		if is_argument_separator(input) {
+			// e.g. the comma in `gcd(12, 18)`
+			break;
+		}
+		if allow_digit_separator {
+			if let Some(remaining) = parse_group_separator(input, base, decimal_separator) {
+				input = remaining;
+				continue;
+			}
+		}
 		if let Ok(((), remaining)) = parse_digit_separator(input, decimal_separator) {
 			input = remaining;
 			parsed_digit_separator = true;
@@ -253,6 +331,86 @@ fn parse_recurring_digits<'a, I: Interrupt>(
 	Ok(((), input))
 }
 
+// e.g. `keep highest 3` or `drop lowest 1`
+struct DiceSelection {
+	keep: bool,
+	highest: bool,
+	amount: u32,
+}
+
+impl DiceSelection {
+	// returns the number of highest (or lowest) dice that should be kept
+	fn keep_count(&self, dice_count: u32) -> FResult<u32> {
+		let (action, valid) = if self.keep {
+			("keep", self.amount != 0 && self.amount <= dice_count)
+		} else {
+			("drop", self.amount < dice_count)
+		};
+		if !valid {
+			return Err(FendError::InvalidDiceSelection {
+				action,
+				selected: self.amount,
+				count: dice_count,
+			});
+		}
+		Ok(if self.keep {
+			self.amount
+		} else {
+			dice_count - self.amount
+		})
+	}
+
+	// dropping the lowest dice is the same as keeping the highest ones
+	fn keeps_highest(&self) -> bool {
+		self.keep == self.highest
+	}
+}
+
+fn parse_dice_selection_word<'a>(input: &'a str, words: &[&str]) -> Option<(&'a str, &'a str)> {
+	let input = input.trim_start();
+	let len = input
+		.find(|ch: char| !ch.is_ascii_alphabetic())
+		.unwrap_or(input.len());
+	let (word, remaining) = input.split_at(len);
+	if words.contains(&word) && remaining.starts_with(char::is_whitespace) {
+		Some((word, remaining))
+	} else {
+		None
+	}
+}
+
+// Parses an optional `keep`/`drop` clause after a dice literal, e.g. the
+// ` keep highest 3` in `4d6 keep highest 3`. If the direction is omitted,
+// `keep` refers to the highest dice and `drop` to the lowest ones.
+fn parse_dice_selection(input: &str) -> Option<(DiceSelection, &str)> {
+	if !input.starts_with(char::is_whitespace) {
+		return None;
+	}
+	let (action, input) = parse_dice_selection_word(input, &["keep", "drop"])?;
+	let keep = action == "keep";
+	let (highest, input) = match parse_dice_selection_word(input, &["highest", "lowest"]) {
+		Some((direction, remaining)) => (direction == "highest", remaining),
+		None => (keep, input),
+	};
+	let input = input.trim_start();
+	let len = input
+		.find(|ch: char| !ch.is_ascii_digit())
+		.unwrap_or(input.len());
+	let (amount, remaining) = input.split_at(len);
+	if remaining.starts_with(|ch: char| ch.is_alphanumeric() || ch == '.') {
+		return None;
+	}
+	let amount = amount.parse().ok()?;
+	Some((
+		DiceSelection {
+			keep,
+			highest,
+			amount,
+		},
+		remaining,
+	))
+}
+
 #[allow(clippy::too_many_lines)]
 fn parse_basic_number<'a, I: Interrupt>(
 	mut input: &'a str,
@@ -296,7 +454,13 @@ fn parse_basic_number<'a, I: Interrupt>(
 	}
 
 	// parse decimal point and at least one digit
-	if let Ok(((), remaining)) = parse_fixed_char(input, decimal_point_char) {
+	// (unless it's a comma separating function arguments)
+	let decimal_point = if is_argument_separator(input) {
+		None
+	} else {
+		parse_fixed_char(input, decimal_point_char).ok()
+	};
+	if let Some(((), remaining)) = decimal_point {
 		is_integer = false;
 		let mut num_nonrec_digits = 0;
 		let mut numerator = Number::zero_with_base(base);
@@ -365,6 +529,18 @@ fn parse_basic_number<'a, I: Interrupt>(
 				if dice_count == 0 || face_count == 0 {
 					return Err(FendError::InvalidDiceSyntax);
 				}
+				if let Some((selection, remaining3)) = parse_dice_selection(remaining2) {
+					let keep = selection.keep_count(dice_count)?;
+					res = Number::new_die_keep(
+						dice_count,
+						face_count,
+						keep,
+						selection.keeps_highest(),
+						int,
+					)?;
+					res = res.with_base(base);
+					return Ok((res, remaining3));
+				}
 				res = Number::new_die(dice_count, face_count, int)?;
 				res = res.with_base(base);
 				return Ok((res, remaining2));
@@ -372,6 +548,15 @@ fn parse_basic_number<'a, I: Interrupt>(
 		}
 	}
 
+	// parse mixed numbers with vulgar fractions, e.g. `2½` or `2 ½`
+	if is_integer && base.base_as_u8() == 10 {
+		if let Ok((fraction, remaining)) = parse_vulgar_fraction(input.trim_start_matches(' '), int)
+		{
+			res = res.add(fraction, decimal_separator, int)?;
+			return Ok((res, remaining));
+		}
+	}
+
 	// parse optional exponent, but only for base 10 and below
 	if base.base_as_u8() <= 10 {
 		let (parsed_exponent, remaining) = if let Ok(((), remaining)) = parse_fixed_char(input, 'e')
@@ -437,43 +622,69 @@ fn parse_basic_number<'a, I: Interrupt>(
 			.next()
 			.is_some_and(|c| SUPERSCRIPT_DIGITS.contains(&c))
 	{
-		if let Ok((mut power_digits, remaining)) = parse_power_number(input) {
-			let mut exponent = Number::zero_with_base(base);
+		let (power_digits, remaining) = parse_power_number(input);
+		let mut exponent = Number::zero_with_base(base);
 
-			power_digits.reverse();
-
-			for (i, digit) in power_digits.into_iter().enumerate() {
-				let num = digit * 10u64.pow(u32::try_from(i).unwrap());
-				exponent = exponent.add(num.into(), decimal_separator, int)?;
-			}
-
-			res = res.pow(exponent, decimal_separator, int)?;
-			input = remaining;
+		// accumulate digit by digit so that arbitrarily long exponents
+		// don't overflow
+		for digit in power_digits {
+			exponent = exponent
+				.mul(10.into(), int)?
+				.add(digit.into(), decimal_separator, int)?;
 		}
+
+		res = res.pow(exponent, decimal_separator, int)?;
+		input = remaining;
 	}
 
 	Ok((res, input))
 }
 
+// Returns the numerator and denominator of a Unicode vulgar fraction, e.g. ½
+fn vulgar_fraction_value(ch: char) -> Option<(u64, u64)> {
+	Some(match ch {
+		'\u{bd}' => (1, 2),
+		'\u{2153}' => (1, 3),
+		'\u{2154}' => (2, 3),
+		'\u{bc}' => (1, 4),
+		'\u{be}' => (3, 4),
+		'\u{2155}' => (1, 5),
+		'\u{2156}' => (2, 5),
+		'\u{2157}' => (3, 5),
+		'\u{2158}' => (4, 5),
+		'\u{2159}' => (1, 6),
+		'\u{215a}' => (5, 6),
+		'\u{2150}' => (1, 7),
+		'\u{215b}' => (1, 8),
+		'\u{215c}' => (3, 8),
+		'\u{215d}' => (5, 8),
+		'\u{215e}' => (7, 8),
+		'\u{2151}' => (1, 9),
+		'\u{2152}' => (1, 10),
+		_ => return None,
+	})
+}
+
+fn parse_vulgar_fraction<'a, I: Interrupt>(input: &'a str, int: &I) -> FResult<(Number, &'a str)> {
+	let (ch, remaining) = parse_char(input)?;
+	let (num, den) = vulgar_fraction_value(ch).ok_or(FendError::UnexpectedChar(ch))?;
+	Ok((Number::from(num).div(Number::from(den), int)?, remaining))
+}
+
 const SUPERSCRIPT_DIGITS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
 
-fn parse_power_number(input: &str) -> FResult<(Vec<u64>, &str)> {
+fn parse_power_number(mut input: &str) -> (Vec<u64>, &str) {
 	let mut digits: Vec<u64> = Vec::new();
 
-	let (mut ch, mut input) = parse_char(input)?;
-	while let Some((idx, _)) = SUPERSCRIPT_DIGITS
-		.iter()
-		.enumerate()
-		.find(|(_, x)| **x == ch)
-	{
-		digits.push(idx as u64);
-		if input.is_empty() {
+	while let Ok((ch, remaining)) = parse_char(input) {
+		let Some(idx) = SUPERSCRIPT_DIGITS.iter().position(|x| *x == ch) else {
 			break;
-		}
-		(ch, input) = parse_char(input)?;
+		};
+		digits.push(idx as u64);
+		input = remaining;
 	}
 
-	Ok((digits, input))
+	(digits, input)
 }
 
 fn parse_number<'a, I: Interrupt>(
@@ -489,15 +700,15 @@ fn parse_number<'a, I: Interrupt>(
 
 fn is_valid_in_ident(ch: char, prev: Option<char>) -> bool {
 	let allowed_chars = [
-		',', '_', '⅛', '¼', '⅜', '½', '⅝', '¾', '⅞', '⅙', '⅓', '⅔', '⅚', '⅕', '⅖', '⅗', '⅘', '°',
-		'$', '℃', '℉', '℧', '℈', '℥', '℔', '¢', '£', '¥', '€', '₩', '₪', '₤', '₨', '฿', '₡', '₣',
-		'₦', '₧', '₫', '₭', '₮', '₯', '₱', '﷼', '﹩', '￠', '￡', '￥', '￦', '㍱', '㍲', '㍳',
-		'㍴', '㍶', '㎀', '㎁', '㎂', '㎃', '㎄', '㎅', '㎆', '㎇', '㎈', '㎉', '㎊', '㎋', '㎌',
-		'㎍', '㎎', '㎏', '㎐', '㎑', '㎒', '㎓', '㎔', '㎕', '㎖', '㎗', '㎘', '㎙', '㎚', '㎛',
-		'㎜', '㎝', '㎞', '㎟', '㎠', '㎡', '㎢', '㎣', '㎤', '㎥', '㎦', '㎧', '㎨', '㎩', '㎪',
-		'㎫', '㎬', '㎭', '㎮', '㎯', '㎰', '㎱', '㎲', '㎳', '㎴', '㎵', '㎶', '㎷', '㎸', '㎹',
-		'㎺', '㎻', '㎼', '㎽', '㎾', '㎿', '㏀', '㏁', '㏃', '㏄', '㏅', '㏆', '㏈', '㏉', '㏊',
-		'㏌', '㏏', '㏐', '㏓', '㏔', '㏕', '㏖', '㏗', '㏙', '㏛', '㏜', '㏝',
+		'_', '⅛', '¼', '⅜', '½', '⅝', '¾', '⅞', '⅙', '⅓', '⅔', '⅚', '⅕', '⅖', '⅗', '⅘', '°', '$',
+		'℃', '℉', '℧', '℈', '℥', '℔', '¢', '£', '¥', '€', '₩', '₪', '₤', '₨', '฿', '₡', '₣', '₦',
+		'₧', '₫', '₭', '₮', '₯', '₱', '﷼', '﹩', '￠', '￡', '￥', '￦', '㍱', '㍲', '㍳', '㍴',
+		'㍶', '㎀', '㎁', '㎂', '㎃', '㎄', '㎅', '㎆', '㎇', '㎈', '㎉', '㎊', '㎋', '㎌', '㎍',
+		'㎎', '㎏', '㎐', '㎑', '㎒', '㎓', '㎔', '㎕', '㎖', '㎗', '㎘', '㎙', '㎚', '㎛', '㎜',
+		'㎝', '㎞', '㎟', '㎠', '㎡', '㎢', '㎣', '㎤', '㎥', '㎦', '㎧', '㎨', '㎩', '㎪', '㎫',
+		'㎬', '㎭', '㎮', '㎯', '㎰', '㎱', '㎲', '㎳', '㎴', '㎵', '㎶', '㎷', '㎸', '㎹', '㎺',
+		'㎻', '㎼', '㎽', '㎾', '㎿', '㏀', '㏁', '㏃', '㏄', '㏅', '㏆', '㏈', '㏉', '㏊', '㏌',
+		'㏏', '㏐', '㏓', '㏔', '㏕', '㏖', '㏗', '㏙', '㏛', '㏜', '㏝',
 	];
 	let only_valid_by_themselves = ['%', '‰', '‱', '′', '″', '’', '”', 'π'];
 	let split_on_subsequent_digit = ['$', '£', '¥'];
@@ -521,7 +732,17 @@ fn is_valid_in_ident(ch: char, prev: Option<char>) -> bool {
 	}
 }
 
-fn parse_ident(input: &str, allow_dots: bool) -> FResult<(Token, &str)> {
+// Returns true if the input starts with a comma that joins two parts of an
+// identifier, e.g. the comma in `a,b = 3`
+fn is_comma_in_ident(input: &str) -> bool {
+	let mut chars = input.chars();
+	chars.next() == Some(',')
+		&& chars
+			.next()
+			.is_some_and(|ch| is_valid_in_ident(ch, Some(',')))
+}
+
+fn parse_ident(input: &str, allow_dots: bool, allow_commas: bool) -> FResult<(Token, &str)> {
 	let (first_char, _) = parse_char(input)?;
 	if !is_valid_in_ident(first_char, None) || first_char == '.' && !allow_dots {
 		return Err(FendError::InvalidCharAtBeginningOfIdent(first_char));
@@ -530,7 +751,10 @@ fn parse_ident(input: &str, allow_dots: bool) -> FResult<(Token, &str)> {
 	let (_, mut remaining) = input.split_at(byte_idx);
 	let mut prev_char = first_char;
 	while let Ok((next_char, remaining_input)) = parse_char(remaining) {
-		if !is_valid_in_ident(next_char, Some(prev_char)) || next_char == '.' && !allow_dots {
+		let is_comma = allow_commas && is_comma_in_ident(remaining);
+		if !is_comma && !is_valid_in_ident(next_char, Some(prev_char))
+			|| next_char == '.' && !allow_dots
+		{
 			break;
 		}
 		remaining = remaining_input;
@@ -541,7 +765,7 @@ fn parse_ident(input: &str, allow_dots: bool) -> FResult<(Token, &str)> {
 	Ok((
 		match ident {
 			"to" | "as" | "in" => Token::Symbol(Symbol::UnitConversion),
-			"per" => Token::Symbol(Symbol::Div),
+			"per" => Token::Symbol(Symbol::Per),
 			"of" => Token::Symbol(Symbol::Of),
 			"mod" => Token::Symbol(Symbol::Mod),
 			"xor" | "XOR" => Token::Symbol(Symbol::BitwiseXor),
@@ -549,12 +773,32 @@ fn parse_ident(input: &str, allow_dots: bool) -> FResult<(Token, &str)> {
 			"or" | "OR" => Token::Symbol(Symbol::BitwiseOr),
 			"nCr" | "choose" => Token::Symbol(Symbol::Combination),
 			"nPr" | "permute" => Token::Symbol(Symbol::Permutation),
+			"if" => Token::Symbol(Symbol::If),
+			"then" => Token::Symbol(Symbol::Then),
+			"else" => Token::Symbol(Symbol::Else),
 			_ => Token::Ident(Ident::new_string(ident.to_string())),
 		},
 		input,
 	))
 }
 
+// Parses the mass number of isotopes like `carbon-14`, which are lexed as a
+// single identifier. There must be no whitespace around the `-`.
+fn parse_isotope_mass_number(input: &str) -> Option<(&str, &str)> {
+	let digits = input.strip_prefix('-')?;
+	let len = digits.bytes().take_while(u8::is_ascii_digit).count();
+	if len == 0 || digits[len..].starts_with(|ch| is_valid_in_ident(ch, Some('0'))) {
+		return None;
+	}
+	Some(digits.split_at(len))
+}
+
+/// Returns true if the whole string would be lexed as a single identifier
+/// (and not e.g. as a keyword like `to`)
+pub(crate) fn is_valid_identifier(s: &str) -> bool {
+	matches!(parse_ident(s, false, true), Ok((Token::Ident(_), "")))
+}
+
 fn parse_symbol(ch: char, input: &mut &str) -> FResult<Token> {
 	let mut test_next = |next: char| {
 		if input.starts_with(next) {
@@ -607,18 +851,25 @@ fn parse_symbol(ch: char, input: &mut &str) -> FResult<Token> {
 				Symbol::ShiftLeft
 			} else if test_next('>') {
 				Symbol::NotEquals
+			} else if test_next('=') {
+				Symbol::LessThanOrEqual
 			} else {
-				return Err(FendError::UnexpectedChar(ch));
+				Symbol::LessThan
 			}
 		}
 		'>' => {
 			if test_next('>') {
 				Symbol::ShiftRight
+			} else if test_next('=') {
+				Symbol::GreaterThanOrEqual
 			} else {
-				return Err(FendError::UnexpectedChar(ch));
+				Symbol::GreaterThan
 			}
 		}
+		'\u{2264}' => Symbol::LessThanOrEqual, // unicode less-than or equal to symbol
+		'\u{2265}' => Symbol::GreaterThanOrEqual, // unicode greater-than or equal to symbol
 		';' => Symbol::Semicolon,
+		',' => Symbol::Comma,
 		_ => return Err(FendError::UnexpectedChar(ch)),
 	}))
 }
@@ -775,20 +1026,62 @@ pub(crate) struct Lexer<'a, 'b, I: Interrupt> {
 	// normally 0; 1 after backslash; 2 after ident after backslash
 	after_backslash_state: u8,
 	after_number_or_to: bool,
+	after_ident_or_close_parens: bool,
+	// one entry per open parenthesis: true if it contains function arguments,
+	// e.g. `gcd(12,18)`, and false if it only groups, e.g. `(1,000 + 1)`
+	parens: Vec<bool>,
 	decimal_separator: DecimalSeparatorStyle,
+	// whether `where` is a keyword, see `Context::set_inline_unit_definitions`
+	where_keyword: bool,
 	int: &'b I,
 }
 
-fn skip_whitespace_and_comments(input: &mut &str) {
+/// Skips a `/* ... */` block comment at the start of the input. Block
+/// comments can be nested, e.g. `/* a /* b */ c */`.
+fn skip_block_comment(input: &mut &str) -> FResult<()> {
+	let mut depth = 0_usize;
+	while !input.is_empty() {
+		if input.starts_with("/*") {
+			depth += 1;
+			*input = &input[2..];
+		} else if input.starts_with("*/") {
+			depth -= 1;
+			*input = &input[2..];
+			if depth == 0 {
+				return Ok(());
+			}
+		} else if let Some(ch) = input.chars().next() {
+			*input = &input[ch.len_utf8()..];
+		}
+	}
+	Err(FendError::UnterminatedBlockComment)
+}
+
+/// Skips a `#!` line at the very start of the input, e.g.
+/// `#!/usr/bin/env fend`, so that fend scripts can be made executable
+fn skip_shebang(input: &str) -> &str {
+	if !input.starts_with("#!") {
+		return input;
+	}
+	match input.find('\n') {
+		Some(idx) => &input[idx..],
+		None => "",
+	}
+}
+
+fn skip_whitespace_and_comments(input: &mut &str) -> FResult<()> {
 	while !input.is_empty() {
-		if input.starts_with("# ") || input.starts_with("#!") {
+		if input.starts_with("/*") {
+			skip_block_comment(input)?;
+			continue;
+		} else if input.starts_with("# ") {
 			if let Some(idx) = input.find('\n') {
 				let (_, remaining) = input.split_at(idx);
 				*input = remaining;
 				continue;
 			}
 			*input = "";
-			return;
+			return Ok(());
 		} else if let Some(ch) = input.chars().next() {
 			if ch.is_whitespace() {
 				let (_, remaining) = input.split_at(ch.len_utf8());
@@ -798,6 +1091,7 @@ fn skip_whitespace_and_comments(input: &mut &str) {
 		}
 		break;
 	}
+	Ok(())
 }
 
 fn parse_date(input: &str) -> FResult<(Date, &str)> {
@@ -832,7 +1126,7 @@ fn parse_date(input: &str) -> FResult<(Date, &str)> {
 
 impl<I: Interrupt> Lexer<'_, '_, I> {
 	fn next_token(&mut self) -> FResult<Option<Token>> {
-		skip_whitespace_and_comments(&mut self.input);
+		skip_whitespace_and_comments(&mut self.input)?;
 		let (ch, following) = {
 			let mut chars = self.input.chars();
 			let ch = chars.next();
@@ -843,11 +1137,33 @@ impl<I: Interrupt> Lexer<'_, '_, I> {
 			Some(ch) => {
 				if ch.is_ascii_digit()
 					|| (ch == self.decimal_separator.decimal_separator()
-						&& self.after_backslash_state == 0)
+						&& self.after_backslash_state == 0
+						&& !is_argument_separator(self.input))
 					|| (ch == 'd' && following.is_some() && following.unwrap().is_ascii_digit())
 				{
+					// inside function call parentheses, a comma separates
+					// arguments (e.g. `gcd(12,18)`) unless it's followed by a
+					// group of three digits (e.g. `abs(1,000)`) or commas are
+					// decimal points
+					let number_input = if self.in_function_arguments()
+						&& self.decimal_separator == DecimalSeparatorStyle::Dot
+					{
+						let end = self
+							.input
+							.match_indices(',')
+							.map(|(idx, _)| idx)
+							.find(|&idx| !is_digit_group(&self.input[idx + 1..]))
+							.unwrap_or(self.input.len());
+						&self.input[..end]
+					} else {
+						self.input
+					};
 					let (num, remaining) =
-						parse_number(self.input, self.decimal_separator, self.int)?;
+						parse_number(number_input, self.decimal_separator, self.int)?;
+					self.input = &self.input[number_input.len() - remaining.len()..];
+					Token::Num(num)
+				} else if vulgar_fraction_value(ch).is_some() {
+					let (num, remaining) = parse_vulgar_fraction(self.input, self.int)?;
 					self.input = remaining;
 					Token::Num(num)
 				} else if ch == '\'' || ch == '"' {
@@ -880,10 +1196,32 @@ impl<I: Interrupt> Lexer<'_, '_, I> {
 					Token::StringLiteral(literal.to_string().into())
 				} else if is_valid_in_ident(ch, None) {
 					// dots aren't allowed in idents after a backslash
-					let (ident, remaining) =
-						parse_ident(self.input, self.after_backslash_state != 1)?;
+					// commas only join identifiers outside function arguments
+					let (ident, remaining) = parse_ident(
+						self.input,
+						self.after_backslash_state != 1,
+						!self.in_function_arguments(),
+					)?;
 					self.input = remaining;
-					ident
+					match ident {
+						Token::Ident(ident) if self.where_keyword && ident.as_str() == "where" => {
+							Token::Symbol(Symbol::Where)
+						}
+						Token::Ident(ident) if crate::ast::is_isotope_element(ident.as_str()) => {
+							if let Some((mass_number, remaining)) =
+								parse_isotope_mass_number(self.input)
+							{
+								self.input = remaining;
+								Token::Ident(Ident::new_string(format!(
+									"{}-{mass_number}",
+									ident.as_str()
+								)))
+							} else {
+								Token::Ident(ident)
+							}
+						}
+						ident => ident,
+					}
 				} else {
 					let (_, remaining) = self.input.split_at(ch.len_utf8());
 					self.input = remaining;
@@ -893,6 +1231,10 @@ impl<I: Interrupt> Lexer<'_, '_, I> {
 			None => return Ok(None),
 		}))
 	}
+
+	fn in_function_arguments(&self) -> bool {
+		self.parens.last().copied().unwrap_or(false)
+	}
 }
 
 impl<I: Interrupt> Iterator for Lexer<'_, '_, I> {
@@ -908,6 +1250,19 @@ impl<I: Interrupt> Iterator for Lexer<'_, '_, I> {
 			res,
 			Some(Ok(Token::Num(_) | Token::Symbol(Symbol::UnitConversion)))
 		);
+		match res {
+			Some(Ok(Token::Symbol(Symbol::OpenParens))) => {
+				self.parens.push(self.after_ident_or_close_parens);
+			}
+			Some(Ok(Token::Symbol(Symbol::CloseParens))) => {
+				self.parens.pop();
+			}
+			_ => (),
+		}
+		self.after_ident_or_close_parens = matches!(
+			res,
+			Some(Ok(Token::Ident(_) | Token::Symbol(Symbol::CloseParens)))
+		);
 		if matches!(res, Some(Ok(Token::Symbol(Symbol::Backslash)))) {
 			self.after_backslash_state = 1;
 		} else if self.after_backslash_state == 1 {
@@ -928,11 +1283,51 @@ pub(crate) fn lex<'a, 'b, I: Interrupt>(
 	ctx: &Context,
 	int: &'b I,
 ) -> Lexer<'a, 'b, I> {
+	let input = if ctx.skip_shebang {
+		skip_shebang(input)
+	} else {
+		input
+	};
 	Lexer {
 		input,
 		after_backslash_state: 0,
 		after_number_or_to: false,
+		after_ident_or_close_parens: false,
+		parens: vec![],
 		decimal_separator: ctx.decimal_separator,
+		where_keyword: ctx.inline_unit_definitions,
 		int,
 	}
 }
+
+/// Start and end byte offsets (end exclusive)
+pub(crate) type ByteSpan = (usize, usize);
+
+/// Lexes the given input, returning each token together with its start and
+/// end byte offsets. If lexing fails, this returns the span of the character
+/// at which the failing token starts.
+pub(crate) fn lex_with_spans<I: Interrupt>(
+	input: &str,
+	ctx: &Context,
+	int: &I,
+) -> Result<Vec<(Token, ByteSpan)>, ByteSpan> {
+	let mut lexer = lex(input, ctx, int);
+	let mut tokens = vec![];
+	loop {
+		let mut remaining = lexer.input;
+		// errors are reported by `lexer.next()` below
+		let _ = skip_whitespace_and_comments(&mut remaining);
+		let start = input.len() - remaining.len();
+		match lexer.next() {
+			None => return Ok(tokens),
+			Some(Ok(token)) => tokens.push((token, (start, input.len() - lexer.input.len()))),
+			Some(Err(_)) => {
+				let end = remaining
+					.chars()
+					.next()
+					.map_or(start, |ch| start + ch.len_utf8());
+				return Err((start, end));
+			}
+		}
+	}
+}
@@ -37,13 +37,13 @@ mod parser;
 mod result;
 mod scope;
 mod serialize;
+mod suggestions;
 mod units;
 mod value;
 
-use std::error::Error;
 use std::fmt::Write;
-use std::sync::Arc;
-use std::{collections::HashMap, fmt, io};
+use std::sync::{Arc, Mutex};
+use std::{collections::HashMap, fmt, io, mem, num::NonZeroUsize};
 
 use error::FendError;
 pub(crate) use eval::Attrs;
@@ -58,6 +58,9 @@ pub struct FendResult {
 	span_result: Vec<Span>,
 	is_unit: bool, // is this the () type
 	attrs: eval::Attrs,
+	approximate: bool,
+	other_info: Vec<String>,
+	currency_info: Vec<CurrencyInfo>,
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -74,6 +77,26 @@ pub enum SpanKind {
 	Other,
 }
 
+impl SpanKind {
+	/// Returns a stable name for this kind of span, as used by
+	/// [`evaluate_to_json`]: one of `number`, `built_in_function`, `keyword`,
+	/// `string`, `date`, `whitespace`, `ident`, `boolean` or `other`.
+	#[must_use]
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Self::Number => "number",
+			Self::BuiltInFunction => "built_in_function",
+			Self::Keyword => "keyword",
+			Self::String => "string",
+			Self::Date => "date",
+			Self::Whitespace => "whitespace",
+			Self::Ident => "ident",
+			Self::Boolean => "boolean",
+			Self::Other => "other",
+		}
+	}
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 struct Span {
 	string: String,
@@ -123,6 +146,21 @@ impl FendResult {
 		})
 	}
 
+	/// Returns additional information about the computation, one entry per
+	/// line. For example, `60 mph to m/s showing work` returns the conversion
+	/// factors that were used.
+	pub fn get_other_info(&self) -> impl Iterator<Item = &str> {
+		self.other_info.iter().map(String::as_str)
+	}
+
+	/// Returns information about the exchange rates that were used in the
+	/// computation, e.g. when they were last updated. This is empty if no
+	/// currency conversion occurred.
+	#[must_use]
+	pub fn currency_info(&self) -> &[CurrencyInfo] {
+		&self.currency_info
+	}
+
 	/// Returns whether or not the result is the `()` type. It can sometimes
 	/// be useful to hide these values.
 	#[must_use]
@@ -136,9 +174,20 @@ impl FendResult {
 			span_result: vec![],
 			is_unit: true,
 			attrs: Attrs::default(),
+			approximate: false,
+			other_info: vec![],
+			currency_info: vec![],
 		}
 	}
 
+	/// Returns whether or not the result is marked as approximate, i.e.
+	/// whether it is prefixed with `approx.`. This is still true if the
+	/// prefix is hidden with the `@noapprox` attribute.
+	#[must_use]
+	pub fn is_approximate(&self) -> bool {
+		self.approximate
+	}
+
 	/// Returns whether or not the result should be outputted with a
 	/// trailing newline. This is controlled by the `@no_trailing_newline`
 	/// attribute.
@@ -164,6 +213,7 @@ enum FCMode {
 enum OutputMode {
 	SimpleText,
 	TerminalFixedWidth,
+	Markdown,
 }
 
 /// An exchange rate handler.
@@ -193,6 +243,217 @@ where
 	}
 }
 
+/// An exchange rate together with optional information about where it came
+/// from, as returned by [`ExchangeRateFnV2`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExchangeRate {
+	value: f64,
+	timestamp_ms: Option<u64>,
+	source: Option<String>,
+}
+
+impl ExchangeRate {
+	/// Creates an exchange rate without any metadata. The value is relative
+	/// to the base currency, as in [`ExchangeRateFn::relative_to_base_currency`].
+	#[must_use]
+	pub fn new(value: f64) -> Self {
+		Self {
+			value,
+			timestamp_ms: None,
+			source: None,
+		}
+	}
+
+	/// Sets the time this exchange rate was published, in milliseconds
+	/// since the Unix epoch.
+	#[must_use]
+	pub fn with_timestamp_ms(mut self, timestamp_ms: u64) -> Self {
+		self.timestamp_ms = Some(timestamp_ms);
+		self
+	}
+
+	/// Sets a description of where this exchange rate came from, e.g. the
+	/// name of the data provider.
+	#[must_use]
+	pub fn with_source(mut self, source: impl Into<String>) -> Self {
+		self.source = Some(source.into());
+		self
+	}
+}
+
+/// An exchange rate handler that can also return when and where the rates
+/// were obtained. This information is available from
+/// [`FendResult::currency_info`].
+pub trait ExchangeRateFnV2 {
+	/// Returns the value of a currency relative to the base currency, like
+	/// [`ExchangeRateFn::relative_to_base_currency`].
+	///
+	/// # Errors
+	/// This function errors out if the currency was not found or the
+	/// conversion is impossible for any reason (HTTP request failed, etc.)
+	fn relative_to_base_currency(
+		&self,
+		currency: &str,
+	) -> Result<ExchangeRate, Box<dyn std::error::Error + Send + Sync + 'static>>;
+}
+
+impl<T> ExchangeRateFnV2 for T
+where
+	T: Fn(&str) -> Result<ExchangeRate, Box<dyn std::error::Error + Send + Sync + 'static>>,
+{
+	fn relative_to_base_currency(
+		&self,
+		currency: &str,
+	) -> Result<ExchangeRate, Box<dyn std::error::Error + Send + Sync + 'static>> {
+		self(currency)
+	}
+}
+
+/// Adapts an [`ExchangeRateFn`] to an [`ExchangeRateFnV2`] that returns no
+/// metadata
+struct ExchangeRateFnV1Adapter<T>(T);
+
+impl<T: ExchangeRateFn> ExchangeRateFnV2 for ExchangeRateFnV1Adapter<T> {
+	fn relative_to_base_currency(
+		&self,
+		currency: &str,
+	) -> Result<ExchangeRate, Box<dyn std::error::Error + Send + Sync + 'static>> {
+		ExchangeRateFn::relative_to_base_currency(&self.0, currency).map(ExchangeRate::new)
+	}
+}
+
+/// Information about an exchange rate used in a computation, as returned by
+/// [`FendResult::currency_info`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CurrencyInfo {
+	currency: String,
+	timestamp_ms: Option<u64>,
+	source: Option<String>,
+}
+
+impl CurrencyInfo {
+	/// The currency that was converted, e.g. `USD`
+	#[must_use]
+	pub fn currency(&self) -> &str {
+		&self.currency
+	}
+
+	/// When the exchange rate was published, in milliseconds since the Unix
+	/// epoch, if the exchange rate handler provided it
+	#[must_use]
+	pub fn timestamp_ms(&self) -> Option<u64> {
+		self.timestamp_ms
+	}
+
+	/// Where the exchange rate came from, if the exchange rate handler
+	/// provided it
+	#[must_use]
+	pub fn source(&self) -> Option<&str> {
+		self.source.as_deref()
+	}
+}
+
+#[derive(Debug)]
+struct UnknownCurrency;
+
+impl fmt::Display for UnknownCurrency {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "unknown currency")
+	}
+}
+
+impl std::error::Error for UnknownCurrency {}
+
+/// An exchange rate handler backed by a fixed map of exchange rates
+struct StaticExchangeRates(HashMap<String, f64>);
+
+impl ExchangeRateFn for StaticExchangeRates {
+	fn relative_to_base_currency(
+		&self,
+		currency: &str,
+	) -> Result<f64, Box<dyn std::error::Error + Send + Sync + 'static>> {
+		match self.0.get(currency) {
+			Some(&rate) => Ok(rate),
+			None => Err(Box::new(UnknownCurrency)),
+		}
+	}
+}
+
+/// An exchange rate handler added to a [`Context`]
+#[derive(Clone)]
+pub(crate) struct ExchangeRateHandler {
+	pub(crate) handler: Arc<dyn ExchangeRateFnV2 + Send + Sync>,
+	// true for the fixed rates from `Context::set_exchange_rates`, which
+	// don't need network access and so are also used in previews
+	is_static: bool,
+}
+
+impl ExchangeRateHandler {
+	fn new<T: ExchangeRateFnV2 + 'static + Send + Sync>(handler: T) -> Self {
+		Self {
+			handler: Arc::new(handler),
+			is_static: false,
+		}
+	}
+}
+
+/// Looks up the offset of named time zones like `America/New_York`, so that
+/// dates and times can be converted to them. fend doesn't include a time zone
+/// database, so this needs to be provided by the host application.
+pub trait TimeZoneResolver {
+	/// Returns the offset from UTC in seconds (positive for time zones ahead
+	/// of UTC) of the given time zone at the given time, which is specified
+	/// in seconds since January 1, 1970 at midnight UTC. This allows taking
+	/// daylight saving time into account. Returns `None` if the time zone is
+	/// unknown.
+	fn utc_offset_secs(&self, zone: &str, unix_time_secs: i64) -> Option<i64>;
+}
+
+impl<T> TimeZoneResolver for T
+where
+	T: Fn(&str, i64) -> Option<i64>,
+{
+	fn utc_offset_secs(&self, zone: &str, unix_time_secs: i64) -> Option<i64> {
+		self(zone, unix_time_secs)
+	}
+}
+
+/// A source of random numbers, used e.g. for rolling dice. Only
+/// [`Random::random_u32`] needs to be implemented; the other methods are
+/// derived from it.
+///
+/// This is implemented for all `FnMut() -> u32` closures.
+pub trait Random {
+	/// Returns a random `u32`, where every value is equally likely.
+	fn random_u32(&mut self) -> u32;
+
+	/// Returns a random `u64`. By default, this combines two `u32` values,
+	/// using the first one as the upper 32 bits.
+	fn random_u64(&mut self) -> u64 {
+		let high = u64::from(self.random_u32());
+		let low = u64::from(self.random_u32());
+		(high << 32) | low
+	}
+
+	/// Fills `dest` with random bytes. By default, every 4 bytes are taken
+	/// from a single `u32` in little-endian order.
+	fn fill_bytes(&mut self, dest: &mut [u8]) {
+		for chunk in dest.chunks_mut(4) {
+			let bytes = self.random_u32().to_le_bytes();
+			chunk.copy_from_slice(&bytes[..chunk.len()]);
+		}
+	}
+}
+
+impl<T> Random for T
+where
+	T: FnMut() -> u32,
+{
+	fn random_u32(&mut self) -> u32 {
+		self()
+	}
+}
+
 /// This controls decimal and thousands separators.
 #[non_exhaustive]
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
@@ -202,6 +463,10 @@ pub enum DecimalSeparatorStyle {
 	Dot,
 	/// Use `,` as the decimal separator and `.` as the thousands separator. This is common in European languages.
 	Comma,
+	/// Use custom decimal and thousands separators. These can only be set
+	/// with [`Context::set_separators`], which checks that the separators can
+	/// be parsed unambiguously.
+	Custom(CustomSeparators),
 }
 
 impl DecimalSeparatorStyle {
@@ -209,6 +474,7 @@ impl DecimalSeparatorStyle {
 		match self {
 			Self::Dot => '.',
 			Self::Comma => ',',
+			Self::Custom(separators) => separators.decimal,
 		}
 	}
 
@@ -216,10 +482,123 @@ impl DecimalSeparatorStyle {
 		match self {
 			Self::Dot => ',',
 			Self::Comma => '.',
+			Self::Custom(separators) => separators.group,
 		}
 	}
 }
 
+/// Custom decimal and thousands separators, as set by
+/// [`Context::set_separators`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CustomSeparators {
+	decimal: char,
+	group: char,
+}
+
+impl CustomSeparators {
+	fn new(decimal: char, group: char) -> FResult<Self> {
+		let check = |ch: char, allow_whitespace: bool| {
+			if ch.is_alphanumeric()
+				|| ch.is_control()
+				|| (ch.is_whitespace() && !allow_whitespace)
+				|| "+-*/×÷^!%()[]{}=<>&|~;:@#$\\\"'`?_".contains(ch)
+			{
+				Err(FendError::InvalidSeparator(ch))
+			} else {
+				Ok(())
+			}
+		};
+		check(decimal, false)?;
+		check(group, true)?;
+		if decimal == group {
+			return Err(FendError::IdenticalSeparators(decimal));
+		}
+		Ok(Self { decimal, group })
+	}
+
+	/// The decimal separator, e.g. `,` in `1 234,5`
+	#[must_use]
+	pub fn decimal(self) -> char {
+		self.decimal
+	}
+
+	/// The thousands separator, e.g. the space in `1 234,5`
+	#[must_use]
+	pub fn group(self) -> char {
+		self.group
+	}
+}
+
+/// This controls how fractions like `1/2` are written.
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum FractionSlashStyle {
+	/// Use an ASCII slash, e.g. `1/2`
+	#[default]
+	Slash,
+	/// Use the Unicode fraction slash (U+2044), e.g. `1⁄2`
+	FractionSlash,
+	/// Use Unicode vulgar fraction characters like `½` where available,
+	/// falling back to an ASCII slash for other fractions
+	Vulgar,
+}
+
+/// This controls how negative numbers are written.
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum NegativeStyle {
+	/// Use a minus sign, e.g. `-5`
+	#[default]
+	Minus,
+	/// Wrap negative numbers in parentheses, as is common in accounting,
+	/// e.g. `(5)` or `($5)`. Note that fend parses `(5)` as a positive
+	/// number, so results in this style can't be evaluated again.
+	Parentheses,
+}
+
+/// This controls how amounts of data like `8e9 bits` are displayed.
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BitBytePreference {
+	/// Keep whichever unit the calculation resulted in, e.g. `8000000000 bits`
+	#[default]
+	Unchanged,
+	/// Use bits with SI prefixes, e.g. `8 Gb`
+	Bits,
+	/// Use bytes with SI prefixes, e.g. `1 GB`
+	Bytes,
+	/// Use bits with binary prefixes, e.g. `7.450580596923828125 Gib`
+	BinaryBits,
+	/// Use bytes with binary prefixes, e.g. `953.67431640625 MiB`
+	BinaryBytes,
+}
+
+/// This controls the sign of the result of `mod` when an operand is negative.
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ModuloConvention {
+	/// The result is never negative, e.g. `-7 mod 3` is `2` and `7 mod -3`
+	/// is `1`
+	#[default]
+	Euclidean,
+	/// The result has the same sign as the dividend, as in C or Rust, e.g.
+	/// `-7 mod 3` is `-1` and `7 mod -3` is `1`
+	Truncated,
+}
+
+/// This controls how much detail is included in error messages.
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ErrorVerbosity {
+	/// Use short error messages, e.g. `incompatible units`. This is useful for
+	/// compact user interfaces.
+	Short,
+	/// Use detailed error messages, e.g. `cannot convert from kg to m: units
+	/// 'kilogram' and 'meter' are incompatible`.
+	#[default]
+	Detailed,
+}
+
 /// This struct contains fend's current context, including some settings
 /// as well as stored variables.
 ///
@@ -229,15 +608,41 @@ impl DecimalSeparatorStyle {
 /// and recreate the context for every calculation, depending on
 /// which is easier.
 #[derive(Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Context {
 	current_time: Option<CurrentTimeInfo>,
 	variables: HashMap<String, value::Value>,
+	max_variables: Option<usize>,
 	fc_mode: FCMode,
-	random_u32: Option<fn() -> u32>,
+	rng: Option<Arc<Mutex<dyn Random + Send>>>,
 	output_mode: OutputMode,
-	get_exchange_rate: Option<Arc<dyn ExchangeRateFn + Send + Sync>>,
+	get_exchange_rate: Vec<ExchangeRateHandler>,
+	timezone_resolver: Option<Arc<dyn TimeZoneResolver + Send + Sync>>,
 	custom_units: Vec<(String, String, String)>,
 	decimal_separator: DecimalSeparatorStyle,
+	auto_scientific_threshold: Option<usize>,
+	max_output_digits: Option<usize>,
+	default_sf_limit: Option<NonZeroUsize>,
+	digit_group_separator: Option<char>,
+	date_style: date::DateStyle,
+	max_suggestion_distance: usize,
+	max_suggestions: usize,
+	error_verbosity: ErrorVerbosity,
+	currency_symbol_spacing: bool,
+	fraction_slash_style: FractionSlashStyle,
+	output_width: usize,
+	show_plus_sign: bool,
+	negative_style: NegativeStyle,
+	modulo_convention: ModuloConvention,
+	base_prefix: bool,
+	preview_disable_assignments: bool,
+	skip_shebang: bool,
+	unit_usage: Option<HashMap<String, usize>>,
+	prefer_mixed_fractions: bool,
+	bit_byte_preference: BitBytePreference,
+	inline_unit_definitions: bool,
+	other_info: Vec<String>,
+	currency_info: Vec<CurrencyInfo>,
 }
 
 impl fmt::Debug for Context {
@@ -246,11 +651,39 @@ impl fmt::Debug for Context {
 		f.debug_struct("Context")
 			.field("current_time", &self.current_time)
 			.field("variables", &self.variables)
+			.field("max_variables", &self.max_variables)
 			.field("fc_mode", &self.fc_mode)
-			.field("random_u32", &self.random_u32)
+			.field("rng", &self.rng.is_some())
 			.field("output_mode", &self.output_mode)
+			.field("timezone_resolver", &self.timezone_resolver.is_some())
 			.field("custom_units", &self.custom_units)
 			.field("decimal_separator_style", &self.decimal_separator)
+			.field("auto_scientific_threshold", &self.auto_scientific_threshold)
+			.field("max_output_digits", &self.max_output_digits)
+			.field("default_sf_limit", &self.default_sf_limit)
+			.field("digit_group_separator", &self.digit_group_separator)
+			.field("date_style", &self.date_style)
+			.field("max_suggestion_distance", &self.max_suggestion_distance)
+			.field("max_suggestions", &self.max_suggestions)
+			.field("error_verbosity", &self.error_verbosity)
+			.field("currency_symbol_spacing", &self.currency_symbol_spacing)
+			.field("fraction_slash_style", &self.fraction_slash_style)
+			.field("output_width", &self.output_width)
+			.field("show_plus_sign", &self.show_plus_sign)
+			.field("negative_style", &self.negative_style)
+			.field("modulo_convention", &self.modulo_convention)
+			.field("base_prefix", &self.base_prefix)
+			.field(
+				"preview_disable_assignments",
+				&self.preview_disable_assignments,
+			)
+			.field("skip_shebang", &self.skip_shebang)
+			.field("unit_usage", &self.unit_usage)
+			.field("prefer_mixed_fractions", &self.prefer_mixed_fractions)
+			.field("bit_byte_preference", &self.bit_byte_preference)
+			.field("inline_unit_definitions", &self.inline_unit_definitions)
+			.field("other_info", &self.other_info)
+			.field("currency_info", &self.currency_info)
 			.finish_non_exhaustive()
 	}
 }
@@ -268,31 +701,55 @@ impl Context {
 		Self {
 			current_time: None,
 			variables: HashMap::new(),
+			max_variables: None,
 			fc_mode: FCMode::CelsiusFahrenheit,
-			random_u32: None,
+			rng: None,
 			output_mode: OutputMode::SimpleText,
-			get_exchange_rate: None,
+			get_exchange_rate: vec![],
+			timezone_resolver: None,
 			custom_units: vec![],
 			decimal_separator: DecimalSeparatorStyle::default(),
+			auto_scientific_threshold: None,
+			max_output_digits: None,
+			default_sf_limit: None,
+			digit_group_separator: None,
+			date_style: date::DateStyle::default(),
+			max_suggestion_distance: 2,
+			max_suggestions: 3,
+			error_verbosity: ErrorVerbosity::default(),
+			currency_symbol_spacing: false,
+			fraction_slash_style: FractionSlashStyle::default(),
+			output_width: 80,
+			show_plus_sign: false,
+			negative_style: NegativeStyle::default(),
+			modulo_convention: ModuloConvention::default(),
+			base_prefix: true,
+			preview_disable_assignments: false,
+			skip_shebang: true,
+			unit_usage: None,
+			prefer_mixed_fractions: false,
+			bit_byte_preference: BitBytePreference::default(),
+			inline_unit_definitions: false,
+			other_info: vec![],
+			currency_info: vec![],
 		}
 	}
 
-	/// This method currently has no effect!
-	///
-	/// Set the current time. This API will likely change in the future!
+	/// Set the current time, which is used by `today`, `next friday` etc.
+	/// This API will likely change in the future!
 	///
 	/// The first argument (`ms_since_1970`) must be the number of elapsed milliseconds
 	/// since January 1, 1970 at midnight UTC, ignoring leap seconds in the same way
 	/// as unix time.
 	///
 	/// The second argument (`tz_offset_secs`) is the current time zone
-	/// offset to UTC, in seconds.
-	pub fn set_current_time_v1(&mut self, _ms_since_1970: u64, _tz_offset_secs: i64) {
-		// self.current_time = Some(CurrentTimeInfo {
-		//     elapsed_unix_time_ms: ms_since_1970,
-		//     timezone_offset_secs: tz_offset_secs,
-		// });
-		self.current_time = None;
+	/// offset to UTC, in seconds. It is positive for time zones ahead of UTC,
+	/// e.g. 3600 for UTC+1.
+	pub fn set_current_time_v1(&mut self, ms_since_1970: u64, tz_offset_secs: i64) {
+		self.current_time = Some(CurrentTimeInfo {
+			elapsed_unix_time_ms: ms_since_1970,
+			timezone_offset_secs: tz_offset_secs,
+		});
 	}
 
 	/// Define the units `C` and `F` as coulomb and farad instead of degrees
@@ -301,14 +758,26 @@ impl Context {
 		self.fc_mode = FCMode::CoulombFarad;
 	}
 
-	/// Set a random number generator
-	pub fn set_random_u32_fn(&mut self, random_u32: fn() -> u32) {
-		self.random_u32 = Some(random_u32);
+	/// Set a random number generator. This can be a plain function or a
+	/// closure that keeps its own state (e.g. a seeded PRNG).
+	///
+	/// The generator is shared between clones of this context, so cloning a
+	/// context does not disable random numbers, and clones don't repeat the
+	/// same sequence of random numbers.
+	pub fn set_random_u32_fn(&mut self, random_u32: impl FnMut() -> u32 + Send + 'static) {
+		self.set_rng(random_u32);
+	}
+
+	/// Set a random number generator that implements the [`Random`] trait.
+	/// Like [`Self::set_random_u32_fn`], the generator is shared between
+	/// clones of this context.
+	pub fn set_rng(&mut self, rng: impl Random + Send + 'static) {
+		self.rng = Some(Arc::new(Mutex::new(rng)));
 	}
 
 	/// Clear the random number generator after setting it with via [`Self::set_random_u32_fn`]
 	pub fn disable_rng(&mut self) {
-		self.random_u32 = None;
+		self.rng = None;
 	}
 
 	/// Change the output mode to fixed-width terminal style. This enables ASCII
@@ -317,6 +786,13 @@ impl Context {
 		self.output_mode = OutputMode::TerminalFixedWidth;
 	}
 
+	/// Change the output mode to markdown. Results are wrapped in inline code
+	/// spans (or code blocks if they span multiple lines), and distributions
+	/// are shown as markdown tables.
+	pub fn set_output_mode_markdown(&mut self) {
+		self.output_mode = OutputMode::Markdown;
+	}
+
 	fn serialize_variables_internal(&self, write: &mut impl io::Write) -> FResult<()> {
 		self.variables.len().serialize(write)?;
 		for (k, v) in &self.variables {
@@ -341,22 +817,48 @@ impl Context {
 
 	fn deserialize_variables_internal(&mut self, read: &mut impl io::Read) -> FResult<()> {
 		let len = usize::deserialize(read)?;
-		self.variables.clear();
-		self.variables.reserve(len);
+		let mut variables = HashMap::new();
 		for _ in 0..len {
 			let s = String::deserialize(read)?;
-			let v = value::Value::deserialize(read)?;
-			self.variables.insert(s, v);
+			let v = value::Value::deserialize(read, 0)?;
+			variables.insert(s, v);
 		}
+		self.variables = variables;
 		Ok(())
 	}
 
+	/// Returns the names of all variables defined in this context, in
+	/// alphabetical order. This includes `_` and `ans`, which store the
+	/// previous result.
+	#[must_use]
+	pub fn variable_names(&self) -> Vec<String> {
+		let mut names: Vec<String> = self.variables.keys().cloned().collect();
+		names.sort();
+		names
+	}
+
+	/// Returns the value of the given variable formatted as plain text (e.g.
+	/// `5 kg`), or `None` if no such variable is defined.
+	#[must_use]
+	pub fn variable_value(&mut self, name: &str) -> Option<String> {
+		let value = self.variables.get(name)?.clone();
+		value
+			.format_to_plain_string(0, Attrs::default(), self, &interrupt::Never)
+			.ok()
+	}
+
+	/// Removes the variable with the given name. Returns true if the variable
+	/// was defined, or false otherwise.
+	pub fn remove_variable(&mut self, name: &str) -> bool {
+		self.variables.remove(name).is_some()
+	}
+
 	/// Deserializes the given variables, replacing all prior variables in
 	/// the given context.
 	///
 	/// # Errors
 	/// Returns an error if the input byte stream is invalid and cannot be
-	/// deserialized.
+	/// deserialized. The existing variables are left unchanged in that case.
 	pub fn deserialize_variables(&mut self, read: &mut impl io::Read) -> Result<(), String> {
 		match self.deserialize_variables_internal(read) {
 			Ok(()) => Ok(()),
@@ -369,7 +871,55 @@ impl Context {
 		&mut self,
 		get_exchange_rate: T,
 	) {
-		self.get_exchange_rate = Some(Arc::new(get_exchange_rate));
+		self.get_exchange_rate = vec![ExchangeRateHandler::new(ExchangeRateFnV1Adapter(
+			get_exchange_rate,
+		))];
+	}
+
+	/// Set an exchange rate handler that can return metadata like the time
+	/// the rates were published. This replaces all previously set handlers.
+	/// The metadata of any rates used in a computation is available from
+	/// [`FendResult::currency_info`].
+	pub fn set_exchange_rate_handler_v2<T: ExchangeRateFnV2 + 'static + Send + Sync>(
+		&mut self,
+		get_exchange_rate: T,
+	) {
+		self.get_exchange_rate = vec![ExchangeRateHandler::new(get_exchange_rate)];
+	}
+
+	/// Add a fallback exchange rate handler, which is used if all previously
+	/// added handlers fail to return an exchange rate. For example, a cached
+	/// offline source can be added before a network source. An error is only
+	/// returned if every handler fails, in which case the error from the last
+	/// handler is shown.
+	pub fn add_exchange_rate_handler<T: ExchangeRateFnV2 + 'static + Send + Sync>(
+		&mut self,
+		get_exchange_rate: T,
+	) {
+		self.get_exchange_rate
+			.push(ExchangeRateHandler::new(get_exchange_rate));
+	}
+
+	/// Set a handler for looking up named time zones, which allows converting
+	/// dates and times with e.g. `now to America/New_York`. Without a
+	/// resolver, only fixed offsets like `UTC+2` are supported.
+	pub fn set_timezone_resolver<T: TimeZoneResolver + 'static + Send + Sync>(
+		&mut self,
+		resolver: T,
+	) {
+		self.timezone_resolver = Some(Arc::new(resolver));
+	}
+
+	/// Use a fixed set of exchange rates instead of a handler function. The
+	/// map should contain currency codes (e.g. `USD`) mapped to their value
+	/// relative to an arbitrary (but consistent) base currency, in the same
+	/// way as [`ExchangeRateFn::relative_to_base_currency`]. Unlike other
+	/// handlers, these rates are also used by [`evaluate_preview_with_interrupt`].
+	pub fn set_exchange_rates(&mut self, rates: HashMap<String, f64>) {
+		self.get_exchange_rate = vec![ExchangeRateHandler {
+			handler: Arc::new(ExchangeRateFnV1Adapter(StaticExchangeRates(rates))),
+			is_static: true,
+		}];
 	}
 
 	pub fn define_custom_unit_v1(
@@ -393,11 +943,288 @@ impl Context {
 		));
 	}
 
+	/// Defines a custom unit, like [`Self::define_custom_unit_v1`], but
+	/// returns an error if the singular or plural name can't be used in an
+	/// expression. Valid names start with a letter (which may be any Unicode
+	/// letter, and may be uppercase) and otherwise consist of letters, digits
+	/// and underscores, e.g. `Foo`, `ångström2` or `my_unit`. Names like `m²`
+	/// are invalid because superscript digits are parsed as exponents.
+	/// Keywords like `to` or `per` are also not allowed. The plural name may
+	/// be empty, in which case the singular name is used.
+	///
+	/// # Errors
+	/// Returns an error if either name is not a valid unit name.
+	pub fn define_custom_unit_v2(
+		&mut self,
+		singular: &str,
+		plural: &str,
+		definition: &str,
+		attribute: &CustomUnitAttribute,
+	) -> Result<(), Error> {
+		let names = if plural.is_empty() {
+			&[singular][..]
+		} else {
+			&[singular, plural][..]
+		};
+		for &name in names {
+			if !lexer::is_valid_identifier(name) {
+				let e = FendError::InvalidUnitName(name.to_string());
+				return Err(Error {
+					kind: e.kind(),
+					message: e.to_string(),
+					span: None,
+				});
+			}
+		}
+		self.define_custom_unit_v1(singular, plural, definition, attribute);
+		Ok(())
+	}
+
 	/// Sets the decimal separator style for this context. This can be used to
 	/// change the number format from e.g. `1,234.00` to `1.234,00`.
 	pub fn set_decimal_separator_style(&mut self, style: DecimalSeparatorStyle) {
 		self.decimal_separator = style;
 	}
+
+	/// Sets custom decimal and digit group separators, e.g. `'·'` and `' '`
+	/// to write numbers like `1 234·5`. These are used both for parsing and
+	/// for formatting, so unlike [`Self::set_decimal_separator_style`] this
+	/// also enables digit grouping in results. Whitespace group separators
+	/// are only recognised when followed by exactly three digits, so e.g.
+	/// `1 23` is not parsed as `123`.
+	///
+	/// # Errors
+	/// Returns an error if the two separators are the same, or if either
+	/// separator is a letter, digit, operator or other character with a
+	/// special meaning. The decimal separator also can't be whitespace.
+	pub fn set_separators(&mut self, decimal: char, group: char) -> Result<(), Error> {
+		let separators = CustomSeparators::new(decimal, group).map_err(|e| Error {
+			kind: e.kind(),
+			message: e.to_string(),
+			span: None,
+		})?;
+		self.decimal_separator = DecimalSeparatorStyle::Custom(separators);
+		self.digit_group_separator = Some(group);
+		Ok(())
+	}
+
+	/// Automatically switch to scientific notation when a result has more than
+	/// the given number of digits before the decimal point. For example, with a
+	/// threshold of 20, `10^30` is shown as `1e30`. This only affects numbers
+	/// in the default `auto` format. It is disabled by default.
+	pub fn set_auto_scientific_threshold(&mut self, digits: usize) {
+		self.auto_scientific_threshold = Some(digits);
+	}
+
+	/// Always print numbers in full again after calling
+	/// [`Self::set_auto_scientific_threshold`].
+	pub fn disable_auto_scientific_threshold(&mut self) {
+		self.auto_scientific_threshold = None;
+	}
+
+	/// Limit the number of digits in a result. Numbers that are too long are
+	/// shown in scientific notation where possible (e.g. `2^1e4` becomes
+	/// `approx. 1.995063117e3010`), and otherwise evaluation fails with an
+	/// `output too long` error. There is no limit by default.
+	pub fn set_max_output_digits(&mut self, digits: usize) {
+		self.max_output_digits = Some(digits);
+	}
+
+	/// Remove the limit set by [`Self::set_max_output_digits`].
+	pub fn disable_max_output_digits(&mut self) {
+		self.max_output_digits = None;
+	}
+
+	/// Round results to the given number of significant figures by default,
+	/// as if `to N sf` had been appended to every calculation. Results with
+	/// an explicit format such as `to 3 sf` or `to 2 dp` are not affected.
+	/// Pass `None` to go back to the default formatting.
+	pub fn set_default_sf_limit(&mut self, sf: Option<NonZeroUsize>) {
+		self.default_sf_limit = sf;
+	}
+
+	/// Limit the number of variables that can be defined, which bounds memory
+	/// usage in long-running sessions. Once the limit is reached, assigning to
+	/// a new variable fails with an error, while existing variables can still
+	/// be reassigned. Nothing is evicted automatically. The `_` and `ans`
+	/// variables don't count towards the limit. There is no limit by default.
+	pub fn set_max_variables(&mut self, max: usize) {
+		self.max_variables = Some(max);
+	}
+
+	/// Group the digits of decimal numbers into blocks of three using
+	/// narrow no-break spaces (U+202F), as recommended by the SI. For example,
+	/// `1234567` is shown as `1 234 567`. This is disabled by default.
+	pub fn set_thin_space_grouping(&mut self, enabled: bool) {
+		self.set_digit_group_separator(enabled.then_some('\u{202f}'));
+	}
+
+	/// Group the digits of decimal numbers into blocks of three using the
+	/// given separator, e.g. `Some(' ')` or `Some('_')`. This is independent of
+	/// the decimal separator style, so combining a space separator with
+	/// [`DecimalSeparatorStyle::Comma`] gives `1 234 567,89`. Pass `None` to
+	/// disable grouping, which is the default.
+	pub fn set_digit_group_separator(&mut self, separator: Option<char>) {
+		self.digit_group_separator = separator;
+	}
+
+	/// Sets the decimal separator, digit grouping and date format according
+	/// to the given locale, e.g. `"en-US"` (`1,234.5` and `July 4, 2024`),
+	/// `"en-GB"` (`1,234.5` and `4 July 2024`) or `"de-DE"` (`1.234,5` and
+	/// `4 July 2024`). Month and weekday names are always in English. Unknown
+	/// locales reset these settings to their defaults.
+	pub fn set_locale(&mut self, locale: &str) {
+		let locale = locale.replace('_', "-").to_ascii_lowercase();
+		let (language, region) = locale.split_once('-').unwrap_or((&locale, ""));
+		let (decimal_separator, group) = match language {
+			"en" | "ja" | "ko" | "zh" | "he" | "th" => (DecimalSeparatorStyle::Dot, Some(',')),
+			"de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" => {
+				(DecimalSeparatorStyle::Comma, Some('.'))
+			}
+			"fr" | "ru" | "pl" | "sv" | "nb" | "fi" | "cs" | "uk" => (
+				DecimalSeparatorStyle::Custom(CustomSeparators {
+					decimal: ',',
+					group: '\u{202f}',
+				}),
+				Some('\u{202f}'),
+			),
+			_ => (DecimalSeparatorStyle::default(), None),
+		};
+		self.decimal_separator = decimal_separator;
+		self.digit_group_separator = group;
+		self.date_style = if language == "en" && region == "us" {
+			date::DateStyle::MonthDayYear
+		} else {
+			date::DateStyle::DayMonthYear
+		};
+	}
+
+	/// Sets the maximum edit distance for "did you mean" suggestions in
+	/// unknown identifier errors. For example, with a distance of 1, `metr`
+	/// suggests `meter` but `mtr` doesn't. Shorter identifiers allow fewer
+	/// edits regardless of this setting. Pass 0 to disable suggestions. The
+	/// default is 2.
+	pub fn set_max_suggestion_distance(&mut self, distance: usize) {
+		self.max_suggestion_distance = distance;
+	}
+
+	/// Sets how many names are suggested at most when an identifier is
+	/// unknown. The closest names are suggested first. The default is 3.
+	pub fn set_max_suggestions(&mut self, count: usize) {
+		self.max_suggestions = count;
+	}
+
+	/// Sets how much detail is included in error messages. By default, errors
+	/// are detailed.
+	pub fn set_error_verbosity(&mut self, verbosity: ErrorVerbosity) {
+		self.error_verbosity = verbosity;
+	}
+
+	/// Insert a space between currency symbols and amounts, e.g. `$ 5` or
+	/// `5 €` instead of `$5` or `5€`. Currency codes like `USD` are always
+	/// separated by a space. This is disabled by default.
+	pub fn set_auto_currency_symbol_spacing(&mut self, enabled: bool) {
+		self.currency_symbol_spacing = enabled;
+	}
+
+	/// Sets how fractions are written, e.g. `1/2`, `1⁄2` or `½`. Note that
+	/// fend may not be able to parse fractions written in the non-default
+	/// styles.
+	pub fn set_fraction_slash_style(&mut self, style: FractionSlashStyle) {
+		self.fraction_slash_style = style;
+	}
+
+	/// Sets the number of columns available for output. This is used to size
+	/// ASCII graphs (e.g. the bar chart for dice rolls) in the terminal output
+	/// mode. Defaults to 80.
+	pub fn set_output_width(&mut self, cols: usize) {
+		self.output_width = cols;
+	}
+
+	/// Prefixes positive numbers with a `+` sign (e.g. `+5`), which can be
+	/// useful for aligning output. Zero is printed without a sign.
+	/// Defaults to false.
+	pub fn set_show_plus_sign(&mut self, enabled: bool) {
+		self.show_plus_sign = enabled;
+	}
+
+	/// Sets how negative numbers are written, e.g. `-5` or `(5)`. Results
+	/// written with parentheses can't be evaluated again, because `(5)` is
+	/// parsed as a positive number. Complex numbers always use a minus sign.
+	pub fn set_negative_style(&mut self, style: NegativeStyle) {
+		self.negative_style = style;
+	}
+
+	/// Sets how `mod` handles negative operands. By default, the result is
+	/// never negative ([`ModuloConvention::Euclidean`]).
+	pub fn set_modulo_convention(&mut self, convention: ModuloConvention) {
+		self.modulo_convention = convention;
+	}
+
+	/// Sets whether numbers in other bases are written with their base
+	/// prefix, e.g. `0xff` or `16#ff`. If disabled, these are written as
+	/// `ff`, which may not evaluate to the same number again. Scientific
+	/// notation (e.g. `0b1e1000`) always includes the prefix. Defaults to
+	/// true.
+	pub fn set_auto_base_prefix(&mut self, enabled: bool) {
+		self.base_prefix = enabled;
+	}
+
+	/// If enabled, `evaluate_preview_with_interrupt` returns an empty result
+	/// when the last statement of the input is an assignment, so previewing
+	/// `a = 5` shows nothing instead of `5`. Earlier assignments are still
+	/// evaluated, so `a = 2; 5a` previews as `10`. Disabled by default.
+	pub fn set_preview_disable_assignments(&mut self, enabled: bool) {
+		self.preview_disable_assignments = enabled;
+	}
+
+	/// Skip a first line starting with `#!` (a "shebang" like
+	/// `#!/usr/bin/env fend`), so that fend scripts can be run directly.
+	/// This only applies at the very start of the input; `#!` anywhere else
+	/// is an error. Enabled by default. Hosts that evaluate snippets from a
+	/// larger document may want to disable this.
+	pub fn set_shebang_handling(&mut self, enabled: bool) {
+		self.skip_shebang = enabled;
+	}
+
+	/// Show exact lengths in inches, feet, yards or miles as mixed fractions
+	/// (e.g. `5 1/6 feet` or `13/16 inches`) instead of decimals, as long as
+	/// the denominator is at most 64. This only applies if no other format
+	/// was requested. Disabled by default.
+	pub fn set_prefer_mixed_fractions(&mut self, enabled: bool) {
+		self.prefer_mixed_fractions = enabled;
+	}
+
+	/// Sets whether amounts of data are shown in bits or bytes, and with SI
+	/// (`kB`, `MB`) or binary (`KiB`, `MiB`) prefixes. The largest prefix that
+	/// keeps the number at least 1 is used. Explicit conversions like
+	/// `1 GB to Mb` are not affected.
+	pub fn set_bit_byte_display_preference(&mut self, preference: BitBytePreference) {
+		self.bit_byte_preference = preference;
+	}
+
+	/// Allow units to be defined inside an expression, e.g.
+	/// `5 widgets where widget = 3 kg`. Names longer than two letters can
+	/// also be written in the plural by adding an `s`. The unit stays defined
+	/// for later calculations in the same way as
+	/// [`Self::define_custom_unit_v2`], but built-in units, functions and
+	/// variables can't be redefined. When enabled, `where` becomes a keyword
+	/// and can no longer be used as a variable name. Disabled by default.
+	pub fn set_inline_unit_definitions(&mut self, enabled: bool) {
+		self.inline_unit_definitions = enabled;
+	}
+
+	/// Keep track of how often each unit is used in successful calculations,
+	/// so that [`get_completions_for_prefix_with_context`] can rank
+	/// frequently used units first. Disabled by default. Disabling this
+	/// clears any usage counts collected so far.
+	pub fn set_unit_usage_tracking(&mut self, enabled: bool) {
+		if !enabled {
+			self.unit_usage = None;
+		} else if self.unit_usage.is_none() {
+			self.unit_usage = Some(HashMap::new());
+		}
+	}
 }
 
 /// These attributes make is possible to change the behaviour of custom units
@@ -415,6 +1242,109 @@ pub enum CustomUnitAttribute {
 	Alias,
 }
 
+/// Information about a built-in unit, as returned by [`all_units`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnitInfo {
+	singular: &'static str,
+	plural: &'static str,
+	dimension: String,
+	prefixable: bool,
+}
+
+impl UnitInfo {
+	/// The singular name of this unit, e.g. `meter`
+	#[must_use]
+	pub fn singular_name(&self) -> &str {
+		self.singular
+	}
+
+	/// The plural name of this unit, e.g. `meters`. Units without a
+	/// separate plural form (like `kg`) return their singular name.
+	#[must_use]
+	pub fn plural_name(&self) -> &str {
+		self.plural
+	}
+
+	/// The base units that this unit is made up of, e.g.
+	/// `kilogram meter second^-2` for `newton`. This is an empty string for
+	/// dimensionless units like `percent` or `dozen`.
+	#[must_use]
+	pub fn dimension(&self) -> &str {
+		&self.dimension
+	}
+
+	/// Whether this unit can be combined with prefixes like `kilo` or `k`
+	#[must_use]
+	pub fn is_prefixable(&self) -> bool {
+		self.prefixable
+	}
+}
+
+/// Returns all built-in units. Custom units and currency codes (like `USD`)
+/// are not included.
+pub fn all_units() -> impl Iterator<Item = UnitInfo> {
+	units::all_units()
+}
+
+/// Describes a unit in terms of SI base units, as returned by
+/// [`describe_unit`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnitDescription {
+	base_units: String,
+	scale: String,
+	offset: String,
+}
+
+impl UnitDescription {
+	/// The base units that the unit is made up of, e.g. `kg m s^-2` for
+	/// `newton`. This is an empty string for dimensionless units.
+	#[must_use]
+	pub fn base_units(&self) -> &str {
+		&self.base_units
+	}
+
+	/// The value of one of this unit in base units, e.g. `1609.344` for
+	/// `mile`. Values that can't be written as a decimal are shown as
+	/// fractions or in terms of π (e.g. `π/180` for `degree`).
+	#[must_use]
+	pub fn scale(&self) -> &str {
+		&self.scale
+	}
+
+	/// The amount added after scaling to convert into base units, e.g.
+	/// `273.15` for `celsius` (since 0 °C is 273.15 K) or `0` for `mile`.
+	#[must_use]
+	pub fn offset(&self) -> &str {
+		&self.offset
+	}
+}
+
+/// Describes the given unit (e.g. `newton`, `mile` or `km`) in terms of SI
+/// base units. Returns `None` if the unit is unknown. Currencies and custom
+/// units are not supported.
+#[must_use]
+pub fn describe_unit(name: &str) -> Option<UnitDescription> {
+	units::describe_unit(name)
+}
+
+/// Returns the names of all built-in functions (e.g. `sin` or `gcd`), including
+/// aliases like `log10`, sorted alphabetically.
+pub fn all_functions() -> impl Iterator<Item = &'static str> {
+	ast::BUILTIN_FUNCTIONS.iter().copied()
+}
+
+/// Returns the names of all built-in constants, e.g. `pi`, `e` or `planck`,
+/// sorted alphabetically.
+pub fn all_constants() -> impl Iterator<Item = &'static str> {
+	let mut constants: Vec<_> = ast::BUILTIN_CONSTANTS
+		.iter()
+		.copied()
+		.chain(units::constant_names())
+		.collect();
+	constants.sort_unstable();
+	constants.into_iter()
+}
+
 /// This function evaluates a string using the given context. Any evaluation using this
 /// function cannot be interrupted.
 ///
@@ -427,26 +1357,131 @@ pub fn evaluate(input: &str, context: &mut Context) -> Result<FendResult, String
 	evaluate_with_interrupt(input, context, &interrupt::Never)
 }
 
+/// The category of an [`Error`] returned by
+/// [`evaluate_with_interrupt_structured`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+	/// Division or modulo by zero, e.g. `1/0`
+	DivideByZero,
+	/// A variable, unit or function name could not be found
+	UnknownIdentifier,
+	/// A conversion between incompatible units, e.g. `1 kg to m`
+	IncompatibleUnits,
+	/// The input could not be lexed or parsed
+	Parse,
+	/// Evaluation was cancelled by the provided [`Interrupt`]
+	Interrupted,
+	/// Any other error
+	Other,
+}
+
+/// An error that occurred while evaluating an expression.
+///
+/// The error message respects the context's [`ErrorVerbosity`], and is
+/// identical to the string returned by [`evaluate_with_interrupt`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+	kind: ErrorKind,
+	message: String,
+	span: Option<(usize, usize)>,
+}
+
+impl Error {
+	/// Returns the category of this error.
+	#[must_use]
+	pub fn kind(&self) -> ErrorKind {
+		self.kind
+	}
+
+	/// Returns the error message.
+	#[must_use]
+	pub fn message(&self) -> &str {
+		&self.message
+	}
+
+	/// Returns the start and end byte offsets (end exclusive) of the part of
+	/// the input that caused this error, e.g. the unknown identifier in
+	/// `sqrt(aiusbdla)`. This is only available for syntax errors and
+	/// unknown identifiers. The span may be empty, e.g. when the input ends
+	/// unexpectedly.
+	#[must_use]
+	pub fn span(&self) -> Option<(usize, usize)> {
+		self.span
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.message)
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for String {
+	fn from(e: Error) -> Self {
+		e.message
+	}
+}
+
 fn evaluate_with_interrupt_internal(
 	input: &str,
 	context: &mut Context,
 	int: &impl Interrupt,
-) -> Result<FendResult, String> {
+) -> Result<FendResult, Error> {
 	if input.is_empty() {
 		// no or blank input: return no output
 		return Ok(FendResult::empty());
 	}
-	let (result, is_unit, attrs) = match eval::evaluate_to_spans(input, None, context, int) {
-		Ok(value) => value,
-		Err(e) => {
-			let mut error: &dyn Error = &e;
-			let mut s = error.to_string();
-			while let Some(inner) = error.source() {
-				write!(&mut s, ": {inner}").unwrap();
-				error = inner;
+	context.other_info.clear();
+	context.currency_info.clear();
+	let (result, is_unit, attrs, approximate) =
+		match eval::evaluate_to_spans(input, None, context, int) {
+			Ok(value) => value,
+			Err(e) => {
+				let kind = e.kind();
+				let span = eval::error_span(input, &e, context, int);
+				if context.error_verbosity == ErrorVerbosity::Short {
+					return Err(Error {
+						kind,
+						message: e.short_description(),
+						span,
+					});
+				}
+				let mut error: &dyn std::error::Error = &e;
+				let mut message = error.to_string();
+				while let Some(inner) = error.source() {
+					write!(&mut message, ": {inner}").unwrap();
+					error = inner;
+				}
+				if let FendError::IdentifierNotFound(ident) = &e {
+					let suggestions = suggestions::suggest_identifier(ident.as_str(), context);
+					if !suggestions.is_empty() {
+						let suggestions = suggestions::format_suggestions(&suggestions);
+						write!(&mut message, " (did you mean {suggestions}?)").unwrap();
+					}
+				}
+				return Err(Error {
+					kind,
+					message,
+					span,
+				});
+			}
+		};
+	if context.unit_usage.is_some() {
+		for ident in eval::identifiers_in(input, context, int) {
+			if units::is_completable_unit(&ident) {
+				if let Some(usage) = &mut context.unit_usage {
+					*usage.entry(ident).or_default() += 1;
+				}
 			}
-			return Err(s);
 		}
+	}
+	let result = if context.output_mode == OutputMode::Markdown {
+		wrap_markdown(result)
+	} else {
+		result
 	};
 	let mut plain_result = String::new();
 	for s in &result {
@@ -457,9 +1492,40 @@ fn evaluate_with_interrupt_internal(
 		span_result: result,
 		is_unit,
 		attrs,
+		approximate,
+		other_info: mem::take(&mut context.other_info),
+		currency_info: mem::take(&mut context.currency_info),
 	})
 }
 
+/// Wraps a result in an inline code span, or in a code block if it spans
+/// multiple lines. Markdown tables (used for distributions) are left as-is.
+fn wrap_markdown(mut spans: Vec<Span>) -> Vec<Span> {
+	let text: String = spans.iter().map(|s| s.string.as_str()).collect();
+	if text.is_empty() || (text.starts_with("| ") && text.contains('\n')) {
+		return spans;
+	}
+	// the fence needs to be longer than any run of backticks in the result
+	let mut longest_run = 0;
+	let mut run = 0;
+	for ch in text.chars() {
+		run = if ch == '`' { run + 1 } else { 0 };
+		longest_run = longest_run.max(run);
+	}
+	let (open, close) = if text.contains('\n') {
+		let fence = "`".repeat(longest_run.max(2) + 1);
+		(format!("{fence}\n"), format!("\n{fence}"))
+	} else if longest_run > 0 {
+		let fence = "`".repeat(longest_run + 1);
+		(format!("{fence} "), format!(" {fence}"))
+	} else {
+		("`".to_string(), "`".to_string())
+	};
+	spans.insert(0, Span::from_string(open));
+	spans.push(Span::from_string(close));
+	spans
+}
+
 /// This function evaluates a string using the given context and the provided
 /// Interrupt object.
 ///
@@ -473,16 +1539,72 @@ pub fn evaluate_with_interrupt(
 	context: &mut Context,
 	int: &impl Interrupt,
 ) -> Result<FendResult, String> {
+	evaluate_with_interrupt_internal(input, context, int).map_err(String::from)
+}
+
+/// This function behaves like [`evaluate_with_interrupt`], but returns a
+/// structured [`Error`] instead of a plain string, so that callers can
+/// distinguish between different kinds of errors.
+///
+/// # Errors
+/// It returns an error if the given string is invalid.
+/// This may be due to parser or runtime errors.
+pub fn evaluate_with_interrupt_structured(
+	input: &str,
+	context: &mut Context,
+	int: &impl Interrupt,
+) -> Result<FendResult, Error> {
 	evaluate_with_interrupt_internal(input, context, int)
 }
 
+/// Evaluates the given string and returns the result as a JSON object, for
+/// use by frontends that can't call the Rust API directly.
+///
+/// A successful result looks like this:
+///
+/// ```json
+/// {"ok":true,"result":"1000 m","approx":false,"empty":false,
+///  "spans":[{"kind":"number","string":"1000"},{"kind":"ident","string":" m"}]}
+/// ```
+///
+/// `empty` is true if there is no output to display (e.g. for an empty input
+/// or the unit type `()`), and span kinds are named as in [`SpanKind::as_str`].
+/// Errors are returned as `{"ok":false,"message":"..."}`.
+pub fn evaluate_to_json(input: &str, context: &mut Context, int: &impl Interrupt) -> String {
+	let res = match evaluate_with_interrupt_internal(input, context, int) {
+		Ok(res) => res,
+		Err(e) => {
+			let mut message = String::new();
+			json::escape_string(e.message(), &mut message);
+			return format!(r#"{{"ok":false,"message":"{message}"}}"#);
+		}
+	};
+	let mut result = String::new();
+	json::escape_string(res.get_main_result(), &mut result);
+	let mut spans = String::new();
+	for (i, span) in res.get_main_result_spans().enumerate() {
+		if i != 0 {
+			spans.push(',');
+		}
+		write!(spans, r#"{{"kind":"{}","string":""#, span.kind().as_str()).unwrap();
+		json::escape_string(span.string(), &mut spans);
+		spans.push_str("\"}");
+	}
+	format!(
+		r#"{{"ok":true,"result":"{result}","approx":{},"empty":{},"spans":[{spans}]}}"#,
+		res.is_approximate(),
+		res.is_unit_type() || res.get_main_result().is_empty(),
+	)
+}
+
 /// Evaluate the given string to use as a live preview.
 ///
 /// Unlike the normal evaluation functions, `evaluate_preview_with_interrupt`
 /// does not mutate the passed-in context, and only returns results suitable
 /// for displaying as a live preview: overly long output, multi-line output,
 /// unit types etc. are all filtered out. RNG functions (e.g. `roll d6`) are
-/// also disabled. Currency conversions (exchange rates) are disabled.
+/// also disabled. Currency conversions (exchange rates) are disabled, unless
+/// they use fixed rates set with [`Context::set_exchange_rates`].
 pub fn evaluate_preview_with_interrupt(
 	input: &str,
 	context: &mut Context,
@@ -493,13 +1615,18 @@ pub fn evaluate_preview_with_interrupt(
 	// because we want variables to still work in multi-statement inputs
 	// like `a = 2; 5a`.
 	let context_clone = context.clone();
-	context.random_u32 = None;
-	context.get_exchange_rate = None;
+	context.rng = None;
+	context
+		.get_exchange_rate
+		.retain(|exchange_rate_fn| exchange_rate_fn.is_static);
 	let result = evaluate_with_interrupt_internal(input, context, int);
 	*context = context_clone;
 	let Ok(result) = result else {
 		return empty;
 	};
+	if context.preview_disable_assignments && eval::ends_with_assignment(input, context, int) {
+		return empty;
+	}
 	let s = result.get_main_result();
 	if s.is_empty()
 		|| result.is_unit_type()
@@ -584,7 +1711,24 @@ static GREEK_UPPERCASE_LETTERS: [(&str, &str); 24] = [
 ];
 
 #[must_use]
-pub fn get_completions_for_prefix(mut prefix: &str) -> (usize, Vec<Completion>) {
+pub fn get_completions_for_prefix(prefix: &str) -> (usize, Vec<Completion>) {
+	completions_for_prefix(prefix, None)
+}
+
+/// Like [`get_completions_for_prefix`], but also completes the names of
+/// variables defined in the given context, which are listed before any
+/// units. If unit usage tracking is enabled (see
+/// [`Context::set_unit_usage_tracking`]), units that have been used more
+/// often are ranked first.
+#[must_use]
+pub fn get_completions_for_prefix_with_context(
+	prefix: &str,
+	context: &Context,
+) -> (usize, Vec<Completion>) {
+	completions_for_prefix(prefix, Some(context))
+}
+
+fn completions_for_prefix(mut prefix: &str, context: Option<&Context>) -> (usize, Vec<Completion>) {
 	if let Some((prefix, letter)) = prefix.rsplit_once('\\') {
 		if letter.starts_with(|c: char| c.is_ascii_alphabetic()) && letter.len() <= 7 {
 			return if letter.starts_with(|c: char| c.is_ascii_uppercase()) {
@@ -616,7 +1760,35 @@ pub fn get_completions_for_prefix(mut prefix: &str) -> (usize, Vec<Completion>)
 	if prefix.is_empty() {
 		return (0, vec![]);
 	}
-	let mut res = units::get_completions_for_prefix(prefix);
+	let units =
+		units::get_completions_for_prefix(prefix, context.and_then(|c| c.unit_usage.as_ref()));
+	let mut res: Vec<Completion> = context
+		.map(Context::variable_names)
+		.unwrap_or_default()
+		.into_iter()
+		.filter(|name| name.starts_with(prefix) && name != prefix)
+		.filter(|name| !units.iter().any(|c| c.display == *name))
+		.map(|name| Completion {
+			insert: name[prefix.len()..].to_string(),
+			display: name,
+		})
+		.collect();
+	res.extend(units);
+	// a prefix that is already the name of a unit (e.g. `s` or `min`) most
+	// likely refers to that unit, so don't suggest functions for it
+	if !units::all_unit_names().any(|name| name == prefix) {
+		for name in ast::BUILTIN_FUNCTIONS {
+			if name.starts_with(prefix)
+				&& *name != prefix
+				&& !res.iter().any(|c| c.display == *name)
+			{
+				res.push(Completion {
+					display: (*name).to_string(),
+					insert: name[prefix.len()..].to_string(),
+				});
+			}
+		}
+	}
 	for c in &mut res {
 		c.display.insert_str(0, prepend);
 	}
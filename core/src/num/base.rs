@@ -7,7 +7,14 @@ use crate::{
 };
 
 #[derive(Copy, Clone, PartialEq, Eq)]
-pub(crate) struct Base(BaseEnum);
+pub(crate) struct Base {
+	kind: BaseEnum,
+	/// Whether digits should be separated into groups, e.g. `1111_1111`
+	grouped: bool,
+	/// Minimum number of integer digits, padded with leading zeroes,
+	/// e.g. `00000101`
+	min_digits: usize,
+}
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 enum BaseEnum {
@@ -24,10 +31,21 @@ enum BaseEnum {
 }
 
 impl Base {
-	pub(crate) const HEX: Self = Self(BaseEnum::Hex);
+	const fn new(kind: BaseEnum) -> Self {
+		Self {
+			kind,
+			grouped: false,
+			min_digits: 0,
+		}
+	}
+
+	pub(crate) const HEX: Self = Self::new(BaseEnum::Hex);
+
+	/// Largest supported value for [`Self::with_min_digits`]
+	pub(crate) const MAX_MIN_DIGITS: usize = 1000;
 
 	pub(crate) const fn base_as_u8(self) -> u8 {
-		match self.0 {
+		match self.kind {
 			BaseEnum::Binary => 2,
 			BaseEnum::Octal => 8,
 			BaseEnum::Hex => 16,
@@ -37,9 +55,9 @@ impl Base {
 
 	pub(crate) const fn from_zero_based_prefix_char(ch: char) -> FResult<Self> {
 		Ok(match ch {
-			'x' => Self(BaseEnum::Hex),
-			'o' => Self(BaseEnum::Octal),
-			'b' => Self(BaseEnum::Binary),
+			'x' => Self::new(BaseEnum::Hex),
+			'o' => Self::new(BaseEnum::Octal),
+			'b' => Self::new(BaseEnum::Binary),
 			_ => return Err(FendError::InvalidBasePrefix),
 		})
 	}
@@ -50,7 +68,7 @@ impl Base {
 		} else if base > 36 {
 			return Err(FendError::BaseTooLarge);
 		}
-		Ok(Self(BaseEnum::Plain(base)))
+		Ok(Self::new(BaseEnum::Plain(base)))
 	}
 
 	pub(crate) const fn from_custom_base(base: u8) -> FResult<Self> {
@@ -59,11 +77,11 @@ impl Base {
 		} else if base > 36 {
 			return Err(FendError::BaseTooLarge);
 		}
-		Ok(Self(BaseEnum::Custom(base)))
+		Ok(Self::new(BaseEnum::Custom(base)))
 	}
 
 	pub(crate) fn write_prefix(self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-		match self.0 {
+		match self.kind {
 			BaseEnum::Binary => write!(f, "0b")?,
 			BaseEnum::Octal => write!(f, "0o")?,
 			BaseEnum::Hex => write!(f, "0x")?,
@@ -73,8 +91,65 @@ impl Base {
 		Ok(())
 	}
 
+	/// Returns this base with a prefix that identifies it when parsing, e.g.
+	/// `0b` for binary or `3#` for base 3. Decimal numbers are left unchanged.
+	pub(crate) const fn with_explicit_prefix(self) -> Self {
+		let kind = match self.kind {
+			BaseEnum::Plain(2) => BaseEnum::Binary,
+			BaseEnum::Plain(8) => BaseEnum::Octal,
+			BaseEnum::Plain(16) => BaseEnum::Hex,
+			BaseEnum::Plain(b) if b != 10 => BaseEnum::Custom(b),
+			kind => kind,
+		};
+		Self { kind, ..self }
+	}
+
+	/// Returns this base without a prefix, e.g. `ff` instead of `0xff`
+	pub(crate) const fn without_prefix(self) -> Self {
+		Self {
+			kind: BaseEnum::Plain(self.base_as_u8()),
+			..self
+		}
+	}
+
+	/// Returns this base with digit grouping enabled
+	pub(crate) const fn with_grouping(self) -> Self {
+		Self {
+			kind: self.kind,
+			grouped: true,
+			min_digits: self.min_digits,
+		}
+	}
+
+	pub(crate) const fn is_grouped(self) -> bool {
+		self.grouped
+	}
+
+	/// Returns this base with the integer part padded to at least
+	/// `min_digits` digits. Numbers that need more digits are printed in full.
+	pub(crate) const fn with_min_digits(self, min_digits: usize) -> Self {
+		Self {
+			kind: self.kind,
+			grouped: self.grouped,
+			min_digits,
+		}
+	}
+
+	pub(crate) const fn min_digits(self) -> usize {
+		self.min_digits
+	}
+
+	/// Number of digits per group: 4 for binary and hex (i.e. two groups per
+	/// byte), and 3 otherwise
+	pub(crate) const fn digit_group_size(self) -> usize {
+		match self.base_as_u8() {
+			2 | 16 => 4,
+			_ => 3,
+		}
+	}
+
 	pub(crate) const fn has_prefix(self) -> bool {
-		!matches!(self.0, BaseEnum::Plain(_))
+		!matches!(self.kind, BaseEnum::Plain(_))
 	}
 
 	pub(crate) const fn digit_as_char(digit: u64) -> Option<char> {
@@ -120,7 +195,8 @@ impl Base {
 	}
 
 	pub(crate) fn serialize(self, write: &mut impl io::Write) -> FResult<()> {
-		match self.0 {
+		self.grouped.serialize(write)?;
+		match self.kind {
 			BaseEnum::Binary => 1u8.serialize(write)?,
 			BaseEnum::Octal => 2u8.serialize(write)?,
 			BaseEnum::Hex => 3u8.serialize(write)?,
@@ -133,35 +209,58 @@ impl Base {
 				b.serialize(write)?;
 			}
 		}
+		self.min_digits.serialize(write)?;
 		Ok(())
 	}
 
 	pub(crate) fn deserialize(read: &mut impl io::Read) -> FResult<Self> {
-		Ok(Self(match u8::deserialize(read)? {
+		let grouped = bool::deserialize(read)?;
+		let kind = match u8::deserialize(read)? {
 			1 => BaseEnum::Binary,
 			2 => BaseEnum::Octal,
 			3 => BaseEnum::Hex,
 			4 => BaseEnum::Custom(u8::deserialize(read)?),
 			5 => BaseEnum::Plain(u8::deserialize(read)?),
 			_ => return Err(FendError::DeserializationError),
-		}))
+		};
+		if let BaseEnum::Custom(b) | BaseEnum::Plain(b) = kind {
+			if !(2..=36).contains(&b) {
+				return Err(FendError::DeserializationError);
+			}
+		}
+		let min_digits = usize::deserialize(read)?;
+		if min_digits > Self::MAX_MIN_DIGITS {
+			return Err(FendError::DeserializationError);
+		}
+		Ok(Self {
+			kind,
+			grouped,
+			min_digits,
+		})
 	}
 }
 
 impl Default for Base {
 	fn default() -> Self {
-		Self(BaseEnum::Plain(10))
+		Self::new(BaseEnum::Plain(10))
 	}
 }
 
 impl fmt::Debug for Base {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		match self.0 {
+		match self.kind {
 			BaseEnum::Binary => write!(f, "binary"),
 			BaseEnum::Octal => write!(f, "octal"),
 			BaseEnum::Hex => write!(f, "hex"),
 			BaseEnum::Custom(b) => write!(f, "base {b} (with prefix)"),
 			BaseEnum::Plain(b) => write!(f, "base {b}"),
+		}?;
+		if self.grouped {
+			write!(f, " (grouped)")?;
+		}
+		if self.min_digits > 0 {
+			write!(f, " (with {} digits)", self.min_digits)?;
 		}
+		Ok(())
 	}
 }
@@ -4,7 +4,7 @@ use crate::interrupt::test_int;
 use crate::num::biguint::BigUint;
 use crate::num::{Base, Exact, FormattingStyle, Range, RangeBound};
 use crate::result::FResult;
-use crate::DecimalSeparatorStyle;
+use crate::{DecimalSeparatorStyle, FractionSlashStyle, ModuloConvention};
 use core::f64;
 use std::{cmp, fmt, hash, io, ops};
 
@@ -127,17 +127,23 @@ impl BigRat {
 	}
 
 	pub(crate) fn deserialize(read: &mut impl io::Read) -> FResult<Self> {
-		Ok(Self {
-			sign: Sign::deserialize(read)?,
-			num: BigUint::deserialize(read)?,
-			den: BigUint::deserialize(read)?,
-		})
+		let sign = Sign::deserialize(read)?;
+		let num = BigUint::deserialize(read)?;
+		let den = BigUint::deserialize(read)?;
+		if den == 0.into() {
+			return Err(FendError::DeserializationError);
+		}
+		Ok(Self { sign, num, den })
 	}
 
 	pub(crate) fn is_integer(&self) -> bool {
 		self.den == 1.into()
 	}
 
+	pub(crate) fn has_denominator_at_most<I: Interrupt>(&self, max: u64, int: &I) -> FResult<bool> {
+		Ok(self.clone().simplify(int)?.den <= max.into())
+	}
+
 	pub(crate) fn try_as_biguint<I: Interrupt>(mut self, int: &I) -> FResult<BigUint> {
 		if self.sign == Sign::Negative && self.num != 0.into() {
 			return Err(FendError::NegativeNumbersNotAllowed);
@@ -149,6 +155,174 @@ impl BigRat {
 		Ok(self.num)
 	}
 
+	/// Returns this integer as a `width`-bit two's complement number, e.g.
+	/// `255` for `-1` with 8 bits. Non-negative numbers are returned
+	/// unchanged. The number must lie between `-2^(width-1)` and `2^width - 1`.
+	pub(crate) fn into_twos_complement<I: Interrupt>(
+		mut self,
+		width: u64,
+		int: &I,
+	) -> FResult<BigUint> {
+		self = self.simplify(int)?;
+		if self.den != 1.into() {
+			return Err(FendError::FractionToInteger);
+		}
+		let limit = BigUint::from(1).lshift_n(&width.into(), int)?;
+		let negative = self.sign == Sign::Negative && self.num != 0.into();
+		let fits = if negative {
+			self.num.clone().lshift_n(&1.into(), int)? <= limit
+		} else {
+			self.num < limit
+		};
+		if !fits {
+			let lowest = Self {
+				sign: Sign::Negative,
+				num: BigUint::from(1).lshift_n(&(width - 1).into(), int)?,
+				den: 1.into(),
+			};
+			let highest = Self {
+				sign: Sign::Positive,
+				num: limit.sub(&1.into()),
+				den: 1.into(),
+			};
+			return Err(out_of_range(
+				self.fm(int)?,
+				Range {
+					start: RangeBound::Closed(lowest.fm(int)?),
+					end: RangeBound::Closed(highest.fm(int)?),
+				},
+			));
+		}
+		Ok(if negative {
+			limit.sub(&self.num)
+		} else {
+			self.num
+		})
+	}
+
+	/// Writes this number as a continued fraction like `[3; 7, 15, 1]`, with
+	/// at most `max_terms` terms. A trailing `...` is added if the expansion
+	/// was cut short or if this number is only an approximation.
+	pub(crate) fn into_continued_fraction<I: Interrupt>(
+		self,
+		max_terms: usize,
+		exact: bool,
+		int: &I,
+	) -> FResult<String> {
+		let (int_part, mut num) = self.num.divmod(&self.den, int)?;
+		let mut den = self.den;
+		// the first term is the floor of the number, so the remaining
+		// fractional part is always non-negative
+		let first = if self.sign == Sign::Negative && num != 0.into() {
+			num = den.clone().sub(&num);
+			format!("-{}", int_part.add(&1.into()).to_decimal_string(int)?)
+		} else if self.sign == Sign::Negative && int_part != 0.into() {
+			format!("-{}", int_part.to_decimal_string(int)?)
+		} else {
+			int_part.to_decimal_string(int)?
+		};
+		let mut terms = vec![];
+		while num != 0.into() && terms.len() + 1 < max_terms {
+			test_int(int)?;
+			let (term, rem) = den.divmod(&num, int)?;
+			terms.push(term.to_decimal_string(int)?);
+			den = num;
+			num = rem;
+		}
+		if num != 0.into() || !exact {
+			terms.push("...".to_string());
+		}
+		if terms.is_empty() {
+			Ok(format!("[{first}]"))
+		} else {
+			Ok(format!("[{first}; {}]", terms.join(", ")))
+		}
+	}
+
+	/// Writes this number as a hexadecimal floating-point literal like
+	/// `0x1.8p0`, as printed by C's `%a`. The mantissa is normalised to
+	/// `1 <= m < 2` and rounded to 13 hex digits after the point (the
+	/// precision of a double). The result is inexact if rounding was needed.
+	pub(crate) fn into_hex_float<I: Interrupt>(self, int: &I) -> FResult<Exact<String>> {
+		const MAX_DIGITS: usize = 13;
+		if self.num == 0.into() {
+			return Ok(Exact::new("0x0p0".to_string(), true));
+		}
+		// scale numerator and denominator so that `den <= num < 2 * den`
+		let num_bits = self.num.bit_length();
+		let den_bits = self.den.bit_length();
+		let mut exponent = i128::from(num_bits) - i128::from(den_bits);
+		let (mut num, mut den) = (self.num, self.den);
+		if num_bits >= den_bits {
+			den = den.lshift_n(&(num_bits - den_bits).into(), int)?;
+		} else {
+			num = num.lshift_n(&(den_bits - num_bits).into(), int)?;
+		}
+		if num < den {
+			num = num.lshift_n(&1.into(), int)?;
+			exponent -= 1;
+		}
+		let mut rem = num.sub(&den);
+		let mut digits = vec![];
+		while rem != 0.into() && digits.len() < MAX_DIGITS {
+			test_int(int)?;
+			let (digit, r) = rem.mul(&16.into(), int)?.divmod(&den, int)?;
+			digits.push(digit.try_as_usize(int)?);
+			rem = r;
+		}
+		let exact = rem == 0.into();
+		// round to nearest, with ties rounding up
+		if rem.mul(&2.into(), int)? >= den {
+			while digits.last() == Some(&15) {
+				digits.pop();
+			}
+			if let Some(last) = digits.last_mut() {
+				*last += 1;
+			} else {
+				// the mantissa was rounded up to 2
+				exponent += 1;
+			}
+		}
+		while digits.last() == Some(&0) {
+			digits.pop();
+		}
+		let mut res = String::new();
+		if self.sign == Sign::Negative {
+			res.push('-');
+		}
+		res.push_str("0x1");
+		if !digits.is_empty() {
+			res.push('.');
+			for digit in digits {
+				res.push(char::from(b"0123456789abcdef"[digit]));
+			}
+		}
+		Ok(Exact::new(format!("{res}p{exponent}"), exact))
+	}
+
+	/// Rounds this number to the nearest multiple of `1/den`, rounding halves
+	/// away from zero
+	pub(crate) fn round_to_denominator<I: Interrupt>(
+		self,
+		den: &BigUint,
+		int: &I,
+	) -> FResult<Exact<Self>> {
+		if *den == 0.into() {
+			return Err(FendError::DivideByZero);
+		}
+		let (mut num, rem) = self.num.mul(den, int)?.divmod(&self.den, int)?;
+		let exact = rem == 0.into();
+		if rem.mul(&2.into(), int)? >= self.den {
+			num = num.add(&1.into());
+		}
+		let result = Self {
+			sign: self.sign,
+			num,
+			den: den.clone(),
+		};
+		Ok(Exact::new(result.simplify(int)?, exact))
+	}
+
 	pub(crate) fn try_as_usize<I: Interrupt>(mut self, int: &I) -> FResult<usize> {
 		if self.sign == Sign::Negative && self.num != 0.into() {
 			return Err(FendError::NegativeNumbersNotAllowed);
@@ -315,11 +489,90 @@ impl BigRat {
 		))
 	}
 
+	/// Computes the natural logarithm of the gamma function using the Lanczos
+	/// approximation, which avoids computing huge factorials, e.g.
+	/// `lgamma(101) = ln(100!)`. Only positive numbers are supported.
+	pub(crate) fn lgamma<I: Interrupt>(self, int: &I) -> FResult<Exact<Self>> {
+		if self <= 0.into() {
+			return Err(out_of_range(
+				self.fm(int)?,
+				Range {
+					start: RangeBound::Open(0),
+					end: RangeBound::None,
+				},
+			));
+		}
+		if self == 1.into() || self == 2.into() {
+			return Ok(Exact::new(0.into(), true));
+		}
+		Ok(Exact::new(
+			Self::from_f64(lanczos_lgamma(self.into_f64(int)?), int)?,
+			false,
+		))
+	}
+
+	/// Computes the beta function `gamma(a) * gamma(b) / gamma(a + b)` for
+	/// positive `a` and `b`. If either argument is an integer the result is
+	/// exact, otherwise it's computed from `lgamma` so that large arguments
+	/// don't overflow.
+	pub(crate) fn beta<I: Interrupt>(self, rhs: Self, int: &I) -> FResult<Exact<Self>> {
+		for x in [&self, &rhs] {
+			if *x <= 0.into() {
+				return Err(out_of_range(
+					x.clone().fm(int)?,
+					Range {
+						start: RangeBound::Open(0),
+						end: RangeBound::None,
+					},
+				));
+			}
+		}
+		let (a, b) = (self.simplify(int)?, rhs.simplify(int)?);
+		// use the smaller integer argument as `n`, to keep the loop short
+		let (other, n) = match (a.is_integer(), b.is_integer()) {
+			(true, true) if a < b => (b, a.num),
+			(true, false) => (b, a.num),
+			(_, true) => (a, b.num),
+			(false, false) => {
+				let (a, b) = (a.into_f64(int)?, b.into_f64(int)?);
+				let result = (lanczos_lgamma(a) + lanczos_lgamma(b) - lanczos_lgamma(a + b)).exp();
+				return Ok(Exact::new(Self::from_f64(result, int)?, false));
+			}
+		};
+		// beta(x, n) = (n - 1)! / (x * (x + 1) * ... * (x + n - 1)), where `x` is
+		// the other argument
+		let mut num = BigUint::from(1);
+		let mut den = other.clone();
+		let mut k = BigUint::from(1);
+		while k < n {
+			test_int(int)?;
+			num = num.mul(&k, int)?;
+			den = den.mul(&other.clone().add(Self::from(k.clone()), int)?, int)?;
+			k = k.add(&1.into());
+		}
+		Ok(Exact::new(Self::from(num).div(&den, int)?, true))
+	}
+
 	pub(crate) fn log10<I: Interrupt>(self, int: &I) -> FResult<Self> {
 		self.log2(int)?
 			.div(&Self::from_f64(std::f64::consts::LOG2_10, int)?, int)
 	}
 
+	pub(crate) fn log<I: Interrupt>(self, base: Self, int: &I) -> FResult<Self> {
+		let result = self.clone().log2(int)?.div(&base.clone().log2(int)?, int)?;
+		// avoid floating-point error if `self` is an integer power of `base`
+		let rounded = result.clone().round(int)?;
+		if rounded
+			.clone()
+			.try_as_i64(int)
+			.is_ok_and(|n| n.abs() <= 1000)
+			&& base.pow(rounded.clone(), int)?.value == self
+		{
+			return Ok(rounded);
+		}
+		Ok(result)
+	}
+
 	fn apply_uint_op<I: Interrupt, R>(
 		mut self,
 		f: impl FnOnce(BigUint, &I) -> FResult<R>,
@@ -340,6 +593,39 @@ impl BigRat {
 		Ok(self.apply_uint_op(BigUint::factorial, int)?.into())
 	}
 
+	pub(crate) fn factorize<I: Interrupt>(self, int: &I) -> FResult<Vec<(BigUint, u64)>> {
+		self.apply_uint_op(BigUint::factorize, int)
+	}
+
+	pub(crate) fn next_prime<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(self.apply_uint_op(BigUint::next_prime, int)?.into())
+	}
+
+	pub(crate) fn prev_prime<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(self.apply_uint_op(BigUint::prev_prime, int)?.into())
+	}
+
+	pub(crate) fn isqrt<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(self
+			.apply_uint_op(|n, int| Ok(n.root_n(&2.into(), int)?.value), int)?
+			.into())
+	}
+
+	pub(crate) fn popcount<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(self.apply_uint_op(|n, _| Ok(n.count_ones()), int)?.into())
+	}
+
+	pub(crate) fn bitlength<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(self.apply_uint_op(|n, _| Ok(n.bit_length()), int)?.into())
+	}
+
+	pub(crate) fn byteswap<I: Interrupt>(self, width: Self, int: &I) -> FResult<Self> {
+		let width = width.apply_uint_op(|w, int| w.try_as_usize(int), int)?;
+		Ok(self
+			.apply_uint_op(|n, int| n.byteswap(width, int), int)?
+			.into())
+	}
+
 	pub(crate) fn floor<I: Interrupt>(self, int: &I) -> FResult<Self> {
 		let float = self.into_f64(int)?.floor();
 		Self::from_f64(float, int)
@@ -434,6 +720,67 @@ impl BigRat {
 		})
 	}
 
+	/// Writes this number out in English words. Halves and quarters are
+	/// written as fractions (e.g. "three quarters"), other decimals that
+	/// terminate are written digit by digit (e.g. "zero point two"), and
+	/// any remaining fractions, or any fraction if `as_fraction` is set, are
+	/// written like "one and two thirds".
+	pub(crate) fn into_words<I: Interrupt>(self, as_fraction: bool, int: &I) -> FResult<String> {
+		if self.sign == Sign::Negative && self.num != 0.into() {
+			return Err(FendError::NegativeNumbersNotAllowed);
+		}
+		let this = self.simplify(int)?;
+		let (integer_part, remainder) = this.num.divmod(&this.den, int)?;
+		let integer_words = integer_part.to_words(int)?;
+		if remainder == 0.into() {
+			return Ok(integer_words);
+		}
+		let mut den_without_2_and_5 = this.den.clone();
+		for factor in [2, 5] {
+			loop {
+				let (quotient, r) = den_without_2_and_5.divmod(&factor.into(), int)?;
+				if r != 0.into() {
+					break;
+				}
+				den_without_2_and_5 = quotient;
+			}
+		}
+		let is_half_or_quarter = this.den == 2.into() || this.den == 4.into();
+		if !as_fraction && !is_half_or_quarter && den_without_2_and_5 == 1.into() {
+			let mut result = integer_words;
+			result.push_str(" point");
+			let mut remainder = remainder;
+			while remainder != 0.into() {
+				let (digit, r) = remainder.mul(&10.into(), int)?.divmod(&this.den, int)?;
+				result.push(' ');
+				result.push_str(&digit.to_words(int)?);
+				remainder = r;
+			}
+			return Ok(result);
+		}
+		let plural = remainder != 1.into();
+		let mut denominator = if this.den == 2.into() {
+			"half".to_string()
+		} else if this.den == 4.into() {
+			"quarter".to_string()
+		} else {
+			this.den.to_ordinal_words(int)?
+		};
+		if plural {
+			if let Some(stem) = denominator.strip_suffix("half") {
+				denominator = format!("{stem}halves");
+			} else {
+				denominator.push('s');
+			}
+		}
+		let fraction = format!("{} {denominator}", remainder.to_words(int)?);
+		if integer_part == 0.into() {
+			Ok(fraction)
+		} else {
+			Ok(format!("{integer_words} and {fraction}"))
+		}
+	}
+
 	fn simplify<I: Interrupt>(mut self, int: &I) -> FResult<Self> {
 		if self.den == 1.into() {
 			return Ok(self);
@@ -455,23 +802,28 @@ impl BigRat {
 		})
 	}
 
-	pub(crate) fn modulo<I: Interrupt>(mut self, mut rhs: Self, int: &I) -> FResult<Self> {
+	pub(crate) fn modulo<I: Interrupt>(
+		mut self,
+		mut rhs: Self,
+		convention: ModuloConvention,
+		int: &I,
+	) -> FResult<Self> {
 		if rhs.num == 0.into() {
 			return Err(FendError::ModuloByZero);
 		}
 		self = self.simplify(int)?;
 		rhs = rhs.simplify(int)?;
-		if (self.sign == Sign::Negative && self.num != 0.into())
-			|| rhs.sign == Sign::Negative
-			|| self.den != 1.into()
-			|| rhs.den != 1.into()
-		{
-			return Err(FendError::ModuloForPositiveInts);
+		if self.den != 1.into() || rhs.den != 1.into() {
+			return Err(FendError::ModuloForIntegers);
 		}
-		Ok(Self {
-			sign: Sign::Positive,
-			num: self.num.divmod(&rhs.num, int)?.1,
-			den: 1.into(),
+		let remainder = self.num.divmod(&rhs.num, int)?.1;
+		if self.sign == Sign::Positive || remainder == 0.into() {
+			return Ok(Self::from(remainder));
+		}
+		// the dividend is negative
+		Ok(match convention {
+			ModuloConvention::Euclidean => Self::from(rhs.num.sub(&remainder)),
+			ModuloConvention::Truncated => -Self::from(remainder),
 		})
 	}
 
@@ -498,13 +850,14 @@ impl BigRat {
 
 	fn format_as_integer<I: Interrupt>(
 		num: &BigUint,
-		base: Base,
 		sign: Sign,
-		term: &'static str,
-		use_parens_if_product: bool,
 		sf_limit: Option<usize>,
+		params: &FormatOptions,
 		int: &I,
 	) -> FResult<Exact<FormattedBigRat>> {
+		let base = params.base;
+		let term = params.term;
+		let use_parens_if_product = params.use_parens_if_fraction;
 		let (ty, exact) = if !term.is_empty() && !base.has_prefix() && num == &1.into() {
 			(FormattedBigRatType::Integer(None, false, term, false), true)
 		} else {
@@ -513,6 +866,8 @@ impl BigRat {
 					base,
 					write_base_prefix: true,
 					sf_limit,
+					group_separator: params.group_separator,
+					min_digits: base.min_digits(),
 				},
 				int,
 			)?;
@@ -527,22 +882,33 @@ impl BigRat {
 				formatted_int.exact,
 			)
 		};
-		Ok(Exact::new(FormattedBigRat { sign, ty }, exact))
+		Ok(Exact::new(
+			FormattedBigRat {
+				sign,
+				plus_sign: false,
+				ty,
+			},
+			exact,
+		))
 	}
 
 	fn format_as_fraction<I: Interrupt>(
 		&self,
-		base: Base,
 		sign: Sign,
-		term: &'static str,
 		mixed: bool,
-		use_parens: bool,
+		params: &FormatOptions,
 		int: &I,
 	) -> FResult<Exact<FormattedBigRat>> {
+		let base = params.base;
+		let term = params.term;
+		let use_parens = params.use_parens_if_fraction;
+		let slash_style = params.fraction_slash_style;
 		let format_options = biguint::FormatOptions {
 			base,
 			write_base_prefix: true,
 			sf_limit: None,
+			group_separator: params.group_separator,
+			min_digits: 0,
 		};
 		let formatted_den = self.den.format(&format_options, int)?;
 		let (pref, num, prefix_exact) = if mixed {
@@ -558,6 +924,23 @@ impl BigRat {
 		};
 		// mixed fractions without a prefix aren't really mixed
 		let actually_mixed = pref.is_some();
+		if slash_style == FractionSlashStyle::Vulgar && term.is_empty() && base.base_as_u8() == 10 {
+			if let Some(vulgar) = vulgar_fraction(&num, &self.den, int)? {
+				return Ok(Exact::new(
+					FormattedBigRat {
+						sign,
+						plus_sign: false,
+						ty: FormattedBigRatType::Integer(pref, false, vulgar, use_parens),
+					},
+					prefix_exact,
+				));
+			}
+		}
+		let slash = if slash_style == FractionSlashStyle::FractionSlash {
+			"\u{2044}"
+		} else {
+			"/"
+		};
 		let (ty, num_exact) =
 			if !term.is_empty() && !actually_mixed && !base.has_prefix() && num == 1.into() {
 				(
@@ -566,6 +949,7 @@ impl BigRat {
 						None,
 						false,
 						term,
+						slash,
 						formatted_den.value,
 						"",
 						use_parens,
@@ -587,6 +971,7 @@ impl BigRat {
 						Some(formatted_num.value),
 						space,
 						isuf1,
+						slash,
 						formatted_den.value,
 						isuf2,
 						use_parens,
@@ -595,22 +980,25 @@ impl BigRat {
 				)
 			};
 		Ok(Exact::new(
-			FormattedBigRat { sign, ty },
+			FormattedBigRat {
+				sign,
+				plus_sign: false,
+				ty,
+			},
 			formatted_den.exact && prefix_exact && num_exact,
 		))
 	}
 
-	#[allow(clippy::too_many_arguments)]
 	fn format_as_decimal<I: Interrupt>(
 		&self,
-		style: FormattingStyle,
-		base: Base,
 		sign: Sign,
-		term: &'static str,
 		mut terminating: impl FnMut() -> FResult<bool>,
-		decimal_separator: DecimalSeparatorStyle,
+		params: &FormatOptions,
 		int: &I,
 	) -> FResult<Exact<FormattedBigRat>> {
+		let style = params.style;
+		let base = params.base;
+		let term = params.term;
 		let integer_part = self.clone().num.div(&self.den, int)?;
 		let sf_limit = if let FormattingStyle::SignificantFigures(sf) = style {
 			Some(sf)
@@ -622,6 +1010,8 @@ impl BigRat {
 				base,
 				write_base_prefix: true,
 				sf_limit,
+				group_separator: params.group_separator,
+				min_digits: base.min_digits(),
 			},
 			int,
 		)?;
@@ -678,12 +1068,13 @@ impl BigRat {
 			num_trailing_digits_to_print,
 			terminating,
 			print_integer_part,
-			decimal_separator,
+			params.decimal_separator,
 			int,
 		)?;
 		Ok(Exact::new(
 			FormattedBigRat {
 				sign,
+				plus_sign: false,
 				ty: FormattedBigRatType::Decimal(
 					formatted_trailing_digits.value,
 					!term.is_empty() && base.base_as_u8() > 10,
@@ -694,6 +1085,145 @@ impl BigRat {
 		))
 	}
 
+	/// Formats a positive number in scientific notation, e.g. 1.23e5. If no
+	/// significant figures limit is given, the mantissa is rounded to
+	/// 10 significant figures.
+	#[allow(clippy::too_many_lines)]
+	fn format_as_scientific<I: Interrupt>(
+		&self,
+		sign: Sign,
+		sf_limit: Option<usize>,
+		params: &FormatOptions,
+		int: &I,
+	) -> FResult<Exact<FormattedBigRat>> {
+		let base = params.base;
+		let term = params.term;
+		let decimal_separator = params.decimal_separator;
+		// exponents can only be parsed in base 10 and below
+		if base.base_as_u8() > 10 {
+			return Err(FendError::ScientificNotationBaseTooLarge);
+		}
+		// the exponent is written in the same base as the mantissa, so
+		// always include a base prefix to avoid confusion with decimal
+		// numbers, e.g. `0b1e1000` rather than `1e1000`
+		let base = base.with_explicit_prefix().with_min_digits(0);
+		let base_as_u64: u64 = base.base_as_u8().into();
+		let b: BigUint = base_as_u64.into();
+
+		let sf_exponent: u64 = sf_limit
+			.unwrap_or(10)
+			.saturating_sub(1)
+			.try_into()
+			.map_err(|_| FendError::ValueTooLarge)?;
+		let scale = BigUint::pow(&b, &sf_exponent.into(), int)?;
+		let upper = scale.clone().mul(&b, int)?;
+
+		// estimate the exponent, then adjust it until the mantissa scaled by
+		// base^sf_exponent lies between `scale` and `upper`. This uses integer
+		// division rather than rational arithmetic, since simplifying huge
+		// fractions is very slow.
+		let estimate =
+			(self.num.log2(int)? - self.den.log2(int)?) / f64::from(base.base_as_u8()).log2();
+		#[allow(clippy::cast_possible_truncation)]
+		let mut exponent = estimate.floor() as i64;
+		let sf_exponent_i64 = i64::try_from(sf_exponent).map_err(|_| FendError::ValueTooLarge)?;
+		let (quotient, remainder, divisor) = loop {
+			let shift = sf_exponent_i64 - exponent;
+			let power = BigUint::pow(&b, &shift.unsigned_abs().into(), int)?;
+			let (num, den) = if shift >= 0 {
+				(self.num.clone().mul(&power, int)?, self.den.clone())
+			} else {
+				(self.num.clone(), self.den.clone().mul(&power, int)?)
+			};
+			let (quotient, remainder) = num.divmod(&den, int)?;
+			if quotient < scale {
+				exponent -= 1;
+			} else if quotient >= upper {
+				exponent += 1;
+			} else {
+				break (quotient, remainder, den);
+			}
+		};
+
+		// round the mantissa to the given number of significant figures
+		let exact = remainder == 0.into();
+		let mut rounded = if remainder.mul(&2.into(), int)? >= divisor {
+			quotient.add(&1.into())
+		} else {
+			quotient
+		};
+		if rounded >= upper {
+			// rounding up overflowed into another digit, e.g. 9.99 => 10.0
+			rounded = rounded.div(&b, int)?;
+			exponent += 1;
+		}
+		// if the mantissa was rounded, its trailing zeros are significant,
+		// e.g. `9.996 to sci to 3 sf` is `approx. 1.00e1`
+		let mut significant_trailing_zeros = 0;
+		if !exact && sf_limit.is_some() {
+			let mut digits = rounded.clone();
+			while significant_trailing_zeros < sf_exponent {
+				let (quotient, remainder) = digits.divmod(&b, int)?;
+				if remainder != 0.into() {
+					break;
+				}
+				digits = quotient;
+				significant_trailing_zeros += 1;
+			}
+		}
+		let mantissa = Self {
+			sign: Sign::Positive,
+			num: rounded,
+			den: scale,
+		};
+
+		let formatted_mantissa = mantissa.format(
+			&FormatOptions {
+				base,
+				style: FormattingStyle::ExactFloat,
+				decimal_separator,
+				..Default::default()
+			},
+			int,
+		)?;
+		let mut formatted_mantissa_value = formatted_mantissa.value.to_string();
+		if significant_trailing_zeros > 0 {
+			let decimal_point = decimal_separator.decimal_separator();
+			if !formatted_mantissa_value.contains(decimal_point) {
+				formatted_mantissa_value.push(decimal_point);
+			}
+			for _ in 0..significant_trailing_zeros {
+				formatted_mantissa_value.push('0');
+			}
+		}
+		let formatted_exponent = BigUint::from(exponent.unsigned_abs()).format(
+			&biguint::FormatOptions {
+				base,
+				write_base_prefix: false,
+				sf_limit: None,
+				group_separator: None,
+				min_digits: 0,
+			},
+			int,
+		)?;
+		let exponent_sign = if exponent < 0 { "-" } else { "" };
+		Ok(Exact::new(
+			FormattedBigRat {
+				sign,
+				plus_sign: false,
+				ty: FormattedBigRatType::Decimal(
+					format!(
+						"{formatted_mantissa_value}e{exponent_sign}{}",
+						formatted_exponent.value
+					),
+					false,
+					term,
+				),
+			},
+			exact && formatted_mantissa.exact && formatted_exponent.exact,
+		))
+	}
+
 	/// Prints the decimal expansion of num/den, where num < den, in the given base.
 	#[allow(clippy::too_many_arguments)]
 	fn format_trailing_digits<I: Interrupt>(
@@ -737,6 +1267,8 @@ impl BigRat {
 						base,
 						write_base_prefix: false,
 						sf_limit: None,
+						group_separator: None,
+						min_digits: 0,
 					},
 					int,
 				)?
@@ -830,6 +1362,8 @@ impl BigRat {
 										base,
 										write_base_prefix: false,
 										sf_limit: None,
+										group_separator: None,
+										min_digits: 0,
 									},
 									int,
 								)?
@@ -1074,6 +1608,30 @@ impl BigRat {
 		let n_minus_r_factorial = self.add(-rhs, int)?.factorial(int)?;
 		n_factorial.div(&n_minus_r_factorial, int)
 	}
+
+	pub(crate) fn gcd<I: Interrupt>(self, rhs: Self, int: &I) -> FResult<Self> {
+		Ok(self
+			.apply_uint_op(
+				|lhs, int| {
+					let rhs = rhs.apply_uint_op(|rhs, _int| Ok(rhs), int)?;
+					BigUint::gcd(lhs, rhs, int)
+				},
+				int,
+			)?
+			.into())
+	}
+
+	pub(crate) fn lcm<I: Interrupt>(self, rhs: Self, int: &I) -> FResult<Self> {
+		Ok(self
+			.apply_uint_op(
+				|lhs, int| {
+					let rhs = rhs.apply_uint_op(|rhs, _int| Ok(rhs), int)?;
+					BigUint::lcm(lhs, rhs, int)
+				},
+				int,
+			)?
+			.into())
+	}
 }
 enum NextDigitErr {
 	Error(FendError),
@@ -1131,13 +1689,50 @@ impl From<BigUint> for BigRat {
 	}
 }
 
-#[derive(Default)]
+#[derive(Clone, Copy, Default)]
 pub(crate) struct FormatOptions {
 	pub(crate) base: Base,
 	pub(crate) style: FormattingStyle,
 	pub(crate) term: &'static str,
 	pub(crate) use_parens_if_fraction: bool,
 	pub(crate) decimal_separator: DecimalSeparatorStyle,
+	/// Separator between groups of digits in the integer part of base 10 numbers
+	pub(crate) group_separator: Option<char>,
+	pub(crate) fraction_slash_style: FractionSlashStyle,
+	/// Whether to print a `+` sign before positive numbers
+	pub(crate) show_plus_sign: bool,
+}
+
+// Returns the Unicode vulgar fraction character for `num/den`, if there is one
+fn vulgar_fraction<I: Interrupt>(
+	num: &BigUint,
+	den: &BigUint,
+	int: &I,
+) -> FResult<Option<&'static str>> {
+	if *num > 10.into() || *den > 10.into() {
+		return Ok(None);
+	}
+	Ok(match (num.try_as_usize(int)?, den.try_as_usize(int)?) {
+		(1, 2) => Some("\u{bd}"),
+		(1, 3) => Some("\u{2153}"),
+		(2, 3) => Some("\u{2154}"),
+		(1, 4) => Some("\u{bc}"),
+		(3, 4) => Some("\u{be}"),
+		(1, 5) => Some("\u{2155}"),
+		(2, 5) => Some("\u{2156}"),
+		(3, 5) => Some("\u{2157}"),
+		(4, 5) => Some("\u{2158}"),
+		(1, 6) => Some("\u{2159}"),
+		(5, 6) => Some("\u{215a}"),
+		(1, 7) => Some("\u{2150}"),
+		(1, 8) => Some("\u{215b}"),
+		(3, 8) => Some("\u{215c}"),
+		(5, 8) => Some("\u{215d}"),
+		(7, 8) => Some("\u{215e}"),
+		(1, 9) => Some("\u{2151}"),
+		(1, 10) => Some("\u{2152}"),
+		_ => None,
+	})
 }
 
 impl Format for BigRat {
@@ -1148,10 +1743,32 @@ impl Format for BigRat {
 	// either a fraction or a potentially approximated floating-point number.
 	// The result 'exact' field indicates whether the number was exact or not.
 	fn format<I: Interrupt>(&self, params: &Self::Params, int: &I) -> FResult<Exact<Self::Out>> {
+		let mut res = self.format_without_plus_sign(params, int)?;
+		res.value.plus_sign =
+			params.show_plus_sign && self.sign == Sign::Positive && self.num != 0.into();
+		Ok(res)
+	}
+}
+
+impl BigRat {
+	fn format_without_plus_sign<I: Interrupt>(
+		&self,
+		params: &FormatOptions,
+		int: &I,
+	) -> FResult<Exact<FormattedBigRat>> {
 		let base = params.base;
 		let style = params.style;
-		let term = params.term;
-		let use_parens_if_fraction = params.use_parens_if_fraction;
+		// digits are only grouped in base 10, or with an explicit grouped base
+		let params = &FormatOptions {
+			group_separator: if base.is_grouped() {
+				Some('_')
+			} else if base.base_as_u8() == 10 {
+				params.group_separator
+			} else {
+				None
+			},
+			..*params
+		};
 
 		let mut x = self.clone().simplify(int)?;
 		let sign = if x.sign == Sign::Positive || x == 0.into() {
@@ -1161,6 +1778,13 @@ impl Format for BigRat {
 		};
 		x.sign = Sign::Positive;
 
+		if let FormattingStyle::Scientific(sf_limit) = style {
+			// zero is printed as a plain integer
+			if x != 0.into() {
+				return x.format_as_scientific(sign, sf_limit, params, int);
+			}
+		}
+
 		// try as integer if possible
 		if x.den == 1.into() {
 			let sf_limit = if let FormattingStyle::SignificantFigures(sf) = style {
@@ -1168,15 +1792,7 @@ impl Format for BigRat {
 			} else {
 				None
 			};
-			return Self::format_as_integer(
-				&x.num,
-				base,
-				sign,
-				term,
-				use_parens_if_fraction,
-				sf_limit,
-				int,
-			);
+			return Self::format_as_integer(&x.num, sign, sf_limit, params, int);
 		}
 
 		let mut terminating_res = None;
@@ -1193,19 +1809,11 @@ impl Format for BigRat {
 			|| (style == FormattingStyle::Exact && !terminating()?);
 		if fraction {
 			let mixed = style == FormattingStyle::MixedFraction || style == FormattingStyle::Exact;
-			return x.format_as_fraction(base, sign, term, mixed, use_parens_if_fraction, int);
+			return x.format_as_fraction(sign, mixed, params, int);
 		}
 
 		// not a fraction, will be printed as a decimal
-		x.format_as_decimal(
-			style,
-			base,
-			sign,
-			term,
-			terminating,
-			params.decimal_separator,
-			int,
-		)
+		x.format_as_decimal(sign, terminating, params, int)
 	}
 }
 
@@ -1220,7 +1828,7 @@ enum FormattedBigRatType {
 	// optional int (numerator)
 	// space
 	// string (empty, "i", "pi", etc.)
-	// '/'
+	// slash ('/' or the Unicode fraction slash)
 	// int (denominator)
 	// string (empty, "i", "pi", etc.) (used for mixed fractions, e.g. 1 2/3 i)
 	// bool (whether or not to wrap the fraction in parentheses)
@@ -1229,6 +1837,7 @@ enum FormattedBigRatType {
 		Option<FormattedBigUint>,
 		bool,
 		&'static str,
+		&'static str,
 		FormattedBigUint,
 		&'static str,
 		bool,
@@ -1244,6 +1853,8 @@ enum FormattedBigRatType {
 pub(crate) struct FormattedBigRat {
 	// whether or not to print a minus sign
 	sign: Sign,
+	// whether or not to print a plus sign
+	plus_sign: bool,
 	ty: FormattedBigRatType,
 }
 
@@ -1251,6 +1862,8 @@ impl fmt::Display for FormattedBigRat {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
 		if self.sign == Sign::Negative {
 			write!(f, "-")?;
+		} else if self.plus_sign {
+			write!(f, "+")?;
 		}
 		match &self.ty {
 			FormattedBigRatType::Integer(int, space, isuf, use_parens) => {
@@ -1268,7 +1881,16 @@ impl fmt::Display for FormattedBigRat {
 					write!(f, ")")?;
 				}
 			}
-			FormattedBigRatType::Fraction(integer, num, space, isuf, den, isuf2, use_parens) => {
+			FormattedBigRatType::Fraction(
+				integer,
+				num,
+				space,
+				isuf,
+				slash,
+				den,
+				isuf2,
+				use_parens,
+			) => {
 				if *use_parens {
 					write!(f, "(")?;
 				}
@@ -1281,7 +1903,7 @@ impl fmt::Display for FormattedBigRat {
 				if *space && !isuf.is_empty() {
 					write!(f, " ")?;
 				}
-				write!(f, "{isuf}/{den}")?;
+				write!(f, "{isuf}{slash}{den}")?;
 				if *space && !isuf2.is_empty() {
 					write!(f, " ")?;
 				}
@@ -1302,6 +1924,36 @@ impl fmt::Display for FormattedBigRat {
 	}
 }
 
+/// Lanczos approximation of `ln(gamma(x))` for `x > 0`, with g = 7
+fn lanczos_lgamma(x: f64) -> f64 {
+	const G: f64 = 7.0;
+	const COEFFICIENTS: [f64; 9] = [
+		0.999_999_999_999_809_9,
+		676.520_368_121_885_1,
+		-1_259.139_216_722_402_8,
+		771.323_428_777_653_1,
+		-176.615_029_162_140_6,
+		12.507_343_278_686_905,
+		-0.138_571_095_265_720_12,
+		9.984_369_578_019_572e-6,
+		1.505_632_735_149_311_6e-7,
+	];
+	if x < 0.5 {
+		// reflection formula: gamma(x) * gamma(1 - x) = pi / sin(pi * x)
+		return (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln()
+			- lanczos_lgamma(1.0 - x);
+	}
+	let x = x - 1.0;
+	let mut sum = COEFFICIENTS[0];
+	let mut offset = 0.0;
+	for c in &COEFFICIENTS[1..] {
+		offset += 1.0;
+		sum += c / (x + offset);
+	}
+	let t = x + G + 0.5;
+	0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
 #[cfg(test)]
 mod tests {
 	use super::sign::Sign;
@@ -51,6 +51,64 @@ impl BigUint {
 		}
 	}
 
+	/// Returns the number of bits needed to represent this number (0 for 0)
+	pub(crate) fn bit_length(&self) -> u64 {
+		if self.is_zero() {
+			0
+		} else {
+			self.bits()
+		}
+	}
+
+	/// Returns the number of set bits
+	pub(crate) fn count_ones(&self) -> u64 {
+		match self {
+			Small(n) => u64::from(n.count_ones()),
+			Large(value) => value.iter().map(|v| u64::from(v.count_ones())).sum(),
+		}
+	}
+
+	/// Largest supported width for [`Self::byteswap`], in bytes
+	const MAX_BYTESWAP_WIDTH: usize = 1024;
+
+	/// Reverses the byte order of this number when written with the given
+	/// number of bytes, e.g. `0x1234` with a width of 2 becomes `0x3412`
+	pub(crate) fn byteswap<I: Interrupt>(&self, width: usize, int: &I) -> FResult<Self> {
+		if !(1..=Self::MAX_BYTESWAP_WIDTH).contains(&width) {
+			return Err(out_of_range(
+				width,
+				Range {
+					start: RangeBound::Closed(1),
+					end: RangeBound::Closed(Self::MAX_BYTESWAP_WIDTH),
+				},
+			));
+		}
+		let bits = u64::try_from(width * 8).map_err(|_| FendError::ValueTooLarge)?;
+		if self.bit_length() > bits {
+			let max = Self::pow(&2.into(), &bits.into(), int)?.sub(&1.into());
+			return Err(out_of_range(
+				self.fm(int)?,
+				Range {
+					start: RangeBound::Closed(Self::from(0).fm(int)?),
+					end: RangeBound::Closed(max.fm(int)?),
+				},
+			));
+		}
+		let mut bytes = vec![0; width];
+		for (i, byte) in bytes.iter_mut().enumerate() {
+			*byte = self.get(i / 8).to_le_bytes()[i % 8];
+		}
+		bytes.reverse();
+		let mut res = Small(0);
+		for (i, chunk) in bytes.chunks(8).enumerate() {
+			test_int(int)?;
+			let mut word = [0; 8];
+			word[..chunk.len()].copy_from_slice(chunk);
+			res.set(i, u64::from_le_bytes(word));
+		}
+		Ok(res)
+	}
+
 	fn is_zero(&self) -> bool {
 		match self {
 			Small(n) => *n == 0,
@@ -140,28 +198,26 @@ impl BigUint {
 
 	#[allow(clippy::cast_precision_loss)]
 	pub(crate) fn log2<I: Interrupt>(&self, int: &I) -> FResult<f64> {
-		let int_log = self.ilog2() as f64;
-		let msb_position = self.bits();
-		let mut fractional_value = self.clone();
-
-		let mut divisor = Self::from(1).lshift_n(&msb_position.into(), int)?;
-		fractional_value.lshift(int)?;
-
-		while fractional_value.bits() > 1023 || divisor.bits() > 1023 {
-			fractional_value.rshift(int)?;
-			divisor.rshift(int)?;
+		test_int(int)?;
+		// only the two most significant words affect the result
+		let top_word = self.ilog2() / u64::from(u64::BITS);
+		let Ok(top_word) = usize::try_from(top_word) else {
+			return Err(FendError::ValueTooLarge);
+		};
+		if top_word == 0 {
+			return Ok((self.get(0) as f64).log2());
 		}
-		let fractional_log = (fractional_value.as_f64() / divisor.as_f64()).log2();
-
-		Ok(int_log + fractional_log)
+		let top = self.get(top_word) as f64 * 2_f64.powi(64) + self.get(top_word - 1) as f64;
+		Ok(top.log2() + 64.0 * (top_word - 1) as f64)
 	}
 
-	fn make_large(&mut self) {
+	fn make_large(&mut self) -> &mut Vec<u64> {
+		if let Small(n) = self {
+			*self = Large(vec![*n]);
+		}
 		match self {
-			Small(n) => {
-				*self = Large(vec![*n]);
-			}
-			Large(_) => (),
+			Large(v) => v,
+			Small(_) => unreachable!("number was just converted to large"),
 		}
 	}
 
@@ -197,11 +253,7 @@ impl BigUint {
 		if new == 0 {
 			return;
 		}
-		self.make_large();
-		match self {
-			Small(_) => unreachable!(),
-			Large(v) => v.push(new),
-		}
+		self.make_large().push(new);
 	}
 
 	pub(crate) fn gcd<I: Interrupt>(mut a: Self, mut b: Self, int: &I) -> FResult<Self> {
@@ -214,6 +266,16 @@ impl BigUint {
 		Ok(a)
 	}
 
+	pub(crate) fn lcm<I: Interrupt>(a: Self, b: Self, int: &I) -> FResult<Self> {
+		if a.is_zero() || b.is_zero() {
+			return Ok(Self::from(0));
+		}
+		// divide before multiplying to keep the intermediate result small
+		let gcd = Self::gcd(a.clone(), b.clone(), int)?;
+		let a = a.div(&gcd, int)?;
+		b.mul(&a, int)
+	}
+
 	pub(crate) fn pow<I: Interrupt>(a: &Self, b: &Self, int: &I) -> FResult<Self> {
 		if a.is_zero() && b.is_zero() {
 			return Err(FendError::ZeroToThePowerOfZero);
@@ -373,14 +435,9 @@ impl BigUint {
 			return Ok(());
 		}
 		let self_clone = self.clone();
-		self.make_large();
-		match self {
-			Small(_) => unreachable!(),
-			Large(v) => {
-				v.clear();
-				v.push(0);
-			}
-		}
+		let v = self.make_large();
+		v.clear();
+		v.push(0);
 		for i in 0..other.value_len() {
 			test_int(int)?;
 			self.add_assign_internal(&self_clone, other.get(i), i);
@@ -449,6 +506,169 @@ impl BigUint {
 		Ok(self.divmod(&Self::from(2), int)?.1 == 0.into())
 	}
 
+	fn pow_mod<I: Interrupt>(&self, exponent: &Self, modulus: &Self, int: &I) -> FResult<Self> {
+		let mut result = Self::from(1);
+		let mut base = self.rem(modulus, int)?;
+		let mut exponent = exponent.clone();
+		while !exponent.is_zero() {
+			test_int(int)?;
+			if !exponent.is_even(int)? {
+				result = result.mul(&base, int)?.rem(modulus, int)?;
+			}
+			base = base.clone().mul(&base, int)?.rem(modulus, int)?;
+			exponent.rshift(int)?;
+		}
+		Ok(result)
+	}
+
+	// Miller-Rabin primality test. Using the first 12 primes as witnesses is
+	// deterministic for all n < 3.3 * 10^24 (and therefore for all 64-bit
+	// integers); for larger numbers the result is probabilistic.
+	pub(crate) fn is_prime<I: Interrupt>(&self, int: &I) -> FResult<bool> {
+		const WITNESSES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+		if *self < 2.into() {
+			return Ok(false);
+		}
+		for p in WITNESSES {
+			if *self == p.into() {
+				return Ok(true);
+			}
+			if self.rem(&p.into(), int)?.is_zero() {
+				return Ok(false);
+			}
+		}
+		// write n - 1 as d * 2^s, where d is odd
+		let one = Self::from(1);
+		let n_minus_one = self.clone().sub(&one);
+		let mut d = n_minus_one.clone();
+		let mut s = 0;
+		while d.is_even(int)? {
+			d.rshift(int)?;
+			s += 1;
+		}
+		'witness: for a in WITNESSES {
+			let mut x = Self::from(a).pow_mod(&d, self, int)?;
+			if x == one || x == n_minus_one {
+				continue;
+			}
+			for _ in 1..s {
+				x = x.clone().mul(&x, int)?.rem(self, int)?;
+				if x == n_minus_one {
+					continue 'witness;
+				}
+			}
+			return Ok(false);
+		}
+		Ok(true)
+	}
+
+	/// Returns the prime factors of this number in ascending order, together
+	/// with their multiplicities. Small factors are found by trial division,
+	/// and any remaining composite factors are split using Pollard's rho
+	/// algorithm.
+	pub(crate) fn factorize<I: Interrupt>(self, int: &I) -> FResult<Vec<(Self, u64)>> {
+		if self.is_zero() {
+			return Err(FendError::OutOfRange {
+				value: Box::new(0),
+				range: Range {
+					start: RangeBound::Closed(Box::new(1)),
+					end: RangeBound::None,
+				},
+			});
+		}
+		let mut factors = vec![];
+		let mut n = self;
+		let mut p = 2;
+		while p < 1000 && Self::from(p * p) <= n {
+			test_int(int)?;
+			let (mut q, mut r) = n.divmod(&p.into(), int)?;
+			while r.is_zero() {
+				factors.push(Self::from(p));
+				n = q;
+				(q, r) = n.divmod(&p.into(), int)?;
+			}
+			p += if p == 2 { 1 } else { 2 };
+		}
+		n.split_factors(&mut factors, int)?;
+		factors.sort();
+		let mut result: Vec<(Self, u64)> = vec![];
+		for factor in factors {
+			match result.last_mut() {
+				Some((prev, count)) if *prev == factor => *count += 1,
+				_ => result.push((factor, 1)),
+			}
+		}
+		Ok(result)
+	}
+
+	fn split_factors<I: Interrupt>(self, factors: &mut Vec<Self>, int: &I) -> FResult<()> {
+		if self == 1.into() {
+			return Ok(());
+		}
+		if self.is_prime(int)? {
+			factors.push(self);
+			return Ok(());
+		}
+		let divisor = self.pollard_rho(int)?;
+		let quotient = self.div(&divisor, int)?;
+		divisor.split_factors(factors, int)?;
+		quotient.split_factors(factors, int)
+	}
+
+	/// Finds a non-trivial divisor of a composite number
+	fn pollard_rho<I: Interrupt>(&self, int: &I) -> FResult<Self> {
+		let one = Self::from(1);
+		let mut c = Self::from(1);
+		loop {
+			let step = |x: &Self| x.clone().mul(x, int)?.add(&c).rem(self, int);
+			let mut x = Self::from(2);
+			let mut y = Self::from(2);
+			let mut divisor = one.clone();
+			while divisor == one {
+				test_int(int)?;
+				x = step(&x)?;
+				y = step(&step(&y)?)?;
+				let diff = if x > y {
+					x.clone().sub(&y)
+				} else {
+					y.clone().sub(&x)
+				};
+				divisor = Self::gcd(diff, self.clone(), int)?;
+			}
+			if divisor != *self {
+				return Ok(divisor);
+			}
+			// the sequence cycled without finding a divisor, so try again with
+			// a different polynomial
+			c = c.add(&one);
+		}
+	}
+
+	pub(crate) fn next_prime<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		let mut candidate = self.add(&1.into());
+		while !candidate.is_prime(int)? {
+			candidate = candidate.add(&1.into());
+		}
+		Ok(candidate)
+	}
+
+	pub(crate) fn prev_prime<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		if self <= 2.into() {
+			return Err(FendError::OutOfRange {
+				value: Box::new(self.format(&FormatOptions::default(), int)?.value),
+				range: Range {
+					start: RangeBound::Closed(Box::new(3)),
+					end: RangeBound::None,
+				},
+			});
+		}
+		let mut candidate = self.sub(&1.into());
+		while !candidate.is_prime(int)? {
+			candidate = candidate.sub(&1.into());
+		}
+		Ok(candidate)
+	}
+
 	pub(crate) fn div<I: Interrupt>(self, other: &Self, int: &I) -> FResult<Self> {
 		Ok(self.divmod(other, int)?.0)
 	}
@@ -531,7 +751,10 @@ impl BigUint {
 			1 => Self::Small(u64::deserialize(read)?),
 			2 => {
 				let len = usize::deserialize(read)?;
-				let mut v = Vec::with_capacity(len);
+				if len == 0 {
+					return Err(FendError::DeserializationError);
+				}
+				let mut v = vec![];
 				for _ in 0..len {
 					v.push(u64::deserialize(read)?);
 				}
@@ -607,16 +830,15 @@ impl BigUint {
 	pub(crate) fn lshift_n<I: Interrupt>(mut self, rhs: &Self, int: &I) -> FResult<Self> {
 		let mut rhs = rhs.try_as_usize(int)?;
 		if rhs > 64 {
-			self.make_large();
-			match &mut self {
-				Large(v) => {
-					while rhs >= 64 {
-						v.insert(0, 0);
-						rhs -= 64;
-					}
-				}
-				Small(_) => unreachable!(),
+			let v = self.make_large();
+			let mut shifted = vec![];
+			while rhs >= 64 {
+				test_int(int)?;
+				shifted.push(0);
+				rhs -= 64;
 			}
+			shifted.extend_from_slice(v);
+			*v = shifted;
 		}
 		for _ in 0..rhs {
 			self.lshift(int)?;
@@ -635,19 +857,12 @@ impl BigUint {
 		Ok(self)
 	}
 
+	/// Writes this number out in English words. Words always describe the
+	/// value itself, so this is independent of the base the number is
+	/// displayed in (e.g. `0xff` becomes "two hundred and fifty-five").
 	pub(crate) fn to_words<I: Interrupt>(&self, int: &I) -> FResult<String> {
 		// it would be nice to implement https://www.mrob.com/pub/math/largenum.html at some point
-		let num = self
-			.format(
-				&FormatOptions {
-					base: Base::from_plain_base(10)?,
-					sf_limit: None,
-					write_base_prefix: false,
-				},
-				int,
-			)?
-			.value
-			.to_string();
+		let num = self.to_decimal_string(int)?;
 
 		if num == "0" {
 			return Ok("zero".to_string());
@@ -685,6 +900,61 @@ impl BigUint {
 
 		Ok(result.trim().to_string())
 	}
+
+	pub(crate) fn to_decimal_string<I: Interrupt>(&self, int: &I) -> FResult<String> {
+		Ok(self
+			.format(
+				&FormatOptions {
+					base: Base::from_plain_base(10)?,
+					sf_limit: None,
+					write_base_prefix: false,
+					group_separator: None,
+					min_digits: 0,
+				},
+				int,
+			)?
+			.value
+			.to_string())
+	}
+
+	/// Writes this number with an English ordinal suffix, e.g. "1st", "12th"
+	/// or "23rd"
+	pub(crate) fn to_ordinal_number<I: Interrupt>(&self, int: &I) -> FResult<String> {
+		let num = self.to_decimal_string(int)?;
+		let last_two = num[num.len().saturating_sub(2)..]
+			.parse::<usize>()
+			.unwrap_or(0);
+		let suffix = match (last_two % 10, last_two / 10) {
+			(_, 1) => "th",
+			(1, _) => "st",
+			(2, _) => "nd",
+			(3, _) => "rd",
+			_ => "th",
+		};
+		Ok(format!("{num}{suffix}"))
+	}
+
+	/// Writes this number out as an English ordinal, e.g. "third" or
+	/// "twenty-first"
+	pub(crate) fn to_ordinal_words<I: Interrupt>(&self, int: &I) -> FResult<String> {
+		let cardinal = self.to_words(int)?;
+		let split_idx = cardinal.rfind([' ', '-']).map_or(0, |idx| idx + 1);
+		let (prefix, last_word) = cardinal.split_at(split_idx);
+		let ordinal = match last_word {
+			"one" => "first".to_string(),
+			"two" => "second".to_string(),
+			"three" => "third".to_string(),
+			"five" => "fifth".to_string(),
+			"eight" => "eighth".to_string(),
+			"nine" => "ninth".to_string(),
+			"twelve" => "twelfth".to_string(),
+			_ => match last_word.strip_suffix('y') {
+				Some(stem) => format!("{stem}ieth"),
+				None => format!("{last_word}th"),
+			},
+		};
+		Ok(format!("{prefix}{ordinal}"))
+	}
 }
 
 const SMALL_NUMBERS: &[&str] = &[
@@ -826,6 +1096,11 @@ pub(crate) struct FormatOptions {
 	pub(crate) base: Base,
 	pub(crate) write_base_prefix: bool,
 	pub(crate) sf_limit: Option<usize>,
+	/// Separator to insert between groups of digits (see
+	/// [`Base::digit_group_size`])
+	pub(crate) group_separator: Option<char>,
+	/// Pad the number with leading zeroes to at least this many digits
+	pub(crate) min_digits: usize,
 }
 
 impl Format for BigUint {
@@ -844,6 +1119,9 @@ impl Format for BigUint {
 				FormattedBigUint {
 					base: base_prefix,
 					ty: FormattedBigUintType::Zero,
+					group_separator: params.group_separator,
+					group_size: params.base.digit_group_size(),
+					min_digits: params.min_digits,
 				},
 				true,
 			));
@@ -856,6 +1134,9 @@ impl Format for BigUint {
 					FormattedBigUint {
 						base: base_prefix,
 						ty: FormattedBigUintType::Simple(num.get(0)),
+						group_separator: params.group_separator,
+						group_size: params.base.digit_group_size(),
+						min_digits: params.min_digits,
 					},
 					true,
 				)
@@ -910,6 +1191,9 @@ impl Format for BigUint {
 					FormattedBigUint {
 						base: base_prefix,
 						ty: FormattedBigUintType::Complex(output, params.sf_limit),
+						group_separator: params.group_separator,
+						group_size: params.base.digit_group_size(),
+						min_digits: params.min_digits,
 					},
 					exact,
 				)
@@ -930,6 +1214,9 @@ enum FormattedBigUintType {
 pub(crate) struct FormattedBigUint {
 	base: Option<Base>,
 	ty: FormattedBigUintType,
+	group_separator: Option<char>,
+	group_size: usize,
+	min_digits: usize,
 }
 
 impl fmt::Display for FormattedBigUint {
@@ -937,15 +1224,37 @@ impl fmt::Display for FormattedBigUint {
 		if let Some(base) = self.base {
 			base.write_prefix(f)?;
 		}
+		let padding = self.min_digits.saturating_sub(self.num_digits());
+		let num_digits = self.num_digits() + padding;
+		// `i` is the position of the digit including any padding
+		let mut write_digit = |i: usize, ch: char| -> Result<(), fmt::Error> {
+			if let Some(separator) = self.group_separator {
+				if i != 0 && (num_digits - i).is_multiple_of(self.group_size) {
+					write!(f, "{separator}")?;
+				}
+			}
+			write!(f, "{ch}")
+		};
+		for i in 0..padding {
+			write_digit(i, '0')?;
+		}
 		match &self.ty {
-			FormattedBigUintType::Zero => write!(f, "0")?,
-			FormattedBigUintType::Simple(i) => write!(f, "{i}")?,
+			FormattedBigUintType::Zero => write_digit(padding, '0')?,
+			FormattedBigUintType::Simple(i) => {
+				if self.group_separator.is_some() || padding > 0 {
+					for (i, ch) in i.to_string().chars().enumerate() {
+						write_digit(padding + i, ch)?;
+					}
+				} else {
+					write!(f, "{i}")?;
+				}
+			}
 			FormattedBigUintType::Complex(s, sf_limit) => {
 				for (i, ch) in s.chars().rev().enumerate() {
 					if sf_limit.is_some() && &Some(i) >= sf_limit {
-						write!(f, "0")?;
+						write_digit(padding + i, '0')?;
 					} else {
-						write!(f, "{ch}")?;
+						write_digit(padding + i, ch)?;
 					}
 				}
 			}
@@ -1102,6 +1411,38 @@ mod tests {
 		Ok(())
 	}
 
+	#[test]
+	fn test_is_prime() -> Res {
+		let int = &crate::interrupt::Never;
+		for p in [
+			2,
+			3,
+			5,
+			37,
+			41,
+			7919,
+			1_000_000_007,
+			18_446_744_073_709_551_557,
+		] {
+			assert!(BigUint::from(p).is_prime(int)?, "{p} should be prime");
+		}
+		// includes Carmichael numbers and strong pseudoprimes to small bases
+		for n in [
+			0,
+			1,
+			4,
+			9,
+			561,
+			1105,
+			2047,
+			3_215_031_751,
+			18_446_744_073_709_551_615,
+		] {
+			assert!(!BigUint::from(n).is_prime(int)?, "{n} should not be prime");
+		}
+		Ok(())
+	}
+
 	#[test]
 	fn test_add_assign_internal() {
 		// 0 += (1 * 1) << (64 * 1)
@@ -1119,6 +1460,24 @@ mod tests {
 		Ok(())
 	}
 
+	#[test]
+	fn test_count_ones() {
+		assert_eq!(BigUint::from(0).count_ones(), 0);
+		assert_eq!(BigUint::from(0b1011).count_ones(), 3);
+		assert_eq!(BigUint::from(u64::MAX).count_ones(), 64);
+		assert_eq!(BigUint::Large(vec![0b111, 0, 1]).count_ones(), 4);
+	}
+
+	#[test]
+	fn test_bit_length() {
+		assert_eq!(BigUint::from(0).bit_length(), 0);
+		assert_eq!(BigUint::from(1).bit_length(), 1);
+		assert_eq!(BigUint::from(255).bit_length(), 8);
+		assert_eq!(BigUint::from(256).bit_length(), 9);
+		assert_eq!(BigUint::Large(vec![0, 1]).bit_length(), 65);
+		assert_eq!(BigUint::Large(vec![5, 0]).bit_length(), 3);
+	}
+
 	#[test]
 	fn test_big_multiplication() -> Res {
 		let int = &crate::interrupt::Never;
@@ -1,9 +1,11 @@
 use crate::error::{FendError, Interrupt};
+use crate::num::bigrat;
+use crate::num::biguint::BigUint;
 use crate::num::real::{self, Real};
 use crate::num::Exact;
-use crate::num::{Base, FormattingStyle};
+use crate::num::FormattingStyle;
 use crate::result::FResult;
-use crate::DecimalSeparatorStyle;
+use crate::ModuloConvention;
 use std::cmp::Ordering;
 use std::ops::Neg;
 use std::{fmt, io};
@@ -149,29 +151,38 @@ impl Complex {
 				real.exact,
 			))
 		} else {
-			let rem = rhs.clone().real.modulo(4.into(), int);
 			// Reduced case: (ix)^y = x^y * i^y
 			if self.real.is_zero() && rhs.imag.is_zero() {
-				if let Ok(n) = rhs.real.try_as_usize(int) {
+				if let Ok(n) = rhs.real.clone().try_as_usize(int) {
 					return self.pow_n(n, int);
 				}
 
+				if rhs.real.is_neg() {
+					// (ix)^-y = 1 / (ix)^y
+					let positive = self.pow(-rhs, int)?;
+					return Exact::new(Self::from(1), true).div(positive, int);
+				}
+
 				let mut result = Exact::new(
-					match rem.and_then(|rem| rem.try_as_usize(int)) {
-						Ok(0) => 1.into(),
-						Ok(1) => Self {
+					match rhs
+						.real
+						.clone()
+						.modulo(4.into(), ModuloConvention::Euclidean, int)?
+						.try_as_usize(int)?
+					{
+						0 => 1.into(),
+						1 => Self {
 							real: 0.into(),
 							imag: 1.into(),
 						},
-						Ok(2) => Self {
+						2 => Self {
 							real: Real::from(1).neg(),
 							imag: 0.into(),
 						},
-						Ok(3) => Self {
+						_ => Self {
 							real: 0.into(),
 							imag: Real::from(1).neg(),
 						},
-						Ok(_) | Err(_) => unreachable!("modulo 4 should always be 0, 1, 2, or 3"),
 					},
 					true,
 				);
@@ -179,7 +190,7 @@ impl Complex {
 				if !self.imag.is_definitely_one() {
 					result = self
 						.imag
-						.pow(2.into(), int)?
+						.pow(rhs.real, int)?
 						.apply(Self::from)
 						.mul(&result, int)?;
 				}
@@ -241,30 +252,42 @@ impl Complex {
 		Ok(Exact::new(self.expect_real()?.round(int)?, true))
 	}
 
+	pub(crate) fn round_to_denominator<I: Interrupt>(
+		self,
+		den: &BigUint,
+		int: &I,
+	) -> FResult<Exact<Real>> {
+		self.expect_real()?.round_to_denominator(den, int)
+	}
+
 	pub(crate) fn arg<I: Interrupt>(self, int: &I) -> FResult<Exact<Real>> {
 		Ok(Exact::new(self.imag.atan2(self.real, int)?, false))
 	}
 
+	/// Formats this number with the given options. Whether fractions are
+	/// wrapped in parentheses depends on `use_parentheses`, so the
+	/// `use_parens_if_fraction` option is ignored.
 	pub(crate) fn format<I: Interrupt>(
 		&self,
 		exact: bool,
-		style: FormattingStyle,
-		base: Base,
 		use_parentheses: UseParentheses,
-		decimal_separator: DecimalSeparatorStyle,
+		options: bigrat::FormatOptions,
 		int: &I,
 	) -> FResult<Exact<Formatted>> {
-		let style = if !self.imag.is_zero() && style == FormattingStyle::Auto {
+		let style = if !self.imag.is_zero() && options.style == FormattingStyle::Auto {
 			FormattingStyle::Exact
 		} else {
-			style
+			options.style
+		};
+		let use_parens = use_parentheses == UseParentheses::IfComplexOrFraction;
+		let options = bigrat::FormatOptions {
+			style,
+			use_parens_if_fraction: use_parens,
+			..options
 		};
 
 		if self.imag.is_zero() {
-			let use_parens = use_parentheses == UseParentheses::IfComplexOrFraction;
-			let x = self
-				.real
-				.format(base, style, false, use_parens, decimal_separator, int)?;
+			let x = self.real.format(false, options, int)?;
 			return Ok(Exact::new(
 				Formatted {
 					first_component: x.value,
@@ -277,10 +300,7 @@ impl Complex {
 		}
 
 		Ok(if self.real.is_zero() {
-			let use_parens = use_parentheses == UseParentheses::IfComplexOrFraction;
-			let x = self
-				.imag
-				.format(base, style, true, use_parens, decimal_separator, int)?;
+			let x = self.imag.format(true, options, int)?;
 			Exact::new(
 				Formatted {
 					first_component: x.value,
@@ -292,28 +312,21 @@ impl Complex {
 			)
 		} else {
 			let mut exact = exact;
-			let real_part = self
-				.real
-				.format(base, style, false, false, decimal_separator, int)?;
+			let options = bigrat::FormatOptions {
+				use_parens_if_fraction: false,
+				..options
+			};
+			let real_part = self.real.format(false, options, int)?;
 			exact = exact && real_part.exact;
+			// the sign of the imaginary part is printed as the separator
+			let options = bigrat::FormatOptions {
+				show_plus_sign: false,
+				..options
+			};
 			let (positive, imag_part) = if self.imag.is_pos() {
-				(
-					true,
-					self.imag
-						.format(base, style, true, false, decimal_separator, int)?,
-				)
+				(true, self.imag.format(true, options, int)?)
 			} else {
-				(
-					false,
-					(-self.imag.clone()).format(
-						base,
-						style,
-						true,
-						false,
-						decimal_separator,
-						int,
-					)?,
-				)
+				(false, (-self.imag.clone()).format(true, options, int)?)
 			};
 			exact = exact && imag_part.exact;
 			let separator = if positive { " + " } else { " - " };
@@ -329,6 +342,7 @@ impl Complex {
 			)
 		})
 	}
+
 	pub(crate) fn frac_pow<I: Interrupt>(self, n: Self, int: &I) -> FResult<Exact<Self>> {
 		if self.imag.is_zero() && n.imag.is_zero() && !self.real.is_neg() {
 			Ok(self.real.pow(n.real, int)?.apply(Self::from))
@@ -587,7 +601,21 @@ impl Complex {
 		}
 	}
 
+	pub(crate) fn lgamma<I: Interrupt>(self, int: &I) -> FResult<Exact<Self>> {
+		Ok(self.expect_real()?.lgamma(int)?.apply(Self::from))
+	}
+
+	pub(crate) fn beta<I: Interrupt>(self, rhs: Self, int: &I) -> FResult<Exact<Self>> {
+		Ok(self
+			.expect_real()?
+			.beta(rhs.expect_real()?, int)?
+			.apply(Self::from))
+	}
+
 	pub(crate) fn log<I: Interrupt>(self, base: Self, int: &I) -> FResult<Self> {
+		if self.imag.is_zero() && self.real.is_pos() && base.imag.is_zero() && base.real.is_pos() {
+			return Ok(Self::from(self.real.log(base.real, int)?));
+		}
 		// log_n(z) = ln(z) / ln(n)
 		let ln = self.ln(int)?;
 		let ln2 = base.ln(int)?;
@@ -613,10 +641,17 @@ impl Complex {
 		self.real.is_definitely_one() && self.imag.is_definitely_zero()
 	}
 
-	pub(crate) fn modulo<I: Interrupt>(self, rhs: Self, int: &I) -> FResult<Self> {
-		Ok(Self::from(
-			self.expect_real()?.modulo(rhs.expect_real()?, int)?,
-		))
+	pub(crate) fn modulo<I: Interrupt>(
+		self,
+		rhs: Self,
+		convention: ModuloConvention,
+		int: &I,
+	) -> FResult<Self> {
+		Ok(Self::from(self.expect_real()?.modulo(
+			rhs.expect_real()?,
+			convention,
+			int,
+		)?))
 	}
 
 	pub(crate) fn bitwise<I: Interrupt>(
@@ -643,6 +678,48 @@ impl Complex {
 			self.expect_real()?.permutation(rhs.expect_real()?, int)?,
 		))
 	}
+
+	pub(crate) fn factorize<I: Interrupt>(self, int: &I) -> FResult<Vec<(BigUint, u64)>> {
+		self.expect_real()?.factorize(int)
+	}
+
+	pub(crate) fn next_prime<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(Self::from(self.expect_real()?.next_prime(int)?))
+	}
+
+	pub(crate) fn prev_prime<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(Self::from(self.expect_real()?.prev_prime(int)?))
+	}
+
+	pub(crate) fn isqrt<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(Self::from(self.expect_real()?.isqrt(int)?))
+	}
+
+	pub(crate) fn popcount<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(Self::from(self.expect_real()?.popcount(int)?))
+	}
+
+	pub(crate) fn bitlength<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(Self::from(self.expect_real()?.bitlength(int)?))
+	}
+
+	pub(crate) fn gcd<I: Interrupt>(self, rhs: Self, int: &I) -> FResult<Self> {
+		Ok(Self::from(
+			self.expect_real()?.gcd(rhs.expect_real()?, int)?,
+		))
+	}
+
+	pub(crate) fn lcm<I: Interrupt>(self, rhs: Self, int: &I) -> FResult<Self> {
+		Ok(Self::from(
+			self.expect_real()?.lcm(rhs.expect_real()?, int)?,
+		))
+	}
+
+	pub(crate) fn byteswap<I: Interrupt>(self, width: Self, int: &I) -> FResult<Self> {
+		Ok(Self::from(
+			self.expect_real()?.byteswap(width.expect_real()?, int)?,
+		))
+	}
 }
 
 impl Exact<Complex> {
@@ -664,6 +664,8 @@ fn format_as_integer<I: Interrupt>(
 				base,
 				write_base_prefix: true,
 				sf_limit,
+				group_separator: None,
+				min_digits: 0,
 			},
 			int,
 		)?;
@@ -1,10 +1,12 @@
 use crate::error::{FendError, Interrupt};
 use crate::interrupt::{test_int, Never};
-use crate::num::bigrat::BigRat;
+use crate::num::bigrat::{self, BigRat};
+use crate::num::biguint::BigUint;
 use crate::num::complex::{self, Complex};
 use crate::result::FResult;
 use crate::serialize::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt::Write;
 use std::ops::Neg;
 use std::{fmt, io};
@@ -30,7 +32,10 @@ impl Dist {
 
 	pub(crate) fn deserialize(read: &mut impl io::Read) -> FResult<Self> {
 		let len = usize::deserialize(read)?;
-		let mut parts = Vec::with_capacity(len);
+		if len == 0 {
+			return Err(FendError::DeserializationError);
+		}
+		let mut parts = vec![];
 		for _ in 0..len {
 			let k = Complex::deserialize(read)?;
 			let v = BigRat::deserialize(read)?;
@@ -77,6 +82,85 @@ impl Dist {
 		Ok(Self { parts })
 	}
 
+	/// Returns the distribution of the sum of the highest (or lowest) `keep`
+	/// dice out of `count` dice, e.g. `4d6 keep highest 3`.
+	pub(crate) fn new_die_keep<I: Interrupt>(
+		count: u32,
+		faces: u32,
+		keep: u32,
+		highest: bool,
+		int: &I,
+	) -> FResult<Self> {
+		assert!(count != 0);
+		assert!(faces != 0);
+		assert!(keep <= count);
+
+		// binomial[n][k] is the number of ways to choose k out of n dice
+		let mut binomial: Vec<Vec<BigUint>> = vec![vec![BigUint::from(1)]];
+		for n in 1..=count as usize {
+			test_int(int)?;
+			let prev = &binomial[n - 1];
+			let mut row = vec![BigUint::from(1)];
+			for k in 1..n {
+				row.push(prev[k - 1].clone().add(&prev[k]));
+			}
+			row.push(BigUint::from(1));
+			binomial.push(row);
+		}
+
+		// Go through the faces starting with the ones we want to keep,
+		// deciding how many dice show each face. We track the number of dice
+		// used so far, the number of dice kept so far and the sum of the kept
+		// dice, along with the number of ways to reach that state.
+		let mut states = BTreeMap::new();
+		states.insert((0, 0, 0_u64), BigUint::from(1));
+		let face_order: Vec<u32> = if highest {
+			(1..=faces).rev().collect()
+		} else {
+			(1..=faces).collect()
+		};
+		for face in face_order {
+			let mut next_states = BTreeMap::new();
+			for ((used, kept, sum), ways) in states {
+				let remaining = count - used;
+				for dice in 0..=remaining {
+					test_int(int)?;
+					let newly_kept = dice.min(keep - kept);
+					let key = (
+						used + dice,
+						kept + newly_kept,
+						sum + u64::from(newly_kept) * u64::from(face),
+					);
+					let ways = ways
+						.clone()
+						.mul(&binomial[remaining as usize][dice as usize], int)?;
+					let entry = next_states.entry(key).or_insert_with(|| BigUint::from(0));
+					*entry = ways.add(entry);
+				}
+			}
+			states = next_states;
+		}
+
+		let total = BigUint::pow(
+			&BigUint::from(u64::from(faces)),
+			&BigUint::from(u64::from(count)),
+			int,
+		)?;
+		let total = BigRat::from(total);
+		let mut sums: BTreeMap<u64, BigUint> = BTreeMap::new();
+		for ((used, _, sum), ways) in states {
+			if used == count {
+				let entry = sums.entry(sum).or_insert_with(|| BigUint::from(0));
+				*entry = ways.add(entry);
+			}
+		}
+		let mut parts = Vec::with_capacity(sums.len());
+		for (sum, ways) in sums {
+			parts.push((Complex::from(sum), BigRat::from(ways).div(&total, int)?));
+		}
+		Ok(Self { parts })
+	}
+
 	pub(crate) fn equals_int<I: Interrupt>(&self, val: u64, int: &I) -> FResult<bool> {
 		Ok(self.parts.len() == 1
 			&& self.parts[0].0.compare(&val.into(), int)? == Some(Ordering::Equal))
@@ -87,7 +171,13 @@ impl Dist {
 		if self.parts.len() == 1 {
 			return Ok(self);
 		}
-		let mut random = ctx.random_u32.ok_or(FendError::RandomNumbersNotAvailable)?();
+		let mut random = ctx
+			.rng
+			.as_ref()
+			.ok_or(FendError::RandomNumbersNotAvailable)?
+			.lock()
+			.map_err(|_| FendError::RandomNumbersNotAvailable)?
+			.random_u32();
 		let mut res = None;
 		for (k, v) in self.parts {
 			random = random.saturating_sub((v.into_f64(int)? * f64::from(u32::MAX)) as u32);
@@ -116,8 +206,98 @@ impl Dist {
 		Ok(Self::from(result.value))
 	}
 
+	pub(crate) fn variance<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		if self.parts.is_empty() {
+			return Err(FendError::EmptyDistribution);
+		} else if self.parts.len() == 1 {
+			return Ok(Self::from(0));
+		}
+
+		let mean = self.clone().mean(int)?.one_point()?;
+		let mut result = Exact::new(Complex::from(0), true);
+		for (k, v) in self.parts {
+			let deviation = Exact::new(k, true).add(-Exact::new(mean.clone(), true), int)?;
+			result = deviation
+				.clone()
+				.mul(&deviation, int)?
+				.mul(&Exact::new(Complex::from(Real::from(v)), true), int)?
+				.add(result, int)?;
+		}
+
+		Ok(Self::from(result.value))
+	}
+
+	fn into_sorted_parts<I: Interrupt>(self, int: &I) -> FResult<Vec<(Complex, BigRat)>> {
+		let mut sorted: Vec<(Complex, BigRat)> = Vec::with_capacity(self.parts.len());
+		for part in self.parts {
+			let mut idx = sorted.len();
+			for (i, (k, _)) in sorted.iter().enumerate() {
+				test_int(int)?;
+				match part.0.compare(k, int)? {
+					Some(Ordering::Less) => {
+						idx = i;
+						break;
+					}
+					Some(_) => (),
+					None => return Err(FendError::UnableToCompare),
+				}
+			}
+			sorted.insert(idx, part);
+		}
+		Ok(sorted)
+	}
+
+	/// Returns the smallest value at which the cumulative probability reaches
+	/// 50%. If it is exactly 50%, the median is halfway between that value and
+	/// the next one (e.g. the median of `d6` is 3.5).
+	pub(crate) fn median<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		if self.parts.is_empty() {
+			return Err(FendError::EmptyDistribution);
+		} else if self.parts.len() == 1 {
+			return Ok(self);
+		}
+
+		let parts = self.into_sorted_parts(int)?;
+		let half = BigRat::from(1).div(&BigRat::from(2), int)?;
+		let mut cumulative = BigRat::from(0);
+		for (i, (k, p)) in parts.iter().enumerate() {
+			cumulative = cumulative.add(p.clone(), int)?;
+			match cumulative.cmp(&half) {
+				Ordering::Less => (),
+				Ordering::Greater => return Ok(Self::from(k.clone())),
+				Ordering::Equal => {
+					let next = parts.get(i + 1).map_or(k, |(next, _)| next);
+					let midpoint = Exact::new(k.clone(), true)
+						.add(Exact::new(next.clone(), true), int)?
+						.div(Exact::new(Complex::from(2), true), int)?;
+					return Ok(Self::from(midpoint.value));
+				}
+			}
+		}
+		Err(FendError::EmptyDistribution)
+	}
+
+	/// Returns the most likely value. If there are multiple such values, the
+	/// smallest one is returned.
+	pub(crate) fn mode<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		if self.parts.len() == 1 {
+			return Ok(self);
+		}
+
+		let mut result: Option<(Complex, BigRat)> = None;
+		for (k, p) in self.into_sorted_parts(int)? {
+			if result.as_ref().is_none_or(|(_, max)| p > *max) {
+				result = Some((k, p));
+			}
+		}
+		result
+			.map(|(k, _)| Self::from(k))
+			.ok_or(FendError::EmptyDistribution)
+	}
+
 	#[allow(
 		clippy::cast_possible_truncation,
+		clippy::cast_precision_loss,
 		clippy::cast_sign_loss,
 		clippy::too_many_arguments
 	)]
@@ -127,19 +307,24 @@ impl Dist {
 		style: FormattingStyle,
 		base: Base,
 		use_parentheses: complex::UseParentheses,
+		show_plus_sign: bool,
 		out: &mut String,
 		ctx: &crate::Context,
 		int: &I,
 	) -> FResult<Exact<()>> {
+		let options = bigrat::FormatOptions {
+			base,
+			style,
+			decimal_separator: ctx.decimal_separator,
+			group_separator: ctx.digit_group_separator,
+			fraction_slash_style: ctx.fraction_slash_style,
+			show_plus_sign,
+			..Default::default()
+		};
 		if self.parts.len() == 1 {
-			let res = self.parts[0].0.format(
-				exact,
-				style,
-				base,
-				use_parentheses,
-				ctx.decimal_separator,
-				int,
-			)?;
+			let res = self.parts[0]
+				.0
+				.format(exact, use_parentheses, options, int)?;
 			write!(out, "{}", res.value)?;
 			Ok(Exact::new((), res.exact))
 		} else {
@@ -158,29 +343,44 @@ impl Dist {
 			if ctx.output_mode == crate::OutputMode::SimpleText {
 				write!(out, "{{ ")?;
 			}
-			let mut first = true;
+			let mut lines = vec![];
 			for (num, _prob, prob_f64) in ordered_kvs {
 				let num = num
 					.format(
 						exact,
-						style,
-						base,
 						use_parentheses,
-						ctx.decimal_separator,
+						bigrat::FormatOptions {
+							show_plus_sign: false,
+							..options
+						},
 						int,
 					)?
 					.value
 					.to_string();
+				lines.push((num, prob_f64));
+			}
+			// the bars use whatever space is left after the widest label
+			let label_width = lines
+				.iter()
+				.map(|(num, _)| num.chars().count().max(3))
+				.max()
+				.unwrap_or(3);
+			let bar_width = ctx.output_width.saturating_sub(label_width + 10).max(1);
+			if ctx.output_mode == crate::OutputMode::Markdown {
+				write!(out, "| Value | Probability |\n| ---: | ---: |")?;
+			}
+			let mut first = true;
+			for (num, prob_f64) in lines {
 				let prob_percentage = prob_f64 * 100.0;
-				if ctx.output_mode == crate::OutputMode::TerminalFixedWidth {
+				if ctx.output_mode == crate::OutputMode::Markdown {
+					write!(out, "\n| {num} | {prob_percentage:.2}% |")?;
+				} else if ctx.output_mode == crate::OutputMode::TerminalFixedWidth {
 					if !first {
 						writeln!(out)?;
 					}
-					let mut bar = String::new();
-					for _ in 0..(prob_f64 / max_prob * 30.0).min(30.0) as u32 {
-						bar.push('#');
-					}
-					write!(out, "{num:>3}: {prob_percentage:>5.2}%  {bar}")?;
+					let bar_len = (prob_f64 / max_prob * bar_width as f64).min(bar_width as f64);
+					let bar = "#".repeat(bar_len as usize);
+					write!(out, "{num:>label_width$}: {prob_percentage:>5.2}%  {bar}")?;
 				} else {
 					if !first {
 						write!(out, ", ")?;
@@ -208,6 +408,7 @@ impl Dist {
 		let mut parts = Vec::<(Complex, BigRat)>::new();
 		for (n1, p1) in &self.parts {
 			for (n2, p2) in &rhs.parts {
+				test_int(int)?;
 				let n = f(n1, n2, int)?;
 				let p = p1.clone().mul(p2, int)?;
 				let mut found = false;
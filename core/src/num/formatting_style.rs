@@ -27,6 +27,9 @@ pub(crate) enum FormattingStyle {
 	/// If not exact: DecimalPlaces(10). If no recurring digits: `ExactFloat`.
 	/// Other numbers: `MixedFraction`, albeit possibly including fractions of pi
 	Exact,
+	/// Print in scientific notation, e.g. 1.23e5, optionally limited
+	/// to the given number of significant figures
+	Scientific(Option<usize>),
 }
 
 impl fmt::Display for FormattingStyle {
@@ -39,6 +42,7 @@ impl fmt::Display for FormattingStyle {
 			Self::DecimalPlaces(d) => write!(f, "{d} dp"),
 			Self::SignificantFigures(s) => write!(f, "{s} sf"),
 			Self::Auto => write!(f, "auto"),
+			Self::Scientific(_) => write!(f, "scientific"),
 		}
 	}
 }
@@ -53,6 +57,8 @@ impl fmt::Debug for FormattingStyle {
 			Self::DecimalPlaces(d) => write!(f, "{d} dp"),
 			Self::SignificantFigures(s) => write!(f, "{s} sf"),
 			Self::Auto => write!(f, "auto"),
+			Self::Scientific(None) => write!(f, "scientific"),
+			Self::Scientific(Some(s)) => write!(f, "scientific ({s} sf)"),
 		}
 	}
 }
@@ -73,6 +79,11 @@ impl FormattingStyle {
 				s.serialize(write)?;
 			}
 			Self::Auto => 7u8.serialize(write)?,
+			Self::Scientific(None) => 8u8.serialize(write)?,
+			Self::Scientific(Some(s)) => {
+				9u8.serialize(write)?;
+				s.serialize(write)?;
+			}
 		}
 		Ok(())
 	}
@@ -86,6 +97,8 @@ impl FormattingStyle {
 			5 => Self::DecimalPlaces(usize::deserialize(read)?),
 			6 => Self::SignificantFigures(usize::deserialize(read)?),
 			7 => Self::Auto,
+			8 => Self::Scientific(None),
+			9 => Self::Scientific(Some(usize::deserialize(read)?)),
 			_ => return Err(FendError::DeserializationError),
 		})
 	}
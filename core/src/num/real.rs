@@ -2,10 +2,10 @@ use crate::error::{FendError, Interrupt};
 use crate::format::Format;
 use crate::num::bigrat::{BigRat, FormattedBigRat};
 use crate::num::Exact;
-use crate::num::{Base, FormattingStyle};
+use crate::num::FormattingStyle;
 use crate::result::FResult;
 use crate::serialize::{Deserialize, Serialize};
-use crate::DecimalSeparatorStyle;
+use crate::ModuloConvention;
 use std::cmp::Ordering;
 use std::ops::Neg;
 use std::{fmt, hash, io};
@@ -92,6 +92,49 @@ impl Real {
 		}
 	}
 
+	pub(crate) fn has_denominator_at_most<I: Interrupt>(&self, max: u64, int: &I) -> FResult<bool> {
+		match &self.pattern {
+			Pattern::Simple(s) => s.has_denominator_at_most(max, int),
+			Pattern::Pi(_) => Ok(false),
+		}
+	}
+
+	pub(crate) fn into_words<I: Interrupt>(self, as_fraction: bool, int: &I) -> FResult<String> {
+		match self.pattern {
+			Pattern::Simple(s) => s.into_words(as_fraction, int),
+			Pattern::Pi(n) => {
+				if n == 0.into() {
+					Ok("zero".to_string())
+				} else {
+					Err(FendError::InexactNumberToWords)
+				}
+			}
+		}
+	}
+
+	/// Exact numbers are expanded in full (up to 100 terms), while
+	/// approximations (like multiples of pi) are limited to 10 terms, since
+	/// later terms would depend on the precision of the approximation.
+	pub(crate) fn into_continued_fraction<I: Interrupt>(
+		self,
+		exact: bool,
+		int: &I,
+	) -> FResult<String> {
+		let exact = exact && (matches!(self.pattern, Pattern::Simple(_)) || self.is_zero());
+		let max_terms = if exact { 100 } else { 10 };
+		self.approximate(int)?
+			.into_continued_fraction(max_terms, exact, int)
+	}
+
+	pub(crate) fn into_hex_float<I: Interrupt>(self, int: &I) -> FResult<Exact<String>> {
+		let exact = matches!(self.pattern, Pattern::Simple(_)) || self.is_zero();
+		Ok(self.approximate(int)?.into_hex_float(int)?.combine(exact))
+	}
+
+	pub(crate) fn into_f64<I: Interrupt>(self, int: &I) -> FResult<f64> {
+		self.approximate(int)?.into_f64(int)
+	}
+
 	fn approximate<I: Interrupt>(self, int: &I) -> FResult<BigRat> {
 		match self.pattern {
 			Pattern::Simple(s) => Ok(s),
@@ -104,6 +147,15 @@ impl Real {
 		}
 	}
 
+	pub(crate) fn round_to_denominator<I: Interrupt>(
+		self,
+		den: &BigUint,
+		int: &I,
+	) -> FResult<Exact<Self>> {
+		let result = self.approximate(int)?.round_to_denominator(den, int)?;
+		Ok(Exact::new(Self::from(result.value), result.exact))
+	}
+
 	pub(crate) fn try_as_biguint<I: Interrupt>(self, int: &I) -> FResult<BigUint> {
 		match self.pattern {
 			Pattern::Simple(s) => s.try_as_biguint(int),
@@ -117,6 +169,23 @@ impl Real {
 		}
 	}
 
+	pub(crate) fn into_twos_complement<I: Interrupt>(
+		self,
+		width: u64,
+		int: &I,
+	) -> FResult<BigUint> {
+		match self.pattern {
+			Pattern::Simple(s) => s.into_twos_complement(width, int),
+			Pattern::Pi(n) => {
+				if n == 0.into() {
+					Ok(BigUint::Small(0))
+				} else {
+					Err(FendError::CannotConvertToInteger)
+				}
+			}
+		}
+	}
+
 	pub(crate) fn try_as_i64<I: Interrupt>(self, int: &I) -> FResult<i64> {
 		match self.pattern {
 			Pattern::Simple(s) => s.try_as_i64(int),
@@ -232,6 +301,17 @@ impl Real {
 		Ok(self.approximate(int)?.ln(int)?.apply(Self::from))
 	}
 
+	pub(crate) fn lgamma<I: Interrupt>(self, int: &I) -> FResult<Exact<Self>> {
+		Ok(self.approximate(int)?.lgamma(int)?.apply(Self::from))
+	}
+
+	pub(crate) fn beta<I: Interrupt>(self, rhs: Self, int: &I) -> FResult<Exact<Self>> {
+		Ok(self
+			.approximate(int)?
+			.beta(rhs.approximate(int)?, int)?
+			.apply(Self::from))
+	}
+
 	pub(crate) fn log2<I: Interrupt>(self, int: &I) -> FResult<Self> {
 		Ok(Self::from(self.approximate(int)?.log2(int)?))
 	}
@@ -240,10 +320,40 @@ impl Real {
 		Ok(Self::from(self.approximate(int)?.log10(int)?))
 	}
 
+	pub(crate) fn log<I: Interrupt>(self, base: Self, int: &I) -> FResult<Self> {
+		Ok(Self::from(
+			self.approximate(int)?.log(base.approximate(int)?, int)?,
+		))
+	}
+
 	pub(crate) fn factorial<I: Interrupt>(self, int: &I) -> FResult<Self> {
 		Ok(Self::from(self.approximate(int)?.factorial(int)?))
 	}
 
+	pub(crate) fn factorize<I: Interrupt>(self, int: &I) -> FResult<Vec<(BigUint, u64)>> {
+		self.approximate(int)?.factorize(int)
+	}
+
+	pub(crate) fn next_prime<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(Self::from(self.approximate(int)?.next_prime(int)?))
+	}
+
+	pub(crate) fn prev_prime<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(Self::from(self.approximate(int)?.prev_prime(int)?))
+	}
+
+	pub(crate) fn isqrt<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(Self::from(self.approximate(int)?.isqrt(int)?))
+	}
+
+	pub(crate) fn popcount<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(Self::from(self.approximate(int)?.popcount(int)?))
+	}
+
+	pub(crate) fn bitlength<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(Self::from(self.approximate(int)?.bitlength(int)?))
+	}
+
 	pub(crate) fn floor<I: Interrupt>(self, int: &I) -> FResult<Self> {
 		Ok(Self::from(self.approximate(int)?.floor(int)?))
 	}
@@ -256,15 +366,16 @@ impl Real {
 		Ok(Self::from(self.approximate(int)?.round(int)?))
 	}
 
+	/// Formats this number with the given options. The `term` option is
+	/// ignored, since it's determined by `imag` and by whether this number is
+	/// a multiple of π.
 	pub(crate) fn format<I: Interrupt>(
 		&self,
-		base: Base,
-		mut style: FormattingStyle,
 		imag: bool,
-		use_parens_if_fraction: bool,
-		decimal_separator: DecimalSeparatorStyle,
+		options: bigrat::FormatOptions,
 		int: &I,
 	) -> FResult<Exact<Formatted>> {
+		let mut style = options.style;
 		let mut pi = false;
 		if style == FormattingStyle::Exact && !self.is_zero() {
 			if let Pattern::Pi(_) = self.pattern {
@@ -298,11 +409,9 @@ impl Real {
 
 		let formatted = rat.format(
 			&bigrat::FormatOptions {
-				base,
 				style,
 				term,
-				use_parens_if_fraction,
-				decimal_separator,
+				..options
 			},
 			int,
 		)?;
@@ -423,11 +532,17 @@ impl Real {
 		}
 	}
 
-	pub(crate) fn modulo<I: Interrupt>(self, rhs: Self, int: &I) -> FResult<Self> {
-		Ok(Self::from(
-			self.expect_rational()?
-				.modulo(rhs.expect_rational()?, int)?,
-		))
+	pub(crate) fn modulo<I: Interrupt>(
+		self,
+		rhs: Self,
+		convention: ModuloConvention,
+		int: &I,
+	) -> FResult<Self> {
+		Ok(Self::from(self.expect_rational()?.modulo(
+			rhs.expect_rational()?,
+			convention,
+			int,
+		)?))
 	}
 
 	pub(crate) fn bitwise<I: Interrupt>(
@@ -456,6 +571,25 @@ impl Real {
 				.permutation(rhs.expect_rational()?, int)?,
 		))
 	}
+
+	pub(crate) fn gcd<I: Interrupt>(self, rhs: Self, int: &I) -> FResult<Self> {
+		Ok(Self::from(
+			self.expect_rational()?.gcd(rhs.expect_rational()?, int)?,
+		))
+	}
+
+	pub(crate) fn lcm<I: Interrupt>(self, rhs: Self, int: &I) -> FResult<Self> {
+		Ok(Self::from(
+			self.expect_rational()?.lcm(rhs.expect_rational()?, int)?,
+		))
+	}
+
+	pub(crate) fn byteswap<I: Interrupt>(self, width: Self, int: &I) -> FResult<Self> {
+		Ok(Self::from(
+			self.expect_rational()?
+				.byteswap(width.expect_rational()?, int)?,
+		))
+	}
 }
 
 impl Exact<Real> {
@@ -8,10 +8,11 @@ use crate::scope::Scope;
 use crate::serialize::{Deserialize, Serialize};
 use crate::units::{lookup_default_unit, query_unit_static};
 use crate::{ast, ident::Ident};
-use crate::{Attrs, DecimalSeparatorStyle, Span, SpanKind};
+use crate::{Attrs, DecimalSeparatorStyle, ModuloConvention, Span, SpanKind};
 use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt::Write;
 use std::ops::Neg;
 use std::sync::Arc;
 use std::{cmp, fmt, io};
@@ -95,6 +96,84 @@ impl Value {
 			.try_as_usize(int)
 	}
 
+	/// Writes this number out in English words. Numbers formatted as
+	/// fractions (e.g. with `to fraction`) are written as fractions.
+	pub(crate) fn into_words<I: Interrupt>(
+		self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<String> {
+		if !self.exact {
+			return Err(FendError::InexactNumberToWords);
+		}
+		let as_fraction = matches!(
+			self.format,
+			FormattingStyle::ImproperFraction | FormattingStyle::MixedFraction
+		);
+		self.into_unitless_complex(decimal_separator, int)?
+			.try_as_real()?
+			.into_words(as_fraction, int)
+	}
+
+	pub(crate) fn into_continued_fraction<I: Interrupt>(
+		self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<String> {
+		let exact = self.exact;
+		self.into_unitless_complex(decimal_separator, int)?
+			.try_as_real()?
+			.into_continued_fraction(exact, int)
+	}
+
+	/// Writes this number as a hexadecimal floating-point literal like
+	/// `0x1.8p0`, prefixed with `approx.` if it had to be rounded
+	pub(crate) fn into_hex_float<I: Interrupt>(
+		self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<String> {
+		let exact = self.exact;
+		let res = self
+			.into_unitless_complex(decimal_separator, int)?
+			.try_as_real()?
+			.into_hex_float(int)?
+			.combine(exact);
+		Ok(if res.exact {
+			res.value
+		} else {
+			format!("approx. {}", res.value)
+		})
+	}
+
+	/// Returns the IEEE 754 bit pattern of this number as a single or double
+	/// precision float, e.g. `0x3ff8000000000000` for `1.5`
+	#[allow(clippy::cast_possible_truncation)]
+	pub(crate) fn into_float_bits<I: Interrupt>(
+		self,
+		single_precision: bool,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Self> {
+		let f = self
+			.into_unitless_complex(decimal_separator, int)?
+			.try_as_real()?
+			.into_f64(int)?;
+		let (bits, digits) = if single_precision {
+			let f = f as f32;
+			if !f.is_finite() {
+				return Err(FendError::ValueTooLarge);
+			}
+			(u64::from(f.to_bits()), 8)
+		} else {
+			if !f.is_finite() {
+				return Err(FendError::ValueTooLarge);
+			}
+			(f.to_bits(), 16)
+		};
+		Ok(Self::from(bits).with_base(Base::HEX.with_min_digits(digits)))
+	}
+
 	pub(crate) fn try_as_usize_unit<I: Interrupt>(self, int: &I) -> FResult<usize> {
 		if !self.exact {
 			return Err(FendError::InexactNumberToInt);
@@ -144,6 +223,15 @@ impl Value {
 	}
 
 	pub(crate) fn with_format(self, format: FormattingStyle) -> Self {
+		// combine scientific notation with a significant figures limit,
+		// e.g. `6.022e23 to scientific to 3 sf`
+		let format = match (self.format, format) {
+			(FormattingStyle::Scientific(_), FormattingStyle::SignificantFigures(sf))
+			| (FormattingStyle::SignificantFigures(sf), FormattingStyle::Scientific(None)) => {
+				FormattingStyle::Scientific(Some(sf))
+			}
+			(_, format) => format,
+		};
 		Self {
 			value: self.value,
 			unit: self.unit,
@@ -154,6 +242,23 @@ impl Value {
 		}
 	}
 
+	/// Used by the `@exact` attribute: exact numbers in the default format
+	/// are shown exactly, e.g. `1/3` instead of `approx. 0.3333333333`.
+	/// Approximate numbers like `sqrt 2` are left unchanged.
+	pub(crate) fn with_exact_format(self) -> Self {
+		if self.exact && self.format == FormattingStyle::Auto {
+			self.with_format(FormattingStyle::Exact)
+		} else {
+			self
+		}
+	}
+
+	/// Groups the digits of this number (e.g. `1111_1111`), keeping its base
+	pub(crate) fn with_digit_grouping(self) -> Self {
+		let base = self.base.with_grouping();
+		self.with_base(base)
+	}
+
 	pub(crate) fn with_base(self, base: Base) -> Self {
 		Self {
 			value: self.value,
@@ -183,6 +288,155 @@ impl Value {
 		})
 	}
 
+	/// Shows this integer as a `width`-bit two's complement number in the
+	/// given base, padded to the full width, e.g. `0xff` for `-1` as an
+	/// 8-bit hex number
+	pub(crate) fn into_twos_complement<I: Interrupt>(
+		self,
+		width: u64,
+		base: Base,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Self> {
+		if !self.exact {
+			return Err(FendError::InexactNumberToInt);
+		}
+		let value = self
+			.into_unitless_complex(decimal_separator, int)?
+			.try_as_real()?
+			.into_twos_complement(width, int)?;
+		// each digit covers a whole number of bits in bases like 2, 8 or 16
+		let radix = base.base_as_u8();
+		let min_digits = if radix.is_power_of_two() {
+			usize::try_from(width.div_ceil(u64::from(radix.trailing_zeros()))).unwrap_or(0)
+		} else {
+			0
+		};
+		Ok(Self {
+			value: Complex::from(Real::from(BigRat::from(value))).into(),
+			unit: Unit::unitless(),
+			exact: true,
+			base: base.with_explicit_prefix().with_min_digits(min_digits),
+			format: FormattingStyle::default(),
+			simplifiable: true,
+		})
+	}
+
+	/// Returns pairs of prime factors and their multiplicities
+	pub(crate) fn factorize<I: Interrupt>(
+		self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Vec<(Self, Self)>> {
+		let (base, format, simplifiable) = (self.base, self.format, self.simplifiable);
+		let to_number = |n: BigUint| Self {
+			value: Complex::from(Real::from(BigRat::from(n))).into(),
+			unit: Unit::unitless(),
+			exact: true,
+			base,
+			format,
+			simplifiable,
+		};
+		Ok(self
+			.into_unitless_complex(decimal_separator, int)?
+			.factorize(int)?
+			.into_iter()
+			.map(|(p, k)| (to_number(p), to_number(k.into())))
+			.collect())
+	}
+
+	pub(crate) fn next_prime<I: Interrupt>(
+		self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Self> {
+		Ok(Self {
+			unit: Unit::unitless(),
+			exact: self.exact,
+			base: self.base,
+			format: self.format,
+			simplifiable: self.simplifiable,
+			value: Dist::from(
+				self.into_unitless_complex(decimal_separator, int)?
+					.next_prime(int)?,
+			),
+		})
+	}
+
+	pub(crate) fn prev_prime<I: Interrupt>(
+		self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Self> {
+		Ok(Self {
+			unit: Unit::unitless(),
+			exact: self.exact,
+			base: self.base,
+			format: self.format,
+			simplifiable: self.simplifiable,
+			value: Dist::from(
+				self.into_unitless_complex(decimal_separator, int)?
+					.prev_prime(int)?,
+			),
+		})
+	}
+
+	pub(crate) fn isqrt<I: Interrupt>(
+		self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Self> {
+		Ok(Self {
+			unit: Unit::unitless(),
+			exact: self.exact,
+			base: self.base,
+			format: self.format,
+			simplifiable: self.simplifiable,
+			value: Dist::from(
+				self.into_unitless_complex(decimal_separator, int)?
+					.isqrt(int)?,
+			),
+		})
+	}
+
+	/// Returns the number of set bits in the binary representation
+	pub(crate) fn popcount<I: Interrupt>(
+		self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Self> {
+		Ok(Self {
+			unit: Unit::unitless(),
+			exact: self.exact,
+			base: Base::default(),
+			format: self.format,
+			simplifiable: self.simplifiable,
+			value: Dist::from(
+				self.into_unitless_complex(decimal_separator, int)?
+					.popcount(int)?,
+			),
+		})
+	}
+
+	/// Returns the number of bits needed to represent this number in binary
+	pub(crate) fn bitlength<I: Interrupt>(
+		self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Self> {
+		Ok(Self {
+			unit: Unit::unitless(),
+			exact: self.exact,
+			base: Base::default(),
+			format: self.format,
+			simplifiable: self.simplifiable,
+			value: Dist::from(
+				self.into_unitless_complex(decimal_separator, int)?
+					.bitlength(int)?,
+			),
+		})
+	}
+
 	fn new(value: impl Into<Dist>, unit_components: Vec<UnitExponent>) -> Self {
 		Self {
 			value: value.into(),
@@ -300,6 +554,7 @@ impl Value {
 	fn modulo<I: Interrupt>(
 		self,
 		rhs: Self,
+		convention: ModuloConvention,
 		decimal_separator: DecimalSeparatorStyle,
 		int: &I,
 	) -> FResult<Self> {
@@ -309,10 +564,11 @@ impl Value {
 			base: self.base,
 			format: self.format,
 			simplifiable: self.simplifiable,
-			value: Dist::from(
-				self.into_unitless_complex(decimal_separator, int)?
-					.modulo(rhs.into_unitless_complex(decimal_separator, int)?, int)?,
-			),
+			value: Dist::from(self.into_unitless_complex(decimal_separator, int)?.modulo(
+				rhs.into_unitless_complex(decimal_separator, int)?,
+				convention,
+				int,
+			)?),
 		})
 	}
 
@@ -374,6 +630,86 @@ impl Value {
 		})
 	}
 
+	pub(crate) fn gcd<I: Interrupt>(
+		self,
+		rhs: Self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Self> {
+		Ok(Self {
+			unit: Unit::unitless(),
+			exact: self.exact && rhs.exact,
+			base: self.base,
+			format: self.format,
+			simplifiable: self.simplifiable,
+			value: Dist::from(
+				self.into_unitless_complex(decimal_separator, int)?
+					.gcd(rhs.into_unitless_complex(decimal_separator, int)?, int)?,
+			),
+		})
+	}
+
+	pub(crate) fn beta<I: Interrupt>(
+		self,
+		rhs: Self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Self> {
+		let (base, format, simplifiable) = (self.base, self.format, self.simplifiable);
+		let exact = self.exact && rhs.exact;
+		let result = self
+			.into_unitless_complex(decimal_separator, int)?
+			.beta(rhs.into_unitless_complex(decimal_separator, int)?, int)?;
+		Ok(Self {
+			value: Dist::from(result.value),
+			unit: Unit::unitless(),
+			exact: exact && result.exact,
+			base,
+			format,
+			simplifiable,
+		})
+	}
+
+	pub(crate) fn lcm<I: Interrupt>(
+		self,
+		rhs: Self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Self> {
+		Ok(Self {
+			unit: Unit::unitless(),
+			exact: self.exact && rhs.exact,
+			base: self.base,
+			format: self.format,
+			simplifiable: self.simplifiable,
+			value: Dist::from(
+				self.into_unitless_complex(decimal_separator, int)?
+					.lcm(rhs.into_unitless_complex(decimal_separator, int)?, int)?,
+			),
+		})
+	}
+
+	/// Reverses the byte order of this integer, which must fit in `width`
+	/// bytes
+	pub(crate) fn byteswap<I: Interrupt>(
+		self,
+		width: Self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Self> {
+		Ok(Self {
+			unit: Unit::unitless(),
+			exact: self.exact && width.exact,
+			base: self.base,
+			format: self.format,
+			simplifiable: self.simplifiable,
+			value: Dist::from(
+				self.into_unitless_complex(decimal_separator, int)?
+					.byteswap(width.into_unitless_complex(decimal_separator, int)?, int)?,
+			),
+		})
+	}
+
 	pub(crate) fn bop<I: Interrupt>(
 		self,
 		op: Bop,
@@ -391,7 +727,12 @@ impl Value {
 			Bop::Minus => self.sub(rhs, context.decimal_separator, int),
 			Bop::Mul => self.mul(rhs, int),
 			Bop::Div => self.div(rhs, int),
-			Bop::Mod => self.modulo(rhs, context.decimal_separator, int),
+			Bop::Mod => self.modulo(
+				rhs,
+				context.modulo_convention,
+				context.decimal_separator,
+				int,
+			),
 			Bop::Pow => self.pow(rhs, context.decimal_separator, int),
 			Bop::Bitwise(bitwise_bop) => {
 				self.bitwise(rhs, bitwise_bop, context.decimal_separator, int)
@@ -413,6 +754,164 @@ impl Value {
 		Ok(false)
 	}
 
+	/// Returns the base units of this number, e.g. `kilogram meter second^-2`
+	/// for newtons, or an empty string if the number is dimensionless. If
+	/// `use_symbols` is set, SI base units are abbreviated (e.g. `kg m s^-2`).
+	pub(crate) fn dimension<I: Interrupt>(&self, use_symbols: bool, int: &I) -> FResult<String> {
+		let (hashmap, _) = self.unit.to_hashmap_and_scale(int)?;
+		let (hashmap, _, _) = Unit::reduce_hashmap(hashmap, int)?;
+		let mut base_units = vec![];
+		for (base_unit, exponent) in hashmap {
+			let name = base_unit.name();
+			let name = if use_symbols {
+				BASE_UNIT_SYMBOLS
+					.iter()
+					.find(|(unit, _)| *unit == name)
+					.map_or(name, |(_, symbol)| symbol)
+			} else {
+				name
+			};
+			if exponent.compare(&1.into(), int)? == Some(Ordering::Equal) {
+				base_units.push(name.to_string());
+			} else {
+				let exponent = exponent.try_as_i64(int)?;
+				base_units.push(format!("{name}^{exponent}"));
+			}
+		}
+		base_units.sort();
+		Ok(base_units.join(" "))
+	}
+
+	/// Returns the value of this number in base units, without any units,
+	/// e.g. `1609.344` for one mile. Temperature offsets are ignored (see
+	/// [`Self::base_unit_offset`]).
+	pub(crate) fn base_unit_scale<I: Interrupt>(&self, int: &I) -> FResult<Self> {
+		let (hashmap, scale) = self.unit.to_hashmap_and_scale(int)?;
+		let (_, adjustment, _) = Unit::reduce_hashmap(hashmap, int)?;
+		let value = Exact::new(self.value.clone(), self.exact)
+			.mul(&scale.mul(&adjustment, int)?.apply(Dist::from), int)?;
+		Ok(Self {
+			value: value.value,
+			unit: Unit::unitless(),
+			exact: value.exact,
+			base: self.base,
+			format: self.format,
+			simplifiable: true,
+		})
+	}
+
+	/// Returns the offset that is added after scaling to convert this unit
+	/// into base units, e.g. `273.15` for degrees Celsius. This is zero for
+	/// units without an offset.
+	pub(crate) fn base_unit_offset<I: Interrupt>(&self, int: &I) -> FResult<Self> {
+		let (hashmap, _) = self.unit.to_hashmap_and_scale(int)?;
+		let (_, _, offset) = Unit::reduce_hashmap(hashmap, int)?;
+		Ok(Self {
+			value: Dist::from(offset.value),
+			unit: Unit::unitless(),
+			exact: offset.exact,
+			base: self.base,
+			format: self.format,
+			simplifiable: true,
+		})
+	}
+
+	/// Describes the conversion from `self` to `result` as a chain of
+	/// conversion factors, e.g.
+	/// `60 mi / h × 1609.344 m/mi × 1/3600 h/s = 26.8224 m / s`.
+	/// Returns `None` for conversions that involve an offset (i.e. absolute
+	/// temperatures), since these can't be written as a product.
+	pub(crate) fn conversion_work<I: Interrupt>(
+		&self,
+		result: &Self,
+		ctx: &crate::Context,
+		int: &I,
+	) -> FResult<Option<String>> {
+		let scale_factor =
+			Unit::compute_scale_factor(&self.unit, &result.unit, ctx.decimal_separator, int)?;
+		if scale_factor.offset.value.compare(&0.into(), int)? != Some(Ordering::Equal) {
+			return Ok(None);
+		}
+		let mut work = self.format(ctx, int)?.to_string();
+		for (component, inverse) in self
+			.unit
+			.components
+			.iter()
+			.map(|c| (c, false))
+			.chain(result.unit.components.iter().map(|c| (c, true)))
+		{
+			let unit = Unit {
+				components: vec![UnitExponent::new(component.unit.clone(), 1)],
+			};
+			let one = Self {
+				value: 1.into(),
+				unit,
+				exact: true,
+				base: Base::default(),
+				format: FormattingStyle::default(),
+				simplifiable: false,
+			};
+			let name = one
+				.unit
+				.format(
+					"unitless",
+					true,
+					Base::default(),
+					FormattingStyle::Auto,
+					false,
+					ctx.decimal_separator,
+					int,
+				)?
+				.value;
+			let dimension = one.dimension(true, int)?;
+			let scale = one.base_unit_scale(int)?;
+			if scale.value.equals_int(1, int)? {
+				continue;
+			}
+			let scale = scale.format(ctx, int)?.to_string();
+			let parenthesize = |s: String| {
+				if s.contains(' ') {
+					format!("({s})")
+				} else {
+					s
+				}
+			};
+			let (name, dimension) = (parenthesize(name), parenthesize(dimension));
+			let mut exponent = component.exponent.clone();
+			if inverse {
+				exponent = -exponent;
+			}
+			if exponent.compare(&1.into(), int)? == Some(Ordering::Equal) {
+				write!(work, " × {scale} {dimension}/{name}").unwrap();
+			} else if exponent.compare(&-Complex::from(1), int)? == Some(Ordering::Equal) {
+				write!(work, " × 1/{scale} {name}/{dimension}").unwrap();
+			} else {
+				let exponent = Self {
+					value: exponent.into(),
+					unit: Unit::unitless(),
+					exact: true,
+					base: Base::default(),
+					format: FormattingStyle::default(),
+					simplifiable: false,
+				}
+				.format(ctx, int)?;
+				write!(work, " × ({scale} {dimension}/{name})^{exponent}").unwrap();
+			}
+		}
+		write!(work, " = {}", result.format(ctx, int)?).unwrap();
+		Ok(Some(work))
+	}
+
+	/// Returns whether this number is exact and can be printed without
+	/// rounding, i.e. without an `approx.` prefix
+	pub(crate) fn is_displayed_exactly<I: Interrupt>(
+		&self,
+		ctx: &crate::Context,
+		int: &I,
+	) -> FResult<bool> {
+		Ok(self.exact && self.format(ctx, int)?.exact)
+	}
+
 	pub(crate) fn is_unitless_one<I: Interrupt>(&self, int: &I) -> FResult<bool> {
 		Ok(self.exact && self.value.equals_int(1, int)? && self.is_unitless(int)?)
 	}
@@ -484,6 +983,204 @@ impl Value {
 		})
 	}
 
+	/// Returns -1, 0 or 1 depending on the sign of this number. Units are
+	/// removed since the result is dimensionless.
+	pub(crate) fn sign<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		let value = self.value.one_point()?;
+		if !value.imag().is_zero() {
+			return Err(FendError::ExpectedARealNumber);
+		}
+		let sign = match value.real().compare(&0.into(), int)? {
+			Ordering::Less => -Dist::from(1),
+			Ordering::Equal => Dist::from(0),
+			Ordering::Greater => Dist::from(1),
+		};
+		Ok(Self {
+			value: sign,
+			unit: Unit::unitless(),
+			exact: true,
+			base: self.base,
+			format: self.format,
+			simplifiable: self.simplifiable,
+		})
+	}
+
+	/// Returns `1 / self`. If the inverted unit corresponds to a named unit
+	/// (e.g. `Hz^-1` is a duration), the result is converted to that unit.
+	pub(crate) fn reciprocal<I: Interrupt>(
+		self,
+		attrs: Attrs,
+		ctx: &mut crate::Context,
+		int: &I,
+	) -> FResult<Self> {
+		let one = Self {
+			value: 1.into(),
+			unit: Unit::unitless(),
+			exact: true,
+			base: self.base,
+			format: self.format,
+			simplifiable: self.simplifiable,
+		};
+		let result = one.div(self, int)?;
+		if let [component] = &result.unit.components[..] {
+			if component.exponent.compare(&0.into(), int)? != Some(Ordering::Less) {
+				return Ok(result);
+			}
+			let (hashmap, _) = result.unit.to_hashmap_and_scale(int)?;
+			if let Ok(base_units) = hashmap
+				.into_iter()
+				.map(|(k, v)| v.try_as_i64(int).map(|v| format!("{}^{v}", k.name())))
+				.collect::<Result<Vec<String>, _>>()
+			{
+				if let Some(new_unit) = lookup_default_unit(&base_units.join(" ")) {
+					let rhs = query_unit_static(new_unit, attrs, ctx, int)?.expect_num()?;
+					return result.convert_to(rhs, ctx.decimal_separator, int);
+				}
+			}
+		}
+		Ok(result)
+	}
+
+	// Returns `x + yi`, converting `y` to the unit of `x`. Both numbers
+	// need to be real.
+	fn to_complex_plane<I: Interrupt>(
+		x: Self,
+		y: Self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Self> {
+		if !x.value.one_point_ref()?.imag().is_zero() || !y.value.one_point_ref()?.imag().is_zero()
+		{
+			return Err(FendError::ExpectedARealNumber);
+		}
+		x.add(y.mul(Self::i(), int)?, decimal_separator, int)
+	}
+
+	/// Returns `sqrt(x^2 + y^2)` in the unit of `x`
+	pub(crate) fn hypot<I: Interrupt>(
+		self,
+		y: Self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Self> {
+		Self::to_complex_plane(self, y, decimal_separator, int)?.abs(int)
+	}
+
+	/// Returns the angle between the positive x axis and the point `(x, y)`,
+	/// where `self` is `y`
+	pub(crate) fn atan2<I: Interrupt>(
+		self,
+		x: Self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Self> {
+		let (base, format, simplifiable) = (self.base, self.format, self.simplifiable);
+		let point = Self::to_complex_plane(x, self, decimal_separator, int)?;
+		let angle = point.value.one_point()?.arg(int)?;
+		Ok(Self {
+			value: Complex::from(angle.value).into(),
+			unit: Unit::unitless(),
+			exact: point.exact && angle.exact,
+			base,
+			format,
+			simplifiable,
+		})
+	}
+
+	// like `compare`, but returns an error if the units are incompatible
+	fn compare_or_err<I: Interrupt>(
+		&self,
+		other: &Self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Ordering> {
+		let difference = self.clone().sub(other.clone(), decimal_separator, int)?;
+		if difference.is_zero(int)? {
+			return Ok(Ordering::Equal);
+		}
+		difference
+			.value
+			.one_point()?
+			.compare(&0.into(), int)?
+			.ok_or(FendError::UnableToCompare)
+	}
+
+	/// Restricts `self` to the interval `[lower, upper]`. The result keeps
+	/// the unit, base and format of `self`.
+	pub(crate) fn clamp<I: Interrupt>(
+		self,
+		lower: Self,
+		upper: Self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<Self> {
+		if lower.compare_or_err(&upper, decimal_separator, int)? == Ordering::Greater {
+			return Err(FendError::InvalidClampBounds);
+		}
+		let bound = if self.compare_or_err(&lower, decimal_separator, int)? == Ordering::Less {
+			lower
+		} else if self.compare_or_err(&upper, decimal_separator, int)? == Ordering::Greater {
+			upper
+		} else {
+			return Ok(self);
+		};
+		let one = Self {
+			value: 1.into(),
+			unit: self.unit.clone(),
+			exact: true,
+			base: self.base,
+			format: self.format,
+			simplifiable: self.simplifiable,
+		};
+		Ok(Self {
+			base: self.base,
+			format: self.format,
+			simplifiable: self.simplifiable,
+			..bound.convert_to(one, decimal_separator, int)?
+		})
+	}
+
+	/// Solves `ax^2 + bx + c = 0`, returning both roots (which may be equal).
+	/// Real roots are returned in ascending order.
+	pub(crate) fn quadratic_roots<I: Interrupt>(
+		a: Self,
+		b: Self,
+		c: Self,
+		decimal_separator: DecimalSeparatorStyle,
+		int: &I,
+	) -> FResult<(Self, Self)> {
+		if a.is_zero(int)? {
+			return Err(FendError::NotAQuadratic);
+		}
+		let discriminant = b.clone().mul(b.clone(), int)?.sub(
+			Self::from(4).mul(a.clone(), int)?.mul(c, int)?,
+			decimal_separator,
+			int,
+		)?;
+		let half = Self::from(1).div(Self::from(2), int)?;
+		let negative =
+			discriminant.compare(&0.into(), decimal_separator, int)? == Some(Ordering::Less);
+		let sqrt_discriminant = if negative {
+			// compute the square root of the absolute value to keep it exact where possible
+			(-discriminant)
+				.pow(half, decimal_separator, int)?
+				.mul(Self::i(), int)?
+		} else {
+			discriminant.pow(half, decimal_separator, int)?
+		};
+		let two_a = Self::from(2).mul(a, int)?;
+		let x1 = (-b.clone())
+			.add(sqrt_discriminant.clone(), decimal_separator, int)?
+			.div(two_a.clone(), int)?;
+		let x2 = (-b)
+			.sub(sqrt_discriminant, decimal_separator, int)?
+			.div(two_a, int)?;
+		if !negative && x1.compare(&x2, decimal_separator, int)? == Some(Ordering::Greater) {
+			return Ok((x2, x1));
+		}
+		Ok((x1, x2))
+	}
+
 	pub(crate) fn make_approximate(self) -> Self {
 		Self {
 			value: self.value,
@@ -514,6 +1211,19 @@ impl Value {
 		Ok(Self::new(Dist::new_die(count, faces, int)?, vec![]))
 	}
 
+	pub(crate) fn new_die_keep<I: Interrupt>(
+		count: u32,
+		faces: u32,
+		keep: u32,
+		highest: bool,
+		int: &I,
+	) -> FResult<Self> {
+		Ok(Self::new(
+			Dist::new_die_keep(count, faces, keep, highest, int)?,
+			vec![],
+		))
+	}
+
 	fn remove_unit_scaling<I: Interrupt>(
 		self,
 		decimal_separator: DecimalSeparatorStyle,
@@ -522,6 +1232,59 @@ impl Value {
 		self.convert_to(Self::unitless(), decimal_separator, int)
 	}
 
+	/// Formats a time duration in years, days, hours, minutes and seconds,
+	/// e.g. `1 hour 23 minutes 20 seconds`. Years are counted as 365 days.
+	/// `second` is used to check that this value is a time.
+	pub(crate) fn format_as_duration<I: Interrupt>(
+		self,
+		second: Self,
+		ctx: &crate::Context,
+		int: &I,
+	) -> FResult<String> {
+		const UNITS: [(u64, &str, &str); 4] = [
+			(365 * 24 * 60 * 60, "year", "years"),
+			(24 * 60 * 60, "day", "days"),
+			(60 * 60, "hour", "hours"),
+			(60, "minute", "minutes"),
+		];
+		let ds = ctx.decimal_separator;
+		let mut remaining = self
+			.convert_to(second.clone(), ds, int)?
+			.div(second, int)?
+			.remove_unit_scaling(ds, int)?
+			.with_base(Base::default())
+			.with_format(FormattingStyle::Auto);
+		let mut result = String::new();
+		if remaining.compare(&0.into(), ds, int)? == Some(Ordering::Less) {
+			result.push('-');
+			remaining = remaining.abs(int)?;
+		}
+		let mut parts = vec![];
+		for (seconds, singular, plural) in UNITS {
+			let count = remaining.clone().div(seconds.into(), int)?.floor(int)?;
+			if count.is_zero(int)? {
+				continue;
+			}
+			let name = if count.value.equals_int(1, int)? {
+				singular
+			} else {
+				plural
+			};
+			parts.push(format!("{} {name}", count.format(ctx, int)?));
+			remaining = remaining.sub(count.mul(seconds.into(), int)?, ds, int)?;
+		}
+		if parts.is_empty() || !remaining.is_zero(int)? {
+			let name = if remaining.value.equals_int(1, int)? {
+				"second"
+			} else {
+				"seconds"
+			};
+			parts.push(format!("{} {name}", remaining.format(ctx, int)?));
+		}
+		result.push_str(&parts.join(" "));
+		Ok(result)
+	}
+
 	pub(crate) fn into_unitless_complex<I: Interrupt>(
 		mut self,
 		decimal_separator: DecimalSeparatorStyle,
@@ -591,6 +1354,29 @@ impl Value {
 		})
 	}
 
+	pub(crate) fn variance<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		let components = [self.unit.components.clone(), self.unit.components].concat();
+		Ok(Self {
+			value: self.value.variance(int)?,
+			unit: Unit { components },
+			..self
+		})
+	}
+
+	pub(crate) fn median<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(Self {
+			value: self.value.median(int)?,
+			..self
+		})
+	}
+
+	pub(crate) fn mode<I: Interrupt>(self, int: &I) -> FResult<Self> {
+		Ok(Self {
+			value: self.value.mode(int)?,
+			..self
+		})
+	}
+
 	fn convert_angle_to_rad<I: Interrupt>(
 		self,
 		scope: Option<Arc<Scope>>,
@@ -651,6 +1437,24 @@ impl Value {
 		})
 	}
 
+	/// Rounds to the nearest multiple of `1/den` and displays the result as a
+	/// fraction, e.g. `0.8125 inch to /16` becomes `13/16 inch`
+	pub(crate) fn round_to_denominator<I: Interrupt>(
+		self,
+		den: &BigUint,
+		int: &I,
+	) -> FResult<Self> {
+		let value = self.value.one_point()?.round_to_denominator(den, int)?;
+		Ok(Self {
+			value: Complex::from(value.value).into(),
+			unit: self.unit,
+			exact: self.exact && value.exact,
+			base: self.base,
+			format: FormattingStyle::MixedFraction,
+			simplifiable: self.simplifiable,
+		})
+	}
+
 	pub(crate) fn fibonacci<I: Interrupt>(
 		self,
 		decimal_separator: DecimalSeparatorStyle,
@@ -811,6 +1615,14 @@ impl Value {
 		self.apply_fn_exact(Complex::ln, true, context.decimal_separator, int)
 	}
 
+	pub(crate) fn lgamma<I: Interrupt>(
+		self,
+		context: &mut crate::Context,
+		int: &I,
+	) -> FResult<Self> {
+		self.apply_fn_exact(Complex::lgamma, true, context.decimal_separator, int)
+	}
+
 	pub(crate) fn log2<I: Interrupt>(self, context: &mut crate::Context, int: &I) -> FResult<Self> {
 		self.apply_fn(Complex::log2, true, context.decimal_separator, int)
 	}
@@ -823,6 +1635,97 @@ impl Value {
 		self.apply_fn(Complex::log10, true, context.decimal_separator, int)
 	}
 
+	/// Computes the logarithm of `self` to the given base as `ln(x) / ln(base)`
+	pub(crate) fn log<I: Interrupt>(
+		self,
+		base: Self,
+		context: &mut crate::Context,
+		int: &I,
+	) -> FResult<Self> {
+		let base = base.remove_unit_scaling(context.decimal_separator, int)?;
+		if !base.is_unitless(int)? {
+			return Err(FendError::ExpectedAUnitlessNumber);
+		}
+		let base_value = base.value.one_point_ref()?;
+		if base_value.compare(&0.into(), int)? != Some(Ordering::Greater)
+			|| base_value.compare(&1.into(), int)? == Some(Ordering::Equal)
+		{
+			return Err(FendError::InvalidLogarithmBase);
+		}
+		let base_value = base.value.one_point()?;
+		self.apply_fn(
+			|x, int| x.log(base_value, int),
+			true,
+			context.decimal_separator,
+			int,
+		)
+	}
+
+	/// Returns true if this is a real number with more digits before the
+	/// decimal point than the context's auto scientific threshold
+	fn exceeds_auto_scientific_threshold<I: Interrupt>(
+		&self,
+		ctx: &crate::Context,
+		int: &I,
+	) -> FResult<bool> {
+		let Some(threshold) = ctx.auto_scientific_threshold else {
+			return Ok(false);
+		};
+		// scientific notation is only supported in base 10 and below
+		if self.base.base_as_u8() > 10 {
+			return Ok(false);
+		}
+		self.has_more_integer_digits_than(threshold, int)
+	}
+
+	/// Returns true if this is a real number with more than the given number
+	/// of digits before the decimal point
+	fn has_more_integer_digits_than<I: Interrupt>(&self, digits: usize, int: &I) -> FResult<bool> {
+		let Ok(value) = self.value.one_point_ref() else {
+			return Ok(false);
+		};
+		if !value.imag().is_zero() {
+			return Ok(false);
+		}
+		let digits: u64 = digits.try_into().map_err(|_| FendError::ValueTooLarge)?;
+		let limit = Real::from(u64::from(self.base.base_as_u8()))
+			.pow(Real::from(digits), int)?
+			.value;
+		let abs = value.clone().abs(int)?.value;
+		Ok(abs.compare(&limit, int)? != Ordering::Less)
+	}
+
+	/// Picks a formatting style that keeps the output within the context's
+	/// maximum number of digits, or returns an error if that isn't possible
+	fn limit_output_digits<I: Interrupt>(
+		&self,
+		format: FormattingStyle,
+		max: usize,
+		int: &I,
+	) -> FResult<FormattingStyle> {
+		if let FormattingStyle::DecimalPlaces(n)
+		| FormattingStyle::SignificantFigures(n)
+		| FormattingStyle::Scientific(Some(n)) = format
+		{
+			if n > max {
+				return Err(FendError::OutputTooLong(max));
+			}
+		}
+		if !self.has_more_integer_digits_than(max, int)? {
+			return Ok(format);
+		}
+		// scientific notation is only supported in base 10 and below
+		if self.base.base_as_u8() > 10 {
+			return Err(FendError::OutputTooLong(max));
+		}
+		Ok(match format {
+			FormattingStyle::SignificantFigures(n) | FormattingStyle::Scientific(Some(n)) => {
+				FormattingStyle::Scientific(Some(n))
+			}
+			_ => FormattingStyle::Scientific(None),
+		})
+	}
+
 	pub(crate) fn format<I: Interrupt>(
 		&self,
 		ctx: &crate::Context,
@@ -834,27 +1737,66 @@ impl Value {
 			UseParentheses::IfComplex
 		};
 		let mut formatted_value = String::new();
-		let format = if !self.exact && self.format == FormattingStyle::Auto {
+		let format = if self.format == FormattingStyle::Auto
+			&& self.exceeds_auto_scientific_threshold(ctx, int)?
+		{
+			FormattingStyle::Scientific(None)
+		} else if let (FormattingStyle::Auto, Some(sf)) = (self.format, ctx.default_sf_limit) {
+			FormattingStyle::SignificantFigures(sf.get())
+		} else if !self.exact && self.format == FormattingStyle::Auto {
 			FormattingStyle::DecimalPlaces(10)
+		} else if self.format == FormattingStyle::Auto
+			&& ctx.prefer_mixed_fractions
+			&& self.is_simple_imperial_length(int)?
+		{
+			FormattingStyle::MixedFraction
 		} else {
 			self.format
 		};
-		let mut exact = self
-			.value
+		let format = match ctx.max_output_digits {
+			Some(max) => self.limit_output_digits(format, max, int)?,
+			None => format,
+		};
+		let in_parens =
+			ctx.negative_style == crate::NegativeStyle::Parentheses && self.is_negative_real();
+		let base = if ctx.base_prefix {
+			self.base
+		} else {
+			self.base.without_prefix()
+		};
+		let negated;
+		let value = if in_parens {
+			negated = -self.value.clone();
+			&negated
+		} else {
+			&self.value
+		};
+		let mut exact = value
 			.format(
 				self.exact,
 				format,
-				self.base,
+				base,
 				use_parentheses,
+				ctx.show_plus_sign && !in_parens,
 				&mut formatted_value,
 				ctx,
 				int,
 			)?
 			.exact;
+		if let Some(max) = ctx.max_output_digits {
+			let radix = self.base.base_as_u8().into();
+			if formatted_value
+				.chars()
+				.filter(|c| c.is_digit(radix))
+				.count() > max
+			{
+				return Err(FendError::OutputTooLong(max));
+			}
+		}
 		let unit_string = self.unit.format(
 			"",
-			self.value.equals_int(1, int)?,
-			self.base,
+			value.equals_int(1, int)?,
+			base,
 			self.format,
 			true,
 			ctx.decimal_separator,
@@ -865,9 +1807,38 @@ impl Value {
 			number: formatted_value,
 			exact,
 			unit_str: unit_string.value,
+			currency_symbol_spacing: ctx.currency_symbol_spacing,
+			in_parens,
 		})
 	}
 
+	/// Whether this is an exact length in inches, feet, yards or miles with a
+	/// small denominator, e.g. `5 1/6 feet` or `13/16 inches`, which reads
+	/// better as a mixed fraction than as a decimal
+	fn is_simple_imperial_length<I: Interrupt>(&self, int: &I) -> FResult<bool> {
+		const IMPERIAL_LENGTHS: &[&str] = &[
+			"inch", "in", "\"", "foot", "ft", "'", "yard", "yd", "mile", "mi",
+		];
+		let [component] = self.unit.components.as_slice() else {
+			return Ok(false);
+		};
+		if !IMPERIAL_LENGTHS.contains(&component.unit.singular_name.as_ref())
+			|| component.exponent.compare(&1.into(), int)? != Some(Ordering::Equal)
+		{
+			return Ok(false);
+		}
+		match self.value.one_point_ref() {
+			Ok(n) if n.imag().is_zero() => n.real().has_denominator_at_most(64, int),
+			_ => Ok(false),
+		}
+	}
+
+	fn is_negative_real(&self) -> bool {
+		self.value
+			.one_point_ref()
+			.is_ok_and(|n| n.imag().is_zero() && n.real().is_neg())
+	}
+
 	pub(crate) fn mul<I: Interrupt>(self, rhs: Self, int: &I) -> FResult<Self> {
 		let components = [self.unit.components, rhs.unit.components].concat();
 		let value =
@@ -1060,6 +2031,57 @@ impl Value {
 			}
 		}
 
+		if ctx.bit_byte_preference != crate::BitBytePreference::Unchanged {
+			if let Some(converted) = result.clone().with_preferred_data_unit(attrs, ctx, int)? {
+				return Ok(converted);
+			}
+		}
+
+		Ok(result)
+	}
+
+	/// Converts an amount of data to bits or bytes as configured with
+	/// `Context::set_bit_byte_display_preference`, using the largest prefix
+	/// that keeps the number at least 1. Returns `None` for other units.
+	fn with_preferred_data_unit<I: Interrupt>(
+		self,
+		attrs: Attrs,
+		ctx: &mut crate::Context,
+		int: &I,
+	) -> FResult<Option<Self>> {
+		use crate::BitBytePreference;
+
+		let [component] = self.unit.components.as_slice() else {
+			return Ok(None);
+		};
+		let is_data_unit = component.unit.base_units.len() == 1
+			&& component.unit.base_units.iter().all(|(base, exp)| {
+				base.name() == "bit"
+					&& exp.compare(&1.into(), int).ok().flatten() == Some(Ordering::Equal)
+			});
+		if !is_data_unit || component.exponent.compare(&1.into(), int)? != Some(Ordering::Equal) {
+			return Ok(None);
+		}
+		let units: &[&str] = match ctx.bit_byte_preference {
+			BitBytePreference::Unchanged => return Ok(None),
+			BitBytePreference::Bits => &["b", "kb", "Mb", "Gb", "Tb", "Pb", "Eb"],
+			BitBytePreference::Bytes => &["B", "kB", "MB", "GB", "TB", "PB", "EB"],
+			BitBytePreference::BinaryBits => &["b", "Kib", "Mib", "Gib", "Tib", "Pib", "Eib"],
+			BitBytePreference::BinaryBytes => &["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"],
+		};
+		let mut result = None;
+		for unit in units {
+			let rhs = query_unit_static(unit, attrs, ctx, int)?.expect_num()?;
+			let converted = self.clone().convert_to(rhs, ctx.decimal_separator, int)?;
+			let magnitude = Complex::from(converted.value.one_point_ref()?.clone().abs(int)?.value);
+			let at_least_one = magnitude.compare(&1.into(), int)? != Some(Ordering::Less);
+			if result.is_none() || at_least_one {
+				result = Some(converted);
+			}
+			if !at_least_one {
+				break;
+			}
+		}
 		Ok(result)
 	}
 
@@ -1110,11 +2132,31 @@ impl fmt::Debug for Value {
 	}
 }
 
+// symbols for SI base units, used by `Value::dimension`
+const BASE_UNIT_SYMBOLS: &[(&str, &str)] = &[
+	("ampere", "A"),
+	("candela", "cd"),
+	("kelvin", "K"),
+	("kilogram", "kg"),
+	("meter", "m"),
+	("mole", "mol"),
+	("second", "s"),
+];
+
+// currency symbols that are written before the amount, e.g. `$5`
+const PREFIX_CURRENCY_SYMBOLS: &[&str] = &["$", "\u{a3}", "\u{a5}"];
+// currency symbols that are written after the amount, e.g. `5€`
+const SUFFIX_CURRENCY_SYMBOLS: &[&str] = &["\u{20ac}"];
+
 #[derive(Debug)]
 pub(crate) struct FormattedValue {
 	exact: bool,
 	number: String,
 	unit_str: String,
+	currency_symbol_spacing: bool,
+	// whether to wrap the number and unit in parentheses instead of
+	// showing a minus sign
+	in_parens: bool,
 }
 
 impl FormattedValue {
@@ -1125,11 +2167,34 @@ impl FormattedValue {
 				kind: SpanKind::Ident,
 			});
 		}
-		if ["$", "\u{a3}", "\u{a5}"].contains(&self.unit_str.as_str()) && !attrs.plain_number {
+		if self.in_parens {
+			spans.push(Span {
+				string: "(".to_string(),
+				kind: SpanKind::Other,
+			});
+		}
+		let in_parens = self.in_parens;
+		self.number_and_unit_spans(spans, attrs);
+		if in_parens {
+			spans.push(Span {
+				string: ")".to_string(),
+				kind: SpanKind::Other,
+			});
+		}
+	}
+
+	fn number_and_unit_spans(self, spans: &mut Vec<Span>, attrs: Attrs) {
+		if PREFIX_CURRENCY_SYMBOLS.contains(&self.unit_str.as_str()) && !attrs.plain_number {
 			spans.push(Span {
 				string: self.unit_str,
 				kind: SpanKind::Ident,
 			});
+			if self.currency_symbol_spacing {
+				spans.push(Span {
+					string: " ".to_string(),
+					kind: SpanKind::Whitespace,
+				});
+			}
 			spans.push(Span {
 				string: self.number,
 				kind: SpanKind::Number,
@@ -1141,6 +2206,14 @@ impl FormattedValue {
 			kind: SpanKind::Number,
 		});
 		if !attrs.plain_number {
+			if self.currency_symbol_spacing
+				&& SUFFIX_CURRENCY_SYMBOLS.contains(&self.unit_str.as_str())
+			{
+				spans.push(Span {
+					string: " ".to_string(),
+					kind: SpanKind::Whitespace,
+				});
+			}
 			spans.push(Span {
 				string: self.unit_str,
 				kind: SpanKind::Ident,
@@ -1154,7 +2227,18 @@ impl fmt::Display for FormattedValue {
 		if !self.exact {
 			write!(f, "approx. ")?;
 		}
-		write!(f, "{}{}", self.number, self.unit_str)?;
+		if self.in_parens {
+			write!(f, "(")?;
+		}
+		if self.currency_symbol_spacing && SUFFIX_CURRENCY_SYMBOLS.contains(&self.unit_str.as_str())
+		{
+			write!(f, "{} {}", self.number, self.unit_str)?;
+		} else {
+			write!(f, "{}{}", self.number, self.unit_str)?;
+		}
+		if self.in_parens {
+			write!(f, ")")?;
+		}
 		Ok(())
 	}
 }
@@ -1185,7 +2269,7 @@ impl Unit {
 
 	pub(crate) fn deserialize(read: &mut impl io::Read) -> FResult<Self> {
 		let len = usize::deserialize(read)?;
-		let mut cs = Vec::with_capacity(len);
+		let mut cs = vec![];
 		for _ in 0..len {
 			cs.push(UnitExponent::deserialize(read)?);
 		}
@@ -1401,7 +2485,10 @@ impl Unit {
 				unit_string.push(' ');
 			}
 			let plural = last_component_plural && i == pluralised_idx;
-			let exp_format = if format == FormattingStyle::Auto {
+			let exp_format = if matches!(
+				format,
+				FormattingStyle::Auto | FormattingStyle::Scientific(_)
+			) {
 				FormattingStyle::Exact
 			} else {
 				format
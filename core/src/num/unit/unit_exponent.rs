@@ -2,6 +2,7 @@ use std::cmp::Ordering;
 use std::{collections::HashMap, fmt, io};
 
 use crate::interrupt::test_int;
+use crate::num::bigrat;
 use crate::num::complex::{self, Complex, UseParentheses};
 use crate::num::{Base, Exact, FormattingStyle};
 use crate::result::FResult;
@@ -42,7 +43,7 @@ impl UnitExponent {
 
 	pub(crate) fn is_percentage_unit(&self) -> bool {
 		let (prefix, name) = self.unit.prefix_and_name(false);
-		prefix.is_empty() && ["%", "percent"].contains(&name)
+		prefix.is_empty() && ["%", "percent", "\u{2030}", "permille", "per_mille"].contains(&name)
 	}
 
 	pub(crate) fn add_to_hashmap<I: Interrupt>(
@@ -105,10 +106,13 @@ impl UnitExponent {
 		} else {
 			let formatted = exp.format(
 				true,
-				format,
-				base,
 				UseParentheses::IfComplexOrFraction,
-				decimal_separator,
+				bigrat::FormatOptions {
+					base,
+					style: format,
+					decimal_separator,
+					..Default::default()
+				},
 				int,
 			)?;
 			(formatted.exact, Some(formatted.value))
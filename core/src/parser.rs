@@ -1,4 +1,6 @@
-use crate::ast::{Bop, Expr};
+use crate::ast::{Bop, ComparisonOp, Expr};
+use crate::date::DayOfWeek;
+use crate::ident::Ident;
 use crate::lexer::{Symbol, Token};
 use crate::value::Value;
 use std::fmt;
@@ -86,6 +88,82 @@ fn parse_number(input: &[Token]) -> ParseResult<'_> {
 fn parse_ident(input: &[Token]) -> ParseResult<'_> {
 	match parse_token(input)? {
 		(Token::Ident(ident), remaining) => {
+			if ident.as_str() == "next" {
+				if let Some((Token::Ident(weekday), remaining2)) = remaining.split_first() {
+					if let Some(weekday) = DayOfWeek::parse(weekday.as_str()) {
+						return Ok((Expr::NextWeekday(weekday), remaining2));
+					}
+				}
+			}
+			if ident.as_str() == "days" {
+				if let Some((Token::Ident(until), remaining2)) = remaining.split_first() {
+					if until.as_str() == "until" {
+						let (date, remaining3) = parse_power(remaining2, true)?;
+						return Ok((Expr::DaysUntil(Box::new(date)), remaining3));
+					}
+				}
+			}
+			// multi-word keys like `week number of` or `day of year of`
+			let multi_word_key = match (ident.as_str(), remaining) {
+				("week", [Token::Ident(number), Token::Symbol(Symbol::Of), rest @ ..])
+					if number.as_str() == "number" =>
+				{
+					Some(("week_number", rest))
+				}
+				(
+					"day",
+					[Token::Symbol(Symbol::Of), Token::Ident(year), Token::Symbol(Symbol::Of), rest @ ..],
+				) if year.as_str() == "year" => Some(("day_of_year", rest)),
+				(
+					"half",
+					[Token::Symbol(Symbol::Sub), Token::Ident(life), Token::Symbol(Symbol::Of), rest @ ..],
+				) if life.as_str() == "life" => Some(("half_life", rest)),
+				_ => None,
+			};
+			if let Some((key, remaining2)) = multi_word_key {
+				let (inner, remaining3) = parse_parens_or_literal(remaining2)?;
+				return Ok((Expr::Of(Ident::new_str(key), Box::new(inner)), remaining3));
+			}
+			// `float64 bits of <expr>` and `float32 bits of <expr>`
+			if let (
+				"float32" | "float64",
+				[Token::Ident(bits), Token::Symbol(Symbol::Of), rest @ ..],
+			) = (ident.as_str(), remaining)
+			{
+				if bits.as_str() == "bits" {
+					// allow negative numbers like `float64 bits of -2`, and
+					// include any units so that e.g. `float64 bits of 1 kg`
+					// is rejected
+					let (mut inner, mut remaining2) = parse_power(rest, true)?;
+					while let Ok((new_inner, remaining3)) = parse_apply_cont(remaining2, &inner) {
+						inner = new_inner;
+						remaining2 = remaining3;
+					}
+					let single_precision = ident.as_str() == "float32";
+					return Ok((
+						Expr::FloatBits(single_precision, Box::new(inner)),
+						remaining2,
+					));
+				}
+			}
+			// `15% off 200` and `15% on 200` (or `15% increase on 200`)
+			if ident.as_str() == "%" {
+				let percent_change = match remaining {
+					[Token::Ident(off), rest @ ..] if off.as_str() == "off" => Some((false, rest)),
+					[Token::Ident(on), rest @ ..] if on.as_str() == "on" => Some((true, rest)),
+					[Token::Ident(increase), Token::Ident(on), rest @ ..]
+						if increase.as_str() == "increase" && on.as_str() == "on" =>
+					{
+						Some((true, rest))
+					}
+					_ => None,
+				};
+				if let Some((increase, remaining2)) = percent_change {
+					if let Ok((inner, remaining3)) = parse_parens_or_literal(remaining2) {
+						return Ok((Expr::PercentChange(increase, Box::new(inner)), remaining3));
+					}
+				}
+			}
 			if ident.as_str() == "light" {
 				if let Ok((ident2, remaining2)) = parse_ident(remaining) {
 					return Ok((
@@ -111,12 +189,24 @@ fn parse_parens(input: &[Token]) -> ParseResult<'_> {
 		return Ok((Expr::Literal(Value::Unit), remaining));
 	}
 	let (inner, mut input) = parse_expression(input)?;
+	// comma-separated lists, e.g. function arguments like `(1, -3, 2)`
+	let mut items = vec![];
+	while let Ok(((), remaining)) = parse_fixed_symbol(input, Symbol::Comma) {
+		let (item, remaining) = parse_expression(remaining)?;
+		items.push(item);
+		input = remaining;
+	}
 	// allow omitting closing parentheses at end of input
 	if !input.is_empty() {
 		let ((), remaining) = parse_fixed_symbol(input, Symbol::CloseParens)?;
 		input = remaining;
 	}
-	Ok((Expr::Parens(Box::new(inner)), input))
+	if items.is_empty() {
+		Ok((Expr::Parens(Box::new(inner)), input))
+	} else {
+		items.insert(0, inner);
+		Ok((Expr::List(items), input))
+	}
 }
 
 fn parse_backslash_lambda(input: &[Token]) -> ParseResult<'_> {
@@ -165,7 +255,9 @@ fn parse_power(input: &[Token], allow_unary: bool) -> ParseResult<'_> {
 		}
 		// The precedence of unary division relative to exponentiation
 		// is not important because /a^b -> (1/a)^b == 1/(a^b)
-		if let Ok(((), remaining)) = parse_fixed_symbol(input, Symbol::Div) {
+		if let Ok(((), remaining)) = parse_fixed_symbol(input, Symbol::Div)
+			.or_else(|_| parse_fixed_symbol(input, Symbol::Per))
+		{
 			let (result, remaining) = parse_power(remaining, true)?;
 			return Ok((Expr::UnaryDiv(Box::new(result)), remaining));
 		}
@@ -278,6 +370,19 @@ fn parse_division_cont(input: &[Token]) -> ParseResult<'_> {
 	Ok((b, input))
 }
 
+// `per` divides by a whole quantity, so that e.g. `240 kcal per 2 servings`
+// is parsed as `240 kcal / (2 servings)` rather than `(240 kcal / 2) servings`
+fn parse_per_cont(input: &[Token]) -> ParseResult<'_> {
+	let ((), input) = parse_fixed_symbol(input, Symbol::Per)?;
+	let (b, input) = parse_power(input, true)?;
+	if let Expr::Literal(Value::Num(_)) = b {
+		if let Ok((b, remaining)) = parse_apply_cont(input, &b) {
+			return Ok((b, remaining));
+		}
+	}
+	Ok((b, input))
+}
+
 fn parse_modulo_cont(input: &[Token]) -> ParseResult<'_> {
 	let ((), input) = parse_fixed_symbol(input, Symbol::Mod)?;
 	let (b, input) = parse_power(input, true)?;
@@ -301,6 +406,11 @@ fn parse_modulo2_cont(input: &[Token]) -> ParseResult<'_> {
 	}) {
 		return Err(ParseError::UnexpectedInput);
 	}
+	// `15% off 200` and `15% on 200` are percentage changes
+	if matches!(input, [Token::Ident(word), _, ..] if matches!(word.as_str(), "off" | "on" | "increase"))
+	{
+		return Err(ParseError::UnexpectedInput);
+	}
 	let (b, input) = parse_power(input, true)?;
 	Ok((b, input))
 }
@@ -314,6 +424,9 @@ fn parse_multiplicative(input: &[Token]) -> ParseResult<'_> {
 		} else if let Ok((term, remaining)) = parse_division_cont(input) {
 			res = Expr::Bop(Bop::Div, Box::new(res.clone()), Box::new(term));
 			input = remaining;
+		} else if let Ok((term, remaining)) = parse_per_cont(input) {
+			res = Expr::Bop(Bop::Div, Box::new(res.clone()), Box::new(term));
+			input = remaining;
 		} else if let Ok((term, remaining)) = parse_modulo_cont(input) {
 			res = Expr::Bop(Bop::Mod, Box::new(res.clone()), Box::new(term));
 			input = remaining;
@@ -366,9 +479,52 @@ fn parse_subtraction_cont(input: &[Token]) -> ParseResult<'_> {
 fn parse_to_cont(input: &[Token]) -> ParseResult<'_> {
 	let ((), input) = parse_fixed_symbol(input, Symbol::UnitConversion)?;
 	let (b, input) = parse_implicit_addition(input)?;
+	// `to UTC+2` converts to a UTC offset instead of adding 2 to the result
+	if matches!(&b, Expr::Ident(i) if i.as_str() == "UTC" || i.as_str() == "GMT") {
+		if let Ok(((bop, offset), remaining)) = parse_utc_offset_with_minutes(input) {
+			return Ok((Expr::Bop(bop, Box::new(b), Box::new(offset)), remaining));
+		}
+		if let Ok((offset, remaining)) = parse_addition_cont(input) {
+			return Ok((
+				Expr::Bop(Bop::Plus, Box::new(b), Box::new(offset)),
+				remaining,
+			));
+		}
+		if let Ok((offset, remaining)) = parse_subtraction_cont(input) {
+			return Ok((
+				Expr::Bop(Bop::Minus, Box::new(b), Box::new(offset)),
+				remaining,
+			));
+		}
+	}
 	Ok((b, input))
 }
 
+// Parses offsets like the `+5:30` in `UTC+5:30`, returning the offset in hours
+fn parse_utc_offset_with_minutes(input: &[Token]) -> ParseResult<'_, (Bop, Expr)> {
+	let (bop, input) = if let Ok(((), remaining)) = parse_fixed_symbol(input, Symbol::Add) {
+		(Bop::Plus, remaining)
+	} else {
+		let ((), remaining) = parse_fixed_symbol(input, Symbol::Sub)?;
+		(Bop::Minus, remaining)
+	};
+	let (hours, input) = parse_number(input)?;
+	let ((), input) = parse_fixed_symbol(input, Symbol::Fn)?;
+	let (minutes, input) = parse_number(input)?;
+	let minutes = Expr::Bop(
+		Bop::Div,
+		Box::new(minutes),
+		Box::new(Expr::Literal(Value::Num(Box::new(60.into())))),
+	);
+	Ok((
+		(
+			bop,
+			Expr::Bop(Bop::Plus, Box::new(hours), Box::new(minutes)),
+		),
+		input,
+	))
+}
+
 fn parse_additive(input: &[Token]) -> ParseResult<'_> {
 	let (mut res, mut input) = parse_implicit_addition(input)?;
 	loop {
@@ -476,7 +632,22 @@ fn parse_permutation(input: &[Token]) -> ParseResult<'_> {
 	Ok((result, input))
 }
 
+fn parse_conditional(input: &[Token]) -> ParseResult<'_> {
+	let (condition, input) = parse_equality(input)?;
+	let ((), input) = parse_fixed_symbol(input, Symbol::Then)?;
+	let (if_true, input) = parse_equality(input)?;
+	let ((), input) = parse_fixed_symbol(input, Symbol::Else)?;
+	let (if_false, input) = parse_equality(input)?;
+	Ok((
+		Expr::If(Box::new(condition), Box::new(if_true), Box::new(if_false)),
+		input,
+	))
+}
+
 fn parse_function(input: &[Token]) -> ParseResult<'_> {
+	if let Ok(((), remaining)) = parse_fixed_symbol(input, Symbol::If) {
+		return parse_conditional(remaining);
+	}
 	let (lhs, input) = parse_permutation(input)?;
 	if let Ok(((), remaining)) = parse_fixed_symbol(input, Symbol::Fn) {
 		if let Expr::Ident(s) = lhs {
@@ -503,10 +674,25 @@ fn parse_equality(input: &[Token]) -> ParseResult<'_> {
 			remaining,
 		))
 	} else {
-		Ok((lhs, input))
+		parse_comparison(lhs, input)
 	}
 }
 
+fn parse_comparison(lhs: Expr, input: &[Token]) -> ParseResult<'_> {
+	let op = match input.first() {
+		Some(Token::Symbol(Symbol::LessThan)) => ComparisonOp::Less,
+		Some(Token::Symbol(Symbol::LessThanOrEqual)) => ComparisonOp::LessOrEqual,
+		Some(Token::Symbol(Symbol::GreaterThan)) => ComparisonOp::Greater,
+		Some(Token::Symbol(Symbol::GreaterThanOrEqual)) => ComparisonOp::GreaterOrEqual,
+		_ => return Ok((lhs, input)),
+	};
+	let (rhs, remaining) = parse_function(&input[1..])?;
+	Ok((
+		Expr::Comparison(op, Box::new(lhs), Box::new(rhs)),
+		remaining,
+	))
+}
+
 fn parse_assignment(input: &[Token]) -> ParseResult<'_> {
 	let (lhs, input) = parse_equality(input)?;
 	if let Ok(((), remaining)) = parse_fixed_symbol(input, Symbol::Equals) {
@@ -519,6 +705,42 @@ fn parse_assignment(input: &[Token]) -> ParseResult<'_> {
 	Ok((lhs, input))
 }
 
+// `unset <ident>` removes a variable, and must be a statement by itself
+fn parse_unset(input: &[Token]) -> Option<(Expr, &[Token])> {
+	if let [Token::Ident(keyword), Token::Ident(name), remaining @ ..] = input {
+		if keyword.as_str() == "unset"
+			&& matches!(
+				remaining.first(),
+				None | Some(Token::Symbol(Symbol::Semicolon))
+			) {
+			return Some((Expr::Unset(name.clone()), remaining));
+		}
+	}
+	None
+}
+
+// `5 widgets where widget = 3 kg` defines a unit that can be used in the
+// preceding expression
+fn parse_unit_definitions(mut result: Expr, mut input: &[Token]) -> ParseResult<'_> {
+	while let Ok(((), remaining)) = parse_fixed_symbol(input, Symbol::Where) {
+		let [Token::Ident(name), Token::Symbol(Symbol::Equals), remaining @ ..] = remaining else {
+			return Err(ParseError::ExpectedIdentifierInAssignment);
+		};
+		let (definition, remaining) = parse_equality(remaining)?;
+		result = Expr::DefineUnit(name.clone(), Box::new(definition), Box::new(result));
+		input = remaining;
+	}
+	Ok((result, input))
+}
+
+fn parse_statement(input: &[Token]) -> ParseResult<'_> {
+	if let Some(res) = parse_unset(input) {
+		return Ok(res);
+	}
+	let (result, input) = parse_assignment(input)?;
+	parse_unit_definitions(result, input)
+}
+
 fn parse_statements(mut input: &[Token]) -> ParseResult<'_> {
 	while let Ok(((), remaining)) = parse_fixed_symbol(input, Symbol::Semicolon) {
 		input = remaining;
@@ -526,13 +748,13 @@ fn parse_statements(mut input: &[Token]) -> ParseResult<'_> {
 	if input.is_empty() {
 		return Ok((Expr::Literal(Value::Unit), &[]));
 	}
-	let (mut result, mut input) = parse_assignment(input)?;
+	let (mut result, mut input) = parse_statement(input)?;
 	while let Ok(((), remaining)) = parse_fixed_symbol(input, Symbol::Semicolon) {
 		if remaining.is_empty() || matches!(remaining[0], Token::Symbol(Symbol::Semicolon)) {
 			input = remaining;
 			continue;
 		}
-		let (rhs, remaining) = parse_assignment(remaining)?;
+		let (rhs, remaining) = parse_statement(remaining)?;
 		result = Expr::Statements(Box::new(result), Box::new(rhs));
 		input = remaining;
 	}
@@ -1,6 +1,6 @@
 use crate::ident::Ident;
 use crate::result::FResult;
-use crate::serialize::{Deserialize, Serialize};
+use crate::serialize::{check_nesting_depth, Deserialize, Serialize};
 use crate::value::Value;
 use crate::{ast::Expr, error::Interrupt};
 use crate::{Attrs, Context};
@@ -55,12 +55,13 @@ impl ScopeValue {
 		Ok(())
 	}
 
-	pub(crate) fn deserialize(read: &mut impl io::Read) -> FResult<Self> {
-		Ok(Self::LazyVariable(Expr::deserialize(read)?, {
+	pub(crate) fn deserialize(read: &mut impl io::Read, depth: usize) -> FResult<Self> {
+		let depth = check_nesting_depth(depth)?;
+		Ok(Self::LazyVariable(Expr::deserialize(read, depth)?, {
 			if bool::deserialize(read)? {
 				None
 			} else {
-				Some(Arc::new(Scope::deserialize(read)?))
+				Some(Arc::new(Scope::deserialize(read, depth)?))
 			}
 		}))
 	}
@@ -111,15 +112,16 @@ impl Scope {
 		Ok(())
 	}
 
-	pub(crate) fn deserialize(read: &mut impl io::Read) -> FResult<Self> {
+	pub(crate) fn deserialize(read: &mut impl io::Read, depth: usize) -> FResult<Self> {
+		let depth = check_nesting_depth(depth)?;
 		Ok(Self {
 			ident: Ident::deserialize(read)?,
-			value: ScopeValue::deserialize(read)?,
+			value: ScopeValue::deserialize(read, depth)?,
 			inner: {
 				if bool::deserialize(read)? {
 					None
 				} else {
-					Some(Arc::new(Self::deserialize(read)?))
+					Some(Arc::new(Self::deserialize(read, depth)?))
 				}
 			},
 		})
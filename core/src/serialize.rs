@@ -34,7 +34,21 @@ macro_rules! impl_serde {
 	};
 }
 
-impl_serde!(u8 i32 u64);
+impl_serde!(u8 i32 u32 u64 i64);
+
+/// Expressions, functions and scopes can be nested arbitrarily deeply. This
+/// limit stops malformed input from overflowing the stack during
+/// deserialization.
+const MAX_NESTING_DEPTH: usize = 256;
+
+/// Returns the nesting depth for the next level of a recursive structure, or
+/// an error if the structure is nested too deeply
+pub(crate) fn check_nesting_depth(depth: usize) -> FResult<usize> {
+	if depth >= MAX_NESTING_DEPTH {
+		return Err(FendError::DeserializationError);
+	}
+	Ok(depth + 1)
+}
 
 impl Serialize for usize {
 	fn serialize(&self, write: &mut impl io::Write) -> FResult<()> {
@@ -62,7 +76,7 @@ impl Serialize for &str {
 impl Deserialize for String {
 	fn deserialize(read: &mut impl io::Read) -> FResult<Self> {
 		let len = usize::deserialize(read)?;
-		let mut buf = Vec::with_capacity(len);
+		let mut buf = vec![];
 		for _ in 0..len {
 			buf.push(u8::deserialize(read)?);
 		}
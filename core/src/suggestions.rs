@@ -0,0 +1,92 @@
+use crate::{ast, units, Context};
+
+/// Returns the known unit, function, constant or variable names that are
+/// closest to the given unknown identifier, nearest first. Names are only
+/// suggested if they're within the context's maximum edit distance.
+pub(crate) fn suggest_identifier(ident: &str, context: &Context) -> Vec<String> {
+	// allow fewer edits for short identifiers, so that e.g. `foo` doesn't
+	// suggest `foot`
+	let max_distance = context
+		.max_suggestion_distance
+		.min(ident.chars().count().saturating_sub(1) / 3);
+	if max_distance == 0 || context.max_suggestions == 0 {
+		return vec![];
+	}
+	let mut candidates: Vec<(usize, String)> = vec![];
+	let mut consider = |name: &str| {
+		if name == ident {
+			return;
+		}
+		if let Some(distance) = bounded_levenshtein(ident, name, max_distance) {
+			candidates.push((distance, name.to_string()));
+		}
+	};
+	let names = units::all_unit_names()
+		.chain(units::constant_names())
+		.chain(ast::BUILTIN_FUNCTIONS.iter().copied())
+		.chain(ast::BUILTIN_CONSTANTS.iter().copied());
+	for name in names {
+		consider(name);
+	}
+	for (singular, plural, _) in &context.custom_units {
+		consider(singular);
+		consider(plural);
+	}
+	for name in context.variables.keys() {
+		consider(name);
+	}
+	candidates.sort();
+	let mut suggestions: Vec<String> = vec![];
+	for (_, name) in candidates {
+		if suggestions.len() == context.max_suggestions {
+			break;
+		}
+		if !suggestions.contains(&name) {
+			suggestions.push(name);
+		}
+	}
+	suggestions
+}
+
+/// Formats suggestions as e.g. `'meter', 'metre' or 'meters'`
+pub(crate) fn format_suggestions(suggestions: &[String]) -> String {
+	let mut result = String::new();
+	for (i, suggestion) in suggestions.iter().enumerate() {
+		if i + 1 == suggestions.len() && i != 0 {
+			result.push_str(" or ");
+		} else if i != 0 {
+			result.push_str(", ");
+		}
+		result.push('\'');
+		result.push_str(suggestion);
+		result.push('\'');
+	}
+	result
+}
+
+/// Computes the Levenshtein distance between `a` and `b`, returning `None` if
+/// it's greater than `limit`.
+fn bounded_levenshtein(a: &str, b: &str, limit: usize) -> Option<usize> {
+	let a: Vec<char> = a.chars().collect();
+	let b: Vec<char> = b.chars().collect();
+	if a.len().abs_diff(b.len()) > limit {
+		return None;
+	}
+	let mut prev: Vec<usize> = (0..=b.len()).collect();
+	let mut curr = vec![0; b.len() + 1];
+	for (i, &ca) in a.iter().enumerate() {
+		curr[0] = i + 1;
+		let mut row_min = curr[0];
+		for (j, &cb) in b.iter().enumerate() {
+			let substitution = prev[j] + usize::from(ca != cb);
+			curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+			row_min = row_min.min(curr[j + 1]);
+		}
+		if row_min > limit {
+			return None;
+		}
+		std::mem::swap(&mut prev, &mut curr);
+	}
+	let distance = prev[b.len()];
+	(distance <= limit).then_some(distance)
+}
@@ -1,4 +1,5 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
 
 use crate::error::{FendError, Interrupt};
 use crate::eval::evaluate_to_value;
@@ -9,6 +10,7 @@ use crate::Attrs;
 
 mod builtin;
 
+pub(crate) use builtin::constant_names;
 pub(crate) use builtin::lookup_default_unit;
 pub(crate) use builtin::IMPLICIT_UNIT_MAP;
 
@@ -30,6 +32,36 @@ pub(crate) struct UnitDef {
 	value: Value,
 }
 
+/// Looks up an exchange rate, trying each handler in turn, and records
+/// its metadata in the context
+fn exchange_rate(currency: &str, context: &mut crate::Context) -> FResult<f64> {
+	let mut result = Err(FendError::NoExchangeRatesAvailable);
+	for exchange_rate_fn in &context.get_exchange_rate {
+		result = exchange_rate_fn
+			.handler
+			.relative_to_base_currency(currency)
+			.map_err(|e| {
+				FendError::Wrap(format!("failed to retrieve {currency} exchange rate"), e)
+			});
+		if result.is_ok() {
+			break;
+		}
+	}
+	let exchange_rate = result?;
+	if !context
+		.currency_info
+		.iter()
+		.any(|info| info.currency == currency)
+	{
+		context.currency_info.push(crate::CurrencyInfo {
+			currency: currency.to_string(),
+			timestamp_ms: exchange_rate.timestamp_ms,
+			source: exchange_rate.source,
+		});
+	}
+	Ok(exchange_rate.value)
+}
+
 fn expr_unit<I: Interrupt>(
 	unit_def: (Cow<'static, str>, Cow<'static, str>, Cow<'static, str>),
 	attrs: Attrs,
@@ -39,14 +71,7 @@ fn expr_unit<I: Interrupt>(
 	let (singular, plural, definition) = unit_def;
 	let mut definition = definition.trim();
 	if definition == "$CURRENCY" {
-		let Some(exchange_rate_fn) = &context.get_exchange_rate else {
-			return Err(FendError::NoExchangeRatesAvailable);
-		};
-		let one_base_in_currency = exchange_rate_fn
-			.relative_to_base_currency(&singular)
-			.map_err(|e| {
-				FendError::Wrap(format!("failed to retrieve {singular} exchange rate"), e)
-			})?;
+		let one_base_in_currency = exchange_rate(&singular, context)?;
 		let value = evaluate_to_value(
 			format!("(1/{one_base_in_currency}) BASE_CURRENCY").as_str(),
 			None,
@@ -280,7 +305,99 @@ fn query_unit_internal(
 	}
 }
 
-pub(crate) fn get_completions_for_prefix(prefix: &str) -> Vec<crate::Completion> {
+pub(crate) fn all_units() -> impl Iterator<Item = crate::UnitInfo> {
+	let mut context = crate::Context::new();
+	// exchange rates don't affect the dimension of a currency
+	context.set_exchange_rate_handler_v1(
+		|_: &str| -> Result<f64, Box<dyn std::error::Error + Send + Sync + 'static>> { Ok(1.0) },
+	);
+	builtin::ALL_UNIT_DEFS
+		.iter()
+		.flat_map(|group| group.iter())
+		.filter_map(move |&(singular, plural, definition, _)| {
+			let plural = if plural.is_empty() { singular } else { plural };
+			let unit_def = expr_unit(
+				(
+					Cow::Borrowed(singular),
+					Cow::Borrowed(plural),
+					Cow::Borrowed(definition),
+				),
+				Attrs::default(),
+				&mut context,
+				&crate::interrupt::Never,
+			)
+			.ok()?;
+			let dimension = unit_def
+				.value
+				.expect_num()
+				.ok()?
+				.dimension(false, &crate::interrupt::Never)
+				.ok()?;
+			Some(crate::UnitInfo {
+				singular,
+				plural,
+				dimension,
+				prefixable: matches!(
+					unit_def.prefix_rule,
+					PrefixRule::LongPrefixAllowed | PrefixRule::ShortPrefixAllowed
+				),
+			})
+		})
+}
+
+pub(crate) fn describe_unit(name: &str) -> Option<crate::UnitDescription> {
+	if name.is_empty() {
+		return None;
+	}
+	let mut context = crate::Context::new();
+	let int = &crate::interrupt::Never;
+	let unit = query_unit_static(name, Attrs::default(), &mut context, int)
+		.ok()?
+		.expect_num()
+		.ok()?;
+	let base_units = unit.dimension(true, int).ok()?;
+	let scale = unit
+		.base_unit_scale(int)
+		.ok()?
+		.with_format(crate::num::FormattingStyle::Exact)
+		.format(&context, int)
+		.ok()?
+		.to_string();
+	let offset = unit
+		.base_unit_offset(int)
+		.ok()?
+		.with_format(crate::num::FormattingStyle::Exact)
+		.format(&context, int)
+		.ok()?
+		.to_string();
+	Some(crate::UnitDescription {
+		base_units,
+		scale,
+		offset,
+	})
+}
+
+/// Returns the singular and plural names of all built-in units
+pub(crate) fn all_unit_names() -> impl Iterator<Item = &'static str> {
+	builtin::ALL_UNIT_DEFS
+		.iter()
+		.flat_map(|group| group.iter())
+		.flat_map(|(singular, plural, _, _)| [*singular, *plural])
+		.filter(|name| !name.is_empty())
+}
+
+/// Returns whether the given name is offered as a completion, i.e. whether
+/// it's the singular name of a built-in unit
+pub(crate) fn is_completable_unit(name: &str) -> bool {
+	builtin::ALL_UNIT_DEFS
+		.iter()
+		.any(|group| group.iter().any(|(s, _, _, _)| *s == name))
+}
+
+pub(crate) fn get_completions_for_prefix(
+	prefix: &str,
+	usage: Option<&HashMap<String, usize>>,
+) -> Vec<crate::Completion> {
 	use crate::Completion;
 
 	let mut result = vec![];
@@ -302,7 +419,13 @@ pub(crate) fn get_completions_for_prefix(prefix: &str) -> Vec<crate::Completion>
 		}
 	}
 
-	result.sort_by(|a, b| a.display().cmp(b.display()));
+	// more frequently used units come first, then sort alphabetically
+	let usage_count = |c: &Completion| usage.and_then(|u| u.get(c.display())).copied();
+	result.sort_by(|a, b| {
+		usage_count(b)
+			.cmp(&usage_count(a))
+			.then_with(|| a.display().cmp(b.display()))
+	});
 
 	result
 }
@@ -55,8 +55,23 @@ const BITS_AND_BYTES: &[UnitTuple] = &[
 	("byte", "bytes", "l@8 bits", ""),
 	("b", "", "s@bit", ""),
 	("B", "", "s@byte", ""),
+	("Bps", "", "s@bytes/second", ""),
 	("octet", "octets", "l@8 bits", ""),
 	("nibble", "nibbles", "l@4 bits", ""),
+	// `bit` only allows long prefixes (e.g. `megabit`), so abbreviations like
+	// `Mbit` need to be defined separately
+	("kbit", "", "1000 bits", ""),
+	("Mbit", "", "1000 kbit", ""),
+	("Gbit", "", "1000 Mbit", ""),
+	("Tbit", "", "1000 Gbit", ""),
+	("Pbit", "", "1000 Tbit", ""),
+	("Ebit", "", "1000 Pbit", ""),
+	("Kibit", "", "1024 bits", ""),
+	("Mibit", "", "1024 Kibit", ""),
+	("Gibit", "", "1024 Mibit", ""),
+	("Tibit", "", "1024 Gibit", ""),
+	("Pibit", "", "1024 Tibit", ""),
+	("Eibit", "", "1024 Pibit", ""),
 ];
 
 const STANDARD_PREFIXES: &[UnitTuple] = &[
@@ -449,6 +464,8 @@ const TIME_UNITS: &[UnitTuple] = &[
 
 const RATIOS: &[UnitTuple] = &[
 	("\u{2030}", "", "=0.001", ""), // per mille
+	("permille", "", "=\u{2030}", ""),
+	("per_mille", "", "=permille", ""),
 	("percent", "", "=0.01", ""),
 	("%", "", "=percent", ""),
 	("bel", "bels", "0.5 * ln(10) neper", ""),
@@ -465,6 +482,12 @@ const RATIOS: &[UnitTuple] = &[
 	("basispoint", "", "0.01 %", ""),
 ];
 
+// countable things, used for rates like `240 kcal per serving`
+const COUNT_UNITS: &[UnitTuple] = &[
+	("serving", "servings", "l@!", ""),
+	("item", "items", "l@!", ""),
+];
+
 const COMMON_PHYSICAL_UNITS: &[UnitTuple] = &[
 	("electron_volt", "electron_volts", "l@electron_charge V", ""),
 	("eV", "", "s@electron_volt", ""),
@@ -818,6 +841,7 @@ pub(crate) const ALL_UNIT_DEFS: &[&[UnitTuple]] = &[
 	COMMON_SI_DERIVED_UNITS,
 	TIME_UNITS,
 	RATIOS,
+	COUNT_UNITS,
 	COMMON_PHYSICAL_UNITS,
 	IMPERIAL_UNITS,
 	LIQUID_UNITS,
@@ -929,6 +953,11 @@ pub(crate) fn query_unit(
 	None
 }
 
+/// Names of physical constants like `planck` or `avogadro`
+pub(crate) fn constant_names() -> impl Iterator<Item = &'static str> {
+	CONSTANTS.iter().map(|(singular, _, _, _)| *singular)
+}
+
 const DEFAULT_UNITS: &[(&str, &str)] = &[
 	("hertz", "second^-1"),
 	("newton", "kilogram^1 meter^1 second^-2"),
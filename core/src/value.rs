@@ -1,10 +1,10 @@
 use crate::ast::Bop;
-use crate::date::{Date, DayOfWeek, Month};
+use crate::date::{Date, DateTime, DayOfWeek, Month};
 use crate::error::{FendError, Interrupt};
 use crate::num::{Base, FormattingStyle, Number};
 use crate::result::FResult;
 use crate::scope::{compare_option_arc_scope, Scope};
-use crate::serialize::{Deserialize, Serialize};
+use crate::serialize::{check_nesting_depth, Deserialize, Serialize};
 use crate::{ast::Expr, ident::Ident};
 use crate::{date, Attrs, Span, SpanKind};
 use std::borrow::Cow;
@@ -26,6 +26,8 @@ pub(crate) enum Value {
 	Dp,
 	Sf,
 	Base(Base),
+	// digit grouping marker, used in e.g. `to binary grouped`
+	Grouped,
 	// user-defined function with a named parameter
 	Fn(Ident, Box<Expr>, Option<Arc<Scope>>),
 	Object(Vec<(Cow<'static, str>, Box<Value>)>),
@@ -35,6 +37,11 @@ pub(crate) enum Value {
 	Month(date::Month),
 	DayOfWeek(date::DayOfWeek),
 	Date(date::Date),
+	// a date with a time of day, e.g. from `now`
+	DateTime(date::DateTime),
+	List(Vec<Self>),
+	// key-value pairs, e.g. prime factors and their multiplicities
+	Map(Vec<(Self, Self)>),
 }
 
 #[derive(Copy, Clone, Eq, PartialEq)]
@@ -61,7 +68,10 @@ impl Value {
 			(Self::Num(a), Self::Num(b)) => a.compare(b, ctx.decimal_separator, int)?,
 			(Self::BuiltInFunction(a), Self::BuiltInFunction(b)) => c(a == b),
 			(Self::Format(a), Self::Format(b)) => c(a == b),
-			(Self::Dp, Self::Dp) | (Self::Sf, Self::Sf) | (Self::Unit, Self::Unit) => c(true),
+			(Self::Dp, Self::Dp)
+			| (Self::Sf, Self::Sf)
+			| (Self::Grouped, Self::Grouped)
+			| (Self::Unit, Self::Unit) => c(true),
 			(Self::Base(a), Self::Base(b)) => c(a == b),
 			(Self::Fn(a1, a2, a3), Self::Fn(b1, b2, b3)) => c(a1 == b1
 				&& a2.compare(b2, ctx, int)?
@@ -86,6 +96,32 @@ impl Value {
 			(Self::Month(a), Self::Month(b)) => c(a == b),
 			(Self::DayOfWeek(a), Self::DayOfWeek(b)) => c(a == b),
 			(Self::Date(a), Self::Date(b)) => c(a == b),
+			(Self::DateTime(a), Self::DateTime(b)) => c(a == b),
+			(Self::List(a), Self::List(b)) => {
+				if a.len() != b.len() {
+					return Ok(None);
+				}
+				for (a, b) in a.iter().zip(b.iter()) {
+					match a.compare(b, ctx, int)? {
+						Some(cmp::Ordering::Equal) => (),
+						_ => return Ok(None),
+					}
+				}
+				return Ok(Some(cmp::Ordering::Equal));
+			}
+			(Self::Map(a), Self::Map(b)) => {
+				if a.len() != b.len() {
+					return Ok(None);
+				}
+				for ((a1, a2), (b1, b2)) in a.iter().zip(b.iter()) {
+					if a1.compare(b1, ctx, int)? != Some(cmp::Ordering::Equal)
+						|| a2.compare(b2, ctx, int)? != Some(cmp::Ordering::Equal)
+					{
+						return Ok(None);
+					}
+				}
+				return Ok(Some(cmp::Ordering::Equal));
+			}
 			_ => None,
 		})
 	}
@@ -151,11 +187,32 @@ impl Value {
 				13u8.serialize(write)?;
 				d.serialize(write)?;
 			}
+			Self::List(items) => {
+				14u8.serialize(write)?;
+				items.len().serialize(write)?;
+				for item in items {
+					item.serialize(write)?;
+				}
+			}
+			Self::Map(kv) => {
+				15u8.serialize(write)?;
+				kv.len().serialize(write)?;
+				for (k, v) in kv {
+					k.serialize(write)?;
+					v.serialize(write)?;
+				}
+			}
+			Self::Grouped => 16u8.serialize(write)?,
+			Self::DateTime(d) => {
+				17u8.serialize(write)?;
+				d.serialize(write)?;
+			}
 		}
 		Ok(())
 	}
 
-	pub(crate) fn deserialize(read: &mut impl io::Read) -> FResult<Self> {
+	pub(crate) fn deserialize(read: &mut impl io::Read, depth: usize) -> FResult<Self> {
+		let depth = check_nesting_depth(depth)?;
 		Ok(match u8::deserialize(read)? {
 			0 => Self::Num(Box::new(Number::deserialize(read)?)),
 			1 => Self::BuiltInFunction(BuiltInFunction::deserialize(read)?),
@@ -165,20 +222,20 @@ impl Value {
 			5 => Self::Base(Base::deserialize(read)?),
 			6 => Self::Fn(
 				Ident::deserialize(read)?,
-				Box::new(Expr::deserialize(read)?),
+				Box::new(Expr::deserialize(read, depth)?),
 				if bool::deserialize(read)? {
-					Some(Arc::new(Scope::deserialize(read)?))
+					Some(Arc::new(Scope::deserialize(read, depth)?))
 				} else {
 					None
 				},
 			),
 			7 => Self::Object({
 				let len = usize::deserialize(read)?;
-				let mut v = Vec::with_capacity(len);
+				let mut v = vec![];
 				for _ in 0..len {
 					v.push((
 						Cow::Owned(String::deserialize(read)?),
-						Box::new(Self::deserialize(read)?),
+						Box::new(Self::deserialize(read, depth)?),
 					));
 				}
 				v
@@ -189,6 +246,27 @@ impl Value {
 			11 => Self::Month(Month::deserialize(read)?),
 			12 => Self::DayOfWeek(DayOfWeek::deserialize(read)?),
 			13 => Self::Date(Date::deserialize(read)?),
+			14 => Self::List({
+				let len = usize::deserialize(read)?;
+				let mut v = vec![];
+				for _ in 0..len {
+					v.push(Self::deserialize(read, depth)?);
+				}
+				v
+			}),
+			15 => Self::Map({
+				let len = usize::deserialize(read)?;
+				let mut v = vec![];
+				for _ in 0..len {
+					v.push((
+						Self::deserialize(read, depth)?,
+						Self::deserialize(read, depth)?,
+					));
+				}
+				v
+			}),
+			16 => Self::Grouped,
+			17 => Self::DateTime(DateTime::deserialize(read)?),
 			_ => return Err(FendError::DeserializationError),
 		})
 	}
@@ -201,6 +279,7 @@ impl Value {
 			Self::Dp => "decimal places",
 			Self::Sf => "significant figures",
 			Self::Base(_) => "base",
+			Self::Grouped => "digit grouping",
 			Self::Object(_) => "object",
 			Self::String(_) => "string",
 			Self::Bool(_) => "bool",
@@ -208,10 +287,13 @@ impl Value {
 			Self::Month(_) => "month",
 			Self::DayOfWeek(_) => "day of week",
 			Self::Date(_) => "date",
+			Self::DateTime(_) => "date and time",
+			Self::List(_) => "list",
+			Self::Map(_) => "map",
 		}
 	}
 
-	fn as_bool(&self) -> FResult<bool> {
+	pub(crate) fn as_bool(&self) -> FResult<bool> {
 		if let Self::Bool(b) = self {
 			Ok(*b)
 		} else {
@@ -308,12 +390,39 @@ impl Value {
 				)?
 			}
 			Self::BuiltInFunction(func) => {
+				if let Expr::List(args) = other {
+					return Self::apply_built_in_function_with_args(
+						func,
+						args,
+						scope.as_ref(),
+						attrs,
+						context,
+						int,
+					);
+				}
 				Self::apply_built_in_function(func, other, scope, attrs, context, int)?
 			}
 			Self::Fn(param, expr, custom_scope) => {
+				if let Expr::List(args) = other {
+					// curry multiple arguments, so `f(a, b)` is equivalent to `f a b`
+					return Self::Fn(param, expr, custom_scope).apply_curried(
+						args,
+						scope.as_ref(),
+						attrs,
+						context,
+						int,
+					);
+				}
 				let new_scope = Scope::with_variable(param, other, scope, custom_scope);
 				return crate::ast::evaluate(*expr, Some(Arc::new(new_scope)), attrs, context, int);
 			}
+			Self::Base(base) => {
+				let other = crate::ast::evaluate(other, scope, attrs, context, int)?;
+				if matches!(other, Self::Grouped) {
+					return Ok(Self::Base(base.with_grouping()));
+				}
+				return Err(FendError::IsNotAFunctionOrNumber(stringified_self));
+			}
 			_ => return Err(FendError::IsNotAFunctionOrNumber(stringified_self)),
 		})
 	}
@@ -330,6 +439,8 @@ impl Value {
 		Ok(Self::Num(Box::new(match func {
 			BuiltInFunction::Approximately => arg.expect_num()?.make_approximate(),
 			BuiltInFunction::Abs => arg.expect_num()?.abs(int)?,
+			BuiltInFunction::Sign => arg.expect_num()?.sign(int)?,
+			BuiltInFunction::Reciprocal => arg.expect_num()?.reciprocal(attrs, context, int)?,
 			BuiltInFunction::Sin => arg.expect_num()?.sin(scope, attrs, context, int)?,
 			BuiltInFunction::Cos => arg.expect_num()?.cos(scope, attrs, context, int)?,
 			BuiltInFunction::Tan => arg.expect_num()?.tan(scope, attrs, context, int)?,
@@ -345,6 +456,7 @@ impl Value {
 			BuiltInFunction::Ln => arg.expect_num()?.ln(context, int)?,
 			BuiltInFunction::Log2 => arg.expect_num()?.log2(context, int)?,
 			BuiltInFunction::Log10 => arg.expect_num()?.log10(context, int)?,
+			BuiltInFunction::Lgamma => arg.expect_num()?.lgamma(context, int)?,
 			BuiltInFunction::Base => {
 				let n: u8 = arg
 					.expect_num()?
@@ -355,6 +467,9 @@ impl Value {
 			}
 			BuiltInFunction::Sample => arg.expect_num()?.sample(context, int)?,
 			BuiltInFunction::Mean => arg.expect_num()?.mean(int)?,
+			BuiltInFunction::Median => arg.expect_num()?.median(int)?,
+			BuiltInFunction::Mode => arg.expect_num()?.mode(int)?,
+			BuiltInFunction::Variance => arg.expect_num()?.variance(int)?,
 			BuiltInFunction::Not => return Ok(Self::Bool(!arg.as_bool()?)),
 			BuiltInFunction::Conjugate => arg.expect_num()?.conjugate()?,
 			BuiltInFunction::Real => arg.expect_num()?.real()?,
@@ -366,9 +481,156 @@ impl Value {
 			BuiltInFunction::Fibonacci => arg
 				.expect_num()?
 				.fibonacci(context.decimal_separator, int)?,
+			BuiltInFunction::Isqrt => arg.expect_num()?.isqrt(context.decimal_separator, int)?,
+			BuiltInFunction::Popcount => {
+				arg.expect_num()?.popcount(context.decimal_separator, int)?
+			}
+			BuiltInFunction::Bitlength => arg
+				.expect_num()?
+				.bitlength(context.decimal_separator, int)?,
+			BuiltInFunction::Factorize => {
+				return Ok(Self::Map(
+					arg.expect_num()?
+						.factorize(context.decimal_separator, int)?
+						.into_iter()
+						.map(|(p, k)| (Self::Num(Box::new(p)), Self::Num(Box::new(k))))
+						.collect(),
+				));
+			}
+			BuiltInFunction::NextPrime => arg
+				.expect_num()?
+				.next_prime(context.decimal_separator, int)?,
+			BuiltInFunction::PrevPrime => arg
+				.expect_num()?
+				.prev_prime(context.decimal_separator, int)?,
+			BuiltInFunction::Quadratic
+			| BuiltInFunction::Gcd
+			| BuiltInFunction::Lcm
+			| BuiltInFunction::Clamp
+			| BuiltInFunction::Hypot
+			| BuiltInFunction::Atan2
+			| BuiltInFunction::Beta
+			| BuiltInFunction::Byteswap => {
+				return Err(FendError::WrongNumberOfArguments {
+					function: func.as_str(),
+					found: 1,
+				})
+			}
 		})))
 	}
 
+	fn apply_curried<I: Interrupt>(
+		self,
+		args: Vec<Expr>,
+		scope: Option<&Arc<Scope>>,
+		attrs: Attrs,
+		context: &mut crate::Context,
+		int: &I,
+	) -> FResult<Self> {
+		let mut result = self;
+		for arg in args {
+			result = result.apply(
+				arg,
+				ApplyMulHandling::OnlyApply,
+				scope.cloned(),
+				attrs,
+				context,
+				int,
+			)?;
+		}
+		Ok(result)
+	}
+
+	fn apply_built_in_function_with_args<I: Interrupt>(
+		func: BuiltInFunction,
+		args: Vec<Expr>,
+		scope: Option<&Arc<Scope>>,
+		attrs: Attrs,
+		context: &mut crate::Context,
+		int: &I,
+	) -> FResult<Self> {
+		let found = args.len();
+		let mut values = Vec::with_capacity(found);
+		for arg in args {
+			values.push(crate::ast::evaluate(
+				arg,
+				scope.cloned(),
+				attrs,
+				context,
+				int,
+			)?);
+		}
+		let mut values = values.into_iter();
+		let mut next_num = || -> FResult<Number> {
+			values
+				.next()
+				.ok_or(FendError::WrongNumberOfArguments {
+					function: func.as_str(),
+					found,
+				})?
+				.expect_num()
+		};
+		match (func, found) {
+			// `log(x, base)`
+			(BuiltInFunction::Log10, 2) => Ok(Self::Num(Box::new(next_num()?.log(
+				next_num()?,
+				context,
+				int,
+			)?))),
+			(BuiltInFunction::Gcd, 2) => Ok(Self::Num(Box::new(next_num()?.gcd(
+				next_num()?,
+				context.decimal_separator,
+				int,
+			)?))),
+			(BuiltInFunction::Lcm, 2) => Ok(Self::Num(Box::new(next_num()?.lcm(
+				next_num()?,
+				context.decimal_separator,
+				int,
+			)?))),
+			(BuiltInFunction::Hypot, 2) => Ok(Self::Num(Box::new(next_num()?.hypot(
+				next_num()?,
+				context.decimal_separator,
+				int,
+			)?))),
+			(BuiltInFunction::Atan2, 2) => Ok(Self::Num(Box::new(next_num()?.atan2(
+				next_num()?,
+				context.decimal_separator,
+				int,
+			)?))),
+			(BuiltInFunction::Beta, 2) => Ok(Self::Num(Box::new(next_num()?.beta(
+				next_num()?,
+				context.decimal_separator,
+				int,
+			)?))),
+			(BuiltInFunction::Byteswap, 2) => Ok(Self::Num(Box::new(next_num()?.byteswap(
+				next_num()?,
+				context.decimal_separator,
+				int,
+			)?))),
+			(BuiltInFunction::Clamp, 3) => {
+				let (x, lower, upper) = (next_num()?, next_num()?, next_num()?);
+				Ok(Self::Num(Box::new(x.clamp(
+					lower,
+					upper,
+					context.decimal_separator,
+					int,
+				)?)))
+			}
+			(BuiltInFunction::Quadratic, 3) => {
+				let (a, b, c) = (next_num()?, next_num()?, next_num()?);
+				let (x1, x2) = Number::quadratic_roots(a, b, c, context.decimal_separator, int)?;
+				Ok(Self::List(vec![
+					Self::Num(Box::new(x1)),
+					Self::Num(Box::new(x2)),
+				]))
+			}
+			_ => Err(FendError::WrongNumberOfArguments {
+				function: func.as_str(),
+				found,
+			}),
+		}
+	}
+
 	pub(crate) fn format_to_plain_string<I: Interrupt>(
 		&self,
 		indent: usize,
@@ -385,6 +647,7 @@ impl Value {
 		Ok(res)
 	}
 
+	#[allow(clippy::too_many_lines)]
 	pub(crate) fn format<I: Interrupt>(
 		&self,
 		indent: usize,
@@ -395,10 +658,13 @@ impl Value {
 	) -> FResult<()> {
 		match self {
 			Self::Num(n) => {
-				n.clone()
-					.simplify(attrs, ctx, int)?
-					.format(ctx, int)?
-					.spans(spans, attrs);
+				let n = n.clone().simplify(attrs, ctx, int)?;
+				let n = if attrs.exact {
+					n.with_exact_format()
+				} else {
+					n
+				};
+				n.format(ctx, int)?.spans(spans, attrs);
 			}
 			Self::BuiltInFunction(name) => {
 				spans.push(Span {
@@ -424,6 +690,12 @@ impl Value {
 					kind: SpanKind::Keyword,
 				});
 			}
+			Self::Grouped => {
+				spans.push(Span {
+					string: "grouped".to_string(),
+					kind: SpanKind::Keyword,
+				});
+			}
 			Self::Base(b) => {
 				spans.push(Span {
 					string: "base ".to_string(),
@@ -433,6 +705,12 @@ impl Value {
 					string: b.base_as_u8().to_string(),
 					kind: SpanKind::Number,
 				});
+				if b.is_grouped() {
+					spans.push(Span {
+						string: " grouped".to_string(),
+						kind: SpanKind::Keyword,
+					});
+				}
 			}
 			Self::Fn(name, expr, _scope) => {
 				let expr_str = expr.format(attrs, ctx, int)?;
@@ -486,9 +764,37 @@ impl Value {
 				kind: crate::SpanKind::Date,
 			}),
 			Self::Date(d) => spans.push(crate::Span {
-				string: d.to_string(),
+				string: d.format(ctx.date_style),
+				kind: crate::SpanKind::Date,
+			}),
+			Self::DateTime(d) => spans.push(crate::Span {
+				string: d.format(ctx.date_style),
 				kind: crate::SpanKind::Date,
 			}),
+			Self::List(items) => {
+				for (i, item) in items.iter().enumerate() {
+					if i != 0 {
+						spans.push(Span::from_string(", ".to_string()));
+					}
+					item.format(indent, spans, attrs, ctx, int)?;
+				}
+			}
+			Self::Map(kv) => {
+				if kv.is_empty() {
+					spans.push(Span::from_string("{}".to_string()));
+					return Ok(());
+				}
+				spans.push(Span::from_string("{ ".to_string()));
+				for (i, (k, v)) in kv.iter().enumerate() {
+					if i != 0 {
+						spans.push(Span::from_string(", ".to_string()));
+					}
+					k.format(indent, spans, attrs, ctx, int)?;
+					spans.push(Span::from_string(": ".to_string()));
+					v.format(indent, spans, attrs, ctx, int)?;
+				}
+				spans.push(Span::from_string(" }".to_string()));
+			}
 		}
 		Ok(())
 	}
@@ -504,6 +810,7 @@ impl Value {
 				Err(FendError::CouldNotFindKeyInObject)
 			}
 			Self::Date(d) => d.get_object_member(key),
+			Self::DateTime(d) => d.get_object_member(key),
 			_ => Err(FendError::ExpectedAnObject),
 		}
 	}
@@ -517,6 +824,7 @@ impl fmt::Debug for Value {
 			Self::Format(fmt) => write!(f, "format: {fmt:?}"),
 			Self::Dp => write!(f, "dp"),
 			Self::Sf => write!(f, "sf"),
+			Self::Grouped => write!(f, "grouped"),
 			Self::Base(b) => write!(f, "base: {b:?}"),
 			Self::Fn(name, expr, scope) => {
 				write!(f, "fn: {name} => {expr:?} (scope: {scope:?})")
@@ -538,6 +846,9 @@ impl fmt::Debug for Value {
 			Self::Month(m) => write!(f, "{m}"),
 			Self::DayOfWeek(d) => write!(f, "{d}"),
 			Self::Date(d) => write!(f, "{d:?}"),
+			Self::DateTime(d) => write!(f, "{d:?}"),
+			Self::List(items) => write!(f, "{items:?}"),
+			Self::Map(kv) => write!(f, "{kv:?}"),
 		}
 	}
 }
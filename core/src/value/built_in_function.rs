@@ -29,9 +29,14 @@ pub(crate) enum BuiltInFunction {
 	Ln,
 	Log2,
 	Log10,
+	Lgamma,
+	Beta,
 	Base,
 	Sample,
 	Mean,
+	Median,
+	Mode,
+	Variance,
 	Not,
 	Conjugate,
 	Real,
@@ -41,6 +46,21 @@ pub(crate) enum BuiltInFunction {
 	Ceil,
 	Round,
 	Fibonacci,
+	Quadratic,
+	Gcd,
+	Lcm,
+	Isqrt,
+	Popcount,
+	Bitlength,
+	NextPrime,
+	PrevPrime,
+	Clamp,
+	Sign,
+	Reciprocal,
+	Hypot,
+	Atan2,
+	Factorize,
+	Byteswap,
 }
 
 impl BuiltInFunction {
@@ -96,9 +116,14 @@ impl BuiltInFunction {
 			Self::Ln => "ln",
 			Self::Log2 => "log2",
 			Self::Log10 => "log10",
+			Self::Lgamma => "lgamma",
+			Self::Beta => "beta",
 			Self::Base => "base",
 			Self::Sample => "sample",
 			Self::Mean => "mean",
+			Self::Median => "median",
+			Self::Mode => "mode",
+			Self::Variance => "variance",
 			Self::Not => "not",
 			Self::Conjugate => "conjugate",
 			Self::Real => "real",
@@ -108,6 +133,21 @@ impl BuiltInFunction {
 			Self::Ceil => "ceil",
 			Self::Round => "round",
 			Self::Fibonacci => "fibonacci",
+			Self::Quadratic => "quadratic",
+			Self::Gcd => "gcd",
+			Self::Lcm => "lcm",
+			Self::Isqrt => "isqrt",
+			Self::Popcount => "popcount",
+			Self::Bitlength => "bitlength",
+			Self::NextPrime => "nextprime",
+			Self::PrevPrime => "prevprime",
+			Self::Clamp => "clamp",
+			Self::Sign => "sign",
+			Self::Reciprocal => "reciprocal",
+			Self::Hypot => "hypot",
+			Self::Atan2 => "atan2",
+			Self::Factorize => "factorize",
+			Self::Byteswap => "byteswap",
 		}
 	}
 
@@ -130,13 +170,38 @@ impl BuiltInFunction {
 			"ln" => Self::Ln,
 			"log2" => Self::Log2,
 			"log10" => Self::Log10,
+			"lgamma" => Self::Lgamma,
+			"beta" => Self::Beta,
 			"base" => Self::Base,
 			"sample" => Self::Sample,
+			"mean" => Self::Mean,
+			"median" => Self::Median,
+			"mode" => Self::Mode,
+			"variance" => Self::Variance,
 			"not" => Self::Not,
 			"conjugate" => Self::Conjugate,
 			"real" => Self::Real,
 			"imag" => Self::Imag,
+			"arg" => Self::Arg,
+			"floor" => Self::Floor,
+			"ceil" => Self::Ceil,
+			"round" => Self::Round,
 			"fibonacci" => Self::Fibonacci,
+			"quadratic" => Self::Quadratic,
+			"gcd" => Self::Gcd,
+			"lcm" => Self::Lcm,
+			"isqrt" => Self::Isqrt,
+			"popcount" => Self::Popcount,
+			"bitlength" => Self::Bitlength,
+			"nextprime" => Self::NextPrime,
+			"prevprime" => Self::PrevPrime,
+			"clamp" => Self::Clamp,
+			"sign" => Self::Sign,
+			"reciprocal" => Self::Reciprocal,
+			"hypot" => Self::Hypot,
+			"atan2" => Self::Atan2,
+			"factorize" => Self::Factorize,
+			"byteswap" => Self::Byteswap,
 			_ => return Err(FendError::DeserializationError),
 		})
 	}
@@ -1,4 +1,4 @@
-use fend_core::{evaluate, Context};
+use fend_core::{evaluate, Context, ErrorVerbosity, FractionSlashStyle, Random};
 
 #[track_caller]
 fn test_serialization_roundtrip(context: &mut Context) {
@@ -58,6 +58,31 @@ fn expect_error(input: &str, error_message: Option<&str>) {
 	}
 }
 
+/// Evaluates the input in the given context, returning the main result or
+/// the error message
+fn eval_in(input: &str, context: &mut Context) -> Result<String, String> {
+	evaluate(input, context).map(|res| res.get_main_result().to_string())
+}
+
+/// Like `test_eval`, but the context is set up by `configure` first. The
+/// output isn't evaluated again, since it may depend on the configuration.
+#[track_caller]
+fn test_eval_with(input: &str, expected: &str, configure: impl FnOnce(&mut Context)) {
+	let mut context = Context::new();
+	context.set_exchange_rate_handler_v1(fend_core::test_utils::dummy_currency_handler);
+	configure(&mut context);
+	assert_eq!(eval_in(input, &mut context), Ok(expected.to_string()));
+}
+
+/// Like `expect_error`, but the context is set up by `configure` first
+#[track_caller]
+fn expect_error_with(input: &str, error_message: &str, configure: impl FnOnce(&mut Context)) {
+	let mut context = Context::new();
+	context.set_exchange_rate_handler_v1(fend_core::test_utils::dummy_currency_handler);
+	configure(&mut context);
+	assert_eq!(eval_in(input, &mut context), Err(error_message.to_string()));
+}
+
 #[test]
 fn two() {
 	test_eval("2", "2");
@@ -5173,7 +5198,6 @@ fn unicode_escape_aaa_uppercase() {
 }
 
 #[test]
-#[ignore]
 fn today() {
 	let mut context = Context::new();
 	context.set_current_time_v1(1617517099000, 0);
@@ -5184,7 +5208,6 @@ fn today() {
 }
 
 #[test]
-#[ignore]
 fn today_with_tz() {
 	let mut context = Context::new();
 	context.set_current_time_v1(1619943083155, 43200);
@@ -5194,6 +5217,45 @@ fn today_with_tz() {
 	);
 }
 
+#[test]
+fn today_at_fixed_times() {
+	let today = |ms_since_1970, tz_offset_secs| {
+		let mut context = Context::new();
+		context.set_current_time_v1(ms_since_1970, tz_offset_secs);
+		evaluate("today", &mut context)
+			.unwrap()
+			.get_main_result()
+			.to_string()
+	};
+	assert_eq!(today(0, 0), "Thursday, 1 January 1970");
+	assert_eq!(today(0, -1), "Wednesday, 31 December 1969");
+	// 2024-02-29 23:30 UTC
+	assert_eq!(today(1709249400000, 0), "Thursday, 29 February 2024");
+	assert_eq!(today(1709249400000, 3600), "Friday, 1 March 2024");
+	assert_eq!(today(1709249400000, -36000), "Thursday, 29 February 2024");
+	// 2000-12-31 12:00 UTC
+	assert_eq!(today(978264000000, 43200), "Monday, 1 January 2001");
+	assert_eq!(today(978264000000, -43200), "Sunday, 31 December 2000");
+}
+
+#[test]
+fn tomorrow_and_yesterday() {
+	let mut context = Context::new();
+	context.set_current_time_v1(1617517099000, 0);
+	assert_eq!(
+		evaluate("tomorrow", &mut context)
+			.unwrap()
+			.get_main_result(),
+		"Monday, 5 April 2021"
+	);
+	assert_eq!(
+		evaluate("yesterday", &mut context)
+			.unwrap()
+			.get_main_result(),
+		"Saturday, 3 April 2021"
+	);
+}
+
 #[test]
 fn acre_foot_to_m_3() {
 	test_eval("acre foot to m^3", "1233.48183754752 m^3");
@@ -5754,6 +5816,33 @@ fn permutation_test() {
 	test_eval("10 permute 3", "720");
 }
 
+#[test]
+fn gcd_test() {
+	test_eval("gcd(48, 36)", "12");
+	test_eval("gcd(17, 5)", "1");
+	test_eval("gcd(0, 5)", "5");
+	test_eval("gcd(2^64, 2^70)", "18446744073709551616");
+}
+
+#[test]
+fn lcm_test() {
+	test_eval("lcm(12, 18)", "36");
+	test_eval("lcm(4, 6)", "12");
+	test_eval("lcm(0, 5)", "0");
+	test_eval("lcm(2^40, 3^20)", "3833759992447475122176");
+}
+
+#[test]
+fn gcd_lcm_errors() {
+	expect_error("gcd(1.5, 3)", Some("1.5 is not an integer"));
+	expect_error(
+		"lcm(-4, 6)",
+		Some("-4 must lie in the interval [0, \u{221e})"),
+	);
+	expect_error("gcd 4", Some("'gcd' does not accept 1 argument"));
+	expect_error("lcm(1, 2, 3)", Some("'lcm' does not accept 3 arguments"));
+}
+
 // ERROR
 #[test]
 fn date_literals() {
@@ -5952,8 +6041,25 @@ fn test_roman() {
 	test_eval_simple("1452 to roman", "MCDLII");
 	test_eval_simple("20002 to roman", "X\u{305}X\u{305}II");
 	expect_error(
-		"1000000001 to roman",
-		Some("1000000001 must lie in the interval [1, 1000000000]"),
+		"4000000000 to roman",
+		Some("4000000000 must lie in the interval [1, 3999999999]"),
+	);
+}
+
+#[test]
+fn test_roman_double_overline() {
+	test_eval_simple("1000000 to roman", "M\u{305}");
+	test_eval_simple("4000000 to roman", "I\u{33f}V\u{33f}");
+	test_eval_simple("5000001 to roman", "V\u{33f}I");
+	test_eval_simple("1000000000 to roman", "M\u{33f}");
+	test_eval_simple(
+		"2500004000 to roman",
+		"M\u{33f}M\u{33f}D\u{33f}I\u{305}V\u{305}",
+	);
+	test_eval_simple(
+		"3999999999 to roman",
+		"M\u{33f}M\u{33f}M\u{33f}C\u{33f}M\u{33f}X\u{33f}C\u{33f}I\u{33f}X\u{33f}\
+		C\u{305}M\u{305}X\u{305}C\u{305}I\u{305}X\u{305}CMXCIX",
 	);
 }
 
@@ -6111,3 +6217,3542 @@ fn test_nested_exchange_rate_error() {
 		"failed to retrieve EUR exchange rate: my error: inner error",
 	);
 }
+
+#[test]
+fn to_scientific() {
+	test_eval_simple("6.022e23 to scientific", "6.022e23");
+	test_eval_simple("123000 to sci", "1.23e5");
+	test_eval_simple("1.5 to sci", "1.5e0");
+	test_eval_simple("0.00012 to sci", "1.2e-4");
+	test_eval_simple("-0.00012 to sci", "-1.2e-4");
+	test_eval("0 to scientific", "0");
+	test_eval_simple("5 m to sci", "5e0 m");
+	test_eval_simple("(3+4i) to scientific", "3e0 + 4e0i");
+	test_eval_simple("1/3 to sci", "approx. 3.333333333e-1");
+	test_eval_simple("pi to sci", "approx. 3.141592654e0");
+	test_eval_simple("100! to sci", "approx. 9.332621544e157");
+	test_eval_simple("0b1100 to sci", "0b1.1e11");
+	expect_error(
+		"255 to hex to sci",
+		Some("scientific notation is only supported in base 10 and below"),
+	);
+}
+
+#[test]
+fn to_scientific_with_sf() {
+	test_eval_simple("6.022e23 to scientific to 3 sf", "approx. 6.02e23");
+	test_eval_simple("6.022e23 to 3 sf to scientific", "approx. 6.02e23");
+	test_eval_simple("9.996 to sci to 3 sf", "approx. 1.00e1");
+	test_eval_simple("9.996 to 3 sf to sci", "approx. 1.00e1");
+	test_eval_simple("0.9999 to sci to 2 sf", "approx. 1.0e0");
+	test_eval_simple("123456 to sci to 2 sf", "approx. 1.2e5");
+	test_eval_simple("150 to sci to 5 sf", "1.5e2");
+}
+
+#[test]
+fn complex_numbers_to_scientific() {
+	test_eval_simple("i to sci", "1e0i");
+	test_eval_simple("(-1 - 2i) to sci", "-1e0 - 2e0i");
+	test_eval_simple("(1/3 + 2i/3) to sci to 3 sf", "approx. 3.33e-1 + 6.67e-1i");
+	test_eval_simple("(2 + 0.9999i) to sci to 2 sf", "approx. 2e0 + 1.0e0i");
+}
+
+#[test]
+fn quadratic_real_roots() {
+	test_eval_simple("quadratic(1, -3, 2)", "1, 2");
+	test_eval_simple("quadratic(2, -4, -6)", "-1, 3");
+	test_eval_simple(
+		"quadratic(1, 0, -2)",
+		"approx. -1.4142135621, approx. 1.4142135621",
+	);
+}
+
+#[test]
+fn quadratic_repeated_root() {
+	test_eval_simple("quadratic(1, -2, 1)", "1, 1");
+}
+
+#[test]
+fn quadratic_complex_roots() {
+	test_eval_simple("quadratic(1, 0, 1)", "i, -i");
+	test_eval_simple("quadratic(1, 2, 5)", "-1 + 2i, -1 - 2i");
+}
+
+#[test]
+fn quadratic_errors() {
+	expect_error(
+		"quadratic(0, 1, 2)",
+		Some("the leading coefficient of a quadratic cannot be zero"),
+	);
+	expect_error(
+		"quadratic(1, 2)",
+		Some("'quadratic' does not accept 2 arguments"),
+	);
+	expect_error(
+		"quadratic 1",
+		Some("'quadratic' does not accept 1 argument"),
+	);
+}
+
+#[test]
+fn comma_separated_arguments() {
+	test_eval("(x: y: x - y)(5, 3)", "2");
+	test_eval("1,000 + 1", "1001");
+	test_eval_simple("(1,000, 2)", "1000, 2");
+	expect_error("sin(1, 2)", Some("'sin' does not accept 2 arguments"));
+}
+
+#[test]
+fn comma_separated_arguments_without_spaces() {
+	test_eval("gcd(12,18)", "6");
+	test_eval("lcm(4,6)", "12");
+	test_eval("hypot(3,4)", "5");
+	test_eval("clamp(15,0,10)", "10");
+	test_eval("log(8,2)", "approx. 3");
+	test_eval_simple("quadratic(1,3,2)", "-2, -1");
+	test_eval("f = x: y: x - y; f(5,3)", "2");
+	test_eval("(x: y: x - y)(5,3)", "2");
+	test_eval("gcd((1,000),18)", "2");
+	expect_error("gcd(1.5,3)", Some("1.5 is not an integer"));
+}
+
+#[test]
+fn digit_separators_inside_parentheses() {
+	test_eval("abs(1,000)", "1000");
+	test_eval("sqrt(1,000,000)", "1000");
+	test_eval("log(1,000)", "approx. 3");
+	test_eval("5 kg (1,000)", "5000 kg");
+	test_eval("gcd(1,000, 18)", "2");
+	test_eval("gcd(12,1,000)", "4");
+	test_eval("abs(1,000) + gcd(4,6)", "1002");
+}
+
+#[test]
+fn commas_in_identifiers_outside_function_arguments() {
+	test_eval("a,b = 3; a,b * 2", "6");
+	test_eval("a = 2; b = 3; gcd(a,b)", "1");
+}
+
+#[test]
+fn comma_separated_arguments_with_comma_decimal_separator() {
+	let mut ctx = Context::new();
+	ctx.set_decimal_separator_style(fend_core::DecimalSeparatorStyle::Comma);
+	assert_eq!(
+		evaluate("(x: y: x - y)(5,5, 3)", &mut ctx)
+			.unwrap()
+			.get_main_result(),
+		"2,5"
+	);
+}
+
+#[test]
+fn comparison_operators() {
+	test_eval("3 < 4", "true");
+	test_eval("3 > 4", "false");
+	test_eval("2 <= 2", "true");
+	test_eval("2 >= 3", "false");
+	test_eval("2 ≤ 2", "true");
+	test_eval("1m > 50cm", "true");
+	test_eval("2 + 3 > 4 + 1", "false");
+	expect_error("1m < 1kg", Some("unable to compare these values"));
+}
+
+#[test]
+fn conditionals() {
+	test_eval("if 1 < 2 then 3 else 4", "3");
+	test_eval("if 1 > 2 then 3 else 4", "4");
+	test_eval("if true then 1 else if true then 2 else 3", "1");
+	expect_error("if 1 then 2 else 3", Some("expected a bool (found number)"));
+}
+
+#[test]
+fn piecewise_function() {
+	test_eval("f = (x: if x < 0 then -x else x); f(-3)", "3");
+	test_eval("f = (x: if x < 0 then -x else x); f 5", "5");
+	test_eval(
+		"sign = (x: if x < 0 then -1 else if x == 0 then 0 else 1); sign(-2)",
+		"-1",
+	);
+	test_eval_simple(
+		"x: if x < 0 then -x else x",
+		"\\x.if x < 0 then (-x) else x",
+	);
+}
+
+#[test]
+fn piecewise_function_serialization() {
+	let mut context = Context::new();
+	evaluate("f = (x: if x < 0 then -x else x)", &mut context).unwrap();
+	let mut v = vec![];
+	context.serialize_variables(&mut v).unwrap();
+	let mut context = Context::new();
+	context.deserialize_variables(&mut v.as_slice()).unwrap();
+	assert_eq!(
+		evaluate("f(-3)", &mut context).unwrap().get_main_result(),
+		"3"
+	);
+	assert_eq!(
+		evaluate("f 4", &mut context).unwrap().get_main_result(),
+		"4"
+	);
+}
+
+#[test]
+fn isqrt_perfect_squares() {
+	test_eval("isqrt 1000000", "1000");
+	test_eval("isqrt 144", "12");
+	test_eval("isqrt(10^40)", "100000000000000000000");
+}
+
+#[test]
+fn isqrt_non_perfect_squares() {
+	test_eval("isqrt 17", "4");
+	test_eval("isqrt 2", "1");
+	test_eval("isqrt(2^100 + 1)", "1125899906842624");
+}
+
+#[test]
+fn isqrt_zero() {
+	test_eval("isqrt 0", "0");
+}
+
+#[test]
+fn isqrt_errors() {
+	expect_error(
+		"isqrt(-4)",
+		Some("-4 must lie in the interval [0, \u{221e})"),
+	);
+	expect_error("isqrt 2.5", Some("2.5 is not an integer"));
+	expect_error("isqrt i", Some("expected a real number"));
+}
+
+#[test]
+fn next_prime() {
+	test_eval("nextprime 100", "101");
+	test_eval("nextprime 0", "2");
+	test_eval("nextprime 2", "3");
+	test_eval("nextprime 1000000000", "1000000007");
+	test_eval("nextprime(2^64)", "18446744073709551629");
+}
+
+#[test]
+fn prev_prime() {
+	test_eval("prevprime 100", "97");
+	test_eval("prevprime 3", "2");
+	test_eval("prevprime 563", "557");
+	test_eval("prevprime(2^64)", "18446744073709551557");
+}
+
+#[test]
+fn prime_errors() {
+	expect_error(
+		"prevprime 2",
+		Some("2 must lie in the interval [3, \u{221e})"),
+	);
+	expect_error(
+		"prevprime 1",
+		Some("1 must lie in the interval [3, \u{221e})"),
+	);
+	expect_error("nextprime 3.5", Some("3.5 is not an integer"));
+	expect_error(
+		"nextprime(-5)",
+		Some("-5 must lie in the interval [0, \u{221e})"),
+	);
+}
+
+#[test]
+fn auto_scientific_threshold_is_disabled_by_default() {
+	test_eval("10^30", "1000000000000000000000000000000");
+}
+
+#[test]
+fn auto_scientific_threshold_boundary() {
+	test_eval_with("10^30", "1e30", |ctx| ctx.set_auto_scientific_threshold(20));
+	test_eval_with("10^20 - 1", "99999999999999999999", |ctx| {
+		ctx.set_auto_scientific_threshold(20)
+	});
+	test_eval_with("10^20", "1e20", |ctx| ctx.set_auto_scientific_threshold(20));
+	test_eval_with("-(10^20)", "-1e20", |ctx| {
+		ctx.set_auto_scientific_threshold(20)
+	});
+	test_eval_with("2^100", "approx. 1.2676506e30", |ctx| {
+		ctx.set_auto_scientific_threshold(20)
+	});
+	test_eval_with("123456", "1.23456e5", |ctx| {
+		ctx.set_auto_scientific_threshold(5)
+	});
+	test_eval_with("12345.6", "12345.6", |ctx| {
+		ctx.set_auto_scientific_threshold(5)
+	});
+}
+
+#[test]
+fn auto_scientific_threshold_only_affects_auto_format() {
+	test_eval_with("10^25 to exact", "10000000000000000000000000", |ctx| {
+		ctx.set_auto_scientific_threshold(20)
+	});
+	test_eval_with("10^25 kg", "1e25 kg", |ctx| {
+		ctx.set_auto_scientific_threshold(20)
+	});
+	test_eval_with("10^25 to hex", "84595161401484a000000", |ctx| {
+		ctx.set_auto_scientific_threshold(20)
+	});
+}
+
+#[test]
+fn auto_scientific_threshold_can_be_disabled() {
+	let mut context = Context::new();
+	context.set_auto_scientific_threshold(5);
+	context.disable_auto_scientific_threshold();
+	assert_eq!(
+		evaluate("10^6", &mut context).unwrap().get_main_result(),
+		"1000000"
+	);
+}
+
+#[test]
+fn thin_space_grouping_integers() {
+	test_eval_with("1234567", "1\u{202f}234\u{202f}567", |ctx| {
+		ctx.set_thin_space_grouping(true)
+	});
+	test_eval_with("123", "123", |ctx| ctx.set_thin_space_grouping(true));
+	test_eval_with("1234", "1\u{202f}234", |ctx| {
+		ctx.set_thin_space_grouping(true)
+	});
+	test_eval_with("-123456", "-123\u{202f}456", |ctx| {
+		ctx.set_thin_space_grouping(true)
+	});
+	test_eval_with(
+		"10^20",
+		"100\u{202f}000\u{202f}000\u{202f}000\u{202f}000\u{202f}000\u{202f}000",
+		|ctx| ctx.set_thin_space_grouping(true),
+	);
+	test_eval_with("1234567 kg", "1\u{202f}234\u{202f}567 kg", |ctx| {
+		ctx.set_thin_space_grouping(true)
+	});
+}
+
+#[test]
+fn thin_space_grouping_decimals_and_fractions() {
+	test_eval_with("1234567.891", "1\u{202f}234\u{202f}567.891", |ctx| {
+		ctx.set_thin_space_grouping(true)
+	});
+	test_eval_with(
+		"1234567/7 to fraction",
+		"1\u{202f}234\u{202f}567/7",
+		|ctx| ctx.set_thin_space_grouping(true),
+	);
+	test_eval_with(
+		"2^64 to 5 sf",
+		"approx. 18\u{202f}446\u{202f}000\u{202f}000\u{202f}000\u{202f}000\u{202f}000",
+		|ctx| ctx.set_thin_space_grouping(true),
+	);
+}
+
+#[test]
+fn thin_space_grouping_only_applies_to_base_10() {
+	test_eval_with("0xffffff", "0xffffff", |ctx| {
+		ctx.set_thin_space_grouping(true)
+	});
+	test_eval_with("1234567 to binary", "100101101011010000111", |ctx| {
+		ctx.set_thin_space_grouping(true)
+	});
+}
+
+#[test]
+fn factorize_test() {
+	test_eval_simple("factorize 360", "{ 2: 3, 3: 2, 5: 1 }");
+	test_eval_simple("factorize 97", "{ 97: 1 }");
+	test_eval_simple("factorize 1", "{}");
+	test_eval_simple("factorize 360 to hex", "{ 2: 3, 3: 2, 5: 1 }");
+	test_eval_simple("factorize 3600 to hex", "{ 2: 4, 3: 2, 5: 2 }");
+	test_eval_simple("factorize 289 to hex", "{ 11: 2 }");
+	test_eval_simple("factorize 100 to binary", "{ 10: 10, 101: 10 }");
+	test_eval_simple("factorise(2^10)", "{ 2: 10 }");
+	test_eval_simple("factorize (2^64 + 1)", "{ 274177: 1, 67280421310721: 1 }");
+	test_eval_simple(
+		"factorize 600851475143",
+		"{ 71: 1, 839: 1, 1471: 1, 6857: 1 }",
+	);
+	test_eval_simple(
+		"factorize (1000003 * 1000033)",
+		"{ 1000003: 1, 1000033: 1 }",
+	);
+}
+
+#[test]
+fn factorize_errors() {
+	expect_error(
+		"factorize 0",
+		Some("0 must lie in the interval [1, \u{221e})"),
+	);
+	expect_error(
+		"factorize (-4)",
+		Some("-4 must lie in the interval [0, \u{221e})"),
+	);
+	expect_error("factorize 2.5", Some("2.5 is not an integer"));
+}
+
+#[test]
+fn unicode_space_digit_separators() {
+	test_eval("1\u{202f}234\u{202f}567", "1234567");
+	test_eval("1\u{a0}234\u{a0}567", "1234567");
+	test_eval("1\u{2009}234", "1234");
+	test_eval("3.141\u{202f}592", "3.141592");
+	test_eval("1\u{202f}000 + 1", "1001");
+}
+
+#[test]
+fn apostrophe_digit_separator() {
+	test_eval("1'234'567", "1234567");
+	test_eval("12'345 + 1", "12346");
+	test_eval("-1'000", "-1000");
+}
+
+#[test]
+fn apostrophe_separator_vs_feet() {
+	test_eval("5'", "5'");
+	test_eval("6'0\" to cm", "182.88 cm");
+	test_eval("1'234\" to inches", "246 inches");
+	test_eval("1'2345 to inches", "2357 inches");
+}
+
+#[test]
+fn detailed_errors_by_default() {
+	expect_error(
+		"1 kg to m",
+		Some("cannot convert from kg to m: units 'kilogram' and 'meter' are incompatible"),
+	);
+	expect_error_with(
+		"1 kg to m",
+		"cannot convert from kg to m: units 'kilogram' and 'meter' are incompatible",
+		|ctx| ctx.set_error_verbosity(ErrorVerbosity::Detailed),
+	);
+}
+
+#[test]
+fn short_errors() {
+	expect_error_with("1 kg to m", "incompatible units", |ctx| {
+		ctx.set_error_verbosity(ErrorVerbosity::Short)
+	});
+	expect_error_with("isqrt (-1)", "value out of range", |ctx| {
+		ctx.set_error_verbosity(ErrorVerbosity::Short)
+	});
+	expect_error_with("gcd(2.5, 1)", "expected an integer", |ctx| {
+		ctx.set_error_verbosity(ErrorVerbosity::Short)
+	});
+	expect_error_with("1/0", "division by zero", |ctx| {
+		ctx.set_error_verbosity(ErrorVerbosity::Short)
+	});
+}
+
+#[test]
+fn test_median() {
+	test_eval("median d1", "1");
+	test_eval("median d2", "1.5");
+	test_eval("median d6", "3.5");
+	test_eval("median 5", "5");
+
+	test_eval("median 2d6", "7");
+	test_eval("median 3d6", "10.5");
+	test_eval("median (d6 - 10)", "-6.5");
+
+	test_eval("median (d6 / d2)", "2.25");
+}
+
+#[test]
+fn test_mode() {
+	test_eval("mode d1", "1");
+	test_eval("mode 5", "5");
+
+	test_eval("mode 2d6", "7");
+	test_eval("mode (d2 + d2 + d2)", "4");
+
+	// multimodal distributions return the smallest modal value
+	test_eval("mode d6", "1");
+	test_eval("mode 3d6", "10");
+	test_eval("mode (d6 * d6)", "6");
+}
+
+#[test]
+fn words_ignore_base() {
+	test_eval_simple("0xff to words", "two hundred and fifty-five");
+	test_eval_simple("0b1010 to words", "ten");
+	test_eval_simple("6#100 to words", "thirty-six");
+	test_eval_simple("255 to hex to words", "two hundred and fifty-five");
+	test_eval_simple("0x10 to words", "sixteen");
+}
+
+#[test]
+fn test_variance() {
+	test_eval("variance d1", "0");
+	test_eval("variance 5", "0");
+	test_eval("variance d2", "0.25");
+	test_eval("variance d6", "approx. 2.9166666666");
+	test_eval_simple("variance d6 to fraction", "35/12");
+	test_eval_simple("variance 2d6 to fraction", "35/6");
+	test_eval("variance (d2 m)", "0.25 m^2");
+}
+
+#[test]
+fn test_standard_deviation() {
+	test_eval("stdev d1", "0");
+	test_eval("stdev d2", "0.5");
+	test_eval("std d2", "0.5");
+	test_eval("stdev d6", "approx. 1.7078251278");
+	test_eval("stdev (d2 m)", "0.5 m");
+}
+
+#[test]
+fn currency_symbols_tight_by_default() {
+	test_eval("$5", "$5");
+	test_eval("5€", "5€");
+	test_eval_with("$5", "$5", |ctx| {
+		ctx.set_auto_currency_symbol_spacing(false)
+	});
+	test_eval_with("£3 + £2", "£5", |ctx| {
+		ctx.set_auto_currency_symbol_spacing(false)
+	});
+	test_eval_with("5€", "5€", |ctx| {
+		ctx.set_auto_currency_symbol_spacing(false)
+	});
+	test_eval_with("5 EUR", "5 EUR", |ctx| {
+		ctx.set_auto_currency_symbol_spacing(false)
+	});
+}
+
+#[test]
+fn currency_symbols_spaced() {
+	test_eval_with("$5", "$ 5", |ctx| {
+		ctx.set_auto_currency_symbol_spacing(true)
+	});
+	test_eval_with("£3 + £2", "£ 5", |ctx| {
+		ctx.set_auto_currency_symbol_spacing(true)
+	});
+	test_eval_with("5€", "5 €", |ctx| {
+		ctx.set_auto_currency_symbol_spacing(true)
+	});
+	test_eval_with("$200/3 to 2dp", "approx. $ 66.66", |ctx| {
+		ctx.set_auto_currency_symbol_spacing(true)
+	});
+	test_eval_with("5 EUR", "5 EUR", |ctx| {
+		ctx.set_auto_currency_symbol_spacing(true)
+	});
+	test_eval_with("5 m", "5 m", |ctx| {
+		ctx.set_auto_currency_symbol_spacing(true)
+	});
+}
+
+#[test]
+fn test_2d6_keep_highest_1() {
+	test_eval_simple(
+		"2d6 keep highest 1",
+		"{ 1: 2.78%, 2: 8.33%, 3: 13.89%, 4: 19.44%, 5: 25.00%, 6: 30.56% }",
+	);
+	test_eval_simple(
+		"2d6 keep 1",
+		"{ 1: 2.78%, 2: 8.33%, 3: 13.89%, 4: 19.44%, 5: 25.00%, 6: 30.56% }",
+	);
+	test_eval_simple(
+		"2d6 drop lowest 1",
+		"{ 1: 2.78%, 2: 8.33%, 3: 13.89%, 4: 19.44%, 5: 25.00%, 6: 30.56% }",
+	);
+}
+
+#[test]
+fn test_2d6_keep_lowest_1() {
+	test_eval_simple(
+		"2d6 keep lowest 1",
+		"{ 1: 30.56%, 2: 25.00%, 3: 19.44%, 4: 13.89%, 5: 8.33%, 6: 2.78% }",
+	);
+	test_eval_simple(
+		"2d6 drop highest 1",
+		"{ 1: 30.56%, 2: 25.00%, 3: 19.44%, 4: 13.89%, 5: 8.33%, 6: 2.78% }",
+	);
+}
+
+#[test]
+fn test_keep_all_dice() {
+	test_eval_simple(
+		"2d6 keep 2",
+		"{ 2: 2.78%, 3: 5.56%, 4: 8.33%, 5: 11.11%, \
+		6: 13.89%, 7: 16.67%, 8: 13.89%, 9: 11.11%, 10: 8.33%, 11: 5.56%, 12: 2.78% }",
+	);
+	test_eval("mean (3d6 drop 0)", "10.5");
+}
+
+#[test]
+fn test_4d6_drop_lowest() {
+	test_eval_simple("mean (4d6 drop lowest 1) to fraction", "15869/1296");
+	test_eval_simple("mean (4d6 keep highest 3) to fraction", "15869/1296");
+	test_eval_simple("mean (4d6 keep lowest 3) to fraction", "11347/1296");
+	test_eval("mode (4d6 keep 3)", "13");
+}
+
+#[test]
+fn test_invalid_dice_selection() {
+	expect_error("4d6 keep 5", Some("cannot keep 5 out of 4 dice"));
+	expect_error("2d6 keep 0", Some("cannot keep 0 out of 2 dice"));
+	expect_error("4d6 drop lowest 4", Some("cannot drop 4 out of 4 dice"));
+}
+
+#[test]
+fn clamp_test() {
+	test_eval("clamp(15, 0, 10)", "10");
+	test_eval("clamp(-3, 0, 10)", "0");
+	test_eval("clamp(5, 0, 10)", "5");
+	test_eval("clamp(2, 1/3, 1)", "1");
+	test_eval("clamp(0xff, 0, 16)", "0x10");
+}
+
+#[test]
+fn clamp_with_units() {
+	test_eval("clamp(5m, 0m, 3m)", "3 m");
+	test_eval("clamp(5m, 0m, 300cm)", "3 m");
+	test_eval("clamp(10cm, 1ft, 3m)", "30.48 cm");
+	test_eval("clamp(1m, 2ft, 3m)", "1 m");
+}
+
+#[test]
+fn clamp_errors() {
+	expect_error(
+		"clamp(5m, 0kg, 3m)",
+		Some("cannot convert from m to kg: units 'meter' and 'kilogram' are incompatible"),
+	);
+	expect_error(
+		"clamp(5, 10, 0)",
+		Some("the lower bound cannot be greater than the upper bound"),
+	);
+	expect_error("clamp(1, 2)", Some("'clamp' does not accept 2 arguments"));
+}
+
+#[test]
+fn hypot_test() {
+	test_eval("hypot(3, 4)", "5");
+	test_eval("hypot(5, 12)", "13");
+	test_eval("hypot(3m, 4m)", "5 m");
+	test_eval("hypot(3m, 400cm)", "5 m");
+	test_eval("hypot(1, 1)", "approx. 1.4142135619");
+}
+
+#[test]
+fn hypot_errors() {
+	expect_error(
+		"hypot(3m, 4kg)",
+		Some("cannot convert from kg to m: units 'kilogram' and 'meter' are incompatible"),
+	);
+	expect_error("hypot(i, 1)", Some("expected a real number"));
+	expect_error("hypot 3", Some("'hypot' does not accept 1 argument"));
+}
+
+#[test]
+fn atan2_quadrants() {
+	test_eval("atan2(1, 1)", "approx. 0.7853981633");
+	test_eval("atan2(1, -1)", "approx. 2.3561944901");
+	test_eval("atan2(-1, -1)", "approx. -2.3561944901");
+	test_eval("atan2(-1, 1)", "approx. -0.7853981633");
+	test_eval("atan2(1, 0)", "approx. 1.5707963267");
+	test_eval("atan2(0, -1)", "approx. 3.1415926535");
+	test_eval("atan2(1m, 100cm)", "approx. 0.7853981633");
+}
+
+#[test]
+fn atan2_errors() {
+	expect_error(
+		"atan2(1m, 1kg)",
+		Some("cannot convert from m to kg: units 'meter' and 'kilogram' are incompatible"),
+	);
+	expect_error(
+		"atan2(1, 2, 3)",
+		Some("'atan2' does not accept 3 arguments"),
+	);
+}
+
+#[test]
+fn squared_unit_conversions() {
+	test_eval("1 m^2 to cm^2", "10000 cm^2");
+	test_eval("2 m^2 to cm^2", "20000 cm^2");
+	test_eval("1 cm^2 to m^2", "0.0001 m^2");
+	test_eval("1 km^2 to m^2", "1000000 m^2");
+	test_eval("1 ft^2 to cm^2", "929.0304 cm^2");
+	test_eval("1 yd^2 to m^2", "0.83612736 m^2");
+	test_eval("1 acre to ft^2", "43560 ft^2");
+	test_eval("1 mi^2 to km^2", "2.589988110336 km^2");
+	test_eval("1 ft^2 to inch^2", "144 inches^2");
+	test_eval("1 m^-2 to cm^-2", "0.0001 cm^-2");
+}
+
+#[test]
+fn cubed_unit_conversions() {
+	test_eval("1 m^3 to cm^3", "1000000 cm^3");
+	test_eval("1 m^3 to L", "1000 L");
+	test_eval("1 L to cm^3", "1000 cm^3");
+	test_eval("1 ft^3 to L", "28.316846592 L");
+	test_eval("1 ft^3 to m^3", "0.028316846592 m^3");
+	test_eval("1 yd^3 to ft^3", "27 ft^3");
+	test_eval("1 ft^3 to inch^3", "1728 inches^3");
+	test_eval("1 gallon to inch^3", "231 inches^3");
+	test_eval("1 kg/m^3 to g/cm^3", "0.001 g / cm^3");
+}
+
+#[test]
+fn power_conversions_with_recurring_digits() {
+	test_eval("1 m^2 to ft^2", "approx. 10.7639104167 ft^2");
+	test_eval_simple("1 m^2 to ft^2 to exact", "10 110890/145161 ft^2");
+	test_eval("1 m^3 to ft^3", "approx. 35.3146667214 ft^3");
+}
+
+#[test]
+fn fraction_slash_style_default() {
+	test_eval_with("1/2 to fraction", "1/2", |ctx| {
+		ctx.set_fraction_slash_style(FractionSlashStyle::Slash)
+	});
+	test_eval_with("7/3 to fraction", "7/3", |ctx| {
+		ctx.set_fraction_slash_style(FractionSlashStyle::Slash)
+	});
+}
+
+#[test]
+fn fraction_slash_style_unicode_slash() {
+	use FractionSlashStyle::FractionSlash;
+	test_eval_with("1/2 to fraction", "1\u{2044}2", |ctx| {
+		ctx.set_fraction_slash_style(FractionSlash)
+	});
+	test_eval_with("-7/3 to fraction", "-7\u{2044}3", |ctx| {
+		ctx.set_fraction_slash_style(FractionSlash)
+	});
+	test_eval_with("1/3", "approx. 0.3333333333", |ctx| {
+		ctx.set_fraction_slash_style(FractionSlash)
+	});
+	test_eval_with("4/3 to mixed_fraction", "1 1\u{2044}3", |ctx| {
+		ctx.set_fraction_slash_style(FractionSlash)
+	});
+	test_eval_with("m^(1/2) to fraction", "1 m^(1/2)", |ctx| {
+		ctx.set_fraction_slash_style(FractionSlash)
+	});
+}
+
+#[test]
+fn fraction_slash_style_vulgar() {
+	use FractionSlashStyle::Vulgar;
+	test_eval_with("1/2 to fraction", "\u{bd}", |ctx| {
+		ctx.set_fraction_slash_style(Vulgar)
+	});
+	test_eval_with("-3/4 to fraction", "-\u{be}", |ctx| {
+		ctx.set_fraction_slash_style(Vulgar)
+	});
+	test_eval_with("5/8 to fraction", "\u{215d}", |ctx| {
+		ctx.set_fraction_slash_style(Vulgar)
+	});
+	test_eval_with("5/3 to mixed_fraction", "1\u{2154}", |ctx| {
+		ctx.set_fraction_slash_style(Vulgar)
+	});
+	test_eval_with("1/3 kg to exact", "\u{2153} kg", |ctx| {
+		ctx.set_fraction_slash_style(Vulgar)
+	});
+	// no vulgar form exists for these
+	test_eval_with("7/3 to fraction", "7/3", |ctx| {
+		ctx.set_fraction_slash_style(Vulgar)
+	});
+	test_eval_with("2/11 to fraction", "2/11", |ctx| {
+		ctx.set_fraction_slash_style(Vulgar)
+	});
+	test_eval_with("pi/3 to exact", "\u{3c0}/3", |ctx| {
+		ctx.set_fraction_slash_style(Vulgar)
+	});
+}
+
+#[test]
+fn sign_test() {
+	test_eval("sign(-4)", "-1");
+	test_eval("sign 0", "0");
+	test_eval("sign 3.2", "1");
+	test_eval("signum (-0.5)", "-1");
+	test_eval("sign (-pi)", "-1");
+	test_eval("sign (1 + 0i)", "1");
+}
+
+#[test]
+fn sign_removes_units() {
+	test_eval("sign(-4 kg)", "-1");
+	test_eval("sign(0 m)", "0");
+	test_eval("sign(5 km/h)", "1");
+}
+
+#[test]
+fn sign_of_complex_number() {
+	expect_error("sign i", Some("expected a real number"));
+	expect_error("sign (1 + i)", Some("expected a real number"));
+}
+
+#[test]
+fn vulgar_fractions() {
+	test_eval("\u{bd}", "0.5");
+	test_eval("\u{bc}", "0.25");
+	test_eval("\u{be}", "0.75");
+	test_eval("\u{2155}", "0.2");
+	test_eval("\u{2156}", "0.4");
+	test_eval("\u{2157}", "0.6");
+	test_eval("\u{2158}", "0.8");
+	test_eval("\u{215b}", "0.125");
+	test_eval("\u{215c}", "0.375");
+	test_eval("\u{215d}", "0.625");
+	test_eval("\u{215e}", "0.875");
+	test_eval("\u{2152}", "0.1");
+	test_eval_simple("\u{2153} to fraction", "1/3");
+	test_eval_simple("\u{2154} to fraction", "2/3");
+	test_eval_simple("\u{2159} to fraction", "1/6");
+	test_eval_simple("\u{215a} to fraction", "5/6");
+	test_eval_simple("\u{2150} to fraction", "1/7");
+	test_eval_simple("\u{2151} to fraction", "1/9");
+}
+
+#[test]
+fn vulgar_fraction_arithmetic() {
+	test_eval("\u{bd} + \u{bc}", "0.75");
+	test_eval("\u{be} kg", "0.75 kg");
+	test_eval("\u{215b} * 8", "1");
+}
+
+#[test]
+fn vulgar_mixed_numbers() {
+	test_eval("2 \u{bd}", "2.5");
+	test_eval("2\u{bd}", "2.5");
+	test_eval("-8\u{bd}", "-8.5");
+	test_eval("-8 \u{bd}", "-8.5");
+	test_eval_simple("2\u{2154} to fraction", "8/3");
+	test_eval("2 \u{bd} cups to mL", "591.47059125 mL");
+}
+
+#[test]
+fn static_exchange_rates() {
+	let mut ctx = Context::new();
+	ctx.set_exchange_rates(std::collections::HashMap::from([
+		("USD".to_string(), 1.0),
+		("EUR".to_string(), 0.5),
+	]));
+	let result = evaluate("10 USD to EUR", &mut ctx).unwrap();
+	assert_eq!(result.get_main_result(), "5 EUR");
+	let result = evaluate("3 EUR to USD", &mut ctx).unwrap();
+	assert_eq!(result.get_main_result(), "6 USD");
+}
+
+#[test]
+fn static_exchange_rates_in_preview() {
+	let mut ctx = Context::new();
+	ctx.set_exchange_rates(std::collections::HashMap::from([
+		("USD".to_string(), 1.0),
+		("EUR".to_string(), 0.5),
+	]));
+	assert_eq!(preview_in("10 USD to EUR", &mut ctx), "5 EUR");
+	assert_eq!(
+		evaluate("10 USD to EUR", &mut ctx)
+			.unwrap()
+			.get_main_result(),
+		"5 EUR"
+	);
+	// other handlers may require network access, so they aren't used
+	ctx.set_exchange_rate_handler_v1(fend_core::test_utils::dummy_currency_handler);
+	assert_eq!(preview_in("10 USD to EUR", &mut ctx), "");
+}
+
+#[test]
+fn log_with_base() {
+	test_eval("log(81, 3)", "approx. 4");
+	test_eval("log(1000, 10)", "approx. 3");
+	test_eval("log(8, 2)", "approx. 3");
+	test_eval("log(1/81, 3)", "approx. -4");
+	test_eval("log(2, 8)", "approx. 0.3333333333");
+	test_eval("log 1000", "approx. 3");
+}
+
+#[test]
+fn log_with_invalid_base() {
+	let msg = "the base of a logarithm must be positive and not equal to 1";
+	expect_error("log(8, 1)", Some(msg));
+	expect_error("log(8, 0)", Some(msg));
+	expect_error("log(8, -2)", Some(msg));
+	expect_error(
+		"log(8, 2 m)",
+		Some("cannot convert from m to unitless: units 'meter' and 'unitless' are incompatible"),
+	);
+}
+
+#[test]
+fn reciprocal_unitless() {
+	test_eval("inv 4", "0.25");
+	test_eval("reciprocal 0.25", "4");
+	test_eval("reciprocal (-2)", "-0.5");
+	test_eval("reciprocal", "reciprocal");
+}
+
+#[test]
+fn reciprocal_with_units() {
+	test_eval("reciprocal (2 Hz)", "0.5 seconds");
+	test_eval("reciprocal (2 s)", "0.5 hertz");
+	test_eval("inv (2 kg)", "0.5 kg^-1");
+	test_eval("inv (1/(2 kg))", "2 kg");
+	test_eval("inv (4 m/s)", "0.25 s / m");
+}
+
+#[test]
+fn reciprocal_of_zero() {
+	expect_error("inv 0", Some("division by zero"));
+	expect_error("reciprocal (0 m)", Some("division by zero"));
+}
+
+#[test]
+fn popcount_test() {
+	test_eval("popcount 0", "0");
+	test_eval("popcount 0b1011", "3");
+	test_eval("popcount 255", "8");
+	test_eval("popcount (2^128 - 1)", "128");
+	test_eval("popcount (2^200)", "1");
+}
+
+#[test]
+fn bitlength_test() {
+	test_eval("bitlength 0", "0");
+	test_eval("bitlength 1", "1");
+	test_eval("bitlength 255", "8");
+	test_eval("bitlength 0xff", "8");
+	test_eval("bitlength (2^64)", "65");
+	test_eval("bitlength (2^200 - 1)", "200");
+}
+
+#[test]
+fn bit_counting_errors() {
+	expect_error("popcount (-1)", Some("-1 must lie in the interval [0, ∞)"));
+	expect_error("bitlength 1.5", Some("1.5 is not an integer"));
+	expect_error("popcount i", Some("expected a real number"));
+}
+
+#[test]
+fn bar_chart_respects_output_width() {
+	test_eval_with("d2", "  1: 50.00%  #######\n  2: 50.00%  #######", |ctx| {
+		ctx.set_output_mode_terminal();
+		ctx.set_output_width(20);
+	});
+	test_eval_with(
+		"d2",
+		"  1: 50.00%  ###########################\n  2: 50.00%  ###########################",
+		|ctx| {
+			ctx.set_output_mode_terminal();
+			ctx.set_output_width(40);
+		},
+	);
+	test_eval_with("d4 * 100", "100: 25.00%  ###########\n200: 25.00%  ###########\n300: 25.00%  ###########\n400: 25.00%  ###########", |ctx| {
+ctx.set_output_mode_terminal();
+ctx.set_output_width(24);
+});
+}
+
+#[test]
+fn bar_chart_with_tiny_output_width() {
+	test_eval_with("d2", "  1: 50.00%  #\n  2: 50.00%  #", |ctx| {
+		ctx.set_output_mode_terminal();
+		ctx.set_output_width(5);
+	});
+}
+
+#[test]
+fn bar_chart_scales_bars_to_most_likely_value() {
+	test_eval_with(
+		"d2 + d2",
+		"  2: 25.00%  #####\n  3: 50.00%  ##########\n  4: 25.00%  #####",
+		|ctx| {
+			ctx.set_output_mode_terminal();
+			ctx.set_output_width(23);
+		},
+	);
+}
+
+#[test]
+fn rng_closure_with_state() {
+	let mut ctx = Context::new();
+	let mut calls = 0;
+	ctx.set_random_u32_fn(move || {
+		calls += 1;
+		if calls % 2 == 1 {
+			0
+		} else {
+			u32::MAX
+		}
+	});
+	assert_eq!(
+		evaluate("roll d6", &mut ctx).unwrap().get_main_result(),
+		"1"
+	);
+	assert_eq!(
+		evaluate("roll d6", &mut ctx).unwrap().get_main_result(),
+		"6"
+	);
+	assert_eq!(
+		evaluate("roll d6", &mut ctx).unwrap().get_main_result(),
+		"1"
+	);
+}
+
+#[test]
+fn rng_survives_clone() {
+	let mut ctx = Context::new();
+	let mut high = true;
+	ctx.set_random_u32_fn(move || {
+		high = !high;
+		if high {
+			u32::MAX
+		} else {
+			0
+		}
+	});
+	assert_eq!(
+		evaluate("roll d6", &mut ctx).unwrap().get_main_result(),
+		"1"
+	);
+	// the clone shares the generator, so it continues the same sequence
+	let mut clone = ctx.clone();
+	assert_eq!(
+		evaluate("roll d6", &mut clone).unwrap().get_main_result(),
+		"6"
+	);
+	assert_eq!(
+		evaluate("roll d6", &mut ctx).unwrap().get_main_result(),
+		"1"
+	);
+	ctx.disable_rng();
+	assert_eq!(
+		evaluate("roll d6", &mut ctx).unwrap_err(),
+		"random numbers are not available"
+	);
+	assert_eq!(
+		evaluate("roll d6", &mut clone).unwrap().get_main_result(),
+		"6"
+	);
+}
+
+#[test]
+fn context_is_send_and_sync() {
+	fn assert_send_sync<T: Send + Sync>() {}
+	assert_send_sync::<Context>();
+}
+
+#[test]
+fn from_roman() {
+	test_eval("II", "2");
+	test_eval("III", "3");
+	test_eval("IV", "4");
+	test_eval("VI", "6");
+	test_eval("VII", "7");
+	test_eval("VIII", "8");
+	test_eval("IX", "9");
+	test_eval("X", "10");
+	test_eval("XIV", "14");
+	test_eval("XXII", "22");
+	test_eval("XLV", "45");
+	test_eval("CXXXIV", "134");
+	test_eval("MCMLXV", "1965");
+	test_eval("MMXX", "2020");
+	test_eval("MMMCDLVI", "3456");
+	test_eval("MCDLII", "1452");
+	test_eval("MMMCMXCIX", "3999");
+	test_eval("MCMLXV to decimal", "1965");
+	test_eval("MMXX + II", "2022");
+	test_eval_simple("MCMLXV to roman", "MCMLXV");
+}
+
+#[test]
+fn from_roman_keeps_existing_identifiers() {
+	test_eval("MMXX + I", "2020 + i");
+	test_eval("CD", "1 cd");
+	test_eval("V", "1 V");
+}
+
+#[test]
+fn invalid_roman_numerals() {
+	expect_error("IIII", Some("'IIII' is not a valid roman numeral"));
+	expect_error("VV", Some("'VV' is not a valid roman numeral"));
+	expect_error("IC", Some("'IC' is not a valid roman numeral"));
+	expect_error("MMMM", Some("'MMMM' is not a valid roman numeral"));
+}
+
+struct SequenceRng {
+	values: Vec<u32>,
+	idx: usize,
+}
+
+impl SequenceRng {
+	fn new(values: &[u32]) -> Self {
+		Self {
+			values: values.to_vec(),
+			idx: 0,
+		}
+	}
+}
+
+impl Random for SequenceRng {
+	fn random_u32(&mut self) -> u32 {
+		let value = self.values[self.idx % self.values.len()];
+		self.idx += 1;
+		value
+	}
+}
+
+#[test]
+fn random_u64_combines_two_u32s() {
+	let mut rng = SequenceRng::new(&[0x1234_5678, 0x9abc_def0]);
+	assert_eq!(rng.random_u64(), 0x1234_5678_9abc_def0);
+	let mut rng = SequenceRng::new(&[u32::MAX]);
+	assert_eq!(rng.random_u64(), u64::MAX);
+	let mut rng = SequenceRng::new(&[0, 1]);
+	assert_eq!(rng.random_u64(), 1);
+	let mut rng = SequenceRng::new(&[1, 0]);
+	assert_eq!(rng.random_u64(), 1 << 32);
+}
+
+#[test]
+fn random_fill_bytes() {
+	let mut rng = SequenceRng::new(&[0x0403_0201, 0x0807_0605]);
+	let mut bytes = [0; 6];
+	rng.fill_bytes(&mut bytes);
+	assert_eq!(bytes, [1, 2, 3, 4, 5, 6]);
+	assert_eq!(rng.idx, 2);
+
+	let mut rng = SequenceRng::new(&[u32::MAX]);
+	let mut bytes = [0; 9];
+	rng.fill_bytes(&mut bytes);
+	assert_eq!(bytes, [0xff; 9]);
+
+	let mut rng = SequenceRng::new(&[1]);
+	rng.fill_bytes(&mut []);
+	assert_eq!(rng.idx, 0);
+}
+
+#[test]
+fn random_closure_implements_random() {
+	let mut next = 0;
+	let mut rng = move || {
+		next += 1;
+		next
+	};
+	assert_eq!(Random::random_u64(&mut rng), (1 << 32) | 2);
+}
+
+#[test]
+fn set_rng_with_custom_generator() {
+	let mut ctx = Context::new();
+	ctx.set_rng(SequenceRng::new(&[0, u32::MAX]));
+	assert_eq!(
+		evaluate("roll d6", &mut ctx).unwrap().get_main_result(),
+		"1"
+	);
+	assert_eq!(
+		evaluate("roll d6", &mut ctx).unwrap().get_main_result(),
+		"6"
+	);
+}
+
+#[test]
+fn binary_digit_grouping() {
+	test_eval_simple("255 to binary grouped", "1111_1111");
+	test_eval("15 to binary grouped", "1111");
+	test_eval_simple("16 to binary grouped", "1_0000");
+	test_eval_simple("0b11111111 to grouped", "0b1111_1111");
+	test_eval_simple("(255 to binary grouped) + 1", "1_0000_0000");
+}
+
+#[test]
+fn hex_digit_grouping() {
+	test_eval_simple("0xdeadbeef to grouped", "0xdead_beef");
+	test_eval_simple("3735928559 to hex grouped", "dead_beef");
+	test_eval_simple("2^20 to base 16 grouped", "10_0000");
+	test_eval_simple("-65535 to hex grouped", "-ffff");
+}
+
+#[test]
+fn decimal_and_octal_digit_grouping() {
+	test_eval_simple("1234567 to grouped", "1_234_567");
+	test_eval_simple("1234567 to decimal grouped", "1_234_567");
+	test_eval_simple("1234567.891 to grouped", "1_234_567.891");
+	test_eval("123 to grouped", "123");
+	test_eval_simple("4095 to octal grouped", "7_777");
+}
+
+#[test]
+fn grouped_base_value() {
+	test_eval("binary grouped", "base 2 grouped");
+	expect_error(
+		"5 to grouped grouped",
+		Some("'grouped' is not a function or number"),
+	);
+}
+
+#[test]
+fn long_superscript_exponent() {
+	test_eval("2⁰⁰⁰⁰⁰⁰⁰⁰⁰⁰⁰⁰⁰⁰⁰⁰⁰⁰⁰⁰⁰⁰", "1");
+	test_eval("1⁹⁹⁹⁹⁹⁹⁹⁹⁹⁹⁹⁹⁹⁹⁹⁹⁹⁹⁹⁹⁹⁹", "1");
+	test_eval("2³+1", "9");
+}
+
+#[test]
+fn imaginary_to_negative_power() {
+	test_eval("i^-1", "-i");
+	test_eval("i^(-2)", "-1");
+	test_eval("(2i)^(-1)", "-0.5i");
+	test_eval("(2i)^(-3)", "0.125i");
+	test_eval("(-2i)^(-1)", "0.5i");
+	expect_error("(2i)^(2^70)", Some("exponent too large"));
+}
+
+struct Timeout(std::time::Instant);
+
+impl fend_core::Interrupt for Timeout {
+	fn should_interrupt(&self) -> bool {
+		self.0.elapsed() > std::time::Duration::from_millis(200)
+	}
+}
+
+fn preview_in(input: &str, context: &mut Context) -> String {
+	fend_core::evaluate_preview_with_interrupt(input, context, &Timeout(std::time::Instant::now()))
+		.get_main_result()
+		.to_string()
+}
+
+#[test]
+fn tricky_inputs_do_not_panic_or_hang() {
+	for input in [
+		"3d6100",
+		"1 << 1e15",
+		"1 << (2^62)",
+		"2^(2^63)",
+		"10^1e10",
+		"0.1^1e10",
+		"e^1e10",
+		"1e1000 nCr 2",
+		"fibonacci 1e7",
+		"1e7!",
+		"isqrt 1e100000",
+		"1e100000 to words",
+		"1/7 to 100000000 dp",
+		"1e999999 to base 3",
+		"\"\\x9\"",
+		"\"\\u{110000}\"",
+		"\"\\u{ffffffffffffffffffff}\"",
+		"i^(-1e30)",
+		"(3i)^pi",
+	] {
+		let start = std::time::Instant::now();
+		let mut context = Context::new();
+		context.set_random_u32_fn(|| 4);
+		let _ = fend_core::evaluate_with_interrupt(input, &mut context, &Timeout(start));
+		assert!(
+			start.elapsed() < std::time::Duration::from_secs(5),
+			"{input} was not interrupted"
+		);
+	}
+}
+
+#[test]
+fn malformed_variable_blobs() {
+	let mut context = Context::new();
+	evaluate("a = 5 kg; b = 1/3; c = 2i + 1; f = x: x + 1", &mut context).unwrap();
+	let mut blob = vec![];
+	context.serialize_variables(&mut blob).unwrap();
+	for len in 0..blob.len() {
+		assert!(Context::new()
+			.deserialize_variables(&mut &blob[..len])
+			.is_err());
+	}
+	for garbage in [
+		&[0xff; 8][..],
+		&[0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+		&[
+			0, 0, 0, 0, 0, 0, 0, 1, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+		],
+		&[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1, b'a', 0xff],
+	] {
+		assert!(Context::new()
+			.deserialize_variables(&mut &garbage[..])
+			.is_err());
+	}
+}
+
+#[test]
+fn space_grouping_with_comma_decimal_separator() {
+	let comma = fend_core::DecimalSeparatorStyle::Comma;
+	test_eval_with("1234567,89", "1 234 567,89", |ctx| {
+		ctx.set_digit_group_separator(Some(' '));
+		ctx.set_decimal_separator_style(comma);
+	});
+	test_eval_with("1234567", "1 234 567", |ctx| {
+		ctx.set_digit_group_separator(Some(' '));
+		ctx.set_decimal_separator_style(comma);
+	});
+	test_eval_with("-1000,5 kg", "-1 000,5 kg", |ctx| {
+		ctx.set_digit_group_separator(Some(' '));
+		ctx.set_decimal_separator_style(comma);
+	});
+	test_eval_with("123,45", "123,45", |ctx| {
+		ctx.set_digit_group_separator(Some(' '));
+		ctx.set_decimal_separator_style(comma);
+	});
+}
+
+#[test]
+fn custom_digit_group_separators() {
+	let dot = fend_core::DecimalSeparatorStyle::Dot;
+	test_eval_with("1234567.89", "1_234_567.89", |ctx| {
+		ctx.set_digit_group_separator(Some('_'));
+		ctx.set_decimal_separator_style(dot);
+	});
+	test_eval_with("1234567.89", "1,234,567.89", |ctx| {
+		ctx.set_digit_group_separator(Some(','));
+		ctx.set_decimal_separator_style(dot);
+	});
+	test_eval_with("1234567.89", "1'234'567.89", |ctx| {
+		ctx.set_digit_group_separator(Some('\''));
+		ctx.set_decimal_separator_style(dot);
+	});
+	test_eval_with("1234567.89", "1234567.89", |ctx| {
+		ctx.set_digit_group_separator(None);
+		ctx.set_decimal_separator_style(dot);
+	});
+	test_eval_with("1234567,89", "1234567,89", |ctx| {
+		ctx.set_digit_group_separator(None);
+		ctx.set_decimal_separator_style(fend_core::DecimalSeparatorStyle::Comma);
+	});
+}
+
+#[test]
+fn max_output_digits_huge_integers() {
+	test_eval_with("2^1e4", "approx. 1.995063117e3010", |ctx| {
+		ctx.set_max_output_digits(1000)
+	});
+	test_eval_with("2^200", "approx. 1.606938044e60", |ctx| {
+		ctx.set_max_output_digits(20)
+	});
+	test_eval_with("2^200 to 3 sf", "approx. 1.61e60", |ctx| {
+		ctx.set_max_output_digits(20)
+	});
+	test_eval_with("-(10^30) kg", "-1e30 kg", |ctx| {
+		ctx.set_max_output_digits(20)
+	});
+	expect_error_with(
+		"2^200 to hex",
+		"output too long: the result has more than 20 digits",
+		|ctx| ctx.set_max_output_digits(20),
+	);
+}
+
+#[test]
+fn max_output_digits_small_values_are_unaffected() {
+	test_eval_with("2^64", "18446744073709551616", |ctx| {
+		ctx.set_max_output_digits(20)
+	});
+	test_eval_with("1/3", "approx. 0.3333333333", |ctx| {
+		ctx.set_max_output_digits(20)
+	});
+	test_eval_with("1/7 to 5 dp", "approx. 0.14285", |ctx| {
+		ctx.set_max_output_digits(20)
+	});
+	test_eval_with("1234.5 m", "1234.5 m", |ctx| ctx.set_max_output_digits(20));
+	test_eval_with("2i + 3", "3 + 2i", |ctx| ctx.set_max_output_digits(20));
+}
+
+#[test]
+fn max_output_digits_long_fractional_parts() {
+	expect_error_with(
+		"1/7 to 100000000 dp",
+		"output too long: the result has more than 1000 digits",
+		|ctx| ctx.set_max_output_digits(1000),
+	);
+	expect_error_with(
+		"1/3^50 to fraction",
+		"output too long: the result has more than 20 digits",
+		|ctx| ctx.set_max_output_digits(20),
+	);
+	expect_error_with(
+		"pi to 25 sf",
+		"output too long: the result has more than 20 digits",
+		|ctx| ctx.set_max_output_digits(20),
+	);
+}
+
+// Builds a serialized context containing a single variable `a`, whose value
+// is given in fend's serialization format
+fn variable_blob(value: &[u8]) -> Vec<u8> {
+	let mut blob = vec![];
+	blob.extend_from_slice(&1u64.to_be_bytes());
+	blob.extend_from_slice(&1u64.to_be_bytes());
+	blob.push(b'a');
+	blob.extend_from_slice(value);
+	blob
+}
+
+// A serialized number with the given numerator, denominator and base
+fn number_value(num: &[u8], den: &[u8], base: &[u8]) -> Vec<u8> {
+	let small = |n: u64| [&[1], &n.to_be_bytes()[..]].concat();
+	let mut value = vec![0]; // Value::Num
+	value.extend_from_slice(&1u64.to_be_bytes()); // one possible value
+	value.extend_from_slice(&[1, 2]); // positive real part
+	value.extend_from_slice(num);
+	value.extend_from_slice(den);
+	value.extend_from_slice(&[1, 2]); // zero imaginary part
+	value.extend_from_slice(&[small(0), small(1)].concat());
+	value.push(2); // probability of 1
+	value.extend_from_slice(&[small(1), small(1)].concat());
+	value.extend_from_slice(&0u64.to_be_bytes()); // no units
+	value.push(1); // exact
+	value.extend_from_slice(base);
+	value.extend_from_slice(&[7, 1]); // auto formatting, simplifiable
+	value
+}
+
+#[track_caller]
+fn expect_deserialization_error(blob: &[u8]) {
+	let mut context = Context::new();
+	evaluate("b = 5", &mut context).unwrap();
+	assert!(context.deserialize_variables(&mut &blob[..]).is_err());
+	// the existing variables are kept
+	assert_eq!(evaluate("b", &mut context).unwrap().get_main_result(), "5");
+}
+
+#[test]
+fn deserialize_handwritten_number() {
+	let small = |n: u64| [&[1], &n.to_be_bytes()[..]].concat();
+	let base = [&[0, 5, 10][..], &0u64.to_be_bytes()].concat();
+	let blob = variable_blob(&number_value(&small(3), &small(2), &base));
+	let mut context = Context::new();
+	context.deserialize_variables(&mut blob.as_slice()).unwrap();
+	assert_eq!(
+		evaluate("a", &mut context).unwrap().get_main_result(),
+		"1.5"
+	);
+}
+
+#[test]
+fn deserialize_invalid_numbers() {
+	let small = |n: u64| [&[1], &n.to_be_bytes()[..]].concat();
+	// zero denominator
+	expect_deserialization_error(&variable_blob(&number_value(
+		&small(3),
+		&small(0),
+		&[0, 5, 10],
+	)));
+	// big integer without any digits
+	let empty_large = [&[2], &0u64.to_be_bytes()[..]].concat();
+	expect_deserialization_error(&variable_blob(&number_value(
+		&empty_large,
+		&small(1),
+		&[0, 5, 10],
+	)));
+	// bases outside of 2..=36
+	for base in [
+		[0, 4, 0],
+		[0, 4, 1],
+		[0, 5, 37],
+		[0, 5, 255],
+		[2, 5, 10],
+		[0, 6, 0],
+	] {
+		expect_deserialization_error(&variable_blob(&number_value(&small(3), &small(1), &base)));
+	}
+	// too many padding digits
+	let base = [&[0, 5, 2][..], &u64::MAX.to_be_bytes()].concat();
+	expect_deserialization_error(&variable_blob(&number_value(&small(3), &small(1), &base)));
+}
+
+#[test]
+fn deserialize_invalid_dates() {
+	let date = |year: i32, month: u8, day: u8| {
+		let mut value = vec![13]; // Value::Date
+		value.extend_from_slice(&year.to_be_bytes());
+		value.extend_from_slice(&[month, day]);
+		variable_blob(&value)
+	};
+	let mut context = Context::new();
+	context
+		.deserialize_variables(&mut date(2024, 2, 29).as_slice())
+		.unwrap();
+	assert_eq!(
+		evaluate("a", &mut context).unwrap().get_main_result(),
+		"Thursday, 29 February 2024"
+	);
+	expect_deserialization_error(&date(2023, 2, 29));
+	expect_deserialization_error(&date(2024, 4, 31));
+	expect_deserialization_error(&date(2024, 13, 1));
+	expect_deserialization_error(&date(2024, 1, 0));
+	expect_deserialization_error(&date(0, 1, 1));
+}
+
+#[test]
+fn deserialize_deeply_nested_expression() {
+	let nested = |depth: usize| {
+		let mut value = vec![6]; // Value::Fn
+		value.extend_from_slice(&1u64.to_be_bytes());
+		value.push(b'x');
+		value.extend(std::iter::repeat_n(2, depth)); // Expr::Parens
+		value.push(1); // Expr::Ident
+		value.extend_from_slice(&1u64.to_be_bytes());
+		value.push(b'x');
+		value.push(0); // no scope
+		variable_blob(&value)
+	};
+	let mut context = Context::new();
+	context
+		.deserialize_variables(&mut nested(10).as_slice())
+		.unwrap();
+	assert_eq!(
+		evaluate("a 4", &mut context).unwrap().get_main_result(),
+		"4"
+	);
+	// deserializing deeply nested values needs more stack space in debug
+	// builds than the default for test threads
+	std::thread::Builder::new()
+		.stack_size(16 * 1024 * 1024)
+		.spawn(move || {
+			expect_deserialization_error(&nested(100_000));
+		})
+		.unwrap()
+		.join()
+		.unwrap();
+}
+
+#[test]
+fn deserialize_garbage() {
+	expect_deserialization_error(&[]);
+	expect_deserialization_error(&[1, 2, 3]);
+	expect_deserialization_error(&variable_blob(&[]));
+	expect_deserialization_error(&variable_blob(&[200]));
+	expect_deserialization_error(&variable_blob(&[1, 0xff]));
+	let mut seed: u32 = 1;
+	for len in 0..200 {
+		let garbage: Vec<u8> = (0..len)
+			.map(|_| {
+				seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+				seed.to_be_bytes()[0]
+			})
+			.collect();
+		// random bytes may happen to be valid, but they must never panic
+		let _ = Context::new().deserialize_variables(&mut garbage.as_slice());
+		let _ = Context::new().deserialize_variables(&mut variable_blob(&garbage).as_slice());
+	}
+}
+
+#[test]
+fn serialize_all_builtin_functions() {
+	for f in ["mean", "arg", "floor", "ceil", "round", "median", "sin"] {
+		let mut context = Context::new();
+		evaluate(&format!("f = {f}"), &mut context).unwrap();
+		test_serialization_roundtrip(&mut context);
+	}
+}
+
+#[test]
+fn max_variables_rejects_new_variables() {
+	let mut context = Context::new();
+	context.set_max_variables(2);
+	evaluate("a = 1", &mut context).unwrap();
+	evaluate("b = 2", &mut context).unwrap();
+	assert_eq!(
+		evaluate("third = 3", &mut context),
+		Err("cannot define more than 2 variables".to_string())
+	);
+	assert_eq!(
+		evaluate("third", &mut context),
+		Err("unknown identifier 'third'".to_string())
+	);
+	assert_eq!(
+		evaluate("a + b", &mut context).unwrap().get_main_result(),
+		"3"
+	);
+}
+
+#[test]
+fn max_variables_allows_reassignment() {
+	let mut context = Context::new();
+	context.set_max_variables(1);
+	evaluate("a = 1", &mut context).unwrap();
+	evaluate("a = 5", &mut context).unwrap();
+	assert_eq!(evaluate("a", &mut context).unwrap().get_main_result(), "5");
+	// `_` and `ans` are always updated
+	assert_eq!(
+		evaluate("ans * 2", &mut context).unwrap().get_main_result(),
+		"10"
+	);
+	assert_eq!(
+		evaluate("_ + 1", &mut context).unwrap().get_main_result(),
+		"11"
+	);
+}
+
+#[test]
+fn max_variables_zero() {
+	let mut context = Context::new();
+	context.set_max_variables(0);
+	assert_eq!(
+		evaluate("a = 1", &mut context),
+		Err("cannot define more than 0 variables".to_string())
+	);
+	assert_eq!(
+		evaluate("2 + 2", &mut context).unwrap().get_main_result(),
+		"4"
+	);
+}
+
+#[track_caller]
+fn expect_error_kind(input: &str, kind: fend_core::ErrorKind) {
+	let mut ctx = Context::new();
+	let err = fend_core::evaluate_with_interrupt_structured(
+		input,
+		&mut ctx,
+		&Timeout(std::time::Instant::now()),
+	)
+	.unwrap_err();
+	assert_eq!(err.kind(), kind);
+	assert_eq!(err.message(), evaluate(input, &mut ctx).unwrap_err());
+}
+
+#[test]
+fn structured_error_kinds() {
+	use fend_core::ErrorKind;
+	expect_error_kind("1/0", ErrorKind::DivideByZero);
+	expect_error_kind("5 mod 0", ErrorKind::DivideByZero);
+	expect_error_kind("nonexistentvariable", ErrorKind::UnknownIdentifier);
+	expect_error_kind("1 kg to m", ErrorKind::IncompatibleUnits);
+	expect_error_kind("1 +", ErrorKind::Parse);
+	expect_error_kind("\"abc", ErrorKind::Parse);
+	expect_error_kind("sqrt(-1, 2)", ErrorKind::Other);
+}
+
+#[test]
+fn structured_error_success() {
+	let mut ctx = Context::new();
+	let res = fend_core::evaluate_with_interrupt_structured(
+		"1 + 1",
+		&mut ctx,
+		&Timeout(std::time::Instant::now()),
+	)
+	.unwrap();
+	assert_eq!(res.get_main_result(), "2");
+}
+
+#[test]
+fn base_conversion_with_digits() {
+	test_eval_simple("5 to binary with 8 digits", "00000101");
+	test_eval("5 to binary with 3 digits", "101");
+	test_eval_simple("0 to binary with 4 digits", "0000");
+	test_eval_simple("-5 to binary with 8 digits", "-00000101");
+	test_eval_simple("5 to binary grouped with 12 digits", "0000_0000_0101");
+	test_eval_simple("255 to hex with 4 digits", "00ff");
+	test_eval_simple("0xff to hex with 8 digits", "000000ff");
+	test_eval_simple("255 to base 16 with (2 * 3) digits", "0000ff");
+	test_eval_simple("5.5 to binary with 8 digits", "00000101.1");
+}
+
+#[test]
+fn base_conversion_with_digits_widens() {
+	test_eval_simple("300 to hex with 2 digits", "12c");
+	test_eval("5 to binary with 1 digit", "101");
+}
+
+#[test]
+fn base_conversion_with_invalid_digits() {
+	expect_error(
+		"5 to binary with 0 digits",
+		Some("0 must lie in the interval [1, 1000]"),
+	);
+	expect_error(
+		"5 to kg with 2 digits",
+		Some("a number of digits can only be specified for bases, e.g. `to binary with 8 digits`"),
+	);
+}
+
+#[test]
+fn base_conversion_with_digits_serialization() {
+	let mut ctx = Context::new();
+	evaluate("a = 5 to binary with 8 digits", &mut ctx).unwrap();
+	test_serialization_roundtrip(&mut ctx);
+	assert_eq!(
+		evaluate("a", &mut ctx).unwrap().get_main_result(),
+		"00000101"
+	);
+}
+
+#[track_caller]
+fn expect_error_span(input: &str, span: Option<(usize, usize)>) {
+	let mut ctx = Context::new();
+	let err = fend_core::evaluate_with_interrupt_structured(
+		input,
+		&mut ctx,
+		&Timeout(std::time::Instant::now()),
+	)
+	.unwrap_err();
+	assert_eq!(err.span(), span);
+}
+
+#[test]
+fn error_spans() {
+	expect_error_span("sqrt(aiusbdla)", Some((5, 13)));
+	expect_error_span("1 kg + foo kg", Some((7, 10)));
+	expect_error_span("@debug foo", Some((7, 10)));
+	expect_error_span("\"abc", Some((0, 1)));
+	expect_error_span("5 ]", Some((2, 3)));
+	expect_error_span("0x", Some((0, 1)));
+	expect_error_span("1 then 2", Some((2, 8)));
+	expect_error_span("1/0", None);
+	expect_error_span("1 kg to m", None);
+}
+
+#[test]
+fn all_units_metadata() {
+	let units: Vec<_> = fend_core::all_units().collect();
+	let find = |name: &str| {
+		units
+			.iter()
+			.find(|u| u.singular_name() == name)
+			.unwrap_or_else(|| panic!("missing unit {name}"))
+	};
+
+	let kilogram = find("kilogram");
+	assert_eq!(kilogram.plural_name(), "kilograms");
+	assert_eq!(kilogram.dimension(), "kilogram");
+	assert!(kilogram.is_prefixable());
+
+	let meter = find("meter");
+	assert_eq!(meter.plural_name(), "meters");
+	assert_eq!(meter.dimension(), "meter");
+
+	let second = find("second");
+	assert_eq!(second.plural_name(), "seconds");
+	assert_eq!(second.dimension(), "second");
+
+	let g = find("g");
+	assert_eq!(g.plural_name(), "g");
+	assert_eq!(g.dimension(), "kilogram");
+	assert!(g.is_prefixable());
+
+	assert_eq!(find("newton").dimension(), "kilogram meter second^-2");
+	assert_eq!(find("mph").dimension(), "meter second^-1");
+	assert_eq!(find("celsius").dimension(), "kelvin");
+	assert_eq!(find("percent").dimension(), "");
+	assert!(!find("kilo").is_prefixable());
+	assert_eq!(find("dollar").dimension(), "BASE_CURRENCY");
+}
+
+#[derive(Debug, PartialEq)]
+enum Json {
+	Bool(bool),
+	String(String),
+	Array(Vec<Json>),
+	Object(Vec<(String, Json)>),
+}
+
+impl Json {
+	fn get(&self, key: &str) -> &Self {
+		let Self::Object(fields) = self else {
+			panic!("expected an object");
+		};
+		&fields.iter().find(|(k, _)| k == key).unwrap().1
+	}
+}
+
+// a minimal JSON parser, supporting only what `evaluate_to_json` can output
+fn parse_json(input: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Json {
+	match input.next().unwrap() {
+		't' => {
+			assert_eq!(input.take(3).collect::<String>(), "rue");
+			Json::Bool(true)
+		}
+		'f' => {
+			assert_eq!(input.take(4).collect::<String>(), "alse");
+			Json::Bool(false)
+		}
+		'"' => {
+			let mut s = String::new();
+			loop {
+				match input.next().unwrap() {
+					'"' => break,
+					'\\' => match input.next().unwrap() {
+						'n' => s.push('\n'),
+						'u' => {
+							let code: String = input.take(4).collect();
+							s.push(
+								char::from_u32(u32::from_str_radix(&code, 16).unwrap()).unwrap(),
+							);
+						}
+						ch => s.push(ch),
+					},
+					ch => s.push(ch),
+				}
+			}
+			Json::String(s)
+		}
+		'[' => {
+			let mut items = vec![];
+			while input.peek() != Some(&']') {
+				items.push(parse_json(input));
+				if input.peek() == Some(&',') {
+					input.next();
+				}
+			}
+			input.next();
+			Json::Array(items)
+		}
+		'{' => {
+			let mut fields = vec![];
+			while input.peek() != Some(&'}') {
+				let Json::String(key) = parse_json(input) else {
+					panic!("expected a key");
+				};
+				assert_eq!(input.next(), Some(':'));
+				fields.push((key, parse_json(input)));
+				if input.peek() == Some(&',') {
+					input.next();
+				}
+			}
+			input.next();
+			Json::Object(fields)
+		}
+		ch => panic!("unexpected character {ch}"),
+	}
+}
+
+fn evaluate_json(input: &str) -> Json {
+	let mut ctx = Context::new();
+	let json = fend_core::evaluate_to_json(input, &mut ctx, &Timeout(std::time::Instant::now()));
+	let mut chars = json.chars().peekable();
+	let res = parse_json(&mut chars);
+	assert_eq!(chars.next(), None);
+	res
+}
+
+#[test]
+fn evaluate_to_json_result() {
+	let span = |kind: &str, string: &str| {
+		Json::Object(vec![
+			("kind".to_string(), Json::String(kind.to_string())),
+			("string".to_string(), Json::String(string.to_string())),
+		])
+	};
+	let res = evaluate_json("1 km to m");
+	assert_eq!(res.get("ok"), &Json::Bool(true));
+	assert_eq!(res.get("result"), &Json::String("1000 m".to_string()));
+	assert_eq!(res.get("approx"), &Json::Bool(false));
+	assert_eq!(res.get("empty"), &Json::Bool(false));
+	assert_eq!(
+		res.get("spans"),
+		&Json::Array(vec![span("number", "1000"), span("ident", " m")])
+	);
+
+	let res = evaluate_json("pi");
+	assert_eq!(res.get("approx"), &Json::Bool(true));
+	let Json::Array(spans) = res.get("spans") else {
+		panic!("expected an array");
+	};
+	assert_eq!(spans[0], span("ident", "approx. "));
+
+	let res = evaluate_json("\"a\\\"b\\n\"");
+	assert_eq!(res.get("result"), &Json::String("a\"b\n".to_string()));
+	assert_eq!(
+		res.get("spans"),
+		&Json::Array(vec![span("string", "a\"b\n")])
+	);
+
+	let res = evaluate_json("");
+	assert_eq!(res.get("empty"), &Json::Bool(true));
+	assert_eq!(res.get("spans"), &Json::Array(vec![]));
+}
+
+#[test]
+fn evaluate_to_json_error() {
+	let res = evaluate_json("1/0");
+	assert_eq!(res.get("ok"), &Json::Bool(false));
+	assert_eq!(
+		res.get("message"),
+		&Json::String("division by zero".to_string())
+	);
+}
+
+#[test]
+fn all_functions_are_sorted_and_evaluate() {
+	let functions: Vec<_> = fend_core::all_functions().collect();
+	for name in ["sin", "sqrt", "gcd", "log10", "abs", "exp"] {
+		assert!(functions.contains(&name), "missing function {name}");
+	}
+	assert!(functions.is_sorted());
+	let mut ctx = Context::new();
+	for name in functions {
+		let res = evaluate(name, &mut ctx).unwrap_or_else(|e| panic!("{name}: {e}"));
+		assert!(
+			!res.get_main_result_spans()
+				.any(|s| s.kind() == fend_core::SpanKind::Number),
+			"{name} is not a function"
+		);
+	}
+}
+
+#[test]
+fn all_constants_are_sorted_and_evaluate() {
+	let constants: Vec<_> = fend_core::all_constants().collect();
+	for name in ["pi", "e", "phi", "planck", "c", "avogadro"] {
+		assert!(constants.contains(&name), "missing constant {name}");
+	}
+	assert!(constants.is_sorted());
+	let mut ctx = Context::new();
+	for name in constants {
+		let res = evaluate(name, &mut ctx).unwrap_or_else(|e| panic!("{name}: {e}"));
+		assert!(
+			res.get_main_result_spans()
+				.any(|s| s.kind() == fend_core::SpanKind::Number),
+			"{name} is not a number"
+		);
+	}
+}
+
+#[test]
+fn exact_conversions() {
+	test_eval("1 inch to cm exact", "2.54 cm");
+	test_eval("1 inch to m exact", "0.0254 m");
+	test_eval("1 mile to km exact", "1.609344 km");
+	test_eval("1 foot to inches exact", "12 inches");
+}
+
+#[test]
+fn inexact_conversions() {
+	expect_error("1 m to inch exact", Some("conversion is not exact"));
+	expect_error("1/3 m to cm exact", Some("conversion is not exact"));
+	expect_error("pi m to cm exact", Some("conversion is not exact"));
+	expect_error("approx. 1 m to cm exact", Some("conversion is not exact"));
+}
+
+#[test]
+fn describe_units() {
+	let newton = fend_core::describe_unit("newton").unwrap();
+	assert_eq!(newton.base_units(), "kg m s^-2");
+	assert_eq!(newton.scale(), "1");
+	let watt = fend_core::describe_unit("watt").unwrap();
+	assert_eq!(watt.base_units(), "kg m^2 s^-3");
+	let mile = fend_core::describe_unit("mile").unwrap();
+	assert_eq!(mile.base_units(), "m");
+	assert_eq!(mile.scale(), "1609.344");
+	let km = fend_core::describe_unit("km").unwrap();
+	assert_eq!(km.base_units(), "m");
+	assert_eq!(km.scale(), "1000");
+	assert_eq!(km.offset(), "0");
+	let celsius = fend_core::describe_unit("celsius").unwrap();
+	assert_eq!(celsius.base_units(), "K");
+	assert_eq!(celsius.scale(), "1");
+	assert_eq!(celsius.offset(), "273.15");
+	let fahrenheit = fend_core::describe_unit("°F").unwrap();
+	assert_eq!(fahrenheit.scale(), "5/9");
+	assert_eq!(fahrenheit.offset(), "255 67/180");
+	assert_eq!(
+		fend_core::describe_unit("percent").unwrap().base_units(),
+		""
+	);
+}
+
+#[test]
+fn describe_unknown_unit() {
+	assert_eq!(fend_core::describe_unit("notaunit"), None);
+	assert_eq!(fend_core::describe_unit(""), None);
+}
+
+#[test]
+fn show_plus_sign() {
+	let mut ctx = Context::new();
+	ctx.set_show_plus_sign(true);
+	assert_eq!(eval_in("5", &mut ctx).unwrap(), "+5");
+	assert_eq!(eval_in("-5", &mut ctx).unwrap(), "-5");
+	assert_eq!(eval_in("0", &mut ctx).unwrap(), "0");
+	assert_eq!(eval_in("5 kg", &mut ctx).unwrap(), "+5 kg");
+	assert_eq!(eval_in("1/2 to fraction", &mut ctx).unwrap(), "+1/2");
+	assert_eq!(eval_in("3 - 2i", &mut ctx).unwrap(), "+3 - 2i");
+	assert_eq!(eval_in("1e30 to scientific", &mut ctx).unwrap(), "+1e30");
+	assert_eq!(eval_in("+5", &mut ctx).unwrap(), "+5");
+}
+
+#[test]
+fn define_custom_unit_with_unicode_name() {
+	let mut ctx = Context::new();
+	ctx.define_custom_unit_v2(
+		"ångström2",
+		"ångström2s",
+		"0.2 nm",
+		&fend_core::CustomUnitAttribute::None,
+	)
+	.unwrap();
+	ctx.define_custom_unit_v2(
+		"Smoot",
+		"",
+		"1.7018 m",
+		&fend_core::CustomUnitAttribute::None,
+	)
+	.unwrap();
+	assert_eq!(eval_in("3 ångström2s to nm", &mut ctx).unwrap(), "0.6 nm");
+	assert_eq!(eval_in("10 Smoot to m", &mut ctx).unwrap(), "17.018 m");
+}
+
+#[test]
+fn define_custom_unit_with_invalid_name() {
+	let mut ctx = Context::new();
+	for name in ["m²", "2x", "to", "two words", ""] {
+		let err = ctx
+			.define_custom_unit_v2(name, "", "1 m", &fend_core::CustomUnitAttribute::None)
+			.unwrap_err();
+		assert_eq!(err.kind(), fend_core::ErrorKind::Parse);
+		assert_eq!(err.message(), format!("`{name}` is not a valid unit name"));
+	}
+	assert!(ctx
+		.define_custom_unit_v2(
+			"foo",
+			"foo bars",
+			"1 m",
+			&fend_core::CustomUnitAttribute::None
+		)
+		.is_err());
+	assert!(evaluate("1 foo", &mut ctx).is_err());
+}
+
+#[test]
+fn negative_style() {
+	let mut ctx = Context::new();
+	ctx.set_exchange_rates(
+		[("USD".to_string(), 1.0), ("EUR".to_string(), 0.5)]
+			.into_iter()
+			.collect(),
+	);
+	assert_eq!(eval_in("-5", &mut ctx).unwrap(), "-5");
+	assert_eq!(eval_in("$5 - $10", &mut ctx).unwrap(), "$-5");
+	assert_eq!(eval_in("-5 EUR", &mut ctx).unwrap(), "-5 EUR");
+
+	ctx.set_negative_style(fend_core::NegativeStyle::Parentheses);
+	assert_eq!(eval_in("-5", &mut ctx).unwrap(), "(5)");
+	assert_eq!(eval_in("5", &mut ctx).unwrap(), "5");
+	assert_eq!(eval_in("-0", &mut ctx).unwrap(), "0");
+	assert_eq!(eval_in("-1 kg", &mut ctx).unwrap(), "(1 kg)");
+	assert_eq!(eval_in("-1/2 to fraction", &mut ctx).unwrap(), "(1/2)");
+	assert_eq!(eval_in("-1/3", &mut ctx).unwrap(), "approx. (0.3333333333)");
+	assert_eq!(eval_in("$5 - $10", &mut ctx).unwrap(), "($5)");
+	assert_eq!(eval_in("-5 EUR", &mut ctx).unwrap(), "(5 EUR)");
+	assert_eq!(eval_in("-3 - 2i", &mut ctx).unwrap(), "-3 - 2i");
+
+	// parentheses don't make numbers negative, so this doesn't round-trip
+	assert_eq!(eval_in("(5)", &mut ctx).unwrap(), "5");
+
+	ctx.set_show_plus_sign(true);
+	assert_eq!(eval_in("-5", &mut ctx).unwrap(), "(5)");
+	assert_eq!(eval_in("5", &mut ctx).unwrap(), "+5");
+}
+
+#[test]
+fn remove_variable() {
+	let mut ctx = Context::new();
+	evaluate("bar = 2; foo = 5", &mut ctx).unwrap();
+	assert_eq!(ctx.variable_names(), vec!["_", "ans", "bar", "foo"]);
+	assert!(ctx.remove_variable("foo"));
+	assert!(!ctx.remove_variable("foo"));
+	assert!(!ctx.remove_variable("baz"));
+	assert_eq!(ctx.variable_names(), vec!["_", "ans", "bar"]);
+	assert_eq!(
+		evaluate("bar * 3", &mut ctx).unwrap().get_main_result(),
+		"6"
+	);
+	assert_eq!(
+		evaluate("foo", &mut ctx),
+		Err("unknown identifier 'foo'".to_string())
+	);
+}
+
+#[test]
+fn unset_variables() {
+	let mut ctx = Context::new();
+	assert_eq!(
+		eval_in("foo = 5; unset foo; foo", &mut ctx),
+		Err("unknown identifier 'foo'".to_string())
+	);
+	assert_eq!(eval_in("foo = 5; baz = 6", &mut ctx), Ok("6".to_string()));
+	assert_eq!(eval_in("unset foo", &mut ctx), Ok("()".to_string()));
+	assert_eq!(eval_in("baz", &mut ctx), Ok("6".to_string()));
+	assert_eq!(
+		eval_in("foo", &mut ctx),
+		Err("unknown identifier 'foo'".to_string())
+	);
+	assert_eq!(
+		eval_in("unset foo; unset foo", &mut ctx),
+		Ok("()".to_string())
+	);
+	assert_eq!(
+		eval_in("foo = 7; unset foo; foo = 8; foo", &mut ctx),
+		Ok("8".to_string())
+	);
+	assert_eq!(eval_in("unset baz; foo", &mut ctx), Ok("8".to_string()));
+	assert_eq!(
+		eval_in("baz", &mut ctx),
+		Err("unknown identifier 'baz'".to_string())
+	);
+}
+
+#[test]
+fn unset_requires_a_single_identifier() {
+	expect_error("unset", Some("unknown identifier 'unset'"));
+	expect_error("unset 5", None);
+	expect_error("unset foo bar", None);
+}
+
+#[test]
+fn scientific_notation_in_other_bases_includes_prefix() {
+	test_eval_simple("256 to binary to scientific", "0b1e1000");
+	test_eval_simple("0b1e1000 to sci", "0b1e1000");
+	test_eval_simple("0b11e100 to sci", "0b1.1e101");
+	test_eval_simple("0b1e-10 to sci", "0b1e-10");
+	test_eval_simple("100 to base 3 to sci", "3#1.0201e11");
+	test_eval_simple("0o7e3 + 1 to sci", "0o7.001e3");
+	expect_error(
+		"0x123 to sci",
+		Some("scientific notation is only supported in base 10 and below"),
+	);
+}
+
+#[test]
+fn binary_scientific_notation_round_trips() {
+	let mut ctx = Context::new();
+	let res = evaluate("(0 + 0b1e100) to binary to sci", &mut ctx).unwrap();
+	assert_eq!(res.get_main_result(), "0b1e100");
+	let res = evaluate(&format!("{} == 16", res.get_main_result()), &mut ctx).unwrap();
+	assert_eq!(res.get_main_result(), "true");
+}
+
+#[test]
+fn auto_scientific_threshold_in_binary() {
+	let mut ctx = Context::new();
+	ctx.set_auto_scientific_threshold(10);
+	let res = evaluate("2^40 to binary", &mut ctx).unwrap();
+	assert_eq!(res.get_main_result(), "0b1e101000");
+}
+
+#[test]
+fn auto_base_prefix() {
+	let mut ctx = Context::new();
+	assert_eq!(eval_in("0xff", &mut ctx).unwrap(), "0xff");
+	assert_eq!(eval_in("0o17", &mut ctx).unwrap(), "0o17");
+	assert_eq!(eval_in("0b101 + 1", &mut ctx).unwrap(), "0b110");
+	assert_eq!(eval_in("16#ff", &mut ctx).unwrap(), "16#ff");
+
+	ctx.set_auto_base_prefix(false);
+	assert_eq!(eval_in("0xff", &mut ctx).unwrap(), "ff");
+	assert_eq!(eval_in("0o17", &mut ctx).unwrap(), "17");
+	assert_eq!(eval_in("0b101 + 1", &mut ctx).unwrap(), "110");
+	assert_eq!(eval_in("16#ff", &mut ctx).unwrap(), "ff");
+	assert_eq!(eval_in("255 to hex", &mut ctx).unwrap(), "ff");
+	assert_eq!(eval_in("0xff kg", &mut ctx).unwrap(), "ff kg");
+	assert_eq!(eval_in("0b1e1000 to sci", &mut ctx).unwrap(), "0b1e1000");
+}
+
+#[test]
+fn to_duration() {
+	test_eval_simple("5000 seconds to duration", "1 hour 23 minutes 20 seconds");
+	test_eval("1 s to duration", "1 second");
+	test_eval("0 s to duration", "0 seconds");
+	test_eval_simple("100 minutes to duration", "1 hour 40 minutes");
+	test_eval("2 weeks to duration", "14 days");
+	test_eval_simple("90061.5 s to duration", "1 day 1 hour 1 minute 1.5 seconds");
+	test_eval_simple("-3700 s to duration", "-1 hour 1 minute 40 seconds");
+	test_eval("730 days to duration", "2 years");
+	test_eval_simple(
+		"1e12 s to duration",
+		"31709 years 289 days 1 hour 46 minutes 40 seconds",
+	);
+	test_eval("1/3 s to duration", "approx. 0.3333333333 seconds");
+}
+
+#[test]
+fn to_duration_requires_a_time() {
+	expect_error(
+		"5 kg to duration",
+		Some("cannot convert from kg to seconds: units 'kilogram' and 'second' are incompatible"),
+	);
+	expect_error("5 to duration", None);
+}
+
+#[test]
+fn byteswap() {
+	test_eval("byteswap(0x1234, 2)", "0x3412");
+	test_eval("byteswap(0x12, 2)", "0x1200");
+	test_eval("byteswap(0x12345678, 4)", "0x78563412");
+	test_eval("byteswap(0x0102030405060708, 8)", "0x807060504030201");
+	test_eval("byteswap(0x8070605040302010, 8)", "0x1020304050607080");
+	test_eval("byteswap(258, 2)", "513");
+}
+
+#[test]
+fn byteswap_errors() {
+	expect_error(
+		"byteswap(0x123456, 2)",
+		Some("1193046 must lie in the interval [0, 65535]"),
+	);
+	expect_error(
+		"byteswap(1, 0)",
+		Some("0 must lie in the interval [1, 1024]"),
+	);
+	expect_error("byteswap(1.5, 2)", Some("1.5 is not an integer"));
+	expect_error("byteswap 5", Some("'byteswap' does not accept 1 argument"));
+}
+
+#[test]
+fn iso_8601_durations() {
+	test_eval("PT1H30M to minutes", "90 minutes");
+	test_eval("PT45S", "45 seconds");
+	test_eval("P2W", "2 weeks");
+	test_eval("P1D", "1 day");
+	test_eval("P1DT2H to hours", "26 hours");
+	test_eval("P1W2D to days", "9 days");
+	test_eval("PT10M to seconds", "600 seconds");
+	test_eval("PT1H + 30 minutes", "1.5 hours");
+	test_eval("2 * PT15M to minutes", "30 minutes");
+}
+
+#[test]
+fn invalid_iso_8601_durations() {
+	for input in [
+		"PT1H1H", "PT30M1H", "P1H", "P1Y", "P1M", "PTH", "P1DT", "P2D1W",
+	] {
+		expect_error(
+			input,
+			Some(&format!(
+				"'{input}' is not a valid ISO 8601 duration (only weeks, days, hours, minutes and seconds are supported)"
+			)),
+		);
+	}
+	expect_error_kind("PT1H1H", fend_core::ErrorKind::Parse);
+	test_eval("PT", "1 pt");
+	test_eval("P", "1 P");
+}
+
+#[test]
+fn preview_assignments() {
+	let mut ctx = Context::new();
+	assert_eq!(preview_in("foo = 5", &mut ctx), "5");
+	assert_eq!(preview_in("foo = 2; 5foo", &mut ctx), "10");
+	assert!(!ctx.variable_names().contains(&"foo".to_string()));
+
+	ctx.set_preview_disable_assignments(true);
+	assert_eq!(preview_in("foo = 5", &mut ctx), "");
+	assert_eq!(preview_in("@debug foo = 5", &mut ctx), "");
+	assert_eq!(preview_in("foo = 2; baz = 3", &mut ctx), "");
+	assert_eq!(preview_in("foo = 2; 5foo", &mut ctx), "10");
+	assert_eq!(preview_in("foo = 2; baz = foo + 1; baz * 2", &mut ctx), "6");
+	assert_eq!(preview_in("2 + 3", &mut ctx), "5");
+	assert_eq!(preview_in("foo = ", &mut ctx), "");
+	assert!(!ctx.variable_names().contains(&"foo".to_string()));
+	assert_eq!(
+		evaluate("foo = 5", &mut ctx).unwrap().get_main_result(),
+		"5"
+	);
+}
+
+#[test]
+fn add_weeks_months_and_years_to_date() {
+	test_eval_simple("@2024-01-01 + 1 week", "Monday, 8 January 2024");
+	test_eval_simple("@2024-01-31 + 2 months", "Sunday, 31 March 2024");
+	test_eval_simple("@2024-02-29 + 4 years", "Tuesday, 29 February 2028");
+	expect_error(
+		"@2024-02-29 + 1 year",
+		Some("February 29, 2025 does not exist, did you mean Friday, 28 February 2025 or Saturday, 1 March 2025?"),
+	);
+}
+
+#[test]
+fn relative_dates_without_current_time() {
+	expect_error("next friday", Some("unable to get the current date"));
+	expect_error(
+		"days until @2024-12-25",
+		Some("unable to get the current date"),
+	);
+	expect_error("days until 5", Some("expected a date"));
+	test_eval("days", "1 day");
+}
+
+#[test]
+fn next_weekday() {
+	let mut context = Context::new();
+	// Sunday, 4 April 2021
+	context.set_current_time_v1(1617517099000, 0);
+	assert_eq!(
+		eval_in("next monday", &mut context).unwrap(),
+		"Monday, 5 April 2021"
+	);
+	assert_eq!(
+		eval_in("next Friday", &mut context).unwrap(),
+		"Friday, 9 April 2021"
+	);
+	assert_eq!(
+		eval_in("next sunday", &mut context).unwrap(),
+		"Sunday, 11 April 2021"
+	);
+	assert_eq!(
+		eval_in("next friday + 1 week", &mut context).unwrap(),
+		"Friday, 16 April 2021"
+	);
+}
+
+#[test]
+fn days_until() {
+	let mut context = Context::new();
+	// Sunday, 4 April 2021
+	context.set_current_time_v1(1617517099000, 0);
+	assert_eq!(
+		eval_in("days until @2021-12-25", &mut context).unwrap(),
+		"265 days"
+	);
+	assert_eq!(
+		eval_in("days until @2021-04-04", &mut context).unwrap(),
+		"0 days"
+	);
+	assert_eq!(
+		eval_in("days until @2021-04-01", &mut context).unwrap(),
+		"-3 days"
+	);
+	assert_eq!(
+		eval_in("days until @1970-01-01", &mut context).unwrap(),
+		"-18721 days"
+	);
+	assert_eq!(
+		eval_in("days until next friday", &mut context).unwrap(),
+		"5 days"
+	);
+	assert_eq!(
+		eval_in("days until @2021-04-25 to weeks", &mut context).unwrap(),
+		"3 weeks"
+	);
+}
+
+#[test]
+fn conversion_showing_work() {
+	let work = |input| {
+		let mut context = Context::new();
+		let res = evaluate(input, &mut context).unwrap();
+		(
+			res.get_main_result().to_string(),
+			res.get_other_info().map(str::to_string).collect::<Vec<_>>(),
+		)
+	};
+	assert_eq!(
+		work("60 mi/h to m/s showing work"),
+		(
+			"26.8224 m / s".to_string(),
+			vec!["60 mi / h × 1609.344 m/mi × 1/3600 h/s = 26.8224 m / s".to_string()]
+		)
+	);
+	assert_eq!(
+		work("60 mph to m/s showing work").1,
+		vec!["60 mph × 0.44704 (m s^-1)/mph = 26.8224 m / s"]
+	);
+	assert_eq!(
+		work("1 mile to km showing work").1,
+		vec!["1 mile × 1609.344 m/mile × 1/1000 km/m = 1.609344 km"]
+	);
+	assert_eq!(
+		work("2 m^2 to ft^2 showing work").1,
+		vec!["2 m^2 × (0.3048 m/ft)^-2 = approx. 21.5278208334 ft^2"]
+	);
+	// absolute temperature conversions aren't a simple product
+	assert_eq!(
+		work("100 °C to °F showing work"),
+		("212 °F".to_string(), vec![])
+	);
+	assert!(work("60 mph to m/s").1.is_empty());
+}
+
+#[test]
+fn custom_separators() {
+	let mut context = Context::new();
+	context.set_separators('·', ' ').unwrap();
+	assert_eq!(eval_in("1 234·5", &mut context).unwrap(), "1 234·5");
+	assert_eq!(
+		eval_in("1234567·891", &mut context).unwrap(),
+		"1 234 567·891"
+	);
+	assert_eq!(eval_in("1 234·5 + 1", &mut context).unwrap(), "1 235·5");
+	assert_eq!(eval_in("·5 * 3", &mut context).unwrap(), "1·5");
+	assert_eq!(eval_in("2·5 kg to g", &mut context).unwrap(), "2 500 g");
+	assert_eq!(eval_in("5 m", &mut context).unwrap(), "5 m");
+	assert_eq!(eval_in("gcd(12, 18)", &mut context).unwrap(), "6");
+
+	let mut context = Context::new();
+	context.set_separators(',', '.').unwrap();
+	assert_eq!(
+		evaluate("1.234,5 * 2", &mut context)
+			.unwrap()
+			.get_main_result(),
+		"2.469"
+	);
+}
+
+#[test]
+fn invalid_custom_separators() {
+	let set_separators = |decimal, group| {
+		Context::new()
+			.set_separators(decimal, group)
+			.map_err(|e| e.to_string())
+	};
+	assert_eq!(
+		set_separators('.', '.'),
+		Err("the decimal and group separators must be different (both are '.')".to_string())
+	);
+	assert_eq!(
+		set_separators('1', ','),
+		Err("'1' cannot be used as a separator".to_string())
+	);
+	assert_eq!(
+		set_separators('.', 'a'),
+		Err("'a' cannot be used as a separator".to_string())
+	);
+	assert_eq!(
+		set_separators('+', ' '),
+		Err("'+' cannot be used as a separator".to_string())
+	);
+	assert_eq!(
+		set_separators(' ', ','),
+		Err("' ' cannot be used as a separator".to_string())
+	);
+	assert_eq!(set_separators('.', '\u{a0}'), Ok(()));
+}
+
+#[test]
+fn now() {
+	let mut context = Context::new();
+	// Sunday, 4 April 2021 06:18:19 UTC
+	context.set_current_time_v1(1617517099000, 0);
+	assert_eq!(
+		eval_in("now", &mut context).unwrap(),
+		"Sunday, 4 April 2021 06:18:19"
+	);
+	assert_eq!(
+		eval_in("now + 90 minutes", &mut context).unwrap(),
+		"Sunday, 4 April 2021 07:48:19"
+	);
+	assert_eq!(
+		eval_in("now - 7 hours", &mut context).unwrap(),
+		"Saturday, 3 April 2021 23:18:19"
+	);
+	assert_eq!(
+		eval_in("now + 18 hours", &mut context).unwrap(),
+		"Monday, 5 April 2021 00:18:19"
+	);
+	assert_eq!(
+		eval_in("now + 1 month + 1 hour", &mut context).unwrap(),
+		"Tuesday, 4 May 2021 07:18:19"
+	);
+	assert_eq!(
+		eval_in("date of (now + 2 hours)", &mut context).unwrap(),
+		"Sunday, 4 April 2021"
+	);
+	assert_eq!(
+		eval_in("day_of_week of (now + 1 day)", &mut context).unwrap(),
+		"Monday"
+	);
+}
+
+#[test]
+fn now_with_tz() {
+	let mut context = Context::new();
+	context.set_current_time_v1(1617517099000, -7 * 3600);
+	assert_eq!(
+		evaluate("now", &mut context).unwrap().get_main_result(),
+		"Saturday, 3 April 2021 23:18:19"
+	);
+	context.set_current_time_v1(1617517099000, 5 * 3600 + 30 * 60);
+	assert_eq!(
+		evaluate("now", &mut context).unwrap().get_main_result(),
+		"Sunday, 4 April 2021 11:48:19"
+	);
+}
+
+#[test]
+fn now_errors() {
+	expect_error("now", Some("unable to get the current date"));
+	let mut context = Context::new();
+	context.set_current_time_v1(1617517099000, 0);
+	assert_eq!(
+		evaluate("now + 1 kg", &mut context).unwrap_err(),
+		"cannot convert from kg to seconds: units 'kilogram' and 'second' are incompatible"
+	);
+	assert_eq!(
+		evaluate("now + 0.5 s", &mut context).unwrap_err(),
+		"cannot convert fraction to integer"
+	);
+}
+
+#[test]
+fn modulo_negative_operands() {
+	test_eval("-7 mod 3", "2");
+	test_eval("(-7) mod 3", "2");
+	test_eval("7 mod -3", "1");
+	test_eval("-7 mod -3", "2");
+	test_eval("-6 mod 3", "0");
+	test_eval("-7 % 3", "2");
+	expect_error("1.5 mod 1", Some("modulo is only supported for integers"));
+	expect_error("-5 mod 0", Some("modulo by zero"));
+}
+
+#[test]
+fn modulo_truncated_convention() {
+	let mut context = Context::new();
+	context.set_modulo_convention(fend_core::ModuloConvention::Truncated);
+	assert_eq!(eval_in("7 mod 3", &mut context).unwrap(), "1");
+	assert_eq!(eval_in("-7 mod 3", &mut context).unwrap(), "-1");
+	assert_eq!(eval_in("7 mod -3", &mut context).unwrap(), "1");
+	assert_eq!(eval_in("-7 mod -3", &mut context).unwrap(), "-1");
+	assert_eq!(eval_in("-6 mod 3", &mut context).unwrap(), "0");
+}
+
+#[test]
+fn weekday_keyword_of_date() {
+	test_eval_simple("weekday of @2024-07-04", "Thursday");
+	test_eval_simple("weekday of ('2024-07-04' to date)", "Thursday");
+}
+
+#[test]
+fn day_of_year() {
+	test_eval("day of year of @2024-01-01", "1");
+	test_eval("day of year of @2024-07-04", "186");
+	test_eval("day_of_year of @2024-12-31", "366");
+	test_eval("day of year of @2023-12-31", "365");
+	test_eval("day of year of (@2024-01-01 + 59 days)", "60");
+}
+
+#[test]
+fn iso_week_number() {
+	test_eval("week number of @2024-07-04", "27");
+	test_eval("week_number of ('2024-07-04' to date)", "27");
+	// early January can belong to the last week of the previous year
+	test_eval("week number of @2021-01-01", "53");
+	test_eval("week number of @2016-01-03", "53");
+	test_eval("week number of @2010-01-03", "53");
+	test_eval("week number of @2021-01-04", "1");
+	// late December can belong to the first week of the next year
+	test_eval("week number of @2024-12-30", "1");
+	test_eval("week number of @2025-12-29", "1");
+	test_eval("week number of @2026-01-01", "1");
+	// years with 53 weeks
+	test_eval("week number of @2020-12-31", "53");
+	test_eval("week number of @2015-12-31", "53");
+	test_eval("week number of @2004-12-31", "53");
+	test_eval("week number of @2023-12-31", "52");
+}
+
+#[test]
+fn unknown_identifier_suggestions() {
+	expect_error(
+		"metr",
+		Some("unknown identifier 'metr' (did you mean 'meter' or 'metre'?)"),
+	);
+	expect_error(
+		"sqrrt 4",
+		Some("unknown identifier 'sqrrt' (did you mean 'sqrt'?)"),
+	);
+	expect_error(
+		"5 galons",
+		Some("unknown identifier 'galons' (did you mean 'gallons'?)"),
+	);
+	expect_error("aiusbdla", Some("unknown identifier 'aiusbdla'"));
+	expect_error("foo", Some("unknown identifier 'foo'"));
+}
+
+#[test]
+fn unknown_identifier_suggests_variables() {
+	let mut context = Context::new();
+	evaluate("velocity = 5", &mut context).unwrap();
+	assert_eq!(
+		evaluate("velocty * 2", &mut context).unwrap_err(),
+		"unknown identifier 'velocty' (did you mean 'velocity'?)"
+	);
+}
+
+#[test]
+fn locale_en_us() {
+	let mut context = Context::new();
+	context.set_locale("en-US");
+	assert_eq!(eval_in("1234567.5", &mut context).unwrap(), "1,234,567.5");
+	assert_eq!(eval_in("1,234.5 * 2", &mut context).unwrap(), "2,469");
+	assert_eq!(
+		eval_in("@2024-07-04", &mut context).unwrap(),
+		"Thursday, July 4, 2024"
+	);
+}
+
+#[test]
+fn locale_de_de() {
+	let mut context = Context::new();
+	context.set_locale("de_DE");
+	assert_eq!(eval_in("1234567,5", &mut context).unwrap(), "1.234.567,5");
+	assert_eq!(eval_in("1.234,5 * 2", &mut context).unwrap(), "2.469");
+	assert_eq!(
+		eval_in("@2024-07-04", &mut context).unwrap(),
+		"Thursday, 4 July 2024"
+	);
+}
+
+#[test]
+fn unknown_locale_uses_defaults() {
+	let mut context = Context::new();
+	context.set_locale("de-DE");
+	context.set_locale("xx-YY");
+	assert_eq!(eval_in("1234567.5", &mut context).unwrap(), "1234567.5");
+	assert_eq!(
+		eval_in("@2024-07-04", &mut context).unwrap(),
+		"Thursday, 4 July 2024"
+	);
+}
+
+#[test]
+fn max_suggestion_distance() {
+	let mut context = Context::new();
+	evaluate("temperature = 1", &mut context).unwrap();
+	evaluate("temperatures = 2", &mut context).unwrap();
+	evaluate("temperance = 3", &mut context).unwrap();
+	assert_eq!(
+		evaluate("temperatur", &mut context).unwrap_err(),
+		"unknown identifier 'temperatur' (did you mean 'temperature' or 'temperatures'?)"
+	);
+	context.set_max_suggestion_distance(1);
+	assert_eq!(
+		evaluate("temperatur", &mut context).unwrap_err(),
+		"unknown identifier 'temperatur' (did you mean 'temperature'?)"
+	);
+	context.set_max_suggestion_distance(3);
+	assert_eq!(
+		evaluate("temperatur", &mut context).unwrap_err(),
+		"unknown identifier 'temperatur' (did you mean 'temperature', 'temperatures' or 'temperance'?)"
+	);
+	context.set_max_suggestion_distance(0);
+	assert_eq!(
+		evaluate("temperatur", &mut context).unwrap_err(),
+		"unknown identifier 'temperatur'"
+	);
+}
+
+#[test]
+fn max_suggestions() {
+	let mut context = Context::new();
+	context.set_max_suggestions(1);
+	assert_eq!(
+		evaluate("metr", &mut context).unwrap_err(),
+		"unknown identifier 'metr' (did you mean 'meter'?)"
+	);
+	context.set_max_suggestions(0);
+	assert_eq!(
+		evaluate("metr", &mut context).unwrap_err(),
+		"unknown identifier 'metr'"
+	);
+}
+
+#[test]
+fn exchange_rate_metadata() {
+	let mut context = Context::new();
+	context.set_exchange_rate_handler_v2(|currency: &str| {
+		let rate = match currency {
+			"USD" => 1.0,
+			"EUR" => 0.5,
+			_ => return Err(TestError("unknown currency".into()).into()),
+		};
+		Ok(fend_core::ExchangeRate::new(rate)
+			.with_timestamp_ms(1_700_000_000_000)
+			.with_source("test rates"))
+	});
+	let result = evaluate("2 USD to EUR", &mut context).unwrap();
+	assert_eq!(result.get_main_result(), "1 EUR");
+	let mut currencies: Vec<_> = result
+		.currency_info()
+		.iter()
+		.map(|info| (info.currency(), info.timestamp_ms(), info.source()))
+		.collect();
+	currencies.sort_unstable();
+	assert_eq!(
+		currencies,
+		[
+			("EUR", Some(1_700_000_000_000), Some("test rates")),
+			("USD", Some(1_700_000_000_000), Some("test rates")),
+		]
+	);
+
+	let result = evaluate("2 + 2", &mut context).unwrap();
+	assert!(result.currency_info().is_empty());
+}
+
+#[test]
+fn exchange_rate_handler_v1_has_no_metadata() {
+	let mut context = Context::new();
+	context.set_exchange_rate_handler_v1(fend_core::test_utils::dummy_currency_handler);
+	let result = evaluate("5 GBP to USD", &mut context).unwrap();
+	assert_eq!(result.currency_info().len(), 2);
+	for info in result.currency_info() {
+		assert!(["GBP", "USD"].contains(&info.currency()));
+		assert_eq!(info.timestamp_ms(), None);
+		assert_eq!(info.source(), None);
+	}
+}
+
+#[test]
+fn time_zone_fixed_offsets() {
+	let mut context = Context::new();
+	context.set_current_time_v1(1_700_000_000_000, 3600);
+	assert_eq!(
+		eval_in("now", &mut context).unwrap(),
+		"Tuesday, 14 November 2023 23:13:20"
+	);
+	assert_eq!(
+		eval_in("now to UTC", &mut context).unwrap(),
+		"Tuesday, 14 November 2023 22:13:20 UTC"
+	);
+	assert_eq!(
+		eval_in("now to UTC+5", &mut context).unwrap(),
+		"Wednesday, 15 November 2023 03:13:20 UTC+5"
+	);
+	assert_eq!(
+		eval_in("now to GMT-3.5", &mut context).unwrap(),
+		"Tuesday, 14 November 2023 18:43:20 UTC-3:30"
+	);
+	assert_eq!(
+		eval_in("now to UTC-3:30", &mut context).unwrap(),
+		"Tuesday, 14 November 2023 18:43:20 UTC-3:30"
+	);
+	assert_eq!(
+		eval_in("now to UTC+5:45", &mut context).unwrap(),
+		"Wednesday, 15 November 2023 03:58:20 UTC+5:45"
+	);
+	assert_eq!(
+		eval_in("now to GMT + 5:30", &mut context).unwrap(),
+		"Wednesday, 15 November 2023 03:43:20 UTC+5:30"
+	);
+	assert_eq!(
+		eval_in("@2024-01-01 to UTC+2", &mut context).unwrap_err(),
+		"dates without a time can't be converted to a time zone"
+	);
+	assert_eq!(
+		eval_in("@2024-01-01 to Europe/London", &mut context).unwrap_err(),
+		"dates without a time can't be converted to a time zone"
+	);
+	assert_eq!(
+		eval_in("now to UTC+5 to UTC", &mut context).unwrap(),
+		"Tuesday, 14 November 2023 22:13:20 UTC"
+	);
+	assert_eq!(
+		eval_in("now to UTC+20", &mut context).unwrap_err(),
+		"UTC offsets must be between -18 and +18 hours"
+	);
+	assert_eq!(
+		eval_in("now to America/New_York", &mut context).unwrap_err(),
+		"unknown time zone 'America/New_York'"
+	);
+}
+
+#[test]
+fn time_zone_resolver_handles_dst() {
+	let mut context = Context::new();
+	context.set_current_time_v1(1_700_000_000_000, 3600);
+	// daylight saving time ended on 5 November 2023 at 06:00 UTC
+	context.set_timezone_resolver(|zone: &str, unix_time_secs: i64| match zone {
+		"America/New_York" if unix_time_secs < 1_699_164_000 => Some(-4 * 3600),
+		"America/New_York" => Some(-5 * 3600),
+		_ => None,
+	});
+	assert_eq!(
+		eval_in("now to America/New_York", &mut context).unwrap(),
+		"Tuesday, 14 November 2023 17:13:20 America/New_York"
+	);
+	assert_eq!(
+		eval_in("now - 10 days to America/New_York", &mut context).unwrap(),
+		"Saturday, 4 November 2023 18:13:20 America/New_York"
+	);
+	assert_eq!(
+		eval_in("now to Europe/Nowhere", &mut context).unwrap_err(),
+		"unknown time zone 'Europe/Nowhere'"
+	);
+}
+
+#[test]
+fn exchange_rate_handler_fallback() {
+	let mut context = Context::new();
+	context.set_exchange_rate_handler_v2(|_: &str| Err(TestError("offline cache".into()).into()));
+	context.add_exchange_rate_handler(|currency: &str| match currency {
+		"USD" => Ok(fend_core::ExchangeRate::new(1.0).with_source("network")),
+		"EUR" => Ok(fend_core::ExchangeRate::new(0.5).with_source("network")),
+		_ => Err(TestError("unknown currency".into()).into()),
+	});
+	let result = evaluate("2 USD to EUR", &mut context).unwrap();
+	assert_eq!(result.get_main_result(), "1 EUR");
+	assert!(result
+		.currency_info()
+		.iter()
+		.all(|info| info.source() == Some("network")));
+	assert_eq!(
+		evaluate("2 GBP", &mut context).unwrap_err(),
+		"failed to retrieve GBP exchange rate: my error: unknown currency"
+	);
+
+	// setting a handler replaces all previous handlers
+	context.set_exchange_rate_handler_v2(|_: &str| Err(TestError("offline cache".into()).into()));
+	assert_eq!(
+		evaluate("2 USD to EUR", &mut context).unwrap_err(),
+		"failed to retrieve EUR exchange rate: my error: offline cache"
+	);
+}
+
+#[test]
+fn block_comments() {
+	test_eval("2 /* two */ + 3", "5");
+	test_eval("1 + /* spanning\nmultiple\nlines */ 1", "2");
+	test_eval("/**/ 5", "5");
+	test_eval("2 /* a */ /* b */ * 3", "6");
+	test_eval("6 /2*3", "9");
+}
+
+#[test]
+fn nested_block_comments() {
+	test_eval("/* a /* b */ c */ 4", "4");
+	test_eval("/* /* /* deep */ */ */ 4 # trailing", "4");
+	expect_error("/* a /* b */ 4", Some("unterminated block comment"));
+	expect_error_kind("2 /* unterminated", fend_core::ErrorKind::Parse);
+}
+
+#[test]
+fn comment_markers_in_strings() {
+	test_eval_simple("\"a /* b */ c\"", "a /* b */ c");
+	test_eval_simple("\"# not a comment\"", "# not a comment");
+	test_eval_simple("\"*/\" /* comment */", "*/");
+}
+
+#[test]
+fn decimals_to_words() {
+	test_eval_simple("0.2 to words", "zero point two");
+	test_eval_simple("1.125 to words", "one point one two five");
+	test_eval_simple("0.05 to words", "zero point zero five");
+	test_eval_simple("0.75 to words", "three quarters");
+	test_eval_simple("3/4 to words", "three quarters");
+	test_eval_simple("0.5 to words", "one half");
+	test_eval_simple("1.25 to words", "one and one quarter");
+	expect_error(
+		"pi to words",
+		Some("only exact numbers can be written in words"),
+	);
+	expect_error("-0.5 to words", Some("negative numbers are not allowed"));
+}
+
+#[test]
+fn fractions_to_words() {
+	test_eval_simple("3/4 to fraction to words", "three quarters");
+	test_eval_simple("1/2 to fraction to words", "one half");
+	test_eval_simple("7/2 to mixed_frac to words", "three and one half");
+	test_eval_simple("3/20 to fraction to words", "three twentieths");
+	test_eval_simple("21/22 to words", "twenty-one twenty-seconds");
+	test_eval_simple("1/101 to words", "one one hundred and first");
+}
+
+#[test]
+fn recurring_decimals_to_words() {
+	test_eval_simple("1/3 to words", "one third");
+	test_eval_simple("2/3 to words", "two thirds");
+	test_eval_simple("5/3 to words", "one and two thirds");
+	test_eval_simple("0.1(6) to words", "one sixth");
+	test_eval_simple("1/12 to words", "one twelfth");
+}
+
+#[test]
+fn shebang_only_at_start() {
+	test_eval("#!/usr/bin/env fend\n2 * 3", "6");
+	expect_error("1 + 1\n#!foo\n2", Some("unexpected character '#'"));
+	expect_error(" #!/usr/bin/env fend\n1", Some("unexpected character '#'"));
+	test_eval("2 # comment #! not a shebang", "2");
+}
+
+#[test]
+fn shebang_handling_disabled() {
+	let mut context = Context::new();
+	context.set_shebang_handling(false);
+	assert_eq!(
+		evaluate("#!/usr/bin/env fend\n1 + 1", &mut context).unwrap_err(),
+		"unexpected character '#'"
+	);
+	context.set_shebang_handling(true);
+	assert_eq!(
+		evaluate("#!/usr/bin/env fend\n1 + 1", &mut context)
+			.unwrap()
+			.get_main_result(),
+		"2"
+	);
+}
+
+#[test]
+fn ordinal_words() {
+	test_eval_simple("1 to ordinal", "first");
+	test_eval_simple("2 to ordinal", "second");
+	test_eval_simple("3 to ordinal", "third");
+	test_eval_simple("5 to ordinal", "fifth");
+	test_eval_simple("8 to ordinal", "eighth");
+	test_eval_simple("9 to ordinal", "ninth");
+	test_eval_simple("12 to ordinal", "twelfth");
+	test_eval_simple("20 to ordinal", "twentieth");
+	test_eval_simple("21 to ordinal", "twenty-first");
+	test_eval_simple("22 to ordinal", "twenty-second");
+	test_eval_simple("100 to ordinal", "one hundredth");
+	test_eval_simple("101 to ordinal", "one hundred and first");
+	test_eval_simple("1000 to ordinal", "one thousandth");
+	test_eval_simple("0 to ordinal", "zeroth");
+}
+
+#[test]
+fn ordinal_words_errors() {
+	expect_error("0.5 to ordinal", Some("cannot convert fraction to integer"));
+	expect_error("-1 to ordinal", Some("negative numbers are not allowed"));
+}
+
+#[test]
+fn per_with_count() {
+	test_eval("240 kcal per 2 servings", "120 kcal / serving");
+	test_eval("240 kcal per serving", "240 kcal / serving");
+	test_eval("120 kcal per serving * 3 servings", "360 kcal");
+	test_eval("5 m per 2 s", "2.5 m / s");
+	test_eval("60 miles per 2 hours to mph", "30 mph");
+	test_eval("10 per 2", "5");
+	test_eval("3 items", "3 items");
+}
+
+#[test]
+fn to_ordinal_number() {
+	let expected = [
+		"0th", "1st", "2nd", "3rd", "4th", "5th", "6th", "7th", "8th", "9th", "10th", "11th",
+		"12th", "13th", "14th", "15th", "16th", "17th", "18th", "19th", "20th", "21st", "22nd",
+		"23rd", "24th", "25th",
+	];
+	for (i, ordinal) in expected.iter().enumerate() {
+		test_eval_simple(&format!("{i} to ordinal number"), ordinal);
+	}
+	test_eval_simple("111 to ordinal number", "111th");
+	test_eval_simple("1002 to ordinal number", "1002nd");
+	test_eval_simple("0xff to ordinal number", "255th");
+	expect_error(
+		"1.5 to ordinal number",
+		Some("cannot convert fraction to integer"),
+	);
+}
+
+#[track_caller]
+fn test_markdown(input: &str, expected: &str) {
+	let mut ctx = Context::new();
+	ctx.set_output_mode_markdown();
+	let result = evaluate(input, &mut ctx).unwrap();
+	assert_eq!(result.get_main_result(), expected);
+}
+
+#[test]
+fn markdown_output_mode() {
+	test_markdown("1 + 1", "`2`");
+	test_markdown("5 kg to g", "`5000 g`");
+	test_markdown("\"a`b\"", "`` a`b ``");
+	test_markdown(
+		"2d6",
+		"| Value | Probability |\n\
+		| ---: | ---: |\n\
+		| 2 | 2.78% |\n\
+		| 3 | 5.56% |\n\
+		| 4 | 8.33% |\n\
+		| 5 | 11.11% |\n\
+		| 6 | 13.89% |\n\
+		| 7 | 16.67% |\n\
+		| 8 | 13.89% |\n\
+		| 9 | 11.11% |\n\
+		| 10 | 8.33% |\n\
+		| 11 | 5.56% |\n\
+		| 12 | 2.78% |",
+	);
+}
+
+#[test]
+fn round_to_denominator() {
+	test_eval_simple("0.8125 inch to /16", "13/16 inches");
+	test_eval_simple("1.8125 inches to /16", "1 13/16 inches");
+	test_eval_simple("0.5 inch to /16", "1/2 inches");
+	test_eval_simple("0.8 inch to /16", "approx. 13/16 inches");
+	test_eval_simple("5 cm to inch to /16", "approx. 1 15/16 inches");
+	test_eval_simple("-0.8 to /16", "approx. -13/16");
+	test_eval("0.01 to /16", "approx. 0");
+	expect_error("2 to /0", Some("division by zero"));
+}
+
+#[test]
+fn completions_rank_frequently_used_units_first() {
+	let completions = |context: &Context| {
+		fend_core::get_completions_for_prefix_with_context("ka", context)
+			.1
+			.iter()
+			.map(|c| c.display().to_string())
+			.collect::<Vec<_>>()
+	};
+	let mut context = Context::new();
+	evaluate("5 katal", &mut context).unwrap();
+	// usage tracking is disabled by default
+	assert_eq!(completions(&context), ["karat", "kat", "katal", "kayser"]);
+
+	context.set_unit_usage_tracking(true);
+	evaluate("5 katal", &mut context).unwrap();
+	assert_eq!(completions(&context), ["katal", "karat", "kat", "kayser"]);
+	evaluate("1 kayser + 1 kayser", &mut context).unwrap();
+	assert_eq!(completions(&context), ["kayser", "katal", "karat", "kat"]);
+	// failed calculations aren't counted
+	assert!(evaluate("5 katal + 1 kayser", &mut context).is_err());
+	assert_eq!(completions(&context), ["kayser", "katal", "karat", "kat"]);
+
+	context.set_unit_usage_tracking(false);
+	assert_eq!(completions(&context), ["karat", "kat", "katal", "kayser"]);
+}
+
+#[test]
+fn to_continued_fraction() {
+	test_eval_simple("415/93 to continued_fraction", "[4; 2, 6, 7]");
+	test_eval_simple("-415/93 to continued_fraction", "[-5; 1, 1, 6, 7]");
+	test_eval_simple("5 to continued_fraction", "[5]");
+	test_eval_simple("0 to continued_fraction", "[0]");
+	test_eval_simple("0.(3) to continued_fraction", "[0; 3]");
+	test_eval_simple(
+		"pi to continued_fraction",
+		"[3; 7, 15, 1, 292, 1, 1, 1, 2, 1, ...]",
+	);
+	test_eval_simple(
+		"sqrt 2 to continued_fraction",
+		"[1; 2, 2, 2, 2, 2, 2, 2, 2, 2, ...]",
+	);
+	expect_error(
+		"5 m to continued_fraction",
+		Some("cannot convert from m to unitless: units 'meter' and 'unitless' are incompatible"),
+	);
+}
+
+#[test]
+fn prefer_mixed_fractions_for_imperial_lengths() {
+	let mut context = Context::new();
+	assert_eq!(
+		eval_in("5 foot 2 inch", &mut context).unwrap(),
+		"approx. 5.1666666666 feet"
+	);
+	assert_eq!(
+		eval_in("13/16 inch", &mut context).unwrap(),
+		"0.8125 inches"
+	);
+
+	context.set_prefer_mixed_fractions(true);
+	assert_eq!(
+		eval_in("5 foot 2 inch", &mut context).unwrap(),
+		"5 1/6 feet"
+	);
+	assert_eq!(eval_in("13/16 inch", &mut context).unwrap(), "13/16 inches");
+	assert_eq!(eval_in("5.5 inches", &mut context).unwrap(), "5 1/2 inches");
+	assert_eq!(eval_in("3 inches", &mut context).unwrap(), "3 inches");
+	// other units, large denominators and explicit formats are unaffected
+	assert_eq!(
+		eval_in("1/3 kg", &mut context).unwrap(),
+		"approx. 0.3333333333 kg"
+	);
+	assert_eq!(
+		eval_in("1/128 inch", &mut context).unwrap(),
+		"0.0078125 inches"
+	);
+	assert_eq!(
+		eval_in("5 cm to inch", &mut context).unwrap(),
+		"approx. 1.968503937 inches"
+	);
+	assert_eq!(
+		eval_in("5.5 inches to float", &mut context).unwrap(),
+		"5.5 inches"
+	);
+}
+
+#[test]
+fn data_rate_conversions() {
+	// bits vs bytes
+	test_eval("1 Gbps to MB/s", "125 MB / s");
+	test_eval("1 GB/s to Gbps", "8 Gbps");
+	test_eval("1 MBps to Mbps", "8 Mbps");
+	test_eval("1 Bps to bps", "8 bps");
+	test_eval("8 Mbps to MB/s", "1 MB / s");
+	test_eval("1 GB to Gb", "8 Gb");
+	test_eval("1 GiB to Gib", "8 Gib");
+	test_eval("1 Mb to kB", "125 kB");
+	test_eval("550Mbit/s to MB/s", "68.75 MB / s");
+	test_eval("1 gigabit to megabytes", "125 megabytes");
+	// SI vs binary prefixes
+	test_eval("1 TiB to TB", "1.099511627776 TB");
+	test_eval("1 TB to GB", "1000 GB");
+	test_eval("1 PiB to TiB", "1024 TiB");
+	test_eval("1 KiB to bytes", "1024 bytes");
+	test_eval("1 kB to bits", "8000 bits");
+	test_eval("1 Gib to MiB", "128 MiB");
+	test_eval("1 MiB/s to kbps", "8388.608 kbps");
+	test_eval("1 Mibps to bps", "1048576 bps");
+	// abbreviations with `bit`
+	test_eval("1 Gbit to Mbit", "1000 Mbit");
+	test_eval("1 Kibit to bits", "1024 bits");
+	test_eval("1 Mibit to Kibit", "1024 Kibit");
+	test_eval("1 Gibit to Gbit", "1.073741824 Gbit");
+	test_eval("1 Gbit/s to Gbps", "1 Gbps");
+}
+
+#[test]
+fn bit_byte_display_preference() {
+	use fend_core::BitBytePreference as P;
+	test_eval_with("8e9 bit", "8000000000 bits", |ctx| {
+		ctx.set_bit_byte_display_preference(P::Unchanged)
+	});
+	test_eval_with("8e9 bit", "8 Gb", |ctx| {
+		ctx.set_bit_byte_display_preference(P::Bits)
+	});
+	test_eval_with("8e9 bit", "1 GB", |ctx| {
+		ctx.set_bit_byte_display_preference(P::Bytes)
+	});
+	test_eval_with("8e9 bit", "7.450580596923828125 Gib", |ctx| {
+		ctx.set_bit_byte_display_preference(P::BinaryBits)
+	});
+	test_eval_with("8e9 bit", "953.67431640625 MiB", |ctx| {
+		ctx.set_bit_byte_display_preference(P::BinaryBytes)
+	});
+	test_eval_with("1024 KiB", "1.048576 MB", |ctx| {
+		ctx.set_bit_byte_display_preference(P::Bytes)
+	});
+	test_eval_with("1024 KiB", "1 MiB", |ctx| {
+		ctx.set_bit_byte_display_preference(P::BinaryBytes)
+	});
+	test_eval_with("-3 MB", "-24 Mb", |ctx| {
+		ctx.set_bit_byte_display_preference(P::Bits)
+	});
+	test_eval_with("1 bit", "0.125 B", |ctx| {
+		ctx.set_bit_byte_display_preference(P::Bytes)
+	});
+	// explicit conversions and other units are unaffected
+	test_eval_with("1 GB to Mb", "8000 Mb", |ctx| {
+		ctx.set_bit_byte_display_preference(P::Bytes)
+	});
+	test_eval_with("5 MB/s", "5 MB / s", |ctx| {
+		ctx.set_bit_byte_display_preference(P::Bits)
+	});
+	test_eval_with("2 kg", "2 kg", |ctx| {
+		ctx.set_bit_byte_display_preference(P::Bytes)
+	});
+}
+
+#[test]
+fn isotope_half_lives() {
+	test_eval("half-life of carbon-14", "5700 years");
+	test_eval("half_life of iodine-131", "8.0252 days");
+	test_eval("half-life of uranium-238 to years", "4468000000 years");
+	test_eval("2 * half-life of carbon-14", "11400 years");
+	expect_error(
+		"half-life of carbon-15",
+		Some("unknown identifier 'carbon-15'"),
+	);
+	expect_error("mass of carbon-14", Some("could not find key in object"));
+	expect_error("half-life of earth", Some("could not find key in object"));
+}
+
+#[test]
+fn isotope_names_do_not_shadow_subtraction() {
+	test_eval("carbon = 20; carbon - 15", "5");
+	test_eval("carbon = 20; carbon - 14", "6");
+	test_eval("carbon = 20; carbon-14", "6");
+	test_eval("(carbon: carbon-14) 100", "86");
+	expect_error("carbon - 14", Some("unknown identifier 'carbon'"));
+}
+
+#[test]
+fn lgamma() {
+	test_eval("lgamma 1", "0");
+	test_eval("log_gamma 2", "0");
+	test_eval("lgamma 100", "approx. 359.1342053695");
+	test_eval("lgamma 0.5", "approx. 0.5723649429");
+	let mut ctx = Context::new();
+	for n in 1..10 {
+		assert_eq!(
+			evaluate(&format!("lgamma {}", n + 1), &mut ctx)
+				.unwrap()
+				.get_main_result(),
+			evaluate(&format!("ln({n}!)"), &mut ctx)
+				.unwrap()
+				.get_main_result(),
+		);
+	}
+	expect_error("lgamma 0", Some("0 must lie in the interval (0, ∞)"));
+	expect_error(
+		"lgamma (-2.5)",
+		Some("-2.5 must lie in the interval (0, ∞)"),
+	);
+	expect_error(
+		"lgamma (2 kg)",
+		Some(
+			"cannot convert from kg to unitless: units 'kilogram' and 'unitless' are incompatible",
+		),
+	);
+}
+
+#[test]
+fn ratio_to_percent_and_permille() {
+	test_eval("0.25 to %", "25%");
+	test_eval("1 to %", "100%");
+	test_eval("0.004 to permille", "4\u{2030}");
+	test_eval("0.004 to per_mille", "4\u{2030}");
+	test_eval("5% to permille", "50\u{2030}");
+	test_eval("2 permille to %", "0.2%");
+	expect_error(
+		"1kg to %",
+		Some("cannot convert from kg to %: units 'kilogram' and 'unitless' are incompatible"),
+	);
+	expect_error(
+		"1kg to permille",
+		Some(
+			"cannot convert from kg to \u{2030}: units 'kilogram' and 'unitless' are incompatible",
+		),
+	);
+}
+
+#[test]
+fn beta_function() {
+	test_eval_simple("beta(2, 3) to fraction", "1/12");
+	test_eval_simple("beta(3, 2) to fraction", "1/12");
+	test_eval_simple("beta(5, 7) to fraction", "1/2310");
+	test_eval("beta(1, 1)", "1");
+	test_eval("beta(1/2, 1)", "2");
+	test_eval_simple("beta(0.5, 3) to fraction", "16/15");
+	test_eval("beta(0.5, 0.5)", "approx. 3.1415926535");
+	test_eval("beta(2.5, 1.5)", "approx. 0.1963495408");
+	expect_error("beta(0, 1)", Some("0 must lie in the interval (0, ∞)"));
+	expect_error("beta(2, -1)", Some("-1 must lie in the interval (0, ∞)"));
+	expect_error("beta(2)", Some("'beta' does not accept 1 argument"));
+	expect_error("beta(i, 2)", Some("expected a real number"));
+}
+
+#[test]
+fn percent_off_and_on() {
+	test_eval("15% off 200", "170");
+	test_eval("15% on 200", "230");
+	test_eval("15% increase on 200", "230");
+	test_eval("100% off 50", "0");
+	test_eval("15% off 200 kg", "170 kg");
+	test_eval("15% off 200 + 10", "180");
+	test_eval("5% off (10% off 200)", "171");
+	test_eval("15% on (100 - 20)", "92");
+	test_eval("f = \\x.15% off x; f 100", "85");
+	// `%` is still parsed as modulo if it isn't followed by a value
+	test_eval("on = 4; 15 % on", "3");
+	test_eval_simple("\\x.x% on 200", "\\x.(x (% on 200))");
+	expect_error("% off 200", Some("expected a number"));
+}
+
+#[test]
+fn inline_unit_definitions() {
+	let mut ctx = Context::new();
+	ctx.set_inline_unit_definitions(true);
+	assert_eq!(
+		eval_in("5 widgets where widget = 3 kg", &mut ctx).unwrap(),
+		"5 widgets"
+	);
+	assert_eq!(eval_in("5 widgets to kg", &mut ctx).unwrap(), "15 kg");
+	assert_eq!(
+		eval_in("2 gadgets to kg where gadget = 2 widgets", &mut ctx).unwrap(),
+		"12 kg"
+	);
+	assert_eq!(
+		eval_in(
+			"3 crates to g where crate = 2 bags where bag = 5 g",
+			&mut ctx
+		)
+		.unwrap(),
+		"30 g"
+	);
+	// redefining a unit replaces the old definition
+	assert_eq!(
+		eval_in("1 widget to kg where widget = 4 kg", &mut ctx).unwrap(),
+		"4 kg"
+	);
+	assert_eq!(
+		eval_in("5 wx where wx = 3 foo", &mut ctx).unwrap_err(),
+		"unknown identifier 'foo'"
+	);
+	assert_eq!(
+		eval_in("5 w where 3 = 4", &mut ctx).unwrap_err(),
+		"expected an identifier"
+	);
+
+	// the value can be used without the unit definition
+	eval_in("x = 5 widgets", &mut ctx).unwrap();
+	let mut v = vec![];
+	ctx.serialize_variables(&mut v).unwrap();
+	let mut ctx = Context::new();
+	ctx.deserialize_variables(&mut v.as_slice()).unwrap();
+	assert_eq!(eval_in("x", &mut ctx).unwrap(), "5 widgets");
+	assert_eq!(eval_in("x to kg", &mut ctx).unwrap(), "20 kg");
+
+	// `where` is a normal identifier unless inline unit definitions are enabled
+	assert_eq!(eval_in("where = 3; where", &mut ctx).unwrap(), "3");
+}
+
+#[test]
+fn inline_unit_definitions_cannot_shadow_existing_names() {
+	let mut ctx = Context::new();
+	ctx.set_inline_unit_definitions(true);
+	assert_eq!(
+		eval_in("5 kg where kg = 3 g", &mut ctx).unwrap_err(),
+		"cannot redefine 'kg', since it is already defined"
+	);
+	assert_eq!(eval_in("1 kg to g", &mut ctx).unwrap(), "1000 g");
+	assert_eq!(
+		eval_in("5 km where km = 3 g", &mut ctx).unwrap_err(),
+		"cannot redefine 'km', since it is already defined"
+	);
+	assert_eq!(
+		eval_in("5 pi where pi = 3 g", &mut ctx).unwrap_err(),
+		"cannot redefine 'pi', since it is already defined"
+	);
+	assert_eq!(
+		eval_in("5 m where m = 2", &mut ctx).unwrap_err(),
+		"cannot redefine 'm', since it is already defined"
+	);
+	assert_eq!(eval_in("1 m to cm", &mut ctx).unwrap(), "100 cm");
+	eval_in("apple = 3", &mut ctx).unwrap();
+	assert_eq!(
+		eval_in("5 apple where apple = 2 g", &mut ctx).unwrap_err(),
+		"cannot redefine 'apple', since it is already defined"
+	);
+	assert_eq!(eval_in("apple", &mut ctx).unwrap(), "3");
+	assert_eq!(
+		eval_in("(\\y.(5 y where y = 2 g)) 1", &mut ctx).unwrap_err(),
+		"cannot redefine 'y', since it is already defined"
+	);
+	assert_eq!(
+		eval_in("x where x = 5", &mut ctx).unwrap_err(),
+		"unit 'x' must be defined in terms of other units, e.g. `x = 3 kg`"
+	);
+	assert_eq!(
+		eval_in("x", &mut ctx).unwrap_err(),
+		"unknown identifier 'x'"
+	);
+}
+
+#[test]
+fn inline_unit_definition_plurals() {
+	let mut ctx = Context::new();
+	ctx.set_inline_unit_definitions(true);
+	assert_eq!(
+		eval_in("5 glorp where glorp = 3 g", &mut ctx).unwrap(),
+		"5 glorps"
+	);
+	// short names are treated as abbreviations without a plural
+	assert_eq!(eval_in("5 zq where zq = 3 g", &mut ctx).unwrap(), "5 zq");
+	assert_eq!(eval_in("5 zq to g", &mut ctx).unwrap(), "15 g");
+	assert_eq!(
+		eval_in("5 glass where glass = 3 g", &mut ctx).unwrap(),
+		"5 glass"
+	);
+}
+
+#[test]
+fn variable_value() {
+	let mut ctx = Context::new();
+	assert_eq!(ctx.variable_value("foo"), None);
+	evaluate("foo = 5 kg; bar = \\x.2x; baz = \"hi\"", &mut ctx).unwrap();
+	assert_eq!(ctx.variable_value("foo").as_deref(), Some("5 kg"));
+	assert_eq!(ctx.variable_value("bar").as_deref(), Some("\\x.(2 x)"));
+	assert_eq!(ctx.variable_value("baz").as_deref(), Some("hi"));
+	assert_eq!(ctx.variable_value("ans").as_deref(), Some("hi"));
+	assert_eq!(ctx.variable_value("qux"), None);
+}
+
+#[test]
+fn default_sf_limit() {
+	let mut ctx = Context::new();
+	ctx.set_default_sf_limit(std::num::NonZeroUsize::new(5));
+	assert_eq!(eval_in("pi", &mut ctx).unwrap(), "approx. 3.1415");
+	assert_eq!(eval_in("10 kg / 3", &mut ctx).unwrap(), "approx. 3.3333 kg");
+	assert_eq!(eval_in("2", &mut ctx).unwrap(), "2");
+	assert_eq!(eval_in("pi to 3 sf", &mut ctx).unwrap(), "approx. 3.14");
+	assert_eq!(eval_in("pi to 2 dp", &mut ctx).unwrap(), "approx. 3.14");
+	ctx.set_default_sf_limit(None);
+	assert_eq!(eval_in("pi", &mut ctx).unwrap(), "approx. 3.1415926535");
+}
+
+#[test]
+fn variables_survive_save_and_load() {
+	let mut ctx = Context::new();
+	eval_in("a = 5; b = 3 kg; f = x: x * 2", &mut ctx).unwrap();
+	let mut saved = vec![];
+	ctx.serialize_variables(&mut saved).unwrap();
+
+	let mut restored = Context::new();
+	restored
+		.deserialize_variables(&mut saved.as_slice())
+		.unwrap();
+	assert_eq!(eval_in("a + 1", &mut restored).unwrap(), "6");
+	assert_eq!(eval_in("b to g", &mut restored).unwrap(), "3000 g");
+	assert_eq!(eval_in("f 4", &mut restored).unwrap(), "8");
+
+	// a corrupt file leaves the existing variables alone
+	let mut corrupt = Context::new();
+	eval_in("c = 7", &mut corrupt).unwrap();
+	assert!(corrupt
+		.deserialize_variables(&mut &saved[..saved.len() / 2])
+		.is_err());
+	assert_eq!(eval_in("c", &mut corrupt).unwrap(), "7");
+}
+
+#[test]
+fn completions_include_variables() {
+	let completions = |prefix, context: &Context| {
+		fend_core::get_completions_for_prefix_with_context(prefix, context)
+			.1
+			.iter()
+			.map(|c| (c.display().to_string(), c.insert().to_string()))
+			.collect::<Vec<_>>()
+	};
+	let mut context = Context::new();
+	evaluate("kappa = 5; katal = 2; zzz_total = 3", &mut context).unwrap();
+	assert_eq!(
+		completions("ka", &context),
+		[
+			("kappa".to_string(), "ppa".to_string()),
+			("karat".to_string(), "rat".to_string()),
+			("kat".to_string(), "t".to_string()),
+			("katal".to_string(), "tal".to_string()),
+			("kayser".to_string(), "yser".to_string()),
+		]
+	);
+	assert_eq!(
+		completions("zzz", &context),
+		[("zzz_total".to_string(), "_total".to_string())]
+	);
+	assert!(completions("zzz_total", &context).is_empty());
+	// variables aren't completed without a context
+	assert!(fend_core::get_completions_for_prefix("zzz").1.is_empty());
+}
+
+#[test]
+fn completions_include_functions() {
+	let completions = |prefix| {
+		fend_core::get_completions_for_prefix(prefix)
+			.1
+			.iter()
+			.map(|c| c.display().to_string())
+			.collect::<Vec<_>>()
+	};
+	assert!(completions("sq").contains(&"sqrt".to_string()));
+	let si = completions("si");
+	assert!(si.contains(&"sin".to_string()));
+	assert!(si.contains(&"sinh".to_string()));
+	assert_eq!(completions("sin"), ["sinh"]);
+	assert_eq!(completions("fac"), ["factorise", "factorize"]);
+	// `byte` is a unit, so `byteswap` isn't suggested
+	assert!(completions("by").contains(&"byteswap".to_string()));
+	assert!(!completions("byte").contains(&"byteswap".to_string()));
+}
+
+#[test]
+fn no_approx_attribute() {
+	let mut ctx = Context::new();
+	for attr in ["@noapprox", "@no_approx", "@plain"] {
+		let res = evaluate(&format!("{attr} pi to 5 sf"), &mut ctx).unwrap();
+		assert_eq!(res.get_main_result(), "3.1415");
+		// the result is still reported as approximate
+		assert!(res.is_approximate());
+	}
+	let res = evaluate("@no_approx 1/4", &mut ctx).unwrap();
+	assert_eq!(res.get_main_result(), "0.25");
+	assert!(!res.is_approximate());
+	assert!(evaluate("pi", &mut ctx).unwrap().is_approximate());
+}
+
+#[test]
+fn exact_attribute() {
+	test_eval_simple("@exact 1/3", "1/3");
+	test_eval_simple("@exact 2/3 + 1/6", "5/6");
+	test_eval_simple("@exact 1/3 kg", "1/3 kg");
+	test_eval_simple("@exact pi", "\u{3c0}");
+	test_eval("@exact 0.25", "0.25");
+	// approximate numbers can't be shown exactly
+	test_eval("@exact sqrt 2", "approx. 1.4142135619");
+	// explicit formats take precedence
+	test_eval("@exact 1/3 to 2 dp", "approx. 0.33");
+	test_eval_simple("@exact 1/7 to float", "0.(142857)");
+}
+
+#[test]
+fn hex_float() {
+	test_eval_simple("1.0 to hex float", "0x1p0");
+	test_eval_simple("0.5 to hex float", "0x1p-1");
+	test_eval_simple("1.5 to hex float", "0x1.8p0");
+	test_eval_simple("3 to hex float", "0x1.8p1");
+	test_eval_simple("-2.75 to hex_float", "-0x1.6p1");
+	test_eval_simple("0 to hexfloat", "0x0p0");
+	test_eval_simple("2^-1074 to hex float", "0x1p-1074");
+	test_eval_simple("0.1 to hex float", "approx. 0x1.999999999999ap-4");
+	test_eval_simple("1/3 to hex float", "approx. 0x1.5555555555555p-2");
+	// rounding can carry into the exponent
+	test_eval_simple("(1 - 2^-60) to hex float", "approx. 0x1p0");
+	expect_error(
+		"1.5 kg to hex float",
+		Some(
+			"cannot convert from kg to unitless: units 'kilogram' and 'unitless' are incompatible",
+		),
+	);
+}
+
+#[test]
+fn float_bits() {
+	test_eval_simple("float64 bits of 0", "0x0000000000000000");
+	test_eval("float64 bits of 1", "0x3ff0000000000000");
+	test_eval("float64 bits of 1.5", "0x3ff8000000000000");
+	test_eval("float64 bits of -2", "0xc000000000000000");
+	test_eval("float64 bits of 0.1", "0x3fb999999999999a");
+	test_eval("float64 bits of pi", "0x400921fb54442d18");
+	test_eval_simple("float32 bits of 0", "0x00000000");
+	test_eval("float32 bits of 1.5", "0x3fc00000");
+	test_eval("float32 bits of -2", "0xc0000000");
+	test_eval("float32 bits of 0.1", "0x3dcccccd");
+	expect_error("float32 bits of 1e39", Some("value is too large"));
+	expect_error("float64 bits of (10^400)", Some("value is too large"));
+	test_eval("float64 bits of 2^10", "0x4090000000000000");
+	test_eval("x = 1.5; float32 bits of x", "0x3fc00000");
+	test_eval("\\x.float32 bits of x", "\\x.float32 bits of x");
+	for input in ["float64 bits of 1 kg", "float32 bits of (1 kg)"] {
+		expect_error(
+			input,
+			Some("cannot convert from kg to unitless: units 'kilogram' and 'unitless' are incompatible"),
+		);
+	}
+}
+
+#[test]
+fn twos_complement() {
+	test_eval("-1 to 8 bit hex", "0xff");
+	test_eval("-128 to 8 bit hex", "0x80");
+	test_eval("127 to 8 bit hex", "0x7f");
+	test_eval("255 to 8 bit hex", "0xff");
+	test_eval_simple("15 to 8 bit hex", "0x0f");
+	test_eval("-1 to 16 bit binary", "0b1111111111111111");
+	test_eval_simple("5 to 8 bits binary", "0b00000101");
+	test_eval("-1 to 32 bit octal", "0o37777777777");
+	test_eval("-1 to 64 bit hex", "0xffffffffffffffff");
+	test_eval("-2 to 8 bit decimal", "254");
+	expect_error(
+		"-129 to 8 bit hex",
+		Some("-129 must lie in the interval [-128, 255]"),
+	);
+	expect_error(
+		"256 to 8 bit hex",
+		Some("256 must lie in the interval [-128, 255]"),
+	);
+	expect_error(
+		"1.5 to 8 bit hex",
+		Some("cannot convert fraction to integer"),
+	);
+	expect_error(
+		"-1 to 8 bit kg",
+		Some("a bit width can only be combined with a base, e.g. `to 8 bit hex`"),
+	);
+}
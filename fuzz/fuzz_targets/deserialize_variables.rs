@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+	let mut context = fend_core::Context::new();
+	if context.deserialize_variables(&mut &data[..]).is_ok() {
+		// anything that was accepted must also serialize again
+		let mut buf = vec![];
+		let _ = context.serialize_variables(&mut buf);
+	}
+});
@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::time::{Duration, Instant};
+
+struct Timeout(Instant);
+
+impl fend_core::Interrupt for Timeout {
+	fn should_interrupt(&self) -> bool {
+		self.0.elapsed() > Duration::from_millis(500)
+	}
+}
+
+fuzz_target!(|input: &str| {
+	let mut context = fend_core::Context::new();
+	context.set_random_u32_fn(|| 4);
+	let _ = fend_core::evaluate_with_interrupt(input, &mut context, &Timeout(Instant::now()));
+});
@@ -91,7 +91,8 @@ fn create_context() -> fend_core::Context {
 	let date = js_sys::Date::new_0();
 	ctx.set_current_time_v1(
 		date.get_time() as u64,
-		date.get_timezone_offset() as i64 * 60,
+		// `getTimezoneOffset` is positive for time zones behind UTC
+		-(date.get_timezone_offset() as i64) * 60,
 	);
 	ctx.set_random_u32_fn(random_u32);
 	if CURRENCY_DATA.get().is_some_and(|x| !x.is_empty()) {
@@ -147,6 +148,9 @@ pub fn evaluate_fend_with_timeout_multiple(inputs: &str, timeout: u32) -> String
 }
 
 fn decode_hex(s: &str) -> Result<Vec<u8>, String> {
+	if !s.len().is_multiple_of(2) || !s.is_ascii() {
+		return Err("invalid hex string".to_string());
+	}
 	(0..s.len())
 		.step_by(2)
 		.map(|i| u8::from_str_radix(&s[i..i + 2], 16))